@@ -0,0 +1,364 @@
+use crate::storage::{
+    Alert, AlertKind, AlertRepository, NewAlert, Observation, Product, StockState, Subscription,
+};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::info;
+
+#[derive(Debug, Clone)]
+pub struct AlertConfig {
+    /// Minimum drop since the previous check, in percent, for a price drop alert
+    pub price_drop_percent: f64,
+    /// After an alert fires, the same kind of alert for the same product stays quiet this long
+    pub cooldown: Duration,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        AlertConfig {
+            price_drop_percent: 10.0,
+            cooldown: Duration::from_secs(6 * 60 * 60),
+        }
+    }
+}
+
+/// Decides which alerts a new observation raises and records them.
+///
+/// Rules fire on the transition into their condition (the price crossing the target, the
+/// stock going from out to in), compared with the previous successful check, so a product
+/// that sits below its target doesn't alert on every check. On top of that each kind of alert
+/// has a per-product cooldown, so a listing that flaps back and forth doesn't spam.
+///
+/// Price drops and restocks are raised once for everyone watching the product; target price
+/// alerts are raised per subscriber, against their own target. Prices are only ever compared
+/// with prices in the same currency: a check that comes back in another currency (a store
+/// showing a visitor's local prices, say) neither reaches a target nor counts as a drop.
+pub struct AlertEngine {
+    alerts: Arc<dyn AlertRepository>,
+    config: AlertConfig,
+}
+
+impl AlertEngine {
+    pub fn new(alerts: Arc<dyn AlertRepository>, config: AlertConfig) -> Self {
+        AlertEngine { alerts, config }
+    }
+
+    /// Evaluate `current` against the previous successful observation of the same product,
+    /// returning the alerts that were raised
+    pub fn evaluate(
+        &self,
+        product: &Product,
+        subscriptions: &[Subscription],
+        previous: Option<&Observation>,
+        current: &Observation,
+    ) -> anyhow::Result<Vec<Alert>> {
+        if current.error.is_some() {
+            return Ok(Vec::new());
+        }
+
+        // Only kept in the alert when it's comparable with the current price
+        let previous_price = previous.and_then(|p| p.price).filter(|p| {
+            current
+                .price
+                .is_none_or(|price| price.currency() == p.currency())
+        });
+        let mut raised = Vec::new();
+        for (username, kind, message) in self.triggered(product, subscriptions, previous, current) {
+            if self.cooling_down(product.id, kind, username, current.observed_at)? {
+                info!(
+                    "Alert suppressed by cooldown - product id: {}, kind: {}, username: {:?}",
+                    product.id,
+                    kind.as_str(),
+                    username
+                );
+                continue;
+            }
+
+            let alert = self.alerts.record(NewAlert {
+                product_id: product.id,
+                username: username.map(str::to_string),
+                kind,
+                triggered_at: current.observed_at,
+                price: current.price,
+                previous_price,
+                message,
+            })?;
+            info!(
+                "Alert raised - product id: {}, name: {}, kind: {}, message: {}",
+                product.id,
+                product.name,
+                alert.kind.as_str(),
+                alert.message
+            );
+            raised.push(alert);
+        }
+        Ok(raised)
+    }
+
+    // The rules whose condition just became true, with the subscriber each is for (if only
+    // one) and a message
+    fn triggered<'a>(
+        &self,
+        product: &Product,
+        subscriptions: &'a [Subscription],
+        previous: Option<&Observation>,
+        current: &Observation,
+    ) -> Vec<(Option<&'a str>, AlertKind, String)> {
+        let mut triggered = Vec::new();
+        let previous_price = previous.and_then(|p| p.price);
+
+        for subscription in subscriptions {
+            let (Some(price), Some(target)) = (current.price, subscription.target_price) else {
+                continue;
+            };
+            // A previous price in another currency says nothing about crossing the target
+            let was_above = previous_price
+                .filter(|previous| previous.currency() == target.currency())
+                .is_none_or(|previous| previous > target);
+            if price <= target && was_above {
+                triggered.push((
+                    Some(subscription.username.as_str()),
+                    AlertKind::TargetReached,
+                    format!(
+                        "{} is {}, at or below your target of {}",
+                        product.name, price, target
+                    ),
+                ));
+            }
+        }
+
+        if let (Some(price), Some(previous)) = (current.price, previous_price) {
+            let same_currency = price.currency() == previous.currency();
+            let drop = (previous.to_major() - price.to_major()) / previous.to_major() * 100.0;
+            if same_currency && previous.minor() > 0 && drop >= self.config.price_drop_percent {
+                triggered.push((
+                    None,
+                    AlertKind::PriceDrop,
+                    format!(
+                        "{} dropped {:.0}% from {} to {}",
+                        product.name, drop, previous, price
+                    ),
+                ));
+            }
+        }
+
+        let was_out = previous.is_some_and(|p| p.stock == StockState::OutOfStock);
+        if was_out && current.stock == StockState::InStock {
+            let message = match current.price {
+                Some(price) => format!("{} is back in stock at {}", product.name, price),
+                None => format!("{} is back in stock", product.name),
+            };
+            triggered.push((None, AlertKind::BackInStock, message));
+        }
+
+        triggered
+    }
+
+    fn cooling_down(
+        &self,
+        product_id: i64,
+        kind: AlertKind,
+        username: Option<&str>,
+        now: SystemTime,
+    ) -> anyhow::Result<bool> {
+        let last = self.alerts.latest(product_id, kind, username)?;
+        Ok(last.is_some_and(|alert| {
+            now.duration_since(alert.triggered_at)
+                .is_ok_and(|elapsed| elapsed < self.config.cooldown)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::{Currency, Money};
+    use crate::storage::InMemoryAlertRepository;
+
+    fn product() -> Product {
+        Product {
+            id: 3,
+            url: "https://www.amazon.com/dp/B0BHS1ZPMS".to_string(),
+            name: "RTX 4090".to_string(),
+            retailer: "Amazon".to_string(),
+            retailer_id: Some("B0BHS1ZPMS".to_string()),
+            currency: Currency::Usd,
+            added_by: "alice".to_string(),
+            created_at: SystemTime::UNIX_EPOCH,
+            poll_interval: None,
+        }
+    }
+
+    fn subscription(username: &str, target: Option<i64>) -> Subscription {
+        Subscription {
+            product_id: 3,
+            username: username.to_string(),
+            target_price: target.map(|minor| Money::new(minor, Currency::Usd)),
+            created_at: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    // A successful check `minutes` in, at `price` cents
+    fn observation(minutes: u64, price: i64, stock: StockState) -> Observation {
+        Observation {
+            id: minutes as i64,
+            product_id: 3,
+            observed_at: SystemTime::UNIX_EPOCH + Duration::from_secs(minutes * 60),
+            price: Some(Money::new(price, Currency::Usd)),
+            stock,
+            error: None,
+            ships_from: None,
+            sold_by: None,
+            third_party_seller: false,
+        }
+    }
+
+    fn engine() -> (AlertEngine, Arc<InMemoryAlertRepository>) {
+        let alerts = Arc::new(InMemoryAlertRepository::default());
+        let config = AlertConfig {
+            price_drop_percent: 10.0,
+            cooldown: Duration::from_secs(60 * 60),
+        };
+        (AlertEngine::new(alerts.clone(), config), alerts)
+    }
+
+    fn kinds(alerts: &[Alert]) -> Vec<AlertKind> {
+        alerts.iter().map(|alert| alert.kind).collect()
+    }
+
+    #[test]
+    fn back_in_stock_fires_once() {
+        let (engine, _) = engine();
+        let subscriptions = [subscription("alice", None)];
+        let out = observation(0, 159999, StockState::OutOfStock);
+        let back = observation(10, 159999, StockState::InStock);
+        let still_in = observation(20, 159999, StockState::InStock);
+
+        let raised = engine
+            .evaluate(&product(), &subscriptions, Some(&out), &back)
+            .unwrap();
+        assert_eq!(kinds(&raised), [AlertKind::BackInStock]);
+        assert_eq!(raised[0].username, None);
+        assert_eq!(raised[0].message, "RTX 4090 is back in stock at $1,599.99");
+
+        // Staying in stock isn't a transition
+        let raised = engine
+            .evaluate(&product(), &subscriptions, Some(&back), &still_in)
+            .unwrap();
+        assert!(raised.is_empty());
+    }
+
+    #[test]
+    fn target_reached_is_for_each_subscriber() {
+        let (engine, alerts) = engine();
+        let subscriptions = [
+            subscription("alice", Some(160000)),
+            subscription("bob", Some(150000)),
+            subscription("carol", None),
+        ];
+        let before = observation(0, 169999, StockState::InStock);
+        let now = observation(10, 159999, StockState::InStock);
+
+        let raised = engine
+            .evaluate(&product(), &subscriptions, Some(&before), &now)
+            .unwrap();
+        assert_eq!(kinds(&raised), [AlertKind::TargetReached]);
+        assert_eq!(raised[0].username.as_deref(), Some("alice"));
+        assert_eq!(
+            raised[0].message,
+            "RTX 4090 is $1,599.99, at or below your target of $1,600.00"
+        );
+
+        // Bob's alert has its own cooldown, so alice's doesn't hold it back
+        let lower = observation(20, 149999, StockState::InStock);
+        let raised = engine
+            .evaluate(&product(), &subscriptions, Some(&now), &lower)
+            .unwrap();
+        assert_eq!(kinds(&raised), [AlertKind::TargetReached]);
+        assert_eq!(raised[0].username.as_deref(), Some("bob"));
+
+        let latest = |username| {
+            alerts
+                .latest(3, AlertKind::TargetReached, Some(username))
+                .unwrap()
+                .map(|alert| alert.triggered_at)
+        };
+        assert_eq!(latest("alice"), Some(now.observed_at));
+        assert_eq!(latest("bob"), Some(lower.observed_at));
+        assert_eq!(latest("carol"), None);
+    }
+
+    #[test]
+    fn repeats_stay_quiet_during_the_cooldown() {
+        let (engine, _) = engine();
+        let subscriptions = [subscription("alice", None)];
+        let flap = |minutes| {
+            let out = observation(minutes, 159999, StockState::OutOfStock);
+            let back = observation(minutes + 5, 159999, StockState::InStock);
+            engine
+                .evaluate(&product(), &subscriptions, Some(&out), &back)
+                .unwrap()
+        };
+
+        assert_eq!(kinds(&flap(0)), [AlertKind::BackInStock]);
+        assert!(flap(30).is_empty());
+        // An hour after the first alert the cooldown is over
+        assert_eq!(kinds(&flap(60)), [AlertKind::BackInStock]);
+    }
+
+    #[test]
+    fn price_drops_compare_like_currencies() {
+        let (engine, _) = engine();
+        let before = observation(0, 200000, StockState::InStock);
+        let small = observation(10, 190000, StockState::InStock);
+        let big = observation(20, 170000, StockState::InStock);
+
+        let raised = engine
+            .evaluate(&product(), &[], Some(&before), &small)
+            .unwrap();
+        assert!(raised.is_empty());
+
+        let raised = engine
+            .evaluate(&product(), &[], Some(&small), &big)
+            .unwrap();
+        assert_eq!(kinds(&raised), [AlertKind::PriceDrop]);
+        assert_eq!(
+            raised[0].previous_price,
+            Some(Money::new(190000, Currency::Usd))
+        );
+
+        let mut cad = observation(30, 100000, StockState::InStock);
+        cad.price = Some(Money::new(100000, Currency::Cad));
+        let raised = engine.evaluate(&product(), &[], Some(&big), &cad).unwrap();
+        assert!(raised.is_empty());
+    }
+
+    #[test]
+    fn failed_checks_raise_nothing() {
+        let (engine, alerts) = engine();
+        let out = observation(0, 159999, StockState::OutOfStock);
+        let mut failed = observation(10, 159999, StockState::InStock);
+        failed.error = Some("timed out".to_string());
+
+        let raised = engine
+            .evaluate(
+                &product(),
+                &[subscription("alice", Some(170000))],
+                Some(&out),
+                &failed,
+            )
+            .unwrap();
+        assert!(raised.is_empty());
+        assert!(
+            !engine
+                .cooling_down(3, AlertKind::BackInStock, None, failed.observed_at)
+                .unwrap()
+        );
+        assert!(
+            alerts
+                .latest(3, AlertKind::BackInStock, None)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
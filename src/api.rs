@@ -0,0 +1,914 @@
+use crate::AppState;
+use crate::listing::{self, ListingRow, ProductQuery};
+use crate::money::{Currency, Money};
+use crate::products::{
+    self, ProductError, ProductErrors, ProductInput, Removed, Tracked, can_manage,
+};
+use crate::session;
+use crate::storage::{Alert, NewSubscription, Observation, Product, Subscription};
+use crate::tokens::{self, TokenScope};
+use crate::users::User;
+use axum::Json;
+use axum::Router;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{FromRequestParts, Path, Query, State};
+use axum::http::request::Parts;
+use axum::http::{HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, put};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::SystemTime;
+use tracing::{error, info, warn};
+
+/// Routes for version 1 of the JSON API, to be nested under `/api/v1`
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/products", get(list_products).post(create_product))
+        .route(
+            "/products/{id}",
+            get(get_product).put(update_product).delete(delete_product),
+        )
+        .route("/products/{id}/subscription", put(update_subscription))
+        .route("/products/{id}/history", get(product_history))
+        .route("/products/{id}/alerts", get(product_alerts))
+        .fallback(|| async { ApiError::not_found() })
+}
+
+/// A failed API request, sent as `{"error": {"code": ..., "message": ...}}` with a matching
+/// status. `code` is stable for scripts to match on; `message` is for people. Invalid
+/// product details also list what's wrong with each field under `fields`.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    fields: Vec<ProductError>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            code,
+            message: message.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn unauthorized() -> Self {
+        ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            "Sign in or send an API token to use the API",
+        )
+    }
+
+    pub fn invalid_token() -> Self {
+        ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "invalid_token",
+            "API token is invalid or has been revoked",
+        )
+    }
+
+    // Products the caller isn't allowed to see look the same as ones that don't exist
+    pub fn not_found() -> Self {
+        ApiError::new(StatusCode::NOT_FOUND, "not_found", "Not found")
+    }
+
+    pub fn forbidden(message: &str) -> Self {
+        ApiError::new(StatusCode::FORBIDDEN, "forbidden", message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut body = json!({ "error": { "code": self.code, "message": self.message } });
+        if !self.fields.is_empty() {
+            let fields: serde_json::Map<_, _> = self
+                .fields
+                .iter()
+                .map(|e| {
+                    let error = json!({ "code": e.code(), "message": e.message() });
+                    // Named after the request body's field, which is in minutes here
+                    let field = match e.field() {
+                        "poll_interval" => "poll_interval_minutes",
+                        field => field,
+                    };
+                    (field.to_string(), error)
+                })
+                .collect();
+            body["error"]["fields"] = fields.into();
+        }
+        let mut response = (self.status, Json(body)).into_response();
+        if self.status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+impl From<ProductError> for ApiError {
+    fn from(e: ProductError) -> Self {
+        let status = match e {
+            ProductError::AlreadyTracked
+            | ProductError::DuplicateProduct
+            | ProductError::SharedProduct => StatusCode::CONFLICT,
+            _ => StatusCode::UNPROCESSABLE_ENTITY,
+        };
+        ApiError::new(status, e.code(), e.message())
+    }
+}
+
+// Reported as the first error, with the rest alongside it by field
+impl From<ProductErrors> for ApiError {
+    fn from(errors: ProductErrors) -> Self {
+        let Some(first) = errors.first() else {
+            return ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "invalid_body",
+                "Invalid product details",
+            );
+        };
+        ApiError {
+            fields: errors.iter().collect(),
+            ..ApiError::from(first)
+        }
+    }
+}
+
+// Unexpected failures are logged and reported without details, like `AppError` does
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        error!("API request failed: {:#}", e);
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal",
+            "Something went wrong",
+        )
+    }
+}
+
+/// The caller of an API route, identified by an `Authorization: Bearer` token or else the
+/// browser session. Anonymous requests get a JSON 401 rather than the login redirect web
+/// pages use.
+pub struct ApiUser(pub User);
+
+/// Guard for API routes that change data, which read-only tokens may not use. The caller's
+/// role is still checked by each route.
+pub struct ApiWriter(pub User);
+
+// Work out who is calling and what their credentials allow. A signed-in browser session can
+// do anything its user can.
+async fn authenticate(parts: &mut Parts, state: &AppState) -> Result<(User, TokenScope), ApiError> {
+    let Some(authorization) = parts.headers.get(header::AUTHORIZATION) else {
+        return match session::session_user(parts, state).await? {
+            Some(user) => Ok((user, TokenScope::ReadWrite)),
+            None => Err(ApiError::unauthorized()),
+        };
+    };
+
+    let secret = authorization
+        .to_str()
+        .ok()
+        .and_then(|value| value.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+        .map(|(_, secret)| secret)
+        .ok_or_else(ApiError::invalid_token)?;
+    let Some(token) = state.tokens.find_by_hash(&tokens::hash_token(secret))? else {
+        warn!("API token rejected - path: {}", parts.uri);
+        return Err(ApiError::invalid_token());
+    };
+    // A token only works for as long as the account it belongs to
+    let Some(user) = state.users.get(&token.username)? else {
+        return Err(ApiError::invalid_token());
+    };
+
+    state.tokens.touch(token.id, SystemTime::now())?;
+    Ok((user, token.scope))
+}
+
+impl FromRequestParts<AppState> for ApiUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let (user, _) = authenticate(parts, state).await?;
+        Ok(ApiUser(user))
+    }
+}
+
+impl FromRequestParts<AppState> for ApiWriter {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let (user, scope) = authenticate(parts, state).await?;
+        if !scope.can_write() {
+            warn!(
+                "API write with read-only token - username: {}, path: {}",
+                user.username, parts.uri
+            );
+            return Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "insufficient_scope",
+                "This API token is read-only",
+            ));
+        }
+        Ok(ApiWriter(user))
+    }
+}
+
+/// A product id from the path; anything that isn't one is a 404 rather than axum's plain
+/// text 400
+struct ProductId(i64);
+
+impl FromRequestParts<AppState> for ProductId {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Path(id) = Path::<i64>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| ApiError::not_found())?;
+        Ok(ProductId(id))
+    }
+}
+
+/// Body for creating a product or replacing its details. The target price is in the
+/// retailer's currency.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProductRequest {
+    url: String,
+    name: String,
+    retailer: String,
+    target_price: Option<f64>,
+    poll_interval_minutes: Option<u64>,
+}
+
+impl ProductRequest {
+    fn into_input(self, currency: Currency) -> Result<ProductInput, ProductError> {
+        Ok(ProductInput {
+            url: self.url,
+            name: self.name,
+            retailer: self.retailer,
+            retailer_id: None,
+            currency,
+            target_price: target_price(self.target_price, currency)?,
+            poll_interval: self
+                .poll_interval_minutes
+                .map(products::poll_interval_minutes),
+        })
+    }
+}
+
+/// Body for changing your own target price on a product you watch, in the product's currency
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SubscriptionRequest {
+    target_price: Option<f64>,
+}
+
+// A target price sent as a number of major units
+fn target_price(price: Option<f64>, currency: Currency) -> Result<Option<Money>, ProductError> {
+    price
+        .map(|price| Money::from_major(price, currency).ok_or(ProductError::InvalidTargetPrice))
+        .transpose()
+}
+
+// `subscribed` and `target_price` are about the user making the request. Amounts here and in
+// observations and alerts are in major units of the currency next to them.
+#[derive(Debug, Serialize)]
+struct ProductJson {
+    id: i64,
+    url: String,
+    name: String,
+    retailer: String,
+    retailer_id: Option<String>,
+    currency: &'static str,
+    subscribed_at: Option<String>,
+    target_price: Option<f64>,
+    added_by: String,
+    created_at: String,
+    poll_interval_minutes: Option<u64>,
+    latest: Option<ObservationJson>,
+}
+
+impl ProductJson {
+    fn new(
+        product: Product,
+        subscription: Option<&Subscription>,
+        latest: Option<Observation>,
+    ) -> Self {
+        ProductJson {
+            id: product.id,
+            url: product.url,
+            name: product.name,
+            retailer: product.retailer,
+            retailer_id: product.retailer_id,
+            currency: product.currency.code(),
+            subscribed_at: subscription.map(|s| timestamp(s.created_at)),
+            target_price: subscription
+                .and_then(|s| s.target_price)
+                .map(Money::to_major),
+            added_by: product.added_by,
+            created_at: timestamp(product.created_at),
+            poll_interval_minutes: product.poll_interval.map(|d| d.as_secs() / 60),
+            latest: latest.map(ObservationJson::from),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ObservationJson {
+    observed_at: String,
+    price: Option<f64>,
+    currency: Option<&'static str>,
+    stock: &'static str,
+    error: Option<String>,
+    ships_from: Option<String>,
+    sold_by: Option<String>,
+    third_party_seller: bool,
+}
+
+impl From<Observation> for ObservationJson {
+    fn from(observation: Observation) -> Self {
+        ObservationJson {
+            observed_at: timestamp(observation.observed_at),
+            price: observation.price.map(Money::to_major),
+            currency: observation.price.map(|p| p.currency().code()),
+            stock: observation.stock.as_str(),
+            error: observation.error,
+            ships_from: observation.ships_from,
+            sold_by: observation.sold_by,
+            third_party_seller: observation.third_party_seller,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AlertJson {
+    id: i64,
+    kind: &'static str,
+    triggered_at: String,
+    price: Option<f64>,
+    previous_price: Option<f64>,
+    currency: Option<&'static str>,
+    message: String,
+}
+
+impl From<Alert> for AlertJson {
+    fn from(alert: Alert) -> Self {
+        AlertJson {
+            id: alert.id,
+            kind: alert.kind.as_str(),
+            triggered_at: timestamp(alert.triggered_at),
+            price: alert.price.map(Money::to_major),
+            previous_price: alert.previous_price.map(Money::to_major),
+            currency: alert
+                .price
+                .or(alert.previous_price)
+                .map(|p| p.currency().code()),
+            message: alert.message,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ProductListJson {
+    products: Vec<ProductJson>,
+    page: usize,
+    pages: usize,
+    total: usize,
+}
+
+// RFC 3339 in UTC, e.g. "2025-03-04T14:05:00+00:00"
+fn timestamp(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+}
+
+fn parse_body(
+    state: &AppState,
+    body: Result<Json<ProductRequest>, JsonRejection>,
+) -> Result<ProductInput, ApiError> {
+    let Json(body) =
+        body.map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, "invalid_body", e.body_text()))?;
+    // An unknown retailer is reported by validation
+    let currency = state
+        .retailers
+        .get(&body.retailer)
+        .map_or(Currency::Usd, |r| r.currency());
+    Ok(body.into_input(currency)?)
+}
+
+// The product and the user's subscription to it, as long as they're allowed to see it
+fn find_visible_product(
+    state: &AppState,
+    user: &User,
+    id: i64,
+) -> Result<(Product, Option<Subscription>), ApiError> {
+    products::find_visible(state.products.as_ref(), user, id)?.ok_or_else(ApiError::not_found)
+}
+
+fn require_manage(user: &User, product: &Product) -> Result<(), ApiError> {
+    if can_manage(user, product) {
+        return Ok(());
+    }
+    Err(change_forbidden(user, product))
+}
+
+fn change_forbidden(user: &User, product: &Product) -> ApiError {
+    warn!(
+        "API product change forbidden - id: {}, username: {}, owner: {}",
+        product.id, user.username, product.added_by
+    );
+    ApiError::forbidden("You can only change products you added")
+}
+
+// Takes the same filter, search, sort and page parameters as the products page
+async fn list_products(
+    ApiUser(user): ApiUser,
+    State(state): State<AppState>,
+    Query(query): Query<ProductQuery>,
+) -> Result<Json<ProductListJson>, ApiError> {
+    let query = query.normalized();
+    let products = if user.role.can_view_all() {
+        state.products.list()?
+    } else {
+        state.products.list_for_user(&user.username)?
+    };
+    let mut subscriptions: HashMap<i64, Subscription> = state
+        .products
+        .subscriptions_for_user(&user.username)?
+        .into_iter()
+        .map(|s| (s.product_id, s))
+        .collect();
+
+    let rows = products
+        .into_iter()
+        .map(|product| {
+            Ok(ListingRow {
+                latest: state.observations.latest(product.id)?,
+                subscription: subscriptions.remove(&product.id),
+                product,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let listing = listing::apply(&query, rows);
+
+    Ok(Json(ProductListJson {
+        products: listing
+            .rows
+            .into_iter()
+            .map(|row| ProductJson::new(row.product, row.subscription.as_ref(), row.latest))
+            .collect(),
+        page: listing.page,
+        pages: listing.pages,
+        total: listing.total,
+    }))
+}
+
+// Adding a product someone else already tracks subscribes to it instead, answering 200 with
+// the existing product rather than 201
+async fn create_product(
+    ApiWriter(user): ApiWriter,
+    State(state): State<AppState>,
+    body: Result<Json<ProductRequest>, JsonRejection>,
+) -> Result<(StatusCode, Json<ProductJson>), ApiError> {
+    if !user.role.can_edit() {
+        return Err(ApiError::forbidden("Read-only accounts can't add products"));
+    }
+    let input = parse_body(&state, body)?
+        .validate(&state.retailers, state.link_resolver.as_ref())
+        .await?;
+
+    let target_price = input.target_price;
+    let (product, tracked) = products::track(state.products.as_ref(), &user.username, input)?;
+    let status = match tracked {
+        Tracked::Added => {
+            info!(
+                "Product added - id: {}, name: {}, retailer: {}, added by: {}, target price: {:?}",
+                product.id,
+                product.name,
+                product.retailer,
+                user.username,
+                target_price.map(|p| p.to_string())
+            );
+            StatusCode::CREATED
+        }
+        Tracked::Subscribed => {
+            info!(
+                "Product subscribed - id: {}, name: {}, username: {}, target price: {:?}",
+                product.id,
+                product.name,
+                user.username,
+                target_price.map(|p| p.to_string())
+            );
+            StatusCode::OK
+        }
+        Tracked::AlreadySubscribed => return Err(ProductError::AlreadyTracked.into()),
+    };
+
+    let subscription = state.products.subscription(product.id, &user.username)?;
+    let latest = state.observations.latest(product.id)?;
+    Ok((
+        status,
+        Json(ProductJson::new(product, subscription.as_ref(), latest)),
+    ))
+}
+
+async fn get_product(
+    ApiUser(user): ApiUser,
+    State(state): State<AppState>,
+    ProductId(id): ProductId,
+) -> Result<Json<ProductJson>, ApiError> {
+    let (product, subscription) = find_visible_product(&state, &user, id)?;
+    let latest = state.observations.latest(product.id)?;
+    Ok(Json(ProductJson::new(
+        product,
+        subscription.as_ref(),
+        latest,
+    )))
+}
+
+// Replaces every editable field, the same ones as the edit form. The target price is the
+// caller's own and only saved if they watch the product.
+async fn update_product(
+    ApiWriter(user): ApiWriter,
+    State(state): State<AppState>,
+    ProductId(id): ProductId,
+    body: Result<Json<ProductRequest>, JsonRejection>,
+) -> Result<Json<ProductJson>, ApiError> {
+    let (product, subscription) = find_visible_product(&state, &user, id)?;
+    require_manage(&user, &product)?;
+    let input = parse_body(&state, body)?
+        .validate(&state.retailers, state.link_resolver.as_ref())
+        .await?;
+    let product = products::update(
+        state.products.as_ref(),
+        product,
+        &user.username,
+        subscription.is_some(),
+        input,
+    )??
+    .ok_or_else(ApiError::not_found)?;
+
+    let subscription = state.products.subscription(product.id, &user.username)?;
+    let latest = state.observations.latest(product.id)?;
+    Ok(Json(ProductJson::new(
+        product,
+        subscription.as_ref(),
+        latest,
+    )))
+}
+
+// Watch a product you can see, or change your target price for one you already watch
+async fn update_subscription(
+    ApiWriter(user): ApiWriter,
+    State(state): State<AppState>,
+    ProductId(id): ProductId,
+    body: Result<Json<SubscriptionRequest>, JsonRejection>,
+) -> Result<Json<ProductJson>, ApiError> {
+    if !user.role.can_edit() {
+        return Err(ApiError::forbidden(
+            "Read-only accounts can't watch products",
+        ));
+    }
+    let (product, _) = find_visible_product(&state, &user, id)?;
+    let Json(body) =
+        body.map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, "invalid_body", e.body_text()))?;
+    let target_price = target_price(body.target_price, product.currency)?
+        .map(products::check_target_price)
+        .transpose()?;
+
+    let subscription = state.products.subscribe(NewSubscription {
+        product_id: product.id,
+        username: user.username.clone(),
+        target_price,
+    })?;
+    info!(
+        "Product subscribed - id: {}, name: {}, username: {}, target price: {:?}",
+        product.id,
+        product.name,
+        user.username,
+        subscription.target_price.map(|p| p.to_string())
+    );
+
+    let latest = state.observations.latest(product.id)?;
+    Ok(Json(ProductJson::new(product, Some(&subscription), latest)))
+}
+
+// Subscribers stop watching the product, which is deleted once nobody watches it; admins who
+// don't watch it delete it outright
+async fn delete_product(
+    ApiWriter(user): ApiWriter,
+    State(state): State<AppState>,
+    ProductId(id): ProductId,
+) -> Result<StatusCode, ApiError> {
+    let (product, subscription) = find_visible_product(&state, &user, id)?;
+    if !user.role.can_edit() {
+        return Err(ApiError::forbidden(
+            "Read-only accounts can't change products",
+        ));
+    }
+
+    let removed = products::remove(
+        state.products.as_ref(),
+        &user,
+        &product,
+        subscription.is_some(),
+    )?;
+    if removed == Removed::Forbidden {
+        return Err(change_forbidden(&user, &product));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Every check, oldest first
+async fn product_history(
+    ApiUser(user): ApiUser,
+    State(state): State<AppState>,
+    ProductId(id): ProductId,
+) -> Result<Json<Vec<ObservationJson>>, ApiError> {
+    let (product, _) = find_visible_product(&state, &user, id)?;
+    let history = state.observations.history(product.id)?;
+    Ok(Json(
+        history.into_iter().map(ObservationJson::from).collect(),
+    ))
+}
+
+// Every alert raised for the product that the caller would have been sent, newest first
+async fn product_alerts(
+    ApiUser(user): ApiUser,
+    State(state): State<AppState>,
+    ProductId(id): ProductId,
+) -> Result<Json<Vec<AlertJson>>, ApiError> {
+    let (product, _) = find_visible_product(&state, &user, id)?;
+    let alerts = state.alerts.history(product.id)?;
+    Ok(Json(
+        alerts
+            .into_iter()
+            .filter(|alert| alert.is_for(&user.username))
+            .map(AlertJson::from)
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::TestApp;
+    use crate::tokens::TokenScope;
+    use reqwest::Method;
+    use serde_json::{Value, json};
+
+    const PS5: &str = "https://www.amazon.com/dp/B0CL61F39H";
+
+    fn product(url: &str) -> Value {
+        json!({ "url": url, "name": "PS5", "retailer": "Amazon" })
+    }
+
+    #[tokio::test]
+    async fn hides_products_the_caller_cant_see() {
+        let app = TestApp::start().await;
+        let id = app.track("bob", PS5).id;
+        let path = format!("/api/v1/products/{id}");
+        let carol = app.token("carol", TokenScope::ReadWrite);
+
+        for (method, path) in [
+            (Method::GET, path.clone()),
+            (Method::PUT, path.clone()),
+            (Method::DELETE, path.clone()),
+            (Method::GET, format!("{path}/history")),
+            (Method::PUT, format!("{path}/subscription")),
+        ] {
+            let (status, body) = app
+                .call(method.clone(), &path, &carol, Some(&product(PS5)))
+                .await;
+            assert_eq!(status, 404, "{method} {path}");
+            assert_eq!(body["error"]["code"], "not_found", "{method} {path}");
+        }
+
+        // Viewers see everything, but nothing is found where nothing exists
+        let vera = app.token("vera", TokenScope::ReadOnly);
+        let (status, body) = app.call(Method::GET, &path, &vera, None).await;
+        assert_eq!((status, body["id"].as_i64()), (200, Some(id)));
+        let (status, _) = app
+            .call(Method::GET, "/api/v1/products/999", &vera, None)
+            .await;
+        assert_eq!(status, 404);
+    }
+
+    #[tokio::test]
+    async fn only_owners_and_admins_change_products() {
+        let app = TestApp::start().await;
+        let id = app.track("bob", PS5).id;
+        let path = format!("/api/v1/products/{id}");
+        let renamed = json!({ "url": PS5, "name": "PS5 Slim", "retailer": "Amazon" });
+
+        let vera = app.token("vera", TokenScope::ReadWrite);
+        for (method, path, body) in [
+            (Method::POST, "/api/v1/products", Some(product(PS5))),
+            (Method::PUT, path.as_str(), Some(renamed.clone())),
+            (Method::DELETE, path.as_str(), None),
+        ] {
+            let (status, body) = app.call(method.clone(), path, &vera, body.as_ref()).await;
+            assert_eq!(status, 403, "{method} {path}");
+            assert_eq!(body["error"]["code"], "forbidden", "{method} {path}");
+        }
+
+        // Watching it doesn't make it carol's
+        let carol = app.token("carol", TokenScope::ReadWrite);
+        let (status, _) = app
+            .call(
+                Method::POST,
+                "/api/v1/products",
+                &carol,
+                Some(&product(PS5)),
+            )
+            .await;
+        assert_eq!(status, 200);
+        let (status, body) = app.call(Method::PUT, &path, &carol, Some(&renamed)).await;
+        assert_eq!((status, &body["error"]["code"]), (403, &json!("forbidden")));
+
+        let bob = app.token("bob", TokenScope::ReadWrite);
+        let (status, body) = app.call(Method::PUT, &path, &bob, Some(&renamed)).await;
+        assert_eq!((status, &body["name"]), (200, &json!("PS5 Slim")));
+        let alice = app.token("alice", TokenScope::ReadWrite);
+        let (status, _) = app.call(Method::PUT, &path, &alice, Some(&renamed)).await;
+        assert_eq!(status, 200);
+    }
+
+    #[tokio::test]
+    async fn reports_invalid_fields() {
+        let app = TestApp::start().await;
+        let bob = app.token("bob", TokenScope::ReadWrite);
+        let body = json!({
+            "url": "not a url",
+            "name": "",
+            "retailer": "Amazon",
+            "poll_interval_minutes": 7 * 24 * 60 + 1,
+        });
+
+        let (status, body) = app
+            .call(Method::POST, "/api/v1/products", &bob, Some(&body))
+            .await;
+        assert_eq!(status, 422);
+        assert_eq!(
+            body,
+            json!({ "error": {
+                "code": "missing_name",
+                "message": "Product name can't be empty",
+                "fields": {
+                    "name": {
+                        "code": "missing_name",
+                        "message": "Product name can't be empty",
+                    },
+                    "url": {
+                        "code": "malformed_url",
+                        "message": "Enter the full web address of the product page",
+                    },
+                    "poll_interval_minutes": {
+                        "code": "poll_interval_too_long",
+                        "message": "Products must be checked at least every 7 days",
+                    },
+                },
+            }})
+        );
+
+        let (status, body) = app
+            .call(
+                Method::POST,
+                "/api/v1/products",
+                &bob,
+                Some(&json!({ "url": PS5 })),
+            )
+            .await;
+        assert_eq!(
+            (status, &body["error"]["code"]),
+            (400, &json!("invalid_body"))
+        );
+    }
+
+    #[tokio::test]
+    async fn adds_new_products_and_subscribes_to_known_ones() {
+        let app = TestApp::start().await;
+        let bob = app.token("bob", TokenScope::ReadWrite);
+        let carol = app.token("carol", TokenScope::ReadWrite);
+
+        let (status, added) = app
+            .call(Method::POST, "/api/v1/products", &bob, Some(&product(PS5)))
+            .await;
+        assert_eq!(status, 201);
+        assert_eq!(added["added_by"], "bob");
+
+        let legacy_url = "https://www.amazon.com/gp/product/B0CL61F39H";
+        let (status, subscribed) = app
+            .call(
+                Method::POST,
+                "/api/v1/products",
+                &carol,
+                Some(&product(legacy_url)),
+            )
+            .await;
+        assert_eq!(status, 200);
+        assert_eq!(subscribed["id"], added["id"]);
+        assert!(subscribed["subscribed_at"].is_string());
+
+        let (status, body) = app
+            .call(
+                Method::POST,
+                "/api/v1/products",
+                &carol,
+                Some(&product(PS5)),
+            )
+            .await;
+        assert_eq!(
+            (status, &body["error"]["code"]),
+            (409, &json!("already_tracked"))
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_revoked_and_orphaned_tokens() {
+        let app = TestApp::start().await;
+        let bob = app.token("bob", TokenScope::ReadWrite);
+        let (status, _) = app.call(Method::GET, "/api/v1/products", &bob, None).await;
+        assert_eq!(status, 200);
+
+        let token = &app.state.tokens.list_for_user("bob").unwrap()[0];
+        assert!(app.state.tokens.delete(token.id, "bob").unwrap());
+        // A token outliving its account, as when the account is removed
+        let gone = app.token("dave", TokenScope::ReadWrite);
+        let garbage = ("Authorization", "Bearer midas_nope".to_string());
+        let not_bearer = ("Authorization", "Basic Ym9iOmJvYg==".to_string());
+
+        for credentials in [bob, gone, garbage, not_bearer] {
+            let (status, body) = app
+                .call(Method::GET, "/api/v1/products", &credentials, None)
+                .await;
+            assert_eq!(status, 401, "{}", credentials.1);
+            assert_eq!(body["error"]["code"], "invalid_token", "{}", credentials.1);
+        }
+
+        let anonymous = ("Accept", "application/json".to_string());
+        let (status, body) = app
+            .call(Method::GET, "/api/v1/products", &anonymous, None)
+            .await;
+        assert_eq!(
+            (status, &body["error"]["code"]),
+            (401, &json!("unauthorized"))
+        );
+    }
+
+    #[tokio::test]
+    async fn read_only_tokens_only_read() {
+        let app = TestApp::start().await;
+        let id = app.track("bob", PS5).id;
+        let path = format!("/api/v1/products/{id}");
+        let bob = app.token("bob", TokenScope::ReadOnly);
+
+        let (status, _) = app.call(Method::GET, &path, &bob, None).await;
+        assert_eq!(status, 200);
+        let subscription = format!("{path}/subscription");
+        for (method, path) in [
+            (Method::POST, "/api/v1/products"),
+            (Method::PUT, path.as_str()),
+            (Method::PUT, subscription.as_str()),
+            (Method::DELETE, path.as_str()),
+        ] {
+            let (status, body) = app
+                .call(method.clone(), path, &bob, Some(&product(PS5)))
+                .await;
+            assert_eq!(status, 403, "{method} {path}");
+            assert_eq!(
+                body["error"]["code"], "insufficient_scope",
+                "{method} {path}"
+            );
+        }
+        assert!(app.state.products.get(id).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn records_when_tokens_are_used() {
+        let app = TestApp::start().await;
+        let bob = app.token("bob", TokenScope::ReadOnly);
+        let last_used = || app.state.tokens.list_for_user("bob").unwrap()[0].last_used_at;
+        assert_eq!(last_used(), None);
+
+        let before = std::time::SystemTime::now();
+        let (status, _) = app.call(Method::GET, "/api/v1/products", &bob, None).await;
+        assert_eq!(status, 200);
+        assert!(last_used().is_some_and(|used| used >= before));
+    }
+}
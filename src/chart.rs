@@ -0,0 +1,151 @@
+use crate::money::{Currency, Money};
+use crate::storage::{Observation, StockState};
+use maud::{Markup, html};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Chart geometry, in SVG user units. The SVG scales to the width of its container.
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 280.0;
+const LEFT: f64 = 72.0;
+const RIGHT: f64 = 16.0;
+const TOP: f64 = 16.0;
+// Room under the plot for the stock strip and the time axis labels
+const BOTTOM: f64 = 52.0;
+const STRIP_HEIGHT: f64 = 8.0;
+const GRID_LINES: usize = 4;
+
+/// Line chart of a product's price over time, rendered as inline SVG.
+///
+/// Price points are coloured by stock state, a strip along the bottom shows stock state
+/// between checks, and `target` (if any) is drawn as a dashed line. Failed checks are left
+/// out, and so are prices in anything but `currency`, which couldn't share an axis.
+pub fn price_chart(
+    observations: &[Observation],
+    currency: Currency,
+    target: Option<Money>,
+) -> Markup {
+    let checks: Vec<&Observation> = observations.iter().filter(|o| o.error.is_none()).collect();
+    // Plotted in major units
+    let price = |o: &Observation| {
+        o.price
+            .filter(|p| p.currency() == currency)
+            .map(Money::to_major)
+    };
+    let target = target
+        .filter(|t| t.currency() == currency)
+        .map(Money::to_major);
+    let prices: Vec<f64> = checks.iter().filter_map(|o| price(o)).collect();
+    let (Some(first), Some(last)) = (checks.first(), checks.last()) else {
+        return html! {
+            p class="text-center py-10 text-gray-500" { "No successful checks yet" }
+        };
+    };
+
+    let (low, high) = price_range(&prices, target);
+    let start = seconds(first.observed_at);
+    let span = seconds(last.observed_at) - start;
+    let plot_width = WIDTH - LEFT - RIGHT;
+    let plot_height = HEIGHT - TOP - BOTTOM;
+
+    let x = |time: SystemTime| {
+        if span > 0.0 {
+            LEFT + (seconds(time) - start) / span * plot_width
+        } else {
+            LEFT + plot_width / 2.0
+        }
+    };
+    let y = |price: f64| TOP + (high - price) / (high - low) * plot_height;
+
+    let line = checks
+        .iter()
+        .filter_map(|o| price(o).map(|price| format!("{:.1},{:.1}", x(o.observed_at), y(price))))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    // Each check's stock state holds until the next check
+    let strip_top = TOP + plot_height + 6.0;
+    let strip: Vec<(f64, f64, StockState)> = checks
+        .iter()
+        .enumerate()
+        .map(|(i, o)| {
+            let from = x(o.observed_at);
+            let to = checks
+                .get(i + 1)
+                .map_or(LEFT + plot_width, |next| x(next.observed_at));
+            (from, (to - from).max(2.0), o.stock)
+        })
+        .collect();
+
+    html! {
+        svg viewBox=(format!("0 0 {WIDTH} {HEIGHT}")) class="w-full h-auto" role="img" aria-label="Price history chart" {
+            // Horizontal grid lines with price labels
+            @for i in 0..=GRID_LINES {
+                @let price = low + (high - low) * i as f64 / GRID_LINES as f64;
+                line x1=(LEFT) x2=(WIDTH - RIGHT) y1=(y(price)) y2=(y(price)) stroke="#e5e7eb" stroke-width="1" {}
+                text x=(LEFT - 8.0) y=(y(price) + 4.0) text-anchor="end" font-size="12" fill="#6b7280" {
+                    (label(price, currency))
+                }
+            }
+
+            @if let Some(target) = target {
+                line x1=(LEFT) x2=(WIDTH - RIGHT) y1=(y(target)) y2=(y(target)) stroke="#6366f1" stroke-width="1.5" stroke-dasharray="6 4" {}
+                text x=(WIDTH - RIGHT) y=(y(target) - 6.0) text-anchor="end" font-size="12" fill="#6366f1" { "Target" }
+            }
+
+            polyline points=(line) fill="none" stroke="#4f46e5" stroke-width="2" stroke-linejoin="round" {}
+
+            @for check in &checks {
+                @if let Some(price) = price(check) {
+                    circle cx=(format!("{:.1}", x(check.observed_at))) cy=(format!("{:.1}", y(price))) r="3.5" fill=(stock_color(check.stock)) {
+                        title { (crate::format_time(check.observed_at)) ": " (label(price, currency)) " · " (check.stock.label()) }
+                    }
+                }
+            }
+
+            @for (from, width, stock) in &strip {
+                rect x=(format!("{:.1}", from)) y=(strip_top) width=(format!("{:.1}", width)) height=(STRIP_HEIGHT) fill=(stock_color(*stock)) {}
+            }
+
+            text x=(LEFT) y=(HEIGHT - 8.0) text-anchor="start" font-size="12" fill="#6b7280" {
+                (crate::format_time(first.observed_at))
+            }
+            @if span > 0.0 {
+                text x=(WIDTH - RIGHT) y=(HEIGHT - 8.0) text-anchor="end" font-size="12" fill="#6b7280" {
+                    (crate::format_time(last.observed_at))
+                }
+            }
+        }
+    }
+}
+
+// The price range covered by the y axis, padded so the line doesn't touch the edges
+fn price_range(prices: &[f64], target: Option<f64>) -> (f64, f64) {
+    let values = || prices.iter().copied().chain(target);
+    let low = values().reduce(f64::min).unwrap_or(0.0);
+    let high = values().reduce(f64::max).unwrap_or(0.0);
+
+    if high - low < 0.01 {
+        ((low - 1.0).max(0.0), high + 1.0)
+    } else {
+        let padding = (high - low) * 0.1;
+        ((low - padding).max(0.0), high + padding)
+    }
+}
+
+// A price in major units as written for `currency`
+fn label(price: f64, currency: Currency) -> String {
+    Money::from_major(price, currency).map_or_else(String::new, |price| price.to_string())
+}
+
+fn stock_color(stock: StockState) -> &'static str {
+    match stock {
+        StockState::InStock => "#16a34a",
+        StockState::OutOfStock => "#dc2626",
+        StockState::Unknown => "#9ca3af",
+    }
+}
+
+fn seconds(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64())
+}
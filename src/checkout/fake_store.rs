@@ -0,0 +1,301 @@
+//! A stand-in retailer for testing auto-buy end to end: a local HTTP server with a product
+//! page and a JSON checkout API, and the `Retailer` whose checkout steps drive it.
+
+use super::{Cart, CheckoutSteps, NotCharged, PendingOrder, StepFuture};
+use crate::money::{Currency, Money};
+use crate::retailers::{DEFAULT_STYLE, Retailer, RetailerStyle};
+use crate::storage::Product;
+use anyhow::Context;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+
+pub const NAME: &str = "Fake Store";
+
+/// How the store answers an order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrderOutcome {
+    Placed,
+    /// Payment refused, nothing ordered
+    Declined,
+    /// The order goes through but the response is an error, like a timeout after charging
+    FailsAfterPlacing,
+}
+
+#[derive(Debug)]
+struct Shop {
+    price: Money,
+    in_stock: bool,
+    /// Added on at checkout, in percent
+    tax_percent: i64,
+    order_outcome: OrderOutcome,
+    /// Quantity in each cart, by cart number
+    carts: Vec<u32>,
+    /// Total of each checkout
+    checkouts: Vec<Money>,
+    orders: Vec<String>,
+    requests: Vec<String>,
+}
+
+type Shared = Arc<Mutex<Shop>>;
+
+/// The server, selling one product in Canadian dollars
+pub struct FakeStore {
+    base_url: String,
+    shop: Shared,
+}
+
+impl FakeStore {
+    /// Start selling the product at `price`, in stock and without tax
+    pub async fn start(price: &str) -> FakeStore {
+        let shop = Arc::new(Mutex::new(Shop {
+            price: Money::parse(price, Currency::Cad).unwrap(),
+            in_stock: true,
+            tax_percent: 0,
+            order_outcome: OrderOutcome::Placed,
+            carts: Vec::new(),
+            checkouts: Vec::new(),
+            orders: Vec::new(),
+            requests: Vec::new(),
+        }));
+        let app = Router::new()
+            .route("/products/{sku}", get(product_page))
+            .route("/api/cart", post(add_to_cart))
+            .route("/api/checkout", post(checkout))
+            .route("/api/orders", post(place_order))
+            .with_state(shop.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        FakeStore { base_url, shop }
+    }
+
+    pub fn product_url(&self) -> String {
+        format!("{}/products/gpu-1", self.base_url)
+    }
+
+    /// The retailer to register for the store
+    pub fn retailer(&self) -> FakeRetailer {
+        FakeRetailer {
+            base_url: self.base_url.clone(),
+        }
+    }
+
+    pub fn set_in_stock(&self, in_stock: bool) {
+        self.shop.lock().unwrap().in_stock = in_stock;
+    }
+
+    pub fn set_tax_percent(&self, percent: i64) {
+        self.shop.lock().unwrap().tax_percent = percent;
+    }
+
+    pub fn set_order_outcome(&self, outcome: OrderOutcome) {
+        self.shop.lock().unwrap().order_outcome = outcome;
+    }
+
+    /// Every request the store has answered, e.g. "POST /api/cart"
+    pub fn requests(&self) -> Vec<String> {
+        self.shop.lock().unwrap().requests.clone()
+    }
+
+    /// Numbers of the orders placed so far
+    pub fn orders(&self) -> Vec<String> {
+        self.shop.lock().unwrap().orders.clone()
+    }
+}
+
+async fn product_page(State(shop): State<Shared>, Path(sku): Path<String>) -> String {
+    let mut shop = shop.lock().unwrap();
+    shop.requests.push(format!("GET /products/{sku}"));
+    let availability = if shop.in_stock {
+        "https://schema.org/InStock"
+    } else {
+        "https://schema.org/OutOfStock"
+    };
+    let markup = json!({
+        "@context": "https://schema.org",
+        "@type": "Product",
+        "name": "Graphics Card",
+        "sku": sku,
+        "offers": {
+            "@type": "Offer",
+            "price": shop.price.amount(),
+            "priceCurrency": "CAD",
+            "availability": availability,
+        },
+    });
+    format!(
+        "<html><head><script type=\"application/ld+json\">{markup}</script></head><body></body></html>"
+    )
+}
+
+async fn add_to_cart(
+    State(shop): State<Shared>,
+    Json(body): Json<Value>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut shop = shop.lock().unwrap();
+    shop.requests.push("POST /api/cart".to_string());
+    if !shop.in_stock {
+        return Err(StatusCode::CONFLICT);
+    }
+    let quantity = body["quantity"].as_u64().ok_or(StatusCode::BAD_REQUEST)? as u32;
+    shop.carts.push(quantity);
+    let subtotal = Money::new(shop.price.minor() * i64::from(quantity), Currency::Cad);
+    Ok(Json(json!({
+        "cart": shop.carts.len(),
+        "quantity": quantity,
+        "subtotal": subtotal.amount(),
+    })))
+}
+
+async fn checkout(
+    State(shop): State<Shared>,
+    Json(body): Json<Value>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut shop = shop.lock().unwrap();
+    shop.requests.push("POST /api/checkout".to_string());
+    let cart = body["cart"].as_u64().ok_or(StatusCode::BAD_REQUEST)? as usize;
+    let quantity = *shop.carts.get(cart - 1).ok_or(StatusCode::NOT_FOUND)?;
+    let subtotal = shop.price.minor() * i64::from(quantity);
+    let total = Money::new(subtotal + subtotal * shop.tax_percent / 100, Currency::Cad);
+    shop.checkouts.push(total);
+    Ok(Json(json!({
+        "checkout": shop.checkouts.len(),
+        "total": total.amount(),
+    })))
+}
+
+async fn place_order(
+    State(shop): State<Shared>,
+    Json(body): Json<Value>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut shop = shop.lock().unwrap();
+    shop.requests.push("POST /api/orders".to_string());
+    let checkout = body["checkout"].as_u64().ok_or(StatusCode::BAD_REQUEST)? as usize;
+    if checkout == 0 || checkout > shop.checkouts.len() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if shop.order_outcome == OrderOutcome::Declined {
+        return Err(StatusCode::PAYMENT_REQUIRED);
+    }
+    let order_number = format!("FS-{}", 1000 + shop.orders.len() + 1);
+    shop.orders.push(order_number.clone());
+    if shop.order_outcome == OrderOutcome::FailsAfterPlacing {
+        return Err(StatusCode::GATEWAY_TIMEOUT);
+    }
+    Ok(Json(json!({ "order_number": order_number })))
+}
+
+/// Checks out at a `FakeStore` through its API
+pub struct FakeRetailer {
+    base_url: String,
+}
+
+impl FakeRetailer {
+    async fn post(
+        &self,
+        client: &reqwest::Client,
+        path: &str,
+        body: Value,
+    ) -> anyhow::Result<Value> {
+        let response = client
+            .post(format!("{}{}", self.base_url, path))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(serde_json::from_slice(&response.bytes().await?)?)
+    }
+}
+
+fn amount(value: &Value) -> anyhow::Result<Money> {
+    let text = value.as_str().context("missing amount")?;
+    Money::parse(text, Currency::Cad).map_err(|e| anyhow::anyhow!("bad amount {text}: {e:?}"))
+}
+
+impl Retailer for FakeRetailer {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn domains(&self) -> &'static [&'static str] {
+        &["localhost"]
+    }
+
+    fn currency(&self) -> Currency {
+        Currency::Cad
+    }
+
+    fn style(&self) -> RetailerStyle {
+        DEFAULT_STYLE
+    }
+
+    fn checkout(&self) -> Option<&dyn CheckoutSteps> {
+        Some(self)
+    }
+}
+
+impl CheckoutSteps for FakeRetailer {
+    fn add_to_cart<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        _product: &'a Product,
+        quantity: u32,
+    ) -> StepFuture<'a, Cart> {
+        Box::pin(async move {
+            let cart = self
+                .post(client, "/api/cart", json!({ "quantity": quantity }))
+                .await?;
+            Ok(Cart {
+                id: cart["cart"].to_string(),
+                quantity: cart["quantity"].as_u64().context("missing quantity")? as u32,
+                subtotal: amount(&cart["subtotal"])?,
+            })
+        })
+    }
+
+    fn checkout<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        cart: &'a Cart,
+    ) -> StepFuture<'a, PendingOrder> {
+        Box::pin(async move {
+            let cart: u64 = cart.id.parse()?;
+            let checkout = self
+                .post(client, "/api/checkout", json!({ "cart": cart }))
+                .await?;
+            Ok(PendingOrder {
+                id: checkout["checkout"].to_string(),
+                total: amount(&checkout["total"])?,
+            })
+        })
+    }
+
+    fn confirm<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        order: &'a PendingOrder,
+    ) -> StepFuture<'a, String> {
+        Box::pin(async move {
+            let checkout: u64 = order.id.parse()?;
+            let response = self
+                .post(client, "/api/orders", json!({ "checkout": checkout }))
+                .await;
+            let declined = response.as_ref().err().and_then(|e| {
+                e.downcast_ref::<reqwest::Error>()
+                    .and_then(reqwest::Error::status)
+            }) == Some(reqwest::StatusCode::PAYMENT_REQUIRED);
+            if declined {
+                return Err(NotCharged("Payment declined".to_string()).into());
+            }
+            Ok(response?["order_number"]
+                .as_str()
+                .context("missing order number")?
+                .to_string())
+        })
+    }
+}
@@ -0,0 +1,1110 @@
+#[cfg(test)]
+pub mod fake_store;
+
+use crate::money::Money;
+use crate::retailers::RetailerRegistry;
+use crate::scheduler;
+use crate::storage::{Alert, CheckoutRepository, Observation, Product, StockState, Subscription};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+// Names of the steps every checkout goes through, as logged
+const CHECK_LIMITS: &str = "Check limits";
+const ADD_TO_CART: &str = "Add to cart";
+const CHECKOUT: &str = "Checkout";
+const PLACE_ORDER: &str = "Place order";
+
+#[derive(Debug, Clone)]
+pub struct CheckoutConfig {
+    /// Whether orders are actually placed. Until this is turned on every run is a dry run,
+    /// whatever the rule says.
+    pub live_orders: bool,
+    /// The most of a product one order can be for
+    pub max_quantity: u32,
+    /// Timeout for each request to the retailer
+    pub request_timeout: Duration,
+}
+
+impl Default for CheckoutConfig {
+    fn default() -> Self {
+        CheckoutConfig {
+            live_orders: false,
+            max_quantity: 2,
+            request_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A user's standing instruction to buy a product they watch as soon as it's in stock at or
+/// below their target price
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoBuyRule {
+    pub product_id: i64,
+    pub username: String,
+    pub enabled: bool,
+    /// Go through checkout but stop before paying
+    pub dry_run: bool,
+    /// How many to order, as far as `max_spend` stretches
+    pub max_quantity: u32,
+    /// The most an order may cost with taxes and shipping, in the product's currency
+    pub max_spend: Money,
+    pub updated_at: SystemTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AutoBuyError {
+    /// Only subscribers can have a product bought for them
+    NotWatching,
+    /// Auto-buy fires at the target price, so there has to be one
+    NoTargetPrice,
+    /// The retailer has no checkout steps
+    Unsupported,
+    InvalidMaxSpend,
+    /// A max spend below the target could never buy anything
+    MaxSpendBelowTarget,
+    InvalidQuantity,
+}
+
+impl AutoBuyError {
+    pub const ALL: [AutoBuyError; 6] = [
+        AutoBuyError::NotWatching,
+        AutoBuyError::NoTargetPrice,
+        AutoBuyError::Unsupported,
+        AutoBuyError::InvalidMaxSpend,
+        AutoBuyError::MaxSpendBelowTarget,
+        AutoBuyError::InvalidQuantity,
+    ];
+
+    /// Short identifier used in redirect query strings
+    pub fn code(self) -> &'static str {
+        match self {
+            AutoBuyError::NotWatching => "auto_buy_not_watching",
+            AutoBuyError::NoTargetPrice => "auto_buy_no_target",
+            AutoBuyError::Unsupported => "auto_buy_unsupported",
+            AutoBuyError::InvalidMaxSpend => "invalid_max_spend",
+            AutoBuyError::MaxSpendBelowTarget => "max_spend_below_target",
+            AutoBuyError::InvalidQuantity => "invalid_max_quantity",
+        }
+    }
+
+    pub fn parse(code: &str) -> Option<AutoBuyError> {
+        AutoBuyError::ALL.into_iter().find(|e| e.code() == code)
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            AutoBuyError::NotWatching => "Watch the product before turning on auto-buy.",
+            AutoBuyError::NoTargetPrice => {
+                "Set a target price first. Auto-buy orders when the price reaches it."
+            }
+            AutoBuyError::Unsupported => "Auto-buy isn't available for this retailer.",
+            AutoBuyError::InvalidMaxSpend => {
+                "The max spend must be an amount like 1,500.00 in the retailer's currency."
+            }
+            AutoBuyError::MaxSpendBelowTarget => {
+                "The max spend must be at least your target price, or nothing could be bought."
+            }
+            AutoBuyError::InvalidQuantity => {
+                "The quantity must be a whole number within the limit."
+            }
+        }
+    }
+}
+
+/// How a checkout run ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckoutStatus {
+    /// The order was placed and paid for
+    Ordered,
+    /// Everything up to payment went through, and the run stopped there as asked
+    DryRun,
+    /// A limit or safety check stopped the run before it spent anything
+    Stopped,
+    /// A step failed at the retailer
+    Failed,
+}
+
+impl CheckoutStatus {
+    pub const ALL: [CheckoutStatus; 4] = [
+        CheckoutStatus::Ordered,
+        CheckoutStatus::DryRun,
+        CheckoutStatus::Stopped,
+        CheckoutStatus::Failed,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CheckoutStatus::Ordered => "ordered",
+            CheckoutStatus::DryRun => "dry_run",
+            CheckoutStatus::Stopped => "stopped",
+            CheckoutStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<CheckoutStatus> {
+        CheckoutStatus::ALL
+            .into_iter()
+            .find(|s| s.as_str() == status)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CheckoutStatus::Ordered => "Order placed",
+            CheckoutStatus::DryRun => "Dry run",
+            CheckoutStatus::Stopped => "Stopped",
+            CheckoutStatus::Failed => "Failed",
+        }
+    }
+}
+
+/// One step of a checkout run, as logged
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckoutStep {
+    pub at: SystemTime,
+    pub name: String,
+    pub ok: bool,
+    pub message: String,
+}
+
+/// One attempt at buying a product for a user, with every step it took
+#[derive(Debug, Clone)]
+pub struct CheckoutRun {
+    pub id: i64,
+    pub product_id: i64,
+    pub username: String,
+    pub started_at: SystemTime,
+    pub dry_run: bool,
+    pub status: CheckoutStatus,
+    /// How many were put in the cart; 0 if it never got that far
+    pub quantity: u32,
+    /// The order total the retailer quoted at checkout
+    pub total: Option<Money>,
+    pub order_number: Option<String>,
+    pub steps: Vec<CheckoutStep>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewCheckoutRun {
+    pub product_id: i64,
+    pub username: String,
+    pub started_at: SystemTime,
+    pub dry_run: bool,
+    pub status: CheckoutStatus,
+    pub quantity: u32,
+    pub total: Option<Money>,
+    pub order_number: Option<String>,
+    pub steps: Vec<CheckoutStep>,
+    /// What to set the rule's `enabled` to along with recording the run; `None` leaves it
+    pub rule_enabled: Option<bool>,
+}
+
+/// A cart holding the product, as the retailer reports it
+#[derive(Debug, Clone)]
+pub struct Cart {
+    pub id: String,
+    pub quantity: u32,
+    pub subtotal: Money,
+}
+
+/// An order that's been through checkout and only needs paying for
+#[derive(Debug, Clone)]
+pub struct PendingOrder {
+    pub id: String,
+    /// Everything included: taxes, shipping, fees
+    pub total: Money,
+}
+
+/// A step failed in a way that's known not to have charged anything, like a declined card.
+/// `confirm` returns this so the rule can stay on for the next try.
+#[derive(Debug)]
+pub struct NotCharged(pub String);
+
+impl std::fmt::Display for NotCharged {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NotCharged {}
+
+// Whether a failed `confirm` certainly didn't place the order: the retailer said so, or the
+// request never reached it
+fn not_charged(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<NotCharged>().is_some()
+        || error
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_connect() || e.is_builder())
+}
+
+pub type StepFuture<'a, T> = Pin<Box<dyn Future<Output = anyhow::Result<T>> + Send + 'a>>;
+
+/// A retailer's way through checkout. Each step is its own call, so the workflow can log it
+/// and check the amounts before going on, and a dry run can stop before `confirm`.
+pub trait CheckoutSteps: Send + Sync {
+    /// Put `quantity` of the product in a new cart
+    fn add_to_cart<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        product: &'a Product,
+        quantity: u32,
+    ) -> StepFuture<'a, Cart>;
+
+    /// Take the cart through checkout up to payment, where the final total is known
+    fn checkout<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        cart: &'a Cart,
+    ) -> StepFuture<'a, PendingOrder>;
+
+    /// Pay for the order, returning the retailer's order number. Fail with `NotCharged` when
+    /// the retailer refused the order outright; any other error is taken to mean it may have
+    /// gone through.
+    fn confirm<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        order: &'a PendingOrder,
+    ) -> StepFuture<'a, String>;
+}
+
+/// Buys products for users who turned on auto-buy, when a check finds them in stock at or
+/// below their target price.
+///
+/// Every run is logged step by step. Limits are checked before anything goes into a cart
+/// and again against each amount the retailer quotes, and a run stops at the first one that
+/// doesn't hold. A rule turns itself off just before paying and only comes back on if
+/// paying certainly failed, so a product is bought at most once per time it's turned on,
+/// even when the retailer times out after charging.
+pub struct AutoBuyer {
+    checkouts: Arc<dyn CheckoutRepository>,
+    retailers: Arc<RetailerRegistry>,
+    client: reqwest::Client,
+    config: CheckoutConfig,
+}
+
+impl AutoBuyer {
+    pub fn new(
+        checkouts: Arc<dyn CheckoutRepository>,
+        retailers: Arc<RetailerRegistry>,
+        config: CheckoutConfig,
+    ) -> anyhow::Result<Self> {
+        Ok(AutoBuyer {
+            checkouts,
+            retailers,
+            client: scheduler::page_client(config.request_timeout)?,
+            config,
+        })
+    }
+
+    pub fn config(&self) -> &CheckoutConfig {
+        &self.config
+    }
+
+    /// Whether any registered retailer can check out. None of the built-in ones can yet, and
+    /// auto-buy stays out of the UI until one does.
+    pub fn available(&self) -> bool {
+        self.retailers.all().any(|r| r.checkout().is_some())
+    }
+
+    /// Whether products at `retailer` can be bought automatically
+    pub fn supports(&self, retailer: &str) -> bool {
+        self.retailers
+            .get(retailer)
+            .is_some_and(|r| r.checkout().is_some())
+    }
+
+    /// Check an auto-buy rule as entered, with `max_spend` as typed
+    pub fn rule(
+        &self,
+        product: &Product,
+        subscription: Option<&Subscription>,
+        max_spend: &str,
+        max_quantity: u32,
+        dry_run: bool,
+    ) -> Result<AutoBuyRule, AutoBuyError> {
+        let Some(subscription) = subscription else {
+            return Err(AutoBuyError::NotWatching);
+        };
+        if !self.supports(&product.retailer) {
+            return Err(AutoBuyError::Unsupported);
+        }
+        let Some(target) = subscription.target_price else {
+            return Err(AutoBuyError::NoTargetPrice);
+        };
+        let max_spend = Money::parse(max_spend, product.currency)
+            .ok()
+            .filter(|spend| spend.minor() > 0)
+            .ok_or(AutoBuyError::InvalidMaxSpend)?;
+        if max_spend < target {
+            return Err(AutoBuyError::MaxSpendBelowTarget);
+        }
+        if !(1..=self.config.max_quantity).contains(&max_quantity) {
+            return Err(AutoBuyError::InvalidQuantity);
+        }
+
+        Ok(AutoBuyRule {
+            product_id: product.id,
+            username: subscription.username.clone(),
+            enabled: true,
+            dry_run,
+            max_quantity,
+            max_spend,
+            updated_at: SystemTime::now(),
+        })
+    }
+
+    /// Run checkout for every subscriber with auto-buy on who was just alerted to the product
+    /// being in stock at or below their target, returning the runs. A subscriber whose run
+    /// can't be loaded or recorded is logged and skipped.
+    pub async fn handle(
+        &self,
+        product: &Product,
+        subscriptions: &[Subscription],
+        observation: &Observation,
+        alerts: &[Alert],
+    ) -> Vec<CheckoutRun> {
+        let price = observation
+            .price
+            .filter(|_| observation.error.is_none() && observation.stock == StockState::InStock);
+        let Some(price) = price else {
+            return Vec::new();
+        };
+
+        let mut runs = Vec::new();
+        for subscription in subscriptions {
+            let alerted = alerts.iter().any(|a| a.is_for(&subscription.username));
+            let at_target = subscription
+                .target_price
+                .is_some_and(|target| price <= target);
+            if !alerted || !at_target {
+                continue;
+            }
+            let rule = match self.checkouts.rule(product.id, &subscription.username) {
+                Ok(rule) => rule,
+                Err(e) => {
+                    warn!(
+                        "Failed to load auto-buy rule - product id: {}, username: {}, error: {:#}",
+                        product.id, subscription.username, e
+                    );
+                    continue;
+                }
+            };
+            let Some(rule) = rule.filter(|rule| rule.enabled) else {
+                continue;
+            };
+            let Some(steps) = self
+                .retailers
+                .get(&product.retailer)
+                .and_then(|r| r.checkout())
+            else {
+                warn!(
+                    "Auto-buy skipped, retailer can't check out - product id: {}, retailer: {}",
+                    product.id, product.retailer
+                );
+                continue;
+            };
+            match self.run(product, steps, &rule, price, observation).await {
+                Ok(run) => runs.push(run),
+                Err(e) => warn!(
+                    "Failed to record auto-buy run - product id: {}, username: {}, error: {:#}",
+                    product.id, subscription.username, e
+                ),
+            }
+        }
+        runs
+    }
+
+    async fn run(
+        &self,
+        product: &Product,
+        steps: &dyn CheckoutSteps,
+        rule: &AutoBuyRule,
+        price: Money,
+        observation: &Observation,
+    ) -> anyhow::Result<CheckoutRun> {
+        let dry_run = rule.dry_run || !self.config.live_orders;
+        info!(
+            "Auto-buy started - product id: {}, name: {}, username: {}, price: {}, dry run: {}",
+            product.id, product.name, rule.username, price, dry_run
+        );
+        let mut log = RunLog {
+            product,
+            rule,
+            quantity: 0,
+            total: None,
+            order_number: None,
+            steps: Vec::new(),
+            rule_enabled: None,
+        };
+        let started_at = SystemTime::now();
+        let status = self
+            .checkout(&mut log, steps, price, observation, dry_run)
+            .await;
+
+        let run = self.checkouts.record_run(NewCheckoutRun {
+            product_id: product.id,
+            username: rule.username.clone(),
+            started_at,
+            dry_run,
+            status,
+            quantity: log.quantity,
+            total: log.total,
+            order_number: log.order_number,
+            steps: log.steps,
+            rule_enabled: log.rule_enabled,
+        })?;
+        info!(
+            "Auto-buy finished - product id: {}, username: {}, status: {}, order: {:?}",
+            product.id,
+            rule.username,
+            status.as_str(),
+            run.order_number
+        );
+        Ok(run)
+    }
+
+    // Take the product through the retailer's checkout, logging each step, as far as the
+    // limits allow
+    async fn checkout(
+        &self,
+        log: &mut RunLog<'_>,
+        steps: &dyn CheckoutSteps,
+        price: Money,
+        observation: &Observation,
+        dry_run: bool,
+    ) -> CheckoutStatus {
+        let (product, rule) = (log.product, log.rule);
+        // Also false when the amounts are in different currencies
+        let within_limit = |amount: Money| amount <= rule.max_spend;
+
+        if observation.third_party_seller {
+            let seller = observation
+                .sold_by
+                .as_deref()
+                .unwrap_or("a third-party seller");
+            log.step(
+                CHECK_LIMITS,
+                false,
+                format!("Sold by {}, not {}", seller, product.retailer),
+            );
+            return CheckoutStatus::Stopped;
+        }
+        let affordable = if price.minor() > 0 && price.currency() == rule.max_spend.currency() {
+            rule.max_spend.minor() / price.minor()
+        } else {
+            0
+        };
+        let quantity = rule
+            .max_quantity
+            .min(self.config.max_quantity)
+            .min(u32::try_from(affordable).unwrap_or(u32::MAX));
+        if quantity == 0 {
+            log.step(
+                CHECK_LIMITS,
+                false,
+                format!("{} is over the max spend of {}", price, rule.max_spend),
+            );
+            return CheckoutStatus::Stopped;
+        }
+        log.step(
+            CHECK_LIMITS,
+            true,
+            format!(
+                "Buying {} at {} each, spending at most {}",
+                quantity, price, rule.max_spend
+            ),
+        );
+
+        let cart = match steps.add_to_cart(&self.client, product, quantity).await {
+            Ok(cart) => cart,
+            Err(e) => {
+                log.step(ADD_TO_CART, false, format!("{:#}", e));
+                return CheckoutStatus::Failed;
+            }
+        };
+        log.quantity = cart.quantity;
+        if cart.quantity != quantity {
+            log.step(
+                ADD_TO_CART,
+                false,
+                format!(
+                    "Cart {} holds {} instead of {}",
+                    cart.id, cart.quantity, quantity
+                ),
+            );
+            return CheckoutStatus::Stopped;
+        }
+        if !within_limit(cart.subtotal) {
+            log.step(
+                ADD_TO_CART,
+                false,
+                format!(
+                    "Cart subtotal {} is over the max spend of {}",
+                    cart.subtotal, rule.max_spend
+                ),
+            );
+            return CheckoutStatus::Stopped;
+        }
+        log.step(
+            ADD_TO_CART,
+            true,
+            format!(
+                "Cart {} holds {} for {}",
+                cart.id, cart.quantity, cart.subtotal
+            ),
+        );
+
+        let order = match steps.checkout(&self.client, &cart).await {
+            Ok(order) => order,
+            Err(e) => {
+                log.step(CHECKOUT, false, format!("{:#}", e));
+                return CheckoutStatus::Failed;
+            }
+        };
+        log.total = Some(order.total);
+        if !within_limit(order.total) {
+            log.step(
+                CHECKOUT,
+                false,
+                format!(
+                    "Order total {} is over the max spend of {}",
+                    order.total, rule.max_spend
+                ),
+            );
+            return CheckoutStatus::Stopped;
+        }
+        log.step(
+            CHECKOUT,
+            true,
+            format!("Order {} comes to {}", order.id, order.total),
+        );
+
+        if dry_run {
+            log.step(
+                PLACE_ORDER,
+                true,
+                "Dry run, stopped before paying".to_string(),
+            );
+            return CheckoutStatus::DryRun;
+        }
+        // Off before paying, so a charge the retailer doesn't confirm can't be repeated. Only
+        // `enabled` is written, the user may have changed the rest since the run read it.
+        if let Err(e) = self
+            .checkouts
+            .set_rule_enabled(rule.product_id, &rule.username, false)
+        {
+            log.step(
+                PLACE_ORDER,
+                false,
+                format!("Couldn't turn auto-buy off before paying: {:#}", e),
+            );
+            return CheckoutStatus::Failed;
+        }
+        log.rule_enabled = Some(false);
+        match steps.confirm(&self.client, &order).await {
+            Ok(order_number) => {
+                log.step(PLACE_ORDER, true, format!("Placed order {}", order_number));
+                log.order_number = Some(order_number);
+                CheckoutStatus::Ordered
+            }
+            Err(e) if not_charged(&e) => {
+                log.step(PLACE_ORDER, false, format!("{:#}", e));
+                log.rule_enabled = Some(true);
+                CheckoutStatus::Failed
+            }
+            Err(e) => {
+                log.step(
+                    PLACE_ORDER,
+                    false,
+                    format!(
+                        "{:#}. The order may have gone through, so auto-buy is off until it's checked and turned back on.",
+                        e
+                    ),
+                );
+                CheckoutStatus::Failed
+            }
+        }
+    }
+}
+
+// What a run has done so far
+struct RunLog<'a> {
+    product: &'a Product,
+    rule: &'a AutoBuyRule,
+    quantity: u32,
+    total: Option<Money>,
+    order_number: Option<String>,
+    steps: Vec<CheckoutStep>,
+    rule_enabled: Option<bool>,
+}
+
+impl RunLog<'_> {
+    fn step(&mut self, name: &str, ok: bool, message: String) {
+        if ok {
+            info!(
+                "Auto-buy step - product id: {}, username: {}, step: {}, message: {}",
+                self.product.id, self.rule.username, name, message
+            );
+        } else {
+            warn!(
+                "Auto-buy step failed - product id: {}, username: {}, step: {}, message: {}",
+                self.product.id, self.rule.username, name, message
+            );
+        }
+        self.steps.push(CheckoutStep {
+            at: SystemTime::now(),
+            name: name.to_string(),
+            ok,
+            message,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fake_store::{FakeStore, NAME, OrderOutcome};
+    use super::*;
+    use crate::money::Currency;
+    use crate::storage::{AlertKind, InMemoryCheckoutRepository};
+
+    struct Fixture {
+        store: FakeStore,
+        checkouts: Arc<InMemoryCheckoutRepository>,
+        buyer: AutoBuyer,
+        product: Product,
+    }
+
+    async fn fixture(price: &str, config: CheckoutConfig) -> Fixture {
+        let store = FakeStore::start(price).await;
+        let checkouts = Arc::new(InMemoryCheckoutRepository::default());
+        let retailers = Arc::new(RetailerRegistry::new(vec![Box::new(store.retailer())]));
+        let buyer = AutoBuyer::new(checkouts.clone(), retailers, config).unwrap();
+        let product = Product {
+            id: 1,
+            url: store.product_url(),
+            name: "Graphics Card".to_string(),
+            retailer: NAME.to_string(),
+            retailer_id: None,
+            currency: Currency::Cad,
+            added_by: "alice".to_string(),
+            created_at: SystemTime::now(),
+            poll_interval: None,
+        };
+        Fixture {
+            store,
+            checkouts,
+            buyer,
+            product,
+        }
+    }
+
+    fn live() -> CheckoutConfig {
+        CheckoutConfig {
+            live_orders: true,
+            ..CheckoutConfig::default()
+        }
+    }
+
+    fn cad(amount: &str) -> Money {
+        Money::parse(amount, Currency::Cad).unwrap()
+    }
+
+    fn subscription(target: Option<&str>) -> Subscription {
+        Subscription {
+            product_id: 1,
+            username: "alice".to_string(),
+            target_price: target.map(cad),
+            created_at: SystemTime::now(),
+        }
+    }
+
+    fn observation(price: &str) -> Observation {
+        Observation {
+            id: 1,
+            product_id: 1,
+            observed_at: SystemTime::now(),
+            price: Some(cad(price)),
+            stock: StockState::InStock,
+            error: None,
+            ships_from: None,
+            sold_by: None,
+            third_party_seller: false,
+        }
+    }
+
+    fn alert(price: &str) -> Alert {
+        Alert {
+            id: 1,
+            product_id: 1,
+            username: Some("alice".to_string()),
+            kind: AlertKind::TargetReached,
+            triggered_at: SystemTime::now(),
+            price: Some(cad(price)),
+            previous_price: None,
+            message: "Target reached".to_string(),
+        }
+    }
+
+    impl Fixture {
+        fn turn_on(&self, max_spend: &str, max_quantity: u32, dry_run: bool) {
+            let rule = self
+                .buyer
+                .rule(
+                    &self.product,
+                    Some(&subscription(Some("700.00"))),
+                    max_spend,
+                    max_quantity,
+                    dry_run,
+                )
+                .unwrap();
+            self.checkouts.save_rule(&rule).unwrap();
+        }
+
+        async fn handle(&self, price: &str) -> Vec<CheckoutRun> {
+            self.buyer
+                .handle(
+                    &self.product,
+                    &[subscription(Some("700.00"))],
+                    &observation(price),
+                    &[alert(price)],
+                )
+                .await
+        }
+
+        fn ordered(&self) -> bool {
+            self.store
+                .requests()
+                .iter()
+                .any(|r| r == "POST /api/orders")
+        }
+    }
+
+    #[tokio::test]
+    async fn places_an_order_and_turns_the_rule_off() {
+        let f = fixture("649.99", live()).await;
+        f.turn_on("1,500.00", 2, false);
+
+        let runs = f.handle("649.99").await;
+        assert_eq!(runs.len(), 1);
+        let run = &runs[0];
+        assert_eq!(run.status, CheckoutStatus::Ordered);
+        assert!(!run.dry_run);
+        assert_eq!(run.quantity, 2);
+        assert_eq!(run.total, Some(cad("1299.98")));
+        assert_eq!(run.order_number.as_deref(), Some("FS-1001"));
+        let steps: Vec<_> = run.steps.iter().map(|s| (s.name.as_str(), s.ok)).collect();
+        assert_eq!(
+            steps,
+            [
+                (CHECK_LIMITS, true),
+                (ADD_TO_CART, true),
+                (CHECKOUT, true),
+                (PLACE_ORDER, true)
+            ]
+        );
+        assert_eq!(f.store.orders(), ["FS-1001"]);
+
+        let recorded = f.checkouts.recent_runs(1, "alice", 5).unwrap();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].order_number.as_deref(), Some("FS-1001"));
+        assert!(!f.checkouts.rule(1, "alice").unwrap().unwrap().enabled);
+
+        // Bought once; the next alert leaves it alone
+        assert!(f.handle("649.99").await.is_empty());
+        assert_eq!(f.store.orders().len(), 1);
+    }
+
+    /// Raises the max spend as soon as a run has read the rule, like an edit landing mid-run
+    struct EditedDuringRun(Arc<InMemoryCheckoutRepository>);
+
+    impl CheckoutRepository for EditedDuringRun {
+        fn rule(&self, product_id: i64, username: &str) -> anyhow::Result<Option<AutoBuyRule>> {
+            let rule = self.0.rule(product_id, username)?;
+            if let Some(rule) = &rule {
+                self.0.save_rule(&AutoBuyRule {
+                    max_spend: cad("2,000.00"),
+                    ..rule.clone()
+                })?;
+            }
+            Ok(rule)
+        }
+
+        fn save_rule(&self, rule: &AutoBuyRule) -> anyhow::Result<()> {
+            self.0.save_rule(rule)
+        }
+
+        fn set_rule_enabled(
+            &self,
+            product_id: i64,
+            username: &str,
+            enabled: bool,
+        ) -> anyhow::Result<()> {
+            self.0.set_rule_enabled(product_id, username, enabled)
+        }
+
+        fn record_run(&self, run: NewCheckoutRun) -> anyhow::Result<CheckoutRun> {
+            self.0.record_run(run)
+        }
+
+        fn recent_runs(
+            &self,
+            product_id: i64,
+            username: &str,
+            limit: usize,
+        ) -> anyhow::Result<Vec<CheckoutRun>> {
+            self.0.recent_runs(product_id, username, limit)
+        }
+    }
+
+    #[tokio::test]
+    async fn turning_off_keeps_edits_made_during_the_run() {
+        let f = fixture("649.99", live()).await;
+        f.turn_on("1,500.00", 1, false);
+        let retailers = Arc::new(RetailerRegistry::new(vec![Box::new(f.store.retailer())]));
+        let buyer = AutoBuyer::new(
+            Arc::new(EditedDuringRun(f.checkouts.clone())),
+            retailers,
+            live(),
+        )
+        .unwrap();
+
+        let runs = buyer
+            .handle(
+                &f.product,
+                &[subscription(Some("700.00"))],
+                &observation("649.99"),
+                &[alert("649.99")],
+            )
+            .await;
+        assert_eq!(runs[0].status, CheckoutStatus::Ordered);
+        let rule = f.checkouts.rule(1, "alice").unwrap().unwrap();
+        assert!(!rule.enabled);
+        assert_eq!(rule.max_spend, cad("2,000.00"));
+    }
+
+    #[tokio::test]
+    async fn stays_off_when_paying_may_have_charged() {
+        let f = fixture("649.99", live()).await;
+        f.store.set_order_outcome(OrderOutcome::FailsAfterPlacing);
+        f.turn_on("1,500.00", 1, false);
+
+        let run = &f.handle("649.99").await[0];
+        assert_eq!(run.status, CheckoutStatus::Failed);
+        let last = run.steps.last().unwrap();
+        assert_eq!(last.name, PLACE_ORDER);
+        assert!(
+            last.message.contains("may have gone through"),
+            "{}",
+            last.message
+        );
+        // The store did take the order, so it mustn't be placed again
+        assert_eq!(f.store.orders().len(), 1);
+        assert!(!f.checkouts.rule(1, "alice").unwrap().unwrap().enabled);
+        assert!(f.handle("649.99").await.is_empty());
+        assert_eq!(f.store.orders().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn stays_on_when_payment_is_declined() {
+        let f = fixture("649.99", live()).await;
+        f.store.set_order_outcome(OrderOutcome::Declined);
+        f.turn_on("1,500.00", 1, false);
+
+        let run = &f.handle("649.99").await[0];
+        assert_eq!(run.status, CheckoutStatus::Failed);
+        assert_eq!(run.steps.last().unwrap().message, "Payment declined");
+        assert!(f.store.orders().is_empty());
+        assert!(f.checkouts.rule(1, "alice").unwrap().unwrap().enabled);
+
+        f.store.set_order_outcome(OrderOutcome::Placed);
+        assert_eq!(f.handle("649.99").await[0].status, CheckoutStatus::Ordered);
+        assert_eq!(f.store.orders().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn dry_runs_stop_before_paying() {
+        // Asked for by the rule
+        let f = fixture("649.99", live()).await;
+        f.turn_on("1,500.00", 1, true);
+        let runs = f.handle("649.99").await;
+        assert_eq!(runs[0].status, CheckoutStatus::DryRun);
+        assert!(runs[0].dry_run);
+        assert_eq!(runs[0].total, Some(cad("649.99")));
+        assert_eq!(runs[0].order_number, None);
+        assert!(!f.ordered());
+        // A dry run doesn't use the rule up
+        assert!(f.checkouts.rule(1, "alice").unwrap().unwrap().enabled);
+
+        // Forced while live orders are off, whatever the rule says
+        let f = fixture("649.99", CheckoutConfig::default()).await;
+        f.turn_on("1,500.00", 1, false);
+        let runs = f.handle("649.99").await;
+        assert_eq!(runs[0].status, CheckoutStatus::DryRun);
+        assert!(runs[0].dry_run);
+        assert!(!f.ordered());
+        assert!(f.store.orders().is_empty());
+    }
+
+    #[tokio::test]
+    async fn buys_only_as_many_as_the_limits_allow() {
+        // The max spend covers one
+        let f = fixture("649.99", live()).await;
+        f.turn_on("1,000.00", 2, false);
+        assert_eq!(f.handle("649.99").await[0].quantity, 1);
+
+        // The configured cap wins over a rule saved before it was lowered
+        let f = fixture("100.00", live()).await;
+        f.checkouts
+            .save_rule(&AutoBuyRule {
+                product_id: 1,
+                username: "alice".to_string(),
+                enabled: true,
+                dry_run: false,
+                max_quantity: 5,
+                max_spend: cad("1000.00"),
+                updated_at: SystemTime::now(),
+            })
+            .unwrap();
+        let runs = f.handle("100.00").await;
+        assert_eq!(runs[0].quantity, 2);
+        assert_eq!(runs[0].total, Some(cad("200.00")));
+    }
+
+    #[tokio::test]
+    async fn stops_when_the_total_is_over_the_max_spend() {
+        let f = fixture("649.99", live()).await;
+        f.store.set_tax_percent(13);
+        f.turn_on("700.00", 1, false);
+
+        let run = &f.handle("649.99").await[0];
+        assert_eq!(run.status, CheckoutStatus::Stopped);
+        assert_eq!(run.total, Some(cad("734.48")));
+        let last = run.steps.last().unwrap();
+        assert_eq!(last.name, CHECKOUT);
+        assert!(!last.ok);
+        assert!(
+            last.message.contains("over the max spend"),
+            "{}",
+            last.message
+        );
+        assert!(!f.ordered());
+        assert!(f.checkouts.rule(1, "alice").unwrap().unwrap().enabled);
+    }
+
+    #[tokio::test]
+    async fn stops_before_the_cart_for_third_party_sellers() {
+        let f = fixture("649.99", live()).await;
+        f.turn_on("1,500.00", 1, false);
+        let observation = Observation {
+            sold_by: Some("Gadget Outlet".to_string()),
+            third_party_seller: true,
+            ..observation("649.99")
+        };
+
+        let runs = f
+            .buyer
+            .handle(
+                &f.product,
+                &[subscription(Some("700.00"))],
+                &observation,
+                &[alert("649.99")],
+            )
+            .await;
+        assert_eq!(runs[0].status, CheckoutStatus::Stopped);
+        assert_eq!(runs[0].steps.len(), 1);
+        assert_eq!(
+            runs[0].steps[0].message,
+            "Sold by Gadget Outlet, not Fake Store"
+        );
+        assert!(f.store.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn logs_a_failed_step() {
+        let f = fixture("649.99", live()).await;
+        f.store.set_in_stock(false);
+        f.turn_on("1,500.00", 1, false);
+
+        let run = &f.handle("649.99").await[0];
+        assert_eq!(run.status, CheckoutStatus::Failed);
+        let last = run.steps.last().unwrap();
+        assert_eq!(last.name, ADD_TO_CART);
+        assert!(last.message.contains("409"), "{}", last.message);
+    }
+
+    #[tokio::test]
+    async fn only_runs_for_alerted_subscribers_at_their_target() {
+        let f = fixture("749.99", live()).await;
+        f.turn_on("1,500.00", 1, false);
+        let subscriptions = [subscription(Some("700.00"))];
+
+        // Above the target
+        assert!(f.handle("749.99").await.is_empty());
+        // Not alerted
+        let runs = f
+            .buyer
+            .handle(&f.product, &subscriptions, &observation("649.99"), &[])
+            .await;
+        assert!(runs.is_empty());
+        // Out of stock
+        let out = Observation {
+            stock: StockState::OutOfStock,
+            ..observation("649.99")
+        };
+        let runs = f
+            .buyer
+            .handle(&f.product, &subscriptions, &out, &[alert("649.99")])
+            .await;
+        assert!(runs.is_empty());
+        assert!(f.store.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn validates_rules() {
+        let f = fixture("649.99", live()).await;
+        let watching = subscription(Some("700.00"));
+        let rule = |sub: Option<&Subscription>, spend: &str, quantity: u32| {
+            f.buyer.rule(&f.product, sub, spend, quantity, true)
+        };
+
+        assert_eq!(rule(None, "800", 1), Err(AutoBuyError::NotWatching));
+        assert_eq!(
+            rule(Some(&subscription(None)), "800", 1),
+            Err(AutoBuyError::NoTargetPrice)
+        );
+        assert_eq!(
+            rule(Some(&watching), "lots", 1),
+            Err(AutoBuyError::InvalidMaxSpend)
+        );
+        assert_eq!(
+            rule(Some(&watching), "0", 1),
+            Err(AutoBuyError::InvalidMaxSpend)
+        );
+        assert_eq!(
+            rule(Some(&watching), "699.99", 1),
+            Err(AutoBuyError::MaxSpendBelowTarget)
+        );
+        assert_eq!(
+            rule(Some(&watching), "800", 0),
+            Err(AutoBuyError::InvalidQuantity)
+        );
+        assert_eq!(
+            rule(Some(&watching), "800", 3),
+            Err(AutoBuyError::InvalidQuantity)
+        );
+        let ok = rule(Some(&watching), "1,400.00", 2).unwrap();
+        assert_eq!((ok.max_spend, ok.max_quantity), (cad("1400.00"), 2));
+        assert!(ok.enabled && ok.dry_run);
+
+        let amazon = Product {
+            retailer: "Amazon.ca".to_string(),
+            ..f.product.clone()
+        };
+        assert_eq!(
+            f.buyer.rule(&amazon, Some(&watching), "800", 1, true),
+            Err(AutoBuyError::Unsupported)
+        );
+    }
+}
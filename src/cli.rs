@@ -0,0 +1,566 @@
+use crate::config::Config;
+use crate::products::{self, ProductError, ProductInput, Tracked};
+use crate::retailers::{Retailer, RetailerRegistry};
+use crate::scheduler;
+use crate::storage::{self, Database, Repositories};
+use crate::transfer::{self, Format, Importer, ProductRecord, RecordError};
+use crate::urls::{self, HttpShortLinkResolver, ShortLinkResolver};
+use crate::users::{RegisterError, SetRoleError, UserRole, UserStore};
+use anyhow::{Context, anyhow, bail};
+use clap::{Parser, Subcommand};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+/// Track prices and stock of products at online retailers
+#[derive(Debug, Parser)]
+#[command(name = "midas", version)]
+pub struct Cli {
+    /// Config file to read instead of midas.toml
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Print the effective configuration, with secrets redacted, and exit
+    #[arg(long)]
+    pub print_config: bool,
+
+    /// What to do; runs the server if left out
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the web server and the background product checks
+    Serve,
+    /// Create the database or bring its schema up to date, then exit
+    Migrate,
+    /// Manage user accounts
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Manage tracked products
+    #[command(subcommand)]
+    Product(ProductCommand),
+    /// Fetch a product page once and print what can be read off it, without storing anything
+    Check { url: String },
+    /// Write tracked products and their subscribers' target prices as CSV or JSON
+    Export {
+        /// Only this user's products
+        #[arg(long)]
+        user: Option<String>,
+        /// File to write instead of standard output
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// csv or json; by default going by the output file's extension, else json
+        #[arg(long, value_parser = parse_format)]
+        format: Option<Format>,
+        /// Include every check of each product. As CSV, this writes one row per check
+        /// instead of one per product.
+        #[arg(long)]
+        history: bool,
+    },
+    /// Track the products in a CSV or JSON file, such as one written by `export`
+    Import {
+        file: PathBuf,
+        /// User to track products for when the file doesn't say
+        #[arg(long)]
+        user: Option<String>,
+        /// Only show what importing the file would do
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum UserCommand {
+    /// Create an account, prompting for its password
+    Add {
+        username: String,
+        /// Role to give the account instead of the usual one
+        #[arg(long, value_parser = parse_role)]
+        role: Option<UserRole>,
+        /// Read the password from the first line of standard input instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// List every account
+    List,
+    /// Change an account's role (regular, admin or viewer)
+    SetRole {
+        username: String,
+        #[arg(value_parser = parse_role)]
+        role: UserRole,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProductCommand {
+    /// List tracked products with the result of their latest check
+    List {
+        /// Only products this user watches
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Track a product for a user; the retailer is worked out from the URL
+    Add {
+        url: String,
+        /// User to track the product for
+        #[arg(long)]
+        user: String,
+        #[arg(long)]
+        name: String,
+        /// Target price in the retailer's currency, e.g. 399.99
+        #[arg(long)]
+        target: Option<String>,
+        /// Check the page this often instead of the default
+        #[arg(long, value_name = "MINUTES")]
+        interval: Option<u64>,
+    },
+    /// Stop tracking a product for everyone who watches it
+    Remove { id: i64 },
+}
+
+fn parse_format(format: &str) -> Result<Format, String> {
+    Format::parse(format).ok_or_else(|| "expected csv or json".to_string())
+}
+
+fn parse_role(role: &str) -> Result<UserRole, String> {
+    UserRole::parse(role).ok_or_else(|| "expected regular, admin or viewer".to_string())
+}
+
+/// Run any command but `serve`, printing its results to standard output
+pub async fn run(
+    command: Command,
+    config: &Config,
+    retailers: Arc<RetailerRegistry>,
+) -> anyhow::Result<()> {
+    let resolver = || HttpShortLinkResolver::new(scheduler::USER_AGENT, Duration::from_secs(10));
+    match command {
+        Command::Serve => unreachable!("the server is started by main"),
+        Command::Migrate => {
+            let database = Database::open(&config.database.path)?;
+            println!(
+                "Database {} is up to date at schema version {}",
+                config.database.path,
+                database.schema_version()?
+            );
+            Ok(())
+        }
+        Command::User(command) => user(command, &storage::open(&config.database.path)?),
+        Command::Product(command) => {
+            let repositories = storage::open(&config.database.path)?;
+            product(command, &repositories, &retailers, &resolver()?).await
+        }
+        Command::Check { url } => check(&url, config, &retailers, &resolver()?).await,
+        Command::Export {
+            user,
+            output,
+            format,
+            history,
+        } => {
+            let format = format
+                .or_else(|| {
+                    let extension = output.as_ref()?.extension()?;
+                    Format::parse(&extension.to_string_lossy())
+                })
+                .unwrap_or(Format::Json);
+            let repositories = storage::open(&config.database.path)?;
+            let observations = history.then_some(repositories.observations.as_ref());
+            let records = transfer::export(
+                repositories.products.as_ref(),
+                observations,
+                user.as_deref(),
+            )?;
+            let text = if history && format == Format::Csv {
+                transfer::write_history_csv(&records)?
+            } else {
+                transfer::write_records(&records, format)?
+            };
+            match output {
+                Some(path) => {
+                    fs::write(&path, text)
+                        .with_context(|| format!("Couldn't write {}", path.display()))?;
+                    println!("Exported {} products to {}", records.len(), path.display());
+                }
+                None => print!("{text}"),
+            }
+            Ok(())
+        }
+        Command::Import {
+            file,
+            user,
+            dry_run,
+        } => {
+            let text = fs::read_to_string(&file)
+                .with_context(|| format!("Couldn't read {}", file.display()))?;
+            let records = transfer::parse_records(&text, Format::detect(&file, &text))
+                .with_context(|| format!("Invalid import file {}", file.display()))?;
+            let repositories = storage::open(&config.database.path)?;
+            let users = UserStore::new(repositories.users.clone());
+            let importer = Importer {
+                users: &users,
+                products: repositories.products.as_ref(),
+                retailers: &retailers,
+                resolver: &resolver()?,
+            };
+            import(&importer, &records, user.as_deref(), dry_run).await
+        }
+    }
+}
+
+fn user(command: UserCommand, repositories: &Repositories) -> anyhow::Result<()> {
+    let users = UserStore::new(repositories.users.clone());
+    match command {
+        UserCommand::Add {
+            username,
+            role,
+            password_stdin,
+        } => {
+            let password = if password_stdin {
+                let mut line = String::new();
+                io::stdin().lock().read_line(&mut line)?;
+                line.trim_end_matches(['\r', '\n']).to_string()
+            } else {
+                let password = rpassword::prompt_password("Password: ")?;
+                if rpassword::prompt_password("Confirm password: ")? != password {
+                    bail!("The passwords don't match");
+                }
+                password
+            };
+
+            let mut user = users.register(&username, &password)?.map_err(|e| {
+                anyhow!(match e {
+                    RegisterError::InvalidUsername => {
+                        "Usernames must be 3-32 characters of letters, numbers, '.', '_' or '-'"
+                    }
+                    RegisterError::UsernameTaken => "That username is already taken",
+                    RegisterError::WeakPassword => "Passwords must be at least 8 characters long",
+                })
+            })?;
+            // The first account is always made an admin, and has to stay one
+            if let Some(role) = role.filter(|role| *role != user.role) {
+                user = users.set_role(&user.username, role)?.map_err(|e| {
+                    anyhow!(
+                        "Created {} as {}, but couldn't make them {}: {}",
+                        user.username,
+                        user.role.as_str(),
+                        role.as_str(),
+                        set_role_error(e)
+                    )
+                })?;
+            }
+            println!("Created {} ({})", user.username, user.role.as_str());
+        }
+        UserCommand::List => {
+            let rows = users
+                .list()?
+                .into_iter()
+                .map(|account| {
+                    let locked = if account.locked { "locked" } else { "" };
+                    vec![
+                        account.user.username,
+                        account.user.role.as_str().to_string(),
+                        locked.to_string(),
+                    ]
+                })
+                .collect();
+            print_table(&["USERNAME", "ROLE", ""], rows);
+        }
+        UserCommand::SetRole { username, role } => {
+            let user = users.set_role(&username, role)?.map_err(set_role_error)?;
+            println!("{} is now {}", user.username, user.role.as_str());
+        }
+    }
+    Ok(())
+}
+
+fn set_role_error(error: SetRoleError) -> anyhow::Error {
+    anyhow!(match error {
+        SetRoleError::UnknownUser => "There's no user with that name",
+        SetRoleError::LastAdmin => "That's the last admin; promote someone else first",
+    })
+}
+
+async fn product(
+    command: ProductCommand,
+    repositories: &Repositories,
+    retailers: &RetailerRegistry,
+    resolver: &dyn ShortLinkResolver,
+) -> anyhow::Result<()> {
+    let products = repositories.products.as_ref();
+    match command {
+        ProductCommand::List { user } => {
+            let list = match &user {
+                Some(username) => products.list_for_user(username)?,
+                None => products.list()?,
+            };
+            let mut rows = Vec::new();
+            for product in list {
+                let latest = repositories.observations.latest(product.id)?;
+                let (price, stock) = match latest {
+                    Some(observation) if observation.error.is_none() => (
+                        observation.price.map(|p| p.to_string()).unwrap_or_default(),
+                        observation.stock.label().to_string(),
+                    ),
+                    Some(_) => (String::new(), "Check failed".to_string()),
+                    None => (String::new(), "Not checked yet".to_string()),
+                };
+                rows.push(vec![
+                    product.id.to_string(),
+                    product.retailer.clone(),
+                    product.name.clone(),
+                    price,
+                    stock,
+                    products.subscribers(product.id)?.len().to_string(),
+                    product.url,
+                ]);
+            }
+            print_table(
+                &[
+                    "ID", "RETAILER", "NAME", "PRICE", "STOCK", "WATCHERS", "URL",
+                ],
+                rows,
+            );
+        }
+        ProductCommand::Add {
+            url,
+            user,
+            name,
+            target,
+            interval,
+        } => {
+            let users = UserStore::new(repositories.users.clone());
+            let user = users
+                .get(&user)?
+                .ok_or_else(|| anyhow!("No user named {:?}", user))?;
+            if !user.role.can_edit() {
+                bail!(
+                    "{} has read-only access and can't track products",
+                    user.username
+                );
+            }
+
+            let (retailer, url) = find_retailer(&url, retailers, resolver).await?;
+            let target_price =
+                products::parse_target_price(target.as_deref().unwrap_or(""), retailer.currency())
+                    .map_err(|e| anyhow!(e.message()))?;
+            let input = ProductInput {
+                url: url.to_string(),
+                name,
+                retailer: retailer.name().to_string(),
+                retailer_id: None,
+                currency: retailer.currency(),
+                target_price,
+                poll_interval: interval.map(products::poll_interval_minutes),
+            }
+            .validate(retailers, resolver)
+            .await
+            .map_err(|errors| {
+                let messages: Vec<&str> = errors.iter().map(ProductError::message).collect();
+                anyhow!(messages.join("; "))
+            })?;
+
+            let (product, tracked) = products::track(products, &user.username, input)?;
+            match tracked {
+                Tracked::Added => println!("Added product {}: {}", product.id, product.name),
+                Tracked::Subscribed => println!(
+                    "{} now watches product {}: {}",
+                    user.username, product.id, product.name
+                ),
+                Tracked::AlreadySubscribed => println!(
+                    "{} already watches product {}: {}",
+                    user.username, product.id, product.name
+                ),
+            }
+        }
+        ProductCommand::Remove { id } => {
+            if !products.delete(id)? {
+                bail!("There's no product {}", id);
+            }
+            println!("Removed product {}", id);
+        }
+    }
+    Ok(())
+}
+
+// The retailer selling what `input` links to, and the link with any short link expanded
+async fn find_retailer<'a>(
+    input: &str,
+    retailers: &'a RetailerRegistry,
+    resolver: &dyn ShortLinkResolver,
+) -> anyhow::Result<(&'a dyn Retailer, Url)> {
+    let mut url =
+        urls::parse_user_url(input).ok_or_else(|| anyhow!("{:?} isn't a web address", input))?;
+    if retailers.is_short_link(&url) {
+        url = resolver
+            .resolve(&url)
+            .await
+            .context("Couldn't follow the short link")?;
+    }
+    let retailer = retailers.for_url(&url).ok_or_else(|| {
+        let known: Vec<&str> = retailers.all().map(|r| r.name()).collect();
+        anyhow!(
+            "{} isn't on a supported retailer's site ({})",
+            url,
+            known.join(", ")
+        )
+    })?;
+    Ok((retailer, url))
+}
+
+async fn check(
+    input: &str,
+    config: &Config,
+    retailers: &RetailerRegistry,
+    resolver: &dyn ShortLinkResolver,
+) -> anyhow::Result<()> {
+    let (retailer, url) = find_retailer(input, retailers, resolver).await?;
+    let url = retailer.canonicalize_url(&url);
+    let client = scheduler::page_client(config.scheduler_config().request_timeout)?;
+    let html = scheduler::fetch_page(&client, url.as_str())
+        .await
+        .with_context(|| format!("Couldn't fetch {}", url))?;
+    let info = retailer
+        .parse_product_page(&html)
+        .ok_or_else(|| anyhow!("No product data found on {}", url))?;
+
+    let fields = [
+        ("Retailer", Some(retailer.name().to_string())),
+        ("URL", Some(url.to_string())),
+        ("Retailer ID", info.retailer_id),
+        ("Title", info.title),
+        ("Price", info.price.map(|p| p.to_string())),
+        ("Was", info.was_price.map(|p| p.to_string())),
+        ("Stock", Some(info.stock.label().to_string())),
+        ("Availability", info.availability),
+        ("Ships from", info.ships_from),
+        ("Sold by", info.sold_by),
+    ];
+    for (label, value) in fields {
+        if let Some(value) = value {
+            println!("{:<13} {}", format!("{label}:"), value);
+        }
+    }
+    if info.third_party_seller {
+        println!("Sold by a third party, not {}", retailer.name());
+    }
+    Ok(())
+}
+
+async fn import(
+    importer: &Importer<'_>,
+    records: &[ProductRecord],
+    default_user: Option<&str>,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let outcomes: Vec<Result<String, RecordError>> = if dry_run {
+        let outcomes = importer.preview(records, default_user).await?;
+        outcomes
+            .into_iter()
+            .map(|outcome| {
+                outcome.map(|tracked| {
+                    match tracked {
+                        Tracked::Added => "would add the product",
+                        Tracked::Subscribed => "would subscribe to an existing product",
+                        Tracked::AlreadySubscribed => "already watching the product",
+                    }
+                    .to_string()
+                })
+            })
+            .collect()
+    } else {
+        let outcomes = importer.import(records, default_user).await?;
+        outcomes
+            .into_iter()
+            .map(|outcome| {
+                outcome.map(|(product, tracked)| match tracked {
+                    Tracked::Added => format!("added product {}", product.id),
+                    Tracked::Subscribed => format!("subscribed to product {}", product.id),
+                    Tracked::AlreadySubscribed => {
+                        format!("already watching product {}", product.id)
+                    }
+                })
+            })
+            .collect()
+    };
+
+    let mut failed = 0;
+    for (index, (record, outcome)) in records.iter().zip(outcomes).enumerate() {
+        let outcome = outcome.unwrap_or_else(|e| {
+            failed += 1;
+            format!("failed: {}", e.message())
+        });
+        println!("{}. {}: {}", index + 1, record.name, outcome);
+    }
+
+    if dry_run {
+        println!(
+            "{} of {} products can be imported; nothing was changed",
+            records.len() - failed,
+            records.len()
+        );
+        return Ok(());
+    }
+    if failed > 0 {
+        bail!(
+            "{} of {} products couldn't be imported",
+            failed,
+            records.len()
+        );
+    }
+    println!("Imported {} products", records.len());
+    Ok(())
+}
+
+// Left-aligned columns as wide as their widest value
+fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in &rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let mut out = io::stdout().lock();
+    let header = headers.iter().map(|h| h.to_string()).collect();
+    for row in std::iter::once(header).chain(rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{:<width$}", value, width = width))
+            .collect();
+        let _ = writeln!(out, "{}", cells.join("  ").trim_end());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn parses_commands() {
+        Cli::command().debug_assert();
+
+        let cli = Cli::parse_from(["midas"]);
+        assert!(cli.command.is_none());
+
+        let cli = Cli::parse_from([
+            "midas", "user", "set-role", "bob", "viewer", "--config", "m.toml",
+        ]);
+        assert_eq!(cli.config, Some(PathBuf::from("m.toml")));
+        assert!(matches!(
+            cli.command,
+            Some(Command::User(UserCommand::SetRole {
+                role: UserRole::Viewer,
+                ..
+            }))
+        ));
+
+        assert!(Cli::try_parse_from(["midas", "user", "set-role", "bob", "owner"]).is_err());
+        assert!(Cli::try_parse_from(["midas", "product", "add", "https://a.co/d/x"]).is_err());
+    }
+}
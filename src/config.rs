@@ -0,0 +1,600 @@
+use crate::alerts::AlertConfig;
+use crate::checkout::CheckoutConfig;
+use crate::notify::{SmtpConfig, SmtpTls};
+use crate::products::{MAX_POLL_INTERVAL, MIN_POLL_INTERVAL};
+use crate::retailers::RetailerRegistry;
+use crate::scheduler::SchedulerConfig;
+use anyhow::{Context, anyhow, bail};
+use lettre::message::Mailbox;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::info;
+use url::Url;
+
+/// Config file read when neither `--config` nor `MIDAS_CONFIG` names one. Unlike a named
+/// file, it doesn't have to exist.
+pub const DEFAULT_CONFIG_PATH: &str = "midas.toml";
+
+/// What secrets are replaced with in `--print-config` output
+const REDACTED: &str = "[redacted]";
+
+/// Longest wait between two requests to one retailer that makes any sense, which also keeps
+/// the rate limiter's clock arithmetic from overflowing
+const MAX_RETAILER_GAP_SECS: u64 = 24 * 60 * 60;
+
+/// Everything the server can be configured with.
+///
+/// Settings start from built-in defaults, are overridden by the TOML config file and then by
+/// environment variables. Every section and key is optional; keys that don't exist are an
+/// error so typos don't go unnoticed.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub polling: PollingConfig,
+    pub alerts: AlertsConfig,
+    /// Per-retailer settings, keyed by retailer name ("Amazon.ca")
+    pub retailers: BTreeMap<String, RetailerConfig>,
+    pub smtp: SmtpSettings,
+    pub session: SessionConfig,
+    pub checkout: CheckoutSettings,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address to listen on
+    pub bind: IpAddr,
+    pub port: u16,
+    /// Base URL the server is reached at, used to link back to it from emails
+    pub public_url: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 3000,
+            public_url: "http://localhost:3000".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// SQLite database file, or `:memory:` to keep nothing across restarts
+    pub path: String,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            path: "midas.db".to_string(),
+        }
+    }
+}
+
+/// How product pages are checked; see [`SchedulerConfig`]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PollingConfig {
+    /// Check interval for products that don't set their own
+    pub interval_secs: u64,
+    /// Fraction each interval is randomly stretched or shrunk by
+    pub jitter: f64,
+    /// Page fetches in flight at once
+    pub max_concurrent: usize,
+    pub request_timeout_secs: u64,
+    /// Minimum gap between two requests to the same retailer, unless the retailer sets its own
+    pub retailer_min_gap_secs: u64,
+}
+
+impl Default for PollingConfig {
+    fn default() -> Self {
+        let defaults = SchedulerConfig::default();
+        PollingConfig {
+            interval_secs: defaults.default_interval.as_secs(),
+            jitter: defaults.jitter,
+            max_concurrent: defaults.max_concurrent,
+            request_timeout_secs: defaults.request_timeout.as_secs(),
+            retailer_min_gap_secs: defaults.retailer_min_gap.as_secs(),
+        }
+    }
+}
+
+/// When alerts fire; see [`AlertConfig`]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertsConfig {
+    pub price_drop_percent: f64,
+    pub cooldown_secs: u64,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        let defaults = AlertConfig::default();
+        AlertsConfig {
+            price_drop_percent: defaults.price_drop_percent,
+            cooldown_secs: defaults.cooldown.as_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetailerConfig {
+    /// Minimum gap between two requests to this retailer
+    pub min_gap_secs: u64,
+}
+
+/// Outgoing email. Email is turned off unless `host` is set.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmtpSettings {
+    pub host: Option<String>,
+    /// Defaults to the usual port for `tls`
+    pub port: Option<u16>,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sender address, e.g. "Midas <midas@example.com>"
+    pub from: String,
+}
+
+impl Default for SmtpSettings {
+    fn default() -> Self {
+        SmtpSettings {
+            host: None,
+            port: None,
+            tls: SmtpTls::default(),
+            username: None,
+            password: None,
+            from: "Midas <midas@localhost>".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    /// Key material for signing session cookies, at least 64 bytes. Without it a random key
+    /// is generated on every start, which signs everybody out.
+    pub secret: Option<String>,
+}
+
+/// Auto-buy; see [`CheckoutConfig`]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CheckoutSettings {
+    /// Actually pay for orders. Off, every auto-buy run stops before payment.
+    pub live_orders: bool,
+    /// The most of a product a single auto-buy order can be for
+    pub max_quantity: u32,
+    pub request_timeout_secs: u64,
+}
+
+impl Default for CheckoutSettings {
+    fn default() -> Self {
+        let defaults = CheckoutConfig::default();
+        CheckoutSettings {
+            live_orders: defaults.live_orders,
+            max_quantity: defaults.max_quantity,
+            request_timeout_secs: defaults.request_timeout.as_secs(),
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration from the file at `path`, or `MIDAS_CONFIG`, or `midas.toml` if
+    /// there is one, then apply environment variable overrides and check the result.
+    ///
+    /// The overrides are `BIND_ADDRESS`, `PORT`, `PUBLIC_URL`, `DATABASE_PATH`,
+    /// `POLL_INTERVAL_SECS`, `MAX_CONCURRENT_CHECKS`, `SMTP_HOST`, `SMTP_PORT`, `SMTP_TLS`,
+    /// `SMTP_USERNAME`, `SMTP_PASSWORD`, `SMTP_FROM` and `SESSION_SECRET`. Empty variables
+    /// count as unset.
+    pub fn load(path: Option<&Path>, retailers: &RetailerRegistry) -> anyhow::Result<Config> {
+        let path = path
+            .map(Path::to_path_buf)
+            .or_else(|| env::var_os("MIDAS_CONFIG").map(PathBuf::from));
+        let default_path = Path::new(DEFAULT_CONFIG_PATH);
+        let mut config = match path {
+            Some(path) => Config::read(&path)?,
+            None if default_path.exists() => Config::read(default_path)?,
+            None => Config::default(),
+        };
+
+        config.apply_env(|name| env::var(name).ok().filter(|value| !value.is_empty()))?;
+
+        let problems = config.problems(retailers);
+        if !problems.is_empty() {
+            let list: Vec<String> = problems.iter().map(|p| format!("  - {p}")).collect();
+            bail!("Invalid configuration:\n{}", list.join("\n"));
+        }
+        Ok(config)
+    }
+
+    fn read(path: &Path) -> anyhow::Result<Config> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read config file {}", path.display()))?;
+        let config = Config::parse(&text)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        info!("Read configuration from {}", path.display());
+        Ok(config)
+    }
+
+    /// Parse a config file's contents. Anything it leaves out keeps its default.
+    pub fn parse(text: &str) -> anyhow::Result<Config> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Override settings from environment variables, looked up with `var`
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> anyhow::Result<()> {
+        if let Some(bind) = env_value(&var, "BIND_ADDRESS", "an IP address")? {
+            self.server.bind = bind;
+        }
+        if let Some(port) = env_value(&var, "PORT", "a port number")? {
+            self.server.port = port;
+        }
+        if let Some(public_url) = var("PUBLIC_URL") {
+            self.server.public_url = public_url;
+        }
+        if let Some(path) = var("DATABASE_PATH") {
+            self.database.path = path;
+        }
+        if let Some(interval) = env_value(&var, "POLL_INTERVAL_SECS", "a number of seconds")? {
+            self.polling.interval_secs = interval;
+        }
+        if let Some(max) = env_value(&var, "MAX_CONCURRENT_CHECKS", "a whole number")? {
+            self.polling.max_concurrent = max;
+        }
+
+        if let Some(host) = var("SMTP_HOST") {
+            self.smtp.host = Some(host);
+        }
+        if let Some(port) = env_value(&var, "SMTP_PORT", "a port number")? {
+            self.smtp.port = Some(port);
+        }
+        if let Some(tls) = var("SMTP_TLS") {
+            self.smtp.tls = SmtpTls::parse(&tls)
+                .ok_or_else(|| anyhow!("SMTP_TLS must be starttls, tls or none, got {:?}", tls))?;
+        }
+        if let Some(username) = var("SMTP_USERNAME") {
+            self.smtp.username = Some(username);
+        }
+        if let Some(password) = var("SMTP_PASSWORD") {
+            self.smtp.password = Some(password);
+        }
+        if let Some(from) = var("SMTP_FROM") {
+            self.smtp.from = from;
+        }
+
+        if let Some(secret) = var("SESSION_SECRET") {
+            self.session.secret = Some(secret);
+        }
+        Ok(())
+    }
+
+    /// Everything wrong with the configuration, one message per problem, each naming the
+    /// setting it's about
+    fn problems(&self, retailers: &RetailerRegistry) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        let public_url = Url::parse(&self.server.public_url).ok();
+        if !public_url.is_some_and(|url| matches!(url.scheme(), "http" | "https")) {
+            problems.push(format!(
+                "server.public_url must be an http(s) URL, got {:?}",
+                self.server.public_url
+            ));
+        }
+
+        if self.database.path.trim().is_empty() {
+            problems.push("database.path must not be empty".to_string());
+        }
+
+        let polling = &self.polling;
+        let interval_range = MIN_POLL_INTERVAL.as_secs()..=MAX_POLL_INTERVAL.as_secs();
+        if !interval_range.contains(&polling.interval_secs) {
+            problems.push(format!(
+                "polling.interval_secs must be between {} and {}, got {}",
+                interval_range.start(),
+                interval_range.end(),
+                polling.interval_secs
+            ));
+        }
+        if polling.retailer_min_gap_secs > MAX_RETAILER_GAP_SECS {
+            problems.push(format!(
+                "polling.retailer_min_gap_secs must be at most {}, got {}",
+                MAX_RETAILER_GAP_SECS, polling.retailer_min_gap_secs
+            ));
+        }
+        if !(0.0..1.0).contains(&polling.jitter) {
+            problems.push(format!(
+                "polling.jitter must be at least 0 and below 1, got {}",
+                polling.jitter
+            ));
+        }
+        if polling.max_concurrent == 0 {
+            problems.push("polling.max_concurrent must be at least 1".to_string());
+        }
+        if polling.request_timeout_secs == 0 {
+            problems.push("polling.request_timeout_secs must be at least 1".to_string());
+        }
+
+        let drop = self.alerts.price_drop_percent;
+        let drop_valid = drop > 0.0 && drop < 100.0;
+        if !drop_valid {
+            problems.push(format!(
+                "alerts.price_drop_percent must be above 0 and below 100, got {}",
+                drop
+            ));
+        }
+
+        for (name, retailer) in &self.retailers {
+            if retailer.min_gap_secs > MAX_RETAILER_GAP_SECS {
+                problems.push(format!(
+                    "retailers.{:?}.min_gap_secs must be at most {}, got {}",
+                    name, MAX_RETAILER_GAP_SECS, retailer.min_gap_secs
+                ));
+            }
+            if retailers.get(name).is_none() {
+                let known: Vec<&str> = retailers.all().map(|r| r.name()).collect();
+                problems.push(format!(
+                    "retailers.{:?} is not a retailer, expected one of {}",
+                    name,
+                    known.join(", ")
+                ));
+            }
+        }
+
+        let smtp = &self.smtp;
+        if smtp.host.is_some() {
+            if smtp.from.parse::<Mailbox>().is_err() {
+                problems.push(format!(
+                    "smtp.from must be an email address like \"Midas <midas@example.com>\", got {:?}",
+                    smtp.from
+                ));
+            }
+            if smtp.username.is_some() != smtp.password.is_some() {
+                problems.push("smtp.username and smtp.password must be set together".to_string());
+            }
+        }
+
+        if self.session.secret.as_ref().is_some_and(|s| s.len() < 64) {
+            problems.push("session.secret must be at least 64 bytes long".to_string());
+        }
+
+        if self.checkout.max_quantity == 0 {
+            problems.push("checkout.max_quantity must be at least 1".to_string());
+        }
+        if self.checkout.request_timeout_secs == 0 {
+            problems.push("checkout.request_timeout_secs must be at least 1".to_string());
+        }
+
+        problems
+    }
+
+    pub fn bind_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.server.bind, self.server.port)
+    }
+
+    pub fn scheduler_config(&self) -> SchedulerConfig {
+        let polling = &self.polling;
+        SchedulerConfig {
+            default_interval: Duration::from_secs(polling.interval_secs),
+            jitter: polling.jitter,
+            max_concurrent: polling.max_concurrent,
+            retailer_min_gap: Duration::from_secs(polling.retailer_min_gap_secs),
+            retailer_gaps: self
+                .retailers
+                .iter()
+                .map(|(name, retailer)| (name.clone(), Duration::from_secs(retailer.min_gap_secs)))
+                .collect(),
+            request_timeout: Duration::from_secs(polling.request_timeout_secs),
+            ..SchedulerConfig::default()
+        }
+    }
+
+    pub fn alert_config(&self) -> AlertConfig {
+        AlertConfig {
+            price_drop_percent: self.alerts.price_drop_percent,
+            cooldown: Duration::from_secs(self.alerts.cooldown_secs),
+        }
+    }
+
+    pub fn checkout_config(&self) -> CheckoutConfig {
+        CheckoutConfig {
+            live_orders: self.checkout.live_orders,
+            max_quantity: self.checkout.max_quantity,
+            request_timeout: Duration::from_secs(self.checkout.request_timeout_secs),
+        }
+    }
+
+    /// Settings for the email notifier, or `None` if email is turned off
+    pub fn smtp_config(&self) -> Option<SmtpConfig> {
+        let smtp = &self.smtp;
+        Some(SmtpConfig {
+            host: smtp.host.clone()?,
+            port: smtp.port.unwrap_or(smtp.tls.default_port()),
+            tls: smtp.tls,
+            username: smtp.username.clone(),
+            password: smtp.password.clone(),
+            from: smtp.from.clone(),
+            public_url: self.server.public_url.clone(),
+        })
+    }
+
+    /// The configuration as TOML, with passwords and secrets blanked out
+    pub fn to_redacted_toml(&self) -> anyhow::Result<String> {
+        let mut config = self.clone();
+        let redact = |secret: &mut Option<String>| {
+            if secret.is_some() {
+                *secret = Some(REDACTED.to_string());
+            }
+        };
+        redact(&mut config.smtp.password);
+        redact(&mut config.session.secret);
+        Ok(toml::to_string(&config)?)
+    }
+}
+
+// Parse environment variable `name`, if it's set
+fn env_value<T: FromStr>(
+    var: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    expected: &str,
+) -> anyhow::Result<Option<T>> {
+    var(name)
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("{} must be {}, got {:?}", name, expected, value))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn file_overrides_defaults() {
+        let config = Config::parse(
+            r#"
+            [server]
+            port = 8080
+
+            [polling]
+            interval_secs = 600
+
+            [retailers."Amazon.ca"]
+            min_gap_secs = 30
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.server.port, 8080);
+        assert_eq!(config.server.bind, ServerConfig::default().bind);
+        assert_eq!(config.database, DatabaseConfig::default());
+
+        let scheduler = config.scheduler_config();
+        assert_eq!(scheduler.default_interval, Duration::from_secs(600));
+        assert_eq!(
+            scheduler.max_concurrent,
+            SchedulerConfig::default().max_concurrent
+        );
+        assert_eq!(
+            scheduler.retailer_gaps.get("Amazon.ca"),
+            Some(&Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let error = Config::parse("[server]\nprot = 8080\n").unwrap_err();
+        assert!(format!("{error:#}").contains("prot"), "{error:#}");
+    }
+
+    #[test]
+    fn env_overrides_file() {
+        let mut config = Config::parse("[server]\nport = 8080\n[smtp]\ntls = \"tls\"\n").unwrap();
+        config
+            .apply_env(env(&[
+                ("PORT", "9090"),
+                ("DATABASE_PATH", ":memory:"),
+                ("SMTP_HOST", "smtp.example.com"),
+            ]))
+            .unwrap();
+
+        assert_eq!(config.server.port, 9090);
+        assert_eq!(config.database.path, ":memory:");
+        let smtp = config.smtp_config().unwrap();
+        assert_eq!(smtp.host, "smtp.example.com");
+        assert_eq!((smtp.tls, smtp.port), (SmtpTls::Tls, 465));
+
+        let error = config.apply_env(env(&[("PORT", "http")])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "PORT must be a port number, got \"http\""
+        );
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let config = Config::parse(
+            r#"
+            [polling]
+            interval_secs = 5
+            max_concurrent = 0
+
+            [retailers.Walmart]
+            min_gap_secs = 10
+
+            [smtp]
+            host = "smtp.example.com"
+            username = "midas"
+
+            [session]
+            secret = "too short"
+            "#,
+        )
+        .unwrap();
+
+        let problems = config.problems(&RetailerRegistry::default());
+        assert_eq!(problems.len(), 5, "{problems:#?}");
+        assert!(problems[0].starts_with("polling.interval_secs"));
+        assert!(problems[2].starts_with("retailers.\"Walmart\""));
+
+        let mut config = Config::default();
+        config.polling.interval_secs = u64::MAX;
+        config.retailers.insert(
+            "Amazon.ca".to_string(),
+            RetailerConfig {
+                min_gap_secs: u64::MAX,
+            },
+        );
+        let problems = config.problems(&RetailerRegistry::default());
+        assert_eq!(problems.len(), 2, "{problems:#?}");
+        assert!(problems[0].starts_with("polling.interval_secs must be between 60 and 604800"));
+        assert!(problems[1].starts_with("retailers.\"Amazon.ca\".min_gap_secs"));
+
+        assert!(
+            Config::default()
+                .problems(&RetailerRegistry::default())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn redacts_secrets() {
+        let mut config = Config::default();
+        config.smtp.password = Some("hunter2".to_string());
+        config.session.secret = Some("s".repeat(64));
+
+        let printed = config.to_redacted_toml().unwrap();
+        assert!(!printed.contains("hunter2"));
+        assert!(!printed.contains(&"s".repeat(64)));
+
+        // What's printed can be used as a config file
+        let reparsed = Config::parse(&printed).unwrap();
+        assert_eq!(reparsed.server, config.server);
+        assert_eq!(reparsed.smtp.password.as_deref(), Some(REDACTED));
+    }
+}
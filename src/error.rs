@@ -0,0 +1,30 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use tracing::error;
+
+/// An unexpected failure inside a handler (storage errors and the like).
+///
+/// Anything that converts into `anyhow::Error` can be `?`-ed into it; it's logged and the
+/// client gets a plain 500.
+#[derive(Debug)]
+pub struct AppError(anyhow::Error);
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        error!("Request failed: {:#}", self.0);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Something went wrong. Please try again.",
+        )
+            .into_response()
+    }
+}
+
+impl<E> From<E> for AppError
+where
+    E: Into<anyhow::Error>,
+{
+    fn from(err: E) -> Self {
+        AppError(err.into())
+    }
+}
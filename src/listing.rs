@@ -0,0 +1,444 @@
+use crate::money::Money;
+use crate::storage::{Observation, Product, StockState, Subscription};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Products shown per page of the product list
+pub const PAGE_SIZE: usize = 12;
+
+/// Filters, search and sort order for the product list, as carried in the query string.
+///
+/// Every field is optional text so a hand-edited or stale URL never fails to load; values
+/// that don't mean anything are ignored.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ProductQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retailer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stock: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<String>,
+}
+
+impl ProductQuery {
+    /// The same query with surrounding whitespace trimmed and empty values dropped, which is
+    /// what a form submits for "any"
+    pub fn normalized(self) -> Self {
+        let clean = |value: Option<String>| {
+            value
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        ProductQuery {
+            q: clean(self.q),
+            retailer: clean(self.retailer),
+            owner: clean(self.owner),
+            stock: clean(self.stock),
+            target: clean(self.target),
+            sort: clean(self.sort),
+            page: clean(self.page),
+        }
+    }
+
+    pub fn sort_order(&self) -> SortOrder {
+        self.sort
+            .as_deref()
+            .and_then(SortOrder::parse)
+            .unwrap_or(SortOrder::Newest)
+    }
+
+    pub fn stock_state(&self) -> Option<StockState> {
+        match self.stock.as_deref()? {
+            "in_stock" => Some(StockState::InStock),
+            "out_of_stock" => Some(StockState::OutOfStock),
+            "unknown" => Some(StockState::Unknown),
+            _ => None,
+        }
+    }
+
+    pub fn target_filter(&self) -> Option<TargetFilter> {
+        self.target.as_deref().and_then(TargetFilter::parse)
+    }
+
+    /// 1-based page number
+    pub fn page_number(&self) -> usize {
+        self.page
+            .as_deref()
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(1)
+            .max(1)
+    }
+
+    /// Whether anything narrows the list down, as opposed to just sorting or paging it
+    pub fn is_filtered(&self) -> bool {
+        self.q.is_some()
+            || self.retailer.is_some()
+            || self.owner.is_some()
+            || self.stock_state().is_some()
+            || self.target_filter().is_some()
+    }
+
+    /// Query string for `page` of the same listing
+    pub fn with_page(&self, page: usize) -> String {
+        let query = ProductQuery {
+            page: (page > 1).then(|| page.to_string()),
+            ..self.clone()
+        };
+        serde_urlencoded::to_string(&query).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Newest,
+    Oldest,
+    PriceLow,
+    PriceHigh,
+    /// Cheapest relative to the target price first; products already below target lead
+    ClosestToTarget,
+}
+
+impl SortOrder {
+    pub const ALL: [SortOrder; 5] = [
+        SortOrder::Newest,
+        SortOrder::Oldest,
+        SortOrder::PriceLow,
+        SortOrder::PriceHigh,
+        SortOrder::ClosestToTarget,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortOrder::Newest => "newest",
+            SortOrder::Oldest => "oldest",
+            SortOrder::PriceLow => "price_asc",
+            SortOrder::PriceHigh => "price_desc",
+            SortOrder::ClosestToTarget => "target",
+        }
+    }
+
+    pub fn parse(sort: &str) -> Option<SortOrder> {
+        SortOrder::ALL.into_iter().find(|s| s.as_str() == sort)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortOrder::Newest => "Newest first",
+            SortOrder::Oldest => "Oldest first",
+            SortOrder::PriceLow => "Price: low to high",
+            SortOrder::PriceHigh => "Price: high to low",
+            SortOrder::ClosestToTarget => "Closest to target",
+        }
+    }
+}
+
+/// Where the current price sits relative to the product's target price
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TargetFilter {
+    AtOrBelow,
+    Above,
+}
+
+impl TargetFilter {
+    pub const ALL: [TargetFilter; 2] = [TargetFilter::AtOrBelow, TargetFilter::Above];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TargetFilter::AtOrBelow => "below",
+            TargetFilter::Above => "above",
+        }
+    }
+
+    pub fn parse(target: &str) -> Option<TargetFilter> {
+        TargetFilter::ALL.into_iter().find(|t| t.as_str() == target)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TargetFilter::AtOrBelow => "At or below target",
+            TargetFilter::Above => "Above target",
+        }
+    }
+}
+
+/// A product with the result of its latest check, if it has been checked yet, and the
+/// subscription of the user looking at the list, if they watch it
+#[derive(Debug, Clone)]
+pub struct ListingRow {
+    pub product: Product,
+    pub latest: Option<Observation>,
+    pub subscription: Option<Subscription>,
+}
+
+impl ListingRow {
+    pub fn target_price(&self) -> Option<Money> {
+        self.subscription.as_ref().and_then(|s| s.target_price)
+    }
+
+    // Price from the latest check, if that check worked
+    fn price(&self) -> Option<Money> {
+        self.latest
+            .as_ref()
+            .filter(|o| o.error.is_none())
+            .and_then(|o| o.price)
+    }
+
+    fn stock(&self) -> StockState {
+        self.latest
+            .as_ref()
+            .filter(|o| o.error.is_none())
+            .map_or(StockState::Unknown, |o| o.stock)
+    }
+
+    // How far the price is above the target, as a fraction of the target. Only known when
+    // both are in the same currency.
+    fn target_distance(&self) -> Option<f64> {
+        let target = self.target_price().filter(|t| t.minor() > 0)?;
+        let price = self.price().filter(|p| p.currency() == target.currency())?;
+        Some((price.to_major() - target.to_major()) / target.to_major())
+    }
+}
+
+/// One page of a filtered, sorted product list
+#[derive(Debug)]
+pub struct ListingPage {
+    pub rows: Vec<ListingRow>,
+    /// 1-based, clamped to the pages that exist
+    pub page: usize,
+    pub pages: usize,
+    /// Matching products across all pages
+    pub total: usize,
+}
+
+/// Filter, sort and paginate `rows` as `query` asks
+pub fn apply(query: &ProductQuery, mut rows: Vec<ListingRow>) -> ListingPage {
+    let search = query.q.as_deref().map(str::to_lowercase);
+    let stock = query.stock_state();
+    let target = query.target_filter();
+
+    rows.retain(|row| {
+        let product = &row.product;
+        search
+            .as_deref()
+            .is_none_or(|q| product.name.to_lowercase().contains(q))
+            && query
+                .retailer
+                .as_deref()
+                .is_none_or(|r| product.retailer == r)
+            && query
+                .owner
+                .as_deref()
+                .is_none_or(|o| product.added_by.eq_ignore_ascii_case(o))
+            && stock.is_none_or(|s| row.stock() == s)
+            && target.is_none_or(|t| match (row.price(), row.target_price()) {
+                (Some(price), Some(target_price)) => match t {
+                    TargetFilter::AtOrBelow => price <= target_price,
+                    TargetFilter::Above => price > target_price,
+                },
+                _ => false,
+            })
+    });
+
+    // Products missing the value being sorted on always go last, newest first among them.
+    // Prices in different currencies don't compare, so they're grouped by currency first.
+    rows.sort_by(|a, b| {
+        let newest = b.product.id.cmp(&a.product.id);
+        let low = |row: &ListingRow| row.price().map(|p| (p.currency(), p.minor()));
+        let high = |row: &ListingRow| row.price().map(|p| (p.currency(), -p.minor()));
+        match query.sort_order() {
+            SortOrder::Newest => newest,
+            SortOrder::Oldest => a.product.id.cmp(&b.product.id),
+            SortOrder::PriceLow => by_value(low(a), low(b)).then(newest),
+            SortOrder::PriceHigh => by_value(high(a), high(b)).then(newest),
+            SortOrder::ClosestToTarget => {
+                by_value(a.target_distance(), b.target_distance()).then(newest)
+            }
+        }
+    });
+
+    let total = rows.len();
+    let pages = total.div_ceil(PAGE_SIZE).max(1);
+    let page = query.page_number().min(pages);
+    let rows = rows
+        .into_iter()
+        .skip((page - 1) * PAGE_SIZE)
+        .take(PAGE_SIZE)
+        .collect();
+
+    ListingPage {
+        rows,
+        page,
+        pages,
+        total,
+    }
+}
+
+// Ascending, with missing values last
+fn by_value<T: PartialOrd>(a: Option<T>, b: Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Currency;
+    use std::time::SystemTime;
+
+    // Product `id` at `retailer`, last checked at `price` dollars (`None` for never checked)
+    // with `target` as the viewer's target price
+    fn row(
+        id: i64,
+        retailer: &str,
+        price: Option<i64>,
+        stock: StockState,
+        target: Option<i64>,
+    ) -> ListingRow {
+        let product = Product {
+            id,
+            url: format!("https://example.com/{id}"),
+            name: format!("Product {id}"),
+            retailer: retailer.to_string(),
+            retailer_id: None,
+            currency: Currency::Usd,
+            added_by: "alice".to_string(),
+            created_at: SystemTime::UNIX_EPOCH,
+            poll_interval: None,
+        };
+        let latest = price.map(|price| Observation {
+            id,
+            product_id: id,
+            observed_at: SystemTime::UNIX_EPOCH,
+            price: Some(Money::new(price * 100, Currency::Usd)),
+            stock,
+            error: None,
+            ships_from: None,
+            sold_by: None,
+            third_party_seller: false,
+        });
+        let subscription = Some(Subscription {
+            product_id: id,
+            username: "alice".to_string(),
+            target_price: target.map(|target| Money::new(target * 100, Currency::Usd)),
+            created_at: SystemTime::UNIX_EPOCH,
+        });
+        ListingRow {
+            product,
+            latest,
+            subscription,
+        }
+    }
+
+    fn rows() -> Vec<ListingRow> {
+        let mut failed = row(5, "Best Buy", Some(100), StockState::InStock, Some(200));
+        failed.latest.as_mut().unwrap().error = Some("timed out".to_string());
+        vec![
+            row(1, "Amazon", Some(500), StockState::InStock, Some(450)),
+            row(2, "Best Buy", Some(300), StockState::OutOfStock, Some(400)),
+            row(3, "Amazon", None, StockState::Unknown, None),
+            row(4, "Amazon", Some(700), StockState::InStock, None),
+            failed,
+        ]
+    }
+
+    fn query(query: &str) -> ProductQuery {
+        serde_urlencoded::from_str::<ProductQuery>(query)
+            .unwrap()
+            .normalized()
+    }
+
+    fn ids(page: &ListingPage) -> Vec<i64> {
+        page.rows.iter().map(|row| row.product.id).collect()
+    }
+
+    #[test]
+    fn sorts() {
+        let cases: &[(&str, &[i64])] = &[
+            ("", &[5, 4, 3, 2, 1]),
+            ("newest", &[5, 4, 3, 2, 1]),
+            ("oldest", &[1, 2, 3, 4, 5]),
+            // Unpriced products (never checked, or the check failed) go last
+            ("price_asc", &[2, 1, 4, 5, 3]),
+            ("price_desc", &[4, 1, 2, 5, 3]),
+            ("target", &[2, 1, 5, 4, 3]),
+            ("cheapest", &[5, 4, 3, 2, 1]),
+        ];
+        for (sort, expected) in cases {
+            let page = apply(&query(&format!("sort={sort}")), rows());
+            assert_eq!(ids(&page), *expected, "sort={sort}");
+        }
+    }
+
+    #[test]
+    fn filters() {
+        let cases: &[(&str, &[i64])] = &[
+            ("retailer=Amazon", &[4, 3, 1]),
+            ("retailer=Best+Buy", &[5, 2]),
+            ("retailer=Newegg", &[]),
+            ("stock=in_stock", &[4, 1]),
+            ("stock=out_of_stock", &[2]),
+            // A failed check says nothing about the stock
+            ("stock=unknown", &[5, 3]),
+            ("stock=sideways", &[5, 4, 3, 2, 1]),
+            ("target=below", &[2]),
+            ("target=above", &[1]),
+            ("q=++PRODUCT+4+", &[4]),
+            ("owner=ALICE", &[5, 4, 3, 2, 1]),
+            ("owner=bob", &[]),
+            ("retailer=Amazon&stock=in_stock", &[4, 1]),
+        ];
+        for (filter, expected) in cases {
+            let page = apply(&query(filter), rows());
+            assert_eq!(ids(&page), *expected, "{filter}");
+            assert_eq!(page.total, expected.len(), "{filter}");
+        }
+    }
+
+    #[test]
+    fn pages() {
+        let rows = || {
+            (1..=30)
+                .map(|id| row(id, "Amazon", Some(id), StockState::InStock, None))
+                .collect::<Vec<_>>()
+        };
+        let cases: &[(&str, usize, &[i64])] = &[
+            ("", 1, &[30, 29, 28, 27, 26, 25, 24, 23, 22, 21, 20, 19]),
+            ("2", 2, &[18, 17, 16, 15, 14, 13, 12, 11, 10, 9, 8, 7]),
+            ("3", 3, &[6, 5, 4, 3, 2, 1]),
+            // Past the end shows the last page, and nonsense the first
+            ("9", 3, &[6, 5, 4, 3, 2, 1]),
+            ("0", 1, &[30, 29, 28, 27, 26, 25, 24, 23, 22, 21, 20, 19]),
+            ("-1", 1, &[30, 29, 28, 27, 26, 25, 24, 23, 22, 21, 20, 19]),
+        ];
+        for (number, page, expected) in cases {
+            let listing = apply(&query(&format!("page={number}")), rows());
+            assert_eq!(listing.page, *page, "page={number}");
+            assert_eq!(ids(&listing), *expected, "page={number}");
+            assert_eq!((listing.pages, listing.total), (3, 30));
+        }
+
+        let empty = apply(&query("page=2"), Vec::new());
+        assert_eq!((empty.page, empty.pages, empty.total), (1, 1, 0));
+        assert!(empty.rows.is_empty());
+    }
+
+    #[test]
+    fn page_links_keep_the_query() {
+        let query = query("sort=oldest&stock=&page=3");
+        assert_eq!(query.with_page(2), "sort=oldest&page=2");
+        assert_eq!(query.with_page(1), "sort=oldest");
+        assert!(!query.is_filtered());
+    }
+}
@@ -0,0 +1,32 @@
+// Connects pages whose body has `data-live-updates` to the server's live update socket.
+// Every message is a set of out of band fragments, which htmx swaps in by id.
+document.addEventListener('DOMContentLoaded', () => {
+  const path = document.body.dataset.liveUpdates;
+  if (!path) {
+    return;
+  }
+
+  const scheme = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
+  const url = `${scheme}//${window.location.host}${path}`;
+  let delay = 1000;
+
+  const connect = () => {
+    const socket = new WebSocket(url);
+
+    socket.onopen = () => {
+      delay = 1000;
+    };
+
+    socket.onmessage = (event) => {
+      htmx.swap(document.body, event.data, { swapStyle: 'none' });
+    };
+
+    // Back off while the server is away, or the session has ended and the upgrade is refused
+    socket.onclose = () => {
+      setTimeout(connect, delay);
+      delay = Math.min(delay * 2, 30000);
+    };
+  };
+
+  connect();
+});
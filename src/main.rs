@@ -1,62 +1,143 @@
+mod alerts;
+mod api;
+mod chart;
+mod checkout;
+mod cli;
+mod config;
+mod error;
+mod listing;
+mod money;
+mod notify;
+mod parse;
+mod products;
+mod retailers;
+mod scheduler;
+mod session;
+mod storage;
+mod tokens;
+mod transfer;
+mod updates;
+mod urls;
+mod users;
+
+use alerts::AlertEngine;
 use axum::Router;
 use axum::extract::Form;
+use axum::extract::FromRef;
+use axum::extract::Path;
 use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::IntoResponse;
 use axum::response::Response;
 use axum::routing::get;
 use axum::routing::post;
+use axum_extra::extract::SignedCookieJar;
+use axum_extra::extract::cookie::{Cookie, Key};
+use axum_tws::Message;
 use axum_tws::WebSocket;
 use axum_tws::WebSocketUpgrade;
+use checkout::{AutoBuyError, AutoBuyRule, AutoBuyer, CheckoutRun, CheckoutStatus};
+use clap::Parser;
+use cli::{Cli, Command};
+use config::Config;
+use error::AppError;
+use listing::{ListingPage, ListingRow, ProductQuery, SortOrder, TargetFilter};
 use maud::DOCTYPE;
 use maud::Markup;
 use maud::PreEscaped;
 use maud::html;
+use money::{Currency, Money};
+use notify::{
+    EmailNotifier, NewWebhook, NotificationPreferences, Notifications, Notifier, WebhookConfig,
+    WebhookFormat, WebhookNotifier,
+};
+use products::{ProductError, ProductErrors, ProductInput, Tracked, can_manage};
+use retailers::RetailerRegistry;
+use scheduler::Scheduler;
 use serde::Deserialize;
-use std::env;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::sync::{Arc, Mutex};
+use session::{AdminUser, EditorUser, SessionStore};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use storage::{
+    AlertKind, AlertRepository, CheckoutRepository, NewSubscription, Observation,
+    ObservationRepository, PreferencesRepository, Product, ProductRepository, Repositories,
+    StockState, Subscription, TokenRepository, WebhookRepository,
+};
+use tokens::{NewApiToken, TokenScope};
 use tokio::signal;
+use tokio::sync::broadcast;
 use tower_http::services::ServeDir;
 use tracing::{Level, info, warn};
 use tracing_subscriber::FmtSubscriber;
+use transfer::{Importer, ProductRecord, RecordError};
+use updates::{ProductUpdate, UpdateHub};
+use urls::{HttpShortLinkResolver, ShortLinkResolver};
+use users::{User, UserRole, UserStore};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize the tracing subscriber for logging
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
-        .finish();
-    tracing::subscriber::set_global_default(subscriber).expect("Failed to set tracing subscriber");
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve);
+
+    // Initialize the tracing subscriber for logging. Other commands print their results to
+    // stdout, so they log to stderr, and `--print-config` stays quiet.
+    if !cli.print_config {
+        let builder = FmtSubscriber::builder().with_max_level(Level::INFO);
+        if matches!(command, Command::Serve) {
+            tracing::subscriber::set_global_default(builder.finish())
+        } else {
+            tracing::subscriber::set_global_default(builder.with_writer(std::io::stderr).finish())
+        }
+        .expect("Failed to set tracing subscriber");
+    }
 
-    info!("Starting Midas application");
-    let state = create_app_state();
+    let retailers = Arc::new(RetailerRegistry::default());
+    let config = Config::load(cli.config.as_deref(), &retailers)?;
+    if cli.print_config {
+        print!("{}", config.to_redacted_toml()?);
+        return Ok(());
+    }
 
-    let mut app = Router::new()
-        .route("/", get(index))
-        .route("/login", post(login_handler))
-        .route("/dashboard", get(dashboard))
-        .route("/add-product", post(add_product))
-        .route("/products", get(view_products))
-        .route("/clicked", post(clicked))
-        .nest_service("/assets", ServeDir::new("assets"))
-        .with_state(state);
+    match command {
+        Command::Serve => serve(config, retailers).await,
+        command => cli::run(command, &config, retailers).await,
+    }
+}
 
+async fn serve(config: Config, retailers: Arc<RetailerRegistry>) -> anyhow::Result<()> {
+    info!("Starting Midas application");
+    let repositories = storage::open(&config.database.path)?;
+    let state = create_app_state(&config, retailers, repositories)?;
+
+    // Start checking tracked product pages in the background
+    let alert_engine = Arc::new(AlertEngine::new(
+        state.alerts.clone(),
+        config.alert_config(),
+    ));
+    let scheduler = Scheduler::new(
+        state.products.clone(),
+        state.observations.clone(),
+        state.retailers.clone(),
+        alert_engine,
+        state.notifications.clone(),
+        state.auto_buyer.clone(),
+        state.updates.clone(),
+        config.scheduler_config(),
+    )?;
+    tokio::spawn(scheduler.run());
+
+    let mut app = router(state);
     if cfg!(debug_assertions) {
         app = app.route("/_reload", get(handle_upgrade));
     }
 
-    // Get port from environment variable, or use 3000 as default
-    let requested_port = env::var("PORT")
-        .ok()
-        .and_then(|p| p.parse::<u16>().ok())
-        .unwrap_or(3000);
-    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), requested_port);
-
-    // Try to bind to the requested port
+    // Try to bind to the configured address
+    let addr = config.bind_addr();
     let listener = match tokio::net::TcpListener::bind(addr).await {
         Ok(listener) => {
             let addr = listener.local_addr()?;
-            info!("Server started at http://0.0.0.0:{}", addr.port());
+            info!("Server started at http://{}", addr);
             listener
         }
         Err(e) => {
@@ -64,19 +145,16 @@ async fn main() -> anyhow::Result<()> {
             if e.kind() == std::io::ErrorKind::AddrInUse {
                 println!(
                     "Port {} is already in use. Trying to bind to a random available port...",
-                    requested_port
+                    addr.port()
                 );
 
                 // Bind to port 0 (OS will assign an available port)
-                let fallback_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+                let fallback_addr = SocketAddr::new(addr.ip(), 0);
                 let listener = tokio::net::TcpListener::bind(fallback_addr).await?;
 
-                // Get the actual port assigned by the OS
-                let actual_port = listener.local_addr()?.port();
-                info!(
-                    "Server started at http://0.0.0.0:{} (fallback port)",
-                    actual_port
-                );
+                // Get the actual address assigned by the OS
+                let actual_addr = listener.local_addr()?;
+                info!("Server started at http://{} (fallback port)", actual_addr);
 
                 listener
             } else {
@@ -96,6 +174,61 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+// Every page and API route
+fn router(state: AppState) -> Router {
+    let mut routes = Router::new()
+        .route("/", get(index))
+        .route("/login", post(login_handler))
+        .route("/logout", post(logout_handler))
+        .route("/register", get(register_page).post(register_handler))
+        .route(
+            "/account/notifications",
+            get(notifications_page).post(save_notifications_handler),
+        )
+        .route(
+            "/account/webhooks",
+            get(webhooks_page).post(add_webhook_handler),
+        )
+        .route("/account/webhooks/delete", post(delete_webhook_handler))
+        .route(
+            "/account/tokens",
+            get(tokens_page).post(create_token_handler),
+        )
+        .route("/account/tokens/delete", post(delete_token_handler))
+        .route(
+            "/account/password",
+            get(password_page).post(change_password_handler),
+        )
+        .route("/dashboard", get(dashboard))
+        .route("/admin/users", get(admin_users))
+        .route("/admin/users/role", post(set_user_role))
+        .route("/add-product", post(add_product))
+        .route("/products", get(view_products))
+        .route(
+            "/products/import",
+            get(import_products_page).post(import_products),
+        )
+        .route("/products/import/preview", post(preview_import))
+        .route("/products/export.csv", get(export_products_csv))
+        .route("/products/export.json", get(export_products_json))
+        .route("/products/history.csv", get(export_history_csv))
+        .route("/products/{id}", get(product_detail).delete(delete_product))
+        .route(
+            "/products/{id}/edit",
+            get(edit_product_page).post(edit_product_handler),
+        )
+        .route("/products/{id}/watch", post(watch_product));
+    if state.auto_buyer.available() {
+        routes = routes.route("/products/{id}/auto-buy", post(save_auto_buy));
+    }
+    routes
+        .route("/ws/updates", get(live_updates))
+        .nest("/api/v1", api::router())
+        .route("/clicked", post(clicked))
+        .nest_service("/assets", ServeDir::new("assets"))
+        .with_state(state)
+}
+
 async fn handle_upgrade(ws: WebSocketUpgrade) -> Response {
     ws.on_upgrade({
         move |socket| async {
@@ -115,6 +248,109 @@ async fn handle_ws(mut socket: WebSocket) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// How often an open live-update connection checks that its session is still good
+const LIVE_UPDATES_RECHECK: Duration = Duration::from_secs(30);
+
+// Pushes product status changes to an open dashboard or products page. Each message is a
+// `product_status` fragment that htmx swaps in out of band, by id.
+async fn live_updates(
+    user: User,
+    State(state): State<AppState>,
+    jar: SignedCookieJar,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Response {
+    // Browsers send the session cookie along with upgrades from any site, so only pages
+    // served from this host may listen in
+    if !same_origin(&headers) {
+        warn!(
+            "Live updates refused, cross origin - username: {}, origin: {:?}",
+            user.username,
+            headers.get(axum::http::header::ORIGIN)
+        );
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let updates = state.updates.subscribe();
+    let session = jar
+        .get(session::SESSION_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .unwrap_or_default();
+    ws.on_upgrade(move |socket| async move {
+        if let Err(e) = stream_updates(socket, updates, state, session, user).await {
+            warn!("Live update connection failed: {:?}", e);
+        }
+    })
+}
+
+// Whether the request's `Origin` names the host it was sent to. Requests without one are
+// refused too, every browser sends it on upgrades.
+fn same_origin(headers: &HeaderMap) -> bool {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let (Some(origin), Some(host)) = (
+        header(axum::http::header::ORIGIN).and_then(|origin| url::Url::parse(origin).ok()),
+        header(axum::http::header::HOST),
+    ) else {
+        return false;
+    };
+    let authority = match (origin.host_str(), origin.port()) {
+        (Some(name), Some(port)) => format!("{name}:{port}"),
+        (Some(name), None) => name.to_string(),
+        (None, _) => return false,
+    };
+    authority.eq_ignore_ascii_case(host)
+}
+
+async fn stream_updates(
+    mut socket: WebSocket,
+    mut updates: broadcast::Receiver<ProductUpdate>,
+    state: AppState,
+    session: String,
+    mut user: User,
+) -> anyhow::Result<()> {
+    let mut recheck = tokio::time::interval(LIVE_UPDATES_RECHECK);
+    recheck.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            // Pick up sign outs, deleted accounts and role changes; a closed socket sends
+            // the page back through the login check when it reconnects
+            _ = recheck.tick() => match session::user_for_session(&state, &session)? {
+                Some(current) if current.username == user.username => user = current,
+                _ => {
+                    info!("Closing live updates, session ended - username: {}", user.username);
+                    socket.close().await?;
+                    break;
+                }
+            },
+            // The page never sends anything, we only listen for it going away
+            msg = socket.recv() => match msg {
+                Some(Ok(msg)) if !msg.is_close() => {}
+                _ => break,
+            },
+            update = updates.recv() => match update {
+                Ok(update) => {
+                    let subscribed = update
+                        .subscribers
+                        .iter()
+                        .any(|s| s.eq_ignore_ascii_case(&user.username));
+                    if !user.role.can_view_all() && !subscribed {
+                        continue;
+                    }
+                    let fragment = product_status(update.product.id, Some(&update.observation), true);
+                    socket.send(Message::text(fragment.into_string())).await?;
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Live updates lagging - username: {}, skipped: {}",
+                        user.username, skipped
+                    );
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        }
+    }
+    Ok(())
+}
+
 fn header() -> Markup {
     html! {
         (DOCTYPE)
@@ -122,6 +358,9 @@ fn header() -> Markup {
         meta charset="utf-8";
         script src="https://unpkg.com/htmx.org@2.0.4" integrity="sha384-HGfztofotfshcF7+8n44JQL2oJmowVChPTg48S+jvZoztPfvwD79OC/LTtG6dMp+" crossorigin="anonymous" {}
         link href="/assets/output.css" rel="stylesheet";
+        script {
+            (PreEscaped(include_str!("live_updates.js")))
+        }
         @if cfg!(debug_assertions) {
             script {
                 (PreEscaped(include_str!("hot_reload.js")))
@@ -144,56 +383,258 @@ struct LoginForm {
     password: String,
 }
 
-// Role enum to track user permissions
-#[derive(Debug, Clone, PartialEq)]
-enum UserRole {
-    Regular,
-    Admin,
+#[derive(Debug, Clone, Deserialize)]
+struct RegisterForm {
+    username: String,
+    password: String,
+    confirm_password: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ChangePasswordForm {
+    current_password: String,
+    new_password: String,
+    confirm_password: String,
 }
 
-// User structure to store user information
-#[derive(Debug, Clone)]
-struct User {
+#[derive(Debug, Clone, Deserialize)]
+struct SetRoleForm {
     username: String,
     role: UserRole,
 }
 
-// Check if a username has admin privileges
-fn is_admin(username: &str) -> bool {
-    // For simplicity, only "admin" username has admin privileges
-    username.to_lowercase() == "admin"
+// Checkboxes are only submitted when ticked
+#[derive(Debug, Clone, Deserialize)]
+struct NotificationsForm {
+    email: String,
+    email_enabled: Option<String>,
+    target_reached: Option<String>,
+    price_drop: Option<String>,
+    back_in_stock: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
-struct ProductForm {
+struct WebhookForm {
     url: String,
+    format: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct DeleteWebhookForm {
+    id: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TokenForm {
     name: String,
-    retailer: String,
-    target_price: Option<String>,
+    scope: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct DeleteTokenForm {
+    id: i64,
 }
 
-#[derive(Debug, Clone)]
-struct Product {
+// Kept as typed, so a form with errors can be shown again just as it was submitted
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct ProductForm {
     url: String,
     name: String,
     retailer: String,
-    target_price: Option<f64>,
-    added_by: String,
-    created_at: std::time::SystemTime,
+    target_price: Option<String>,
+    // Minutes between checks, empty for the default
+    poll_interval: Option<String>,
+}
+
+impl ProductForm {
+    fn poll_interval(&self) -> Option<Duration> {
+        self.poll_interval
+            .as_deref()
+            .and_then(|s| s.parse::<u64>().ok())
+            .map(products::poll_interval_minutes)
+    }
+}
+
+#[derive(Deserialize)]
+struct AutoBuyForm {
+    // Checkboxes, only sent when ticked
+    enabled: Option<String>,
+    dry_run: Option<String>,
+    #[serde(default)]
+    max_spend: String,
+    #[serde(default)]
+    max_quantity: String,
+}
+
+#[derive(Deserialize)]
+struct ImportForm {
+    // The previewed records as JSON
+    records: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct WatchForm {
+    target_price: Option<String>,
+}
+
+// Choices for how often to check a product, in minutes
+const POLL_INTERVALS: [(u64, &str); 4] = [
+    (5, "5 minutes"),
+    (30, "30 minutes"),
+    (60, "1 hour"),
+    (360, "6 hours"),
+];
+
+// Rough human-readable age, e.g. "3 days ago"
+fn time_ago(time: std::time::SystemTime) -> String {
+    let secs = time.elapsed().map(|d| d.as_secs()).unwrap_or(0);
+    let (value, unit) = match secs {
+        0..60 => return "just now".to_string(),
+        60..3600 => (secs / 60, "minute"),
+        3600..86400 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day"),
+    };
+    format!(
+        "{} {}{} ago",
+        value,
+        unit,
+        if value == 1 { "" } else { "s" }
+    )
+}
+
+// Absolute timestamp for history listings, e.g. "Mar 4, 2025 14:05 UTC"
+fn format_time(time: std::time::SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time)
+        .format("%b %-d, %Y %H:%M UTC")
+        .to_string()
 }
 
-// List of supported retailers
-fn supported_retailers() -> Vec<&'static str> {
-    vec!["Best Buy", "Amazon"] // Supported retailers
+#[derive(Clone)]
+struct AppState {
+    products: Arc<dyn ProductRepository>,
+    observations: Arc<dyn ObservationRepository>,
+    alerts: Arc<dyn AlertRepository>,
+    preferences: Arc<dyn PreferencesRepository>,
+    webhooks: Arc<dyn WebhookRepository>,
+    tokens: Arc<dyn TokenRepository>,
+    checkouts: Arc<dyn CheckoutRepository>,
+    notifications: Arc<Notifications>,
+    auto_buyer: Arc<AutoBuyer>,
+    updates: Arc<UpdateHub>,
+    sessions: SessionStore,
+    users: UserStore,
+    retailers: Arc<RetailerRegistry>,
+    link_resolver: Arc<dyn ShortLinkResolver>,
+    // How often products without their own interval are checked
+    default_poll_interval: Duration,
+    // Key used to sign the session cookie
+    key: Key,
+}
+
+// Lets `SignedCookieJar` find the signing key in our state
+impl FromRef<AppState> for Key {
+    fn from_ref(state: &AppState) -> Self {
+        state.key.clone()
+    }
+}
+
+fn create_app_state(
+    config: &Config,
+    retailers: Arc<RetailerRegistry>,
+    repositories: Repositories,
+) -> anyhow::Result<AppState> {
+    let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+    match config.smtp_config() {
+        Some(config) => {
+            info!(
+                "Email notifications enabled - host: {}, port: {}",
+                config.host, config.port
+            );
+            notifiers.push(Box::new(EmailNotifier::new(config)?));
+        }
+        None => info!("No SMTP host configured, email notifications are disabled"),
+    }
+    notifiers.push(Box::new(WebhookNotifier::new(
+        repositories.webhooks.clone(),
+        WebhookConfig::default(),
+    )));
+
+    let auto_buyer = AutoBuyer::new(
+        repositories.checkouts.clone(),
+        retailers.clone(),
+        config.checkout_config(),
+    )?;
+    if !auto_buyer.available() {
+        info!("No retailer supports checkout, auto-buy is turned off");
+    } else if config.checkout.live_orders {
+        warn!("Live orders are turned on, auto-buy will pay for orders");
+    }
+
+    Ok(AppState {
+        products: repositories.products,
+        observations: repositories.observations,
+        alerts: repositories.alerts,
+        preferences: repositories.preferences.clone(),
+        webhooks: repositories.webhooks,
+        tokens: repositories.tokens,
+        checkouts: repositories.checkouts,
+        notifications: Arc::new(Notifications::new(repositories.preferences, notifiers)),
+        auto_buyer: Arc::new(auto_buyer),
+        updates: Arc::new(UpdateHub::default()),
+        sessions: SessionStore::default(),
+        users: UserStore::new(repositories.users),
+        retailers,
+        link_resolver: Arc::new(HttpShortLinkResolver::new(
+            scheduler::USER_AGENT,
+            Duration::from_secs(10),
+        )?),
+        default_poll_interval: config.scheduler_config().default_interval,
+        key: session::load_key(config.session.secret.as_deref())?,
+    })
 }
 
-type AppState = Arc<Mutex<Vec<Product>>>;
+// Red banner used to report a failed action
+fn error_alert(message: &str) -> Markup {
+    html! {
+        div class="mt-4 p-4 border border-red-300 bg-red-50 text-red-800 rounded-md" {
+            div class="flex" {
+                svg class="h-5 w-5 text-red-400 mr-2" fill="currentColor" viewBox="0 0 20 20" {
+                    path fill-rule="evenodd" d="M10 18a8 8 0 100-16 8 8 0 000 16zM8.707 7.293a1 1 0 00-1.414 1.414L8.586 10l-1.293 1.293a1 1 0 101.414 1.414L10 11.414l1.293 1.293a1 1 0 001.414-1.414L11.414 10l1.293-1.293a1 1 0 00-1.414-1.414L10 8.586 8.707 7.293z" clip-rule="evenodd" {}
+                }
+                p { (message) }
+            }
+        }
+    }
+}
 
-fn create_app_state() -> AppState {
-    Arc::new(Mutex::new(Vec::new()))
+// Green banner used to confirm a successful action
+fn success_alert(message: &str) -> Markup {
+    html! {
+        div class="mt-4 p-4 border border-green-300 bg-green-50 text-green-800 rounded-md" {
+            div class="flex" {
+                svg class="h-5 w-5 text-green-400 mr-2" fill="currentColor" viewBox="0 0 20 20" {
+                    path fill-rule="evenodd" d="M10 18a8 8 0 100-16 8 8 0 000 16zm3.707-9.293a1 1 0 00-1.414-1.414L9 10.586 7.707 9.293a1 1 0 00-1.414 1.414l2 2a1 1 0 001.414 0l4-4z" clip-rule="evenodd" {}
+                }
+                p { (message) }
+            }
+        }
+    }
 }
 
-async fn index() -> impl IntoResponse {
+async fn index(
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> impl IntoResponse {
+    let error_message = params.get("error").map(|e| match e.as_str() {
+        "invalid_credentials" => "Incorrect username or password.",
+        "locked" => "Too many failed sign-in attempts. Please try again in a few minutes.",
+        _ => "An error occurred. Please try again.",
+    });
+
+    let success_message = params
+        .get("registered")
+        .map(|_| "Account created! You can now sign in.");
+
     html! {
         (header())
         body class="font-display flex items-center justify-center min-h-screen bg-gray-100" {
@@ -203,6 +644,13 @@ async fn index() -> impl IntoResponse {
                     p class="mt-2 text-gray-600" { "Please sign in to your account" }
                 }
 
+                @if let Some(message) = error_message {
+                    (error_alert(message))
+                }
+                @if let Some(message) = success_message {
+                    (success_alert(message))
+                }
+
                 form class="mt-8 space-y-6" action="/login" method="POST" {
                     div class="space-y-4" {
                         div {
@@ -225,243 +673,809 @@ async fn index() -> impl IntoResponse {
                         }
                     }
                 }
+
+                p class="text-center text-sm text-gray-600" {
+                    "Don't have an account? "
+                    a href="/register" class="text-indigo-600 hover:text-indigo-800" { "Create one" }
+                }
             }
         }
     }
 }
 
-async fn login_handler(Form(form): Form<LoginForm>) -> impl IntoResponse {
-    // In a real app, you would validate the credentials here
-    // For demo purposes, we'll just redirect to the dashboard
-    if !form.username.is_empty() && !form.password.is_empty() {
-        // Check if the user has admin privileges
-        let is_admin_user = is_admin(&form.username);
-
-        // Log successful login
-        info!(
-            "User logged in - username: {}, role: {}",
-            form.username,
-            if is_admin_user { "admin" } else { "regular" }
-        );
-
-        // Redirect to dashboard on successful login with username and role as query params
-        // In a real app, you would use proper session management (JWT, cookies, etc.)
-        let redirect_url = format!(
-            "/dashboard?user={}&role={}",
-            form.username,
-            if is_admin_user { "admin" } else { "regular" }
-        );
-        axum::response::Redirect::to(&redirect_url).into_response()
-    } else {
-        // Log failed login attempt
-        warn!("Failed login attempt - empty username or password");
-
-        // Return to login page if validation fails (in a real app, you'd add an error message)
-        axum::response::Redirect::to("/").into_response()
-    }
+async fn login_handler(
+    State(state): State<AppState>,
+    jar: SignedCookieJar,
+    Form(form): Form<LoginForm>,
+) -> Result<Response, AppError> {
+    // Password hashing is slow on purpose, so keep it off the async workers
+    let users = state.users.clone();
+    let (username, password) = (form.username.clone(), form.password.clone());
+    let result =
+        tokio::task::spawn_blocking(move || users.authenticate(&username, &password)).await??;
+    Ok(match result {
+        Ok(user) => {
+            // Log successful login
+            info!(
+                "User logged in - username: {}, role: {:?}",
+                user.username, user.role
+            );
+
+            // Start a server-side session and hand the browser a signed cookie for it
+            let session_id = state.sessions.create(&user.username);
+            let jar = jar.add(session::session_cookie(session_id));
+
+            (jar, axum::response::Redirect::to("/dashboard")).into_response()
+        }
+        Err(e) => {
+            // Log failed login attempt
+            warn!(
+                "Failed login attempt - username: {}, reason: {:?}",
+                form.username, e
+            );
+
+            let redirect_url = format!("/?error={}", e.code());
+            axum::response::Redirect::to(&redirect_url).into_response()
+        }
+    })
 }
 
-async fn dashboard(
-    State(state): State<AppState>,
+async fn register_page(
     axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
 ) -> impl IntoResponse {
-    // Get username and role from query params
-    let username = params
-        .get("user")
-        .cloned()
-        .unwrap_or_else(|| "Anonymous".to_string());
-    let role = params
-        .get("role")
-        .cloned()
-        .unwrap_or_else(|| "regular".to_string());
-    let is_admin_user = role == "admin";
-
-    // Check for error or success messages
     let error_message = params.get("error").map(|e| match e.as_str() {
-        "invalid_retailer" => {
-            "Invalid retailer. Please select a supported retailer from the dropdown."
-        }
-        "invalid_url" => {
-            "The URL doesn't match the selected retailer. Please enter a valid product URL."
+        "invalid_username" => {
+            "Usernames must be 3-32 characters of letters, numbers, '.', '_' or '-'."
         }
+        "username_taken" => "That username is already taken.",
+        "weak_password" => "Passwords must be at least 8 characters long.",
+        "mismatch" => "The passwords don't match.",
         _ => "An error occurred. Please try again.",
     });
 
-    let success_message = params
-        .get("success")
-        .map(|_| "Product successfully added for tracking!");
-
     html! {
         (header())
-        body class="font-display" {
-            div class="max-w-7xl mx-auto px-4 sm:px-6 lg:px-8 py-8" {
-                div class="mb-10" {
-                    div class="flex justify-between items-center mb-6" {
-                        div class="flex items-center" {
-                            h1 class="text-3xl font-bold text-gray-900" { "Dashboard" }
-                            @if is_admin_user {
-                                span class="ml-3 inline-flex items-center rounded-full bg-purple-100 px-2.5 py-0.5 text-xs font-medium text-purple-800" {
-                                    "Admin"
-                                }
-                            }
+        body class="font-display flex items-center justify-center min-h-screen bg-gray-100" {
+            div class="w-full max-w-md p-8 space-y-8 bg-white rounded-lg shadow-md" {
+                div class="text-center" {
+                    h1 class="text-3xl font-bold text-gray-900" { "Midas" }
+                    p class="mt-2 text-gray-600" { "Create your account" }
+                }
+
+                @if let Some(message) = error_message {
+                    (error_alert(message))
+                }
+
+                form class="mt-8 space-y-6" action="/register" method="POST" {
+                    div class="space-y-4" {
+                        div {
+                            label class="block text-sm font-medium text-gray-700" for="username" { "Username" }
+                            input id="username" name="username" type="text" required
+                                class="w-full px-3 py-2 mt-1 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500";
                         }
-                        a href="/" class="text-indigo-600 hover:text-indigo-800" { "Sign Out" }
-                    }
-                    p class="text-gray-600" {
-                        @if is_admin_user {
-                            "Admin dashboard - you can view and manage all user products"
-                        } @else {
-                            "Welcome to your Midas Product Tracker dashboard!"
+
+                        div {
+                            label class="block text-sm font-medium text-gray-700" for="password" { "Password" }
+                            input id="password" name="password" type="password" required minlength="8"
+                                class="w-full px-3 py-2 mt-1 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500";
                         }
-                    }
 
-                    // Show error message if present
-                    @if let Some(message) = error_message {
-                        div class="mt-4 p-4 border border-red-300 bg-red-50 text-red-800 rounded-md" {
-                            div class="flex" {
-                                svg class="h-5 w-5 text-red-400 mr-2" fill="currentColor" viewBox="0 0 20 20" {
-                                    path fill-rule="evenodd" d="M10 18a8 8 0 100-16 8 8 0 000 16zM8.707 7.293a1 1 0 00-1.414 1.414L8.586 10l-1.293 1.293a1 1 0 101.414 1.414L10 11.414l1.293 1.293a1 1 0 001.414-1.414L11.414 10l1.293-1.293a1 1 0 00-1.414-1.414L10 8.586 8.707 7.293z" clip-rule="evenodd" {}
-                                }
-                                p { (message) }
-                            }
+                        div {
+                            label class="block text-sm font-medium text-gray-700" for="confirm_password" { "Confirm Password" }
+                            input id="confirm_password" name="confirm_password" type="password" required minlength="8"
+                                class="w-full px-3 py-2 mt-1 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500";
                         }
                     }
 
-                    // Show success message if present
-                    @if let Some(message) = success_message {
-                        div class="mt-4 p-4 border border-green-300 bg-green-50 text-green-800 rounded-md" {
-                            div class="flex" {
-                                svg class="h-5 w-5 text-green-400 mr-2" fill="currentColor" viewBox="0 0 20 20" {
-                                    path fill-rule="evenodd" d="M10 18a8 8 0 100-16 8 8 0 000 16zm3.707-9.293a1 1 0 00-1.414-1.414L9 10.586 7.707 9.293a1 1 0 00-1.414 1.414l2 2a1 1 0 001.414 0l4-4z" clip-rule="evenodd" {}
-                                }
-                                p { (message) }
-                            }
+                    div {
+                        button type="submit"
+                            class="w-full px-4 py-2 text-white bg-indigo-600 rounded-md hover:bg-indigo-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-indigo-500" {
+                            "Create account"
                         }
                     }
                 }
 
-                // Product Tracker Section
-                div class="bg-white shadow rounded-lg p-6 mb-8" {
-                    h2 class="text-2xl font-bold mb-4 text-gray-800" { "Add Product to Track" }
-                    p class="mb-6 text-gray-600" { "Submit products you'd like to track for availability and price changes." }
+                p class="text-center text-sm text-gray-600" {
+                    "Already have an account? "
+                    a href="/" class="text-indigo-600 hover:text-indigo-800" { "Sign in" }
+                }
+            }
+        }
+    }
+}
+
+async fn register_handler(
+    State(state): State<AppState>,
+    Form(form): Form<RegisterForm>,
+) -> Result<Response, AppError> {
+    if form.password != form.confirm_password {
+        return Ok(axum::response::Redirect::to("/register?error=mismatch").into_response());
+    }
 
-                    div class="mb-6 bg-blue-50 rounded-lg p-4 border border-blue-200" {
-                        div class="flex items-center" {
-                            svg class="h-5 w-5 text-blue-400 mr-2" fill="currentColor" viewBox="0 0 20 20" {
-                                path d="M10 18a8 8 0 100-16 8 8 0 000 16zm1-11a1 1 0 10-2 0v2H7a1 1 0 100 2h2v2a1 1 0 102 0v-2h2a1 1 0 100-2h-2V7z" clip-rule="evenodd" fill-rule="evenodd" {}
-                            }
-                            span class="text-blue-800 font-medium" { "Currently Supported Retailers:" }
-                        }
-                        div class="mt-2 flex flex-wrap gap-2" {
-                            @for retailer in supported_retailers() {
-                                @let (bg_color, text_color) = match retailer {
-                                    "Amazon" => ("bg-orange-100", "text-orange-800"),
-                                    "Best Buy" => ("bg-blue-100", "text-blue-800"),
-                                    _ => ("bg-gray-100", "text-gray-800"),
-                                };
-                                span class=(format!("inline-flex items-center rounded-full {} {} px-3 py-1 text-sm font-medium", bg_color, text_color)) {
-                                    (retailer)
-                                }
-                            }
-                        }
-                    }
+    let users = state.users.clone();
+    let (username, password) = (form.username.clone(), form.password.clone());
+    let result =
+        tokio::task::spawn_blocking(move || users.register(&username, &password)).await??;
+    Ok(match result {
+        Ok(user) => {
+            info!(
+                "User registered - username: {}, role: {:?}",
+                user.username, user.role
+            );
+            axum::response::Redirect::to("/?registered=true").into_response()
+        }
+        Err(e) => {
+            warn!(
+                "Registration failed - username: {}, reason: {:?}",
+                form.username, e
+            );
+            let redirect_url = format!("/register?error={}", e.code());
+            axum::response::Redirect::to(&redirect_url).into_response()
+        }
+    })
+}
 
-                    form class="space-y-4" action=(format!("/add-product?user={}&role={}", username, role)) method="POST" {
-                        div {
-                            label class="block text-sm font-medium text-gray-700" for="url" { "Product URL" }
-                            input id="url" name="url" type="url" required placeholder="https://www.amazon.com/dp/B08FC6MR62 or https://www.bestbuy.com/site/..."
-                                class="w-full px-3 py-2 mt-1 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500";
-                        }
+async fn password_page(
+    user: User,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> impl IntoResponse {
+    let error_message = params.get("error").map(|e| match e.as_str() {
+        "wrong_password" => "Your current password is incorrect.",
+        "weak_password" => "Passwords must be at least 8 characters long.",
+        "mismatch" => "The new passwords don't match.",
+        _ => "An error occurred. Please try again.",
+    });
+
+    let success_message = params
+        .get("success")
+        .map(|_| "Password changed. Your other sessions have been signed out.");
+
+    html! {
+        (header())
+        body class="font-display" {
+            div class="max-w-xl mx-auto px-4 sm:px-6 lg:px-8 py-8" {
+                div class="flex justify-between items-center mb-6" {
+                    h1 class="text-3xl font-bold text-gray-900" { "Change Password" }
+                    a href="/dashboard" class="text-indigo-600 hover:text-indigo-800" { "Back to Dashboard" }
+                }
+                p class="text-gray-600" { "Signed in as " span class="font-medium" { (user.username) } }
+
+                @if let Some(message) = error_message {
+                    (error_alert(message))
+                }
+                @if let Some(message) = success_message {
+                    (success_alert(message))
+                }
 
+                div class="bg-white shadow rounded-lg p-6 mt-6" {
+                    form class="space-y-4" action="/account/password" method="POST" {
                         div {
-                            label class="block text-sm font-medium text-gray-700" for="name" { "Product Name" }
-                            input id="name" name="name" type="text" required placeholder="e.g. PlayStation 5 Digital Edition"
+                            label class="block text-sm font-medium text-gray-700" for="current_password" { "Current Password" }
+                            input id="current_password" name="current_password" type="password" required
                                 class="w-full px-3 py-2 mt-1 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500";
                         }
 
                         div {
-                            label class="block text-sm font-medium text-gray-700" for="retailer" { "Retailer" }
-                            select id="retailer" name="retailer" required
-                                class="w-full px-3 py-2 mt-1 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500" {
-                                @for retailer in supported_retailers() {
-                                    option value=(retailer) { (retailer) }
-                                }
-                            }
+                            label class="block text-sm font-medium text-gray-700" for="new_password" { "New Password" }
+                            input id="new_password" name="new_password" type="password" required minlength="8"
+                                class="w-full px-3 py-2 mt-1 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500";
                         }
 
                         div {
-                            label class="block text-sm font-medium text-gray-700" for="target_price" { "Target Price (Optional)" }
-                            div class="mt-1 relative rounded-md shadow-sm" {
-                                div class="absolute inset-y-0 left-0 pl-3 flex items-center pointer-events-none" {
-                                    span class="text-gray-500 sm:text-sm" { "$" }
-                                }
-                                input id="target_price" name="target_price" type="text" placeholder="399.99"
-                                    class="w-full pl-7 pr-12 py-2 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500";
-                            }
+                            label class="block text-sm font-medium text-gray-700" for="confirm_password" { "Confirm New Password" }
+                            input id="confirm_password" name="confirm_password" type="password" required minlength="8"
+                                class="w-full px-3 py-2 mt-1 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500";
                         }
 
                         div {
                             button type="submit"
                                 class="w-full px-4 py-2 text-white bg-indigo-600 rounded-md hover:bg-indigo-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-indigo-500" {
-                                "Add Product"
+                                "Change Password"
                             }
                         }
                     }
                 }
+            }
+        }
+    }
+}
 
-                // View Products Section
-                div class="bg-white shadow rounded-lg p-6" {
-                    div class="flex justify-between items-center mb-4" {
-                        h2 class="text-2xl font-bold text-gray-800" { "Your Tracked Products" }
-                        a href="/products" class="text-indigo-600 hover:text-indigo-800" { "View All Products" }
-                    }
+async fn change_password_handler(
+    user: User,
+    State(state): State<AppState>,
+    jar: SignedCookieJar,
+    Form(form): Form<ChangePasswordForm>,
+) -> Result<Response, AppError> {
+    let users = state.users.clone();
+    let username = user.username.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        users.change_password(
+            &username,
+            &form.current_password,
+            &form.new_password,
+            &form.confirm_password,
+        )
+    })
+    .await??;
 
-                    @let all_products = state.lock().unwrap();
+    Ok(match result {
+        Ok(()) => {
+            info!("Password changed - username: {}", user.username);
 
-                    // Filter products based on user role - admins see all, regular users see only their own
-                    @let visible_products: Vec<_> = if is_admin_user {
-                        all_products.iter().collect()
-                    } else {
-                        all_products.iter().filter(|p| p.added_by == username).collect()
-                    };
+            // Sign out everywhere else, in case the old password had leaked
+            if let Some(cookie) = jar.get(session::SESSION_COOKIE) {
+                state
+                    .sessions
+                    .destroy_others(&user.username, cookie.value());
+            }
+            axum::response::Redirect::to("/account/password?success=true").into_response()
+        }
+        Err(e) => {
+            warn!(
+                "Password change failed - username: {}, reason: {:?}",
+                user.username, e
+            );
+            let redirect_url = format!("/account/password?error={}", e.code());
+            axum::response::Redirect::to(&redirect_url).into_response()
+        }
+    })
+}
 
-                    @if visible_products.is_empty() {
-                        div class="text-center py-8 text-gray-500" {
-                            p { "You haven't added any products to track yet." }
+async fn notifications_page(
+    user: User,
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Markup, AppError> {
+    let error_message = params.get("error").map(|e| match e.as_str() {
+        "invalid_email" => "That doesn't look like a valid email address.",
+        "missing_email" => "Enter an email address to turn on email notifications.",
+        _ => "An error occurred. Please try again.",
+    });
+
+    let success_message = params
+        .get("success")
+        .map(|_| "Notification settings saved.");
+
+    let preferences = state
+        .preferences
+        .get(&user.username)?
+        .unwrap_or_else(|| NotificationPreferences::new(&user.username));
+    let email_available = state.notifications.has_channel("email");
+
+    Ok(html! {
+        (header())
+        body class="font-display" {
+            div class="max-w-xl mx-auto px-4 sm:px-6 lg:px-8 py-8" {
+                div class="flex justify-between items-center mb-6" {
+                    h1 class="text-3xl font-bold text-gray-900" { "Notifications" }
+                    a href="/dashboard" class="text-indigo-600 hover:text-indigo-800" { "Back to Dashboard" }
+                }
+                p class="text-gray-600" {
+                    "Choose how you hear about alerts on the products you track. Alerts can also be posted to "
+                    a href="/account/webhooks" class="text-indigo-600 hover:text-indigo-800" { "webhooks" }
+                    "."
+                }
+
+                @if let Some(message) = error_message {
+                    (error_alert(message))
+                }
+                @if let Some(message) = success_message {
+                    (success_alert(message))
+                }
+                @if !email_available {
+                    div class="mt-4 p-4 border border-yellow-300 bg-yellow-50 text-yellow-800 rounded-md" {
+                        "Email delivery isn't set up on this server yet, so no emails will be sent."
+                    }
+                }
+
+                div class="bg-white shadow rounded-lg p-6 mt-6" {
+                    form class="space-y-6" action="/account/notifications" method="POST" {
+                        div {
+                            label class="block text-sm font-medium text-gray-700" for="email" { "Email Address" }
+                            input id="email" name="email" type="email" value=(preferences.email.as_deref().unwrap_or(""))
+                                class="w-full px-3 py-2 mt-1 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500";
+                            label class="mt-2 flex items-center text-sm text-gray-700" {
+                                input name="email_enabled" type="checkbox" value="on" checked[preferences.email_enabled] class="mr-2";
+                                "Send me alerts by email"
+                            }
+                        }
+
+                        fieldset {
+                            legend class="block text-sm font-medium text-gray-700" { "Alert Me When" }
+                            @for kind in AlertKind::ALL {
+                                label class="mt-2 flex items-center text-sm text-gray-700" {
+                                    input name=(kind.as_str()) type="checkbox" value="on" checked[preferences.wants(kind)] class="mr-2";
+                                    (match kind {
+                                        AlertKind::TargetReached => "The price reaches my target price",
+                                        AlertKind::PriceDrop => "The price drops sharply",
+                                        AlertKind::BackInStock => "A product comes back in stock",
+                                    })
+                                }
+                            }
+                        }
+
+                        div {
+                            button type="submit"
+                                class="w-full px-4 py-2 text-white bg-indigo-600 rounded-md hover:bg-indigo-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-indigo-500" {
+                                "Save"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+async fn save_notifications_handler(
+    user: User,
+    State(state): State<AppState>,
+    Form(form): Form<NotificationsForm>,
+) -> Result<Response, AppError> {
+    let email = form.email.trim();
+    let preferences = NotificationPreferences {
+        username: user.username.clone(),
+        email: (!email.is_empty()).then(|| email.to_string()),
+        email_enabled: form.email_enabled.is_some(),
+        alert_kinds: AlertKind::ALL
+            .into_iter()
+            .filter(|kind| match kind {
+                AlertKind::TargetReached => form.target_reached.is_some(),
+                AlertKind::PriceDrop => form.price_drop.is_some(),
+                AlertKind::BackInStock => form.back_in_stock.is_some(),
+            })
+            .collect(),
+    };
+
+    if let Err(e) = preferences.validate() {
+        warn!(
+            "Notification settings rejected - username: {}, reason: {:?}",
+            user.username, e
+        );
+        let redirect_url = format!("/account/notifications?error={}", e.code());
+        return Ok(axum::response::Redirect::to(&redirect_url).into_response());
+    }
+
+    state.preferences.save(&preferences)?;
+    info!("Notification settings saved - username: {}", user.username);
+    Ok(axum::response::Redirect::to("/account/notifications?success=true").into_response())
+}
+
+async fn webhooks_page(
+    user: User,
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Markup, AppError> {
+    let error_message = params.get("error").map(|e| match e.as_str() {
+        "invalid_url" => "Enter a full http:// or https:// URL for the webhook.",
+        "private_address" => {
+            "Webhooks can't post to this server or to addresses on a private network."
+        }
+        "invalid_format" => "Please pick one of the listed payload formats.",
+        _ => "An error occurred. Please try again.",
+    });
+
+    let success_message = params.get("success").map(|s| match s.as_str() {
+        "deleted" => "Webhook deleted.",
+        _ => "Webhook added. Alerts will be posted to it from now on.",
+    });
+
+    let webhooks = state.webhooks.list_for_user(&user.username)?;
+
+    Ok(html! {
+        (header())
+        body class="font-display" {
+            div class="max-w-3xl mx-auto px-4 sm:px-6 lg:px-8 py-8" {
+                div class="flex justify-between items-center mb-6" {
+                    h1 class="text-3xl font-bold text-gray-900" { "Webhooks" }
+                    a href="/dashboard" class="text-indigo-600 hover:text-indigo-800" { "Back to Dashboard" }
+                }
+                p class="text-gray-600" {
+                    "Alerts on your products are posted as JSON to every webhook below, whatever your "
+                    a href="/account/notifications" class="text-indigo-600 hover:text-indigo-800" { "notification settings" }
+                    " say about email. Only the kinds of alert you've chosen there are sent."
+                }
+
+                @if let Some(message) = error_message {
+                    (error_alert(message))
+                }
+                @if let Some(message) = success_message {
+                    (success_alert(message))
+                }
+
+                div class="bg-white shadow rounded-lg p-6 mt-6" {
+                    h2 class="text-xl font-bold mb-4 text-gray-800" { "Add Webhook" }
+                    form class="space-y-4" action="/account/webhooks" method="POST" {
+                        div {
+                            label class="block text-sm font-medium text-gray-700" for="url" { "URL" }
+                            input id="url" name="url" type="url" required placeholder="https://discord.com/api/webhooks/..."
+                                class="w-full px-3 py-2 mt-1 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500";
+                        }
+                        div {
+                            label class="block text-sm font-medium text-gray-700" for="format" { "Format" }
+                            select id="format" name="format"
+                                class="w-full px-3 py-2 mt-1 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500" {
+                                @for format in WebhookFormat::ALL {
+                                    option value=(format.as_str()) { (format.label()) }
+                                }
+                            }
+                        }
+                        div {
+                            button type="submit"
+                                class="w-full px-4 py-2 text-white bg-indigo-600 rounded-md hover:bg-indigo-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-indigo-500" {
+                                "Add Webhook"
+                            }
                         }
+                    }
+                }
+
+                div class="bg-white shadow rounded-lg p-6 mt-6" {
+                    h2 class="text-xl font-bold mb-4 text-gray-800" { "Your Webhooks" }
+                    @if webhooks.is_empty() {
+                        p class="text-center py-6 text-gray-500" { "You haven't added any webhooks yet." }
                     } @else {
-                        // Admin badge if applicable
-                        @if is_admin_user {
-                            div class="mb-4 flex justify-between items-center" {
-                                span class="inline-flex items-center rounded-full bg-purple-100 px-2.5 py-0.5 text-xs font-medium text-purple-800" {
-                                    svg class="h-3 w-3 mr-1" fill="currentColor" viewBox="0 0 20 20" {
-                                        path d="M13.586 3.586a2 2 0 112.828 2.828l-.793.793-2.828-2.828.793-.793zM11.379 5.793L3 14.172V17h2.828l8.38-8.379-2.83-2.828z" {}
+                        div class="space-y-4" {
+                            @for webhook in &webhooks {
+                                div class="border rounded-lg p-4" {
+                                    div class="flex justify-between items-start" {
+                                        div class="min-w-0" {
+                                            p class="font-medium text-gray-800 break-all" { (webhook.url) }
+                                            p class="mt-1 text-sm text-gray-500" {
+                                                (webhook.format.label()) " · added " (time_ago(webhook.created_at))
+                                            }
+                                        }
+                                        form action="/account/webhooks/delete" method="POST" class="ml-4" {
+                                            input type="hidden" name="id" value=(webhook.id);
+                                            button type="submit" class="text-sm text-red-600 hover:text-red-800" { "Delete" }
+                                        }
+                                    }
+                                    p class="mt-2 text-sm text-gray-700" {
+                                        "Signing secret: "
+                                        code class="px-1 py-0.5 bg-gray-100 rounded" { (webhook.secret) }
                                     }
-                                    "Admin View"
                                 }
-                                span class="text-xs text-gray-500" { "Showing all user products" }
                             }
                         }
+                    }
+                }
 
-                        // Display the 3 most recent products
-                        div class="space-y-4" {
-                            @for product in visible_products.iter().rev().take(3) {
-                                div class="border rounded-lg p-4 hover:bg-gray-50" {
-                                    div class="flex justify-between" {
-                                        h3 class="font-semibold text-lg text-gray-800" { (product.name) }
+                div class="mt-6 text-sm text-gray-600" {
+                    h2 class="font-semibold text-gray-800" { "Verifying deliveries" }
+                    p class="mt-1" {
+                        "Each request carries an " code { "X-Midas-Timestamp" } " header (unix seconds) and an "
+                        code { "X-Midas-Signature" } " header of the form " code { "sha256=<hex>" } ", the HMAC-SHA256 of "
+                        code { "<timestamp>.<body>" } " keyed with the webhook's signing secret. "
+                        "Failed deliveries are retried with backoff on network errors, 429s and 5xx responses."
+                    }
+                }
+            }
+        }
+    })
+}
 
-                                        @if is_admin_user && product.added_by != username {
-                                            span class="text-xs bg-gray-100 text-gray-700 px-2 py-1 rounded" {
-                                                "Added by: " (product.added_by)
+async fn add_webhook_handler(
+    user: User,
+    State(state): State<AppState>,
+    Form(form): Form<WebhookForm>,
+) -> Result<Response, AppError> {
+    let Some(format) = WebhookFormat::parse(&form.format) else {
+        return Ok(
+            axum::response::Redirect::to("/account/webhooks?error=invalid_format").into_response(),
+        );
+    };
+
+    let webhook = match NewWebhook::new(&user.username, &form.url, format) {
+        Ok(webhook) => webhook,
+        Err(e) => {
+            warn!(
+                "Webhook rejected - username: {}, reason: {:?}",
+                user.username, e
+            );
+            let redirect_url = format!("/account/webhooks?error={}", e.code());
+            return Ok(axum::response::Redirect::to(&redirect_url).into_response());
+        }
+    };
+
+    let webhook = state.webhooks.add(webhook)?;
+    info!(
+        "Webhook added - username: {}, id: {}, format: {}",
+        user.username,
+        webhook.id,
+        webhook.format.as_str()
+    );
+    Ok(axum::response::Redirect::to("/account/webhooks?success=added").into_response())
+}
+
+async fn delete_webhook_handler(
+    user: User,
+    State(state): State<AppState>,
+    Form(form): Form<DeleteWebhookForm>,
+) -> Result<Response, AppError> {
+    if !state.webhooks.delete(form.id, &user.username)? {
+        return Ok(
+            axum::response::Redirect::to("/account/webhooks?error=not_found").into_response(),
+        );
+    }
+    info!(
+        "Webhook deleted - username: {}, id: {}",
+        user.username, form.id
+    );
+    Ok(axum::response::Redirect::to("/account/webhooks?success=deleted").into_response())
+}
+
+async fn tokens_page(
+    user: User,
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Markup, AppError> {
+    let error_message = params.get("error").map(|e| match e.as_str() {
+        "missing_name" => "Give the token a name so you can tell it apart later.",
+        "name_too_long" => "Token names can be at most 64 characters.",
+        "invalid_scope" => "Please pick one of the listed scopes.",
+        _ => "An error occurred. Please try again.",
+    });
+    let success_message = params
+        .get("success")
+        .map(|_| "Token revoked. Requests using it will now be refused.");
+
+    render_tokens_page(&state, &user, error_message, success_message, None)
+}
+
+// The secret of a token that was just created is passed in `new_secret`, the only time it
+// is ever shown
+fn render_tokens_page(
+    state: &AppState,
+    user: &User,
+    error_message: Option<&str>,
+    success_message: Option<&str>,
+    new_secret: Option<&str>,
+) -> Result<Markup, AppError> {
+    let tokens = state.tokens.list_for_user(&user.username)?;
+
+    Ok(html! {
+        (header())
+        body class="font-display" {
+            div class="max-w-3xl mx-auto px-4 sm:px-6 lg:px-8 py-8" {
+                div class="flex justify-between items-center mb-6" {
+                    h1 class="text-3xl font-bold text-gray-900" { "API Tokens" }
+                    a href="/dashboard" class="text-indigo-600 hover:text-indigo-800" { "Back to Dashboard" }
+                }
+                p class="text-gray-600" {
+                    "Tokens let scripts use the JSON API under " code { "/api/v1" } " as you. Send one in an "
+                    code { "Authorization: Bearer <token>" } " header. A token can never do more than your "
+                    "account can, and read-only tokens can't change anything."
+                }
+
+                @if let Some(message) = error_message {
+                    (error_alert(message))
+                }
+                @if let Some(message) = success_message {
+                    (success_alert(message))
+                }
+                @if let Some(secret) = new_secret {
+                    div class="mt-4 p-4 border border-green-300 bg-green-50 text-green-800 rounded-md" {
+                        p class="font-medium" { "Token created. Copy it now, it won't be shown again." }
+                        code class="mt-2 block px-2 py-1 bg-white border rounded break-all" { (secret) }
+                    }
+                }
+
+                div class="bg-white shadow rounded-lg p-6 mt-6" {
+                    h2 class="text-xl font-bold mb-4 text-gray-800" { "Create Token" }
+                    form class="space-y-4" action="/account/tokens" method="POST" {
+                        div {
+                            label class="block text-sm font-medium text-gray-700" for="name" { "Name" }
+                            input id="name" name="name" type="text" required maxlength="64" placeholder="e.g. price export script"
+                                class="w-full px-3 py-2 mt-1 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500";
+                        }
+                        div {
+                            label class="block text-sm font-medium text-gray-700" for="scope" { "Scope" }
+                            select id="scope" name="scope"
+                                class="w-full px-3 py-2 mt-1 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500" {
+                                @for scope in TokenScope::ALL {
+                                    option value=(scope.as_str()) { (scope.label()) }
+                                }
+                            }
+                        }
+                        div {
+                            button type="submit"
+                                class="w-full px-4 py-2 text-white bg-indigo-600 rounded-md hover:bg-indigo-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-indigo-500" {
+                                "Create Token"
+                            }
+                        }
+                    }
+                }
+
+                div class="bg-white shadow rounded-lg p-6 mt-6" {
+                    h2 class="text-xl font-bold mb-4 text-gray-800" { "Your Tokens" }
+                    @if tokens.is_empty() {
+                        p class="text-center py-6 text-gray-500" { "You haven't created any tokens yet." }
+                    } @else {
+                        div class="space-y-4" {
+                            @for token in &tokens {
+                                div class="border rounded-lg p-4" {
+                                    div class="flex justify-between items-start" {
+                                        div class="min-w-0" {
+                                            p class="font-medium text-gray-800 break-all" { (token.name) }
+                                            p class="mt-1 text-sm text-gray-500" {
+                                                code class="px-1 py-0.5 bg-gray-100 rounded" { (token.display_prefix) "…" }
+                                                " · " (token.scope.label())
+                                                " · created " (time_ago(token.created_at))
+                                                " · "
+                                                @match token.last_used_at {
+                                                    Some(used_at) => { "last used " (time_ago(used_at)) },
+                                                    None => "never used",
+                                                }
                                             }
                                         }
+                                        form action="/account/tokens/delete" method="POST" class="ml-4" {
+                                            input type="hidden" name="id" value=(token.id);
+                                            button type="submit" class="text-sm text-red-600 hover:text-red-800" { "Revoke" }
+                                        }
                                     }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
 
-                                    div class="text-sm text-gray-600 mt-1 overflow-hidden text-ellipsis" {
-                                        a href=(product.url) target="_blank" class="text-indigo-600 hover:underline" { "View on " (product.retailer) }
+async fn create_token_handler(
+    user: User,
+    State(state): State<AppState>,
+    Form(form): Form<TokenForm>,
+) -> Result<Response, AppError> {
+    let Some(scope) = TokenScope::parse(&form.scope) else {
+        return Ok(
+            axum::response::Redirect::to("/account/tokens?error=invalid_scope").into_response(),
+        );
+    };
+
+    let (token, secret) = match NewApiToken::generate(&user.username, &form.name, scope) {
+        Ok(generated) => generated,
+        Err(e) => {
+            let redirect_url = format!("/account/tokens?error={}", e.code());
+            return Ok(axum::response::Redirect::to(&redirect_url).into_response());
+        }
+    };
+
+    let token = state.tokens.add(token)?;
+    info!(
+        "API token created - username: {}, id: {}, scope: {}",
+        user.username,
+        token.id,
+        token.scope.as_str()
+    );
+
+    // Rendered directly rather than redirecting, so the secret never ends up in a URL
+    let page = render_tokens_page(&state, &user, None, None, Some(&secret))?;
+    Ok(([(axum::http::header::CACHE_CONTROL, "no-store")], page).into_response())
+}
+
+async fn delete_token_handler(
+    user: User,
+    State(state): State<AppState>,
+    Form(form): Form<DeleteTokenForm>,
+) -> Result<Response, AppError> {
+    if !state.tokens.delete(form.id, &user.username)? {
+        return Ok(axum::response::Redirect::to("/account/tokens?error=not_found").into_response());
+    }
+    info!(
+        "API token revoked - username: {}, id: {}",
+        user.username, form.id
+    );
+    Ok(axum::response::Redirect::to("/account/tokens?success=revoked").into_response())
+}
+
+async fn logout_handler(State(state): State<AppState>, jar: SignedCookieJar) -> impl IntoResponse {
+    // Destroy the server-side session so the cookie can't be replayed
+    if let Some(cookie) = jar.get(session::SESSION_COOKIE) {
+        state.sessions.destroy(cookie.value());
+    }
+    let jar = jar.remove(Cookie::build(session::SESSION_COOKIE).path("/"));
+
+    (jar, axum::response::Redirect::to("/"))
+}
+
+// Shown when a signed-in user tries something their role doesn't allow
+fn forbidden_page(message: &str) -> Markup {
+    html! {
+        (header())
+        body class="font-display flex items-center justify-center min-h-screen bg-gray-100" {
+            div class="w-full max-w-md p-8 space-y-6 bg-white rounded-lg shadow-md text-center" {
+                h1 class="text-3xl font-bold text-gray-900" { "Access denied" }
+                p class="text-gray-600" { (message) }
+                a href="/dashboard" class="text-indigo-600 hover:text-indigo-800" { "Back to Dashboard" }
+            }
+        }
+    }
+}
+
+fn not_found_page(message: &str) -> Markup {
+    html! {
+        (header())
+        body class="font-display flex items-center justify-center min-h-screen bg-gray-100" {
+            div class="w-full max-w-md p-8 space-y-6 bg-white rounded-lg shadow-md text-center" {
+                h1 class="text-3xl font-bold text-gray-900" { "Not found" }
+                p class="text-gray-600" { (message) }
+                a href="/products" class="text-indigo-600 hover:text-indigo-800" { "Back to Products" }
+            }
+        }
+    }
+}
+
+async fn admin_users(
+    AdminUser(admin): AdminUser,
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Markup, AppError> {
+    let error_message = params.get("error").map(|e| match e.as_str() {
+        "unknown_user" => "That user no longer exists.",
+        "last_admin" => "You can't remove the last admin. Promote someone else first.",
+        _ => "An error occurred. Please try again.",
+    });
+
+    let success_message = params.get("updated").map(|_| "Role updated.");
+
+    let accounts = state.users.list()?;
+
+    Ok(html! {
+        (header())
+        body class="font-display" {
+            div class="max-w-5xl mx-auto px-4 sm:px-6 lg:px-8 py-8" {
+                div class="flex justify-between items-center mb-6" {
+                    h1 class="text-3xl font-bold text-gray-900" { "Manage Users" }
+                    a href="/dashboard" class="text-indigo-600 hover:text-indigo-800" { "Back to Dashboard" }
+                }
+                p class="text-gray-600" {
+                    "Admins can manage every product and user. Viewers can see all products but can't change anything."
+                }
+
+                @if let Some(message) = error_message {
+                    (error_alert(message))
+                }
+                @if let Some(message) = success_message {
+                    (success_alert(message))
+                }
+
+                div class="bg-white shadow rounded-lg mt-6 overflow-hidden" {
+                    table class="min-w-full divide-y divide-gray-200" {
+                        thead class="bg-gray-50" {
+                            tr {
+                                th class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase" { "Username" }
+                                th class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase" { "Status" }
+                                th class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase" { "Role" }
+                            }
+                        }
+                        tbody class="divide-y divide-gray-200" {
+                            @for account in &accounts {
+                                tr {
+                                    td class="px-6 py-4 text-sm text-gray-900" {
+                                        (account.user.username)
+                                        @if account.user.username == admin.username {
+                                            span class="ml-2 text-xs text-gray-500" { "(you)" }
+                                        }
+                                    }
+                                    td class="px-6 py-4 text-sm" {
+                                        @if account.locked {
+                                            span class="text-red-600" { "Locked" }
+                                        } @else {
+                                            span class="text-gray-600" { "Active" }
+                                        }
                                     }
-                                    @if let Some(price) = product.target_price {
-                                        p class="mt-2 text-sm text-gray-700" { "Target Price: $" (format!("{:.2}", price)) }
+                                    td class="px-6 py-4 text-sm" {
+                                        form class="flex items-center space-x-2" action="/admin/users/role" method="POST" {
+                                            input type="hidden" name="username" value=(account.user.username);
+                                            select name="role"
+                                                class="px-2 py-1 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500" {
+                                                @for role in UserRole::ALL {
+                                                    option value=(role.as_str()) selected[role == account.user.role] { (role.label()) }
+                                                }
+                                            }
+                                            button type="submit" class="text-indigo-600 hover:text-indigo-800" { "Save" }
+                                        }
                                     }
                                 }
                             }
@@ -470,116 +1484,1182 @@ async fn dashboard(
                 }
             }
         }
+    })
+}
+
+async fn set_user_role(
+    AdminUser(admin): AdminUser,
+    State(state): State<AppState>,
+    Form(form): Form<SetRoleForm>,
+) -> Result<Response, AppError> {
+    Ok(match state.users.set_role(&form.username, form.role)? {
+        Ok(user) => {
+            info!(
+                "Role changed - username: {}, role: {:?}, changed by: {}",
+                user.username, user.role, admin.username
+            );
+            axum::response::Redirect::to("/admin/users?updated=true").into_response()
+        }
+        Err(e) => {
+            warn!(
+                "Role change failed - username: {}, role: {:?}, reason: {:?}",
+                form.username, form.role, e
+            );
+            let redirect_url = format!("/admin/users?error={}", e.code());
+            axum::response::Redirect::to(&redirect_url).into_response()
+        }
+    })
+}
+
+async fn dashboard(
+    user: User,
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Markup, AppError> {
+    let role = user.role;
+    let username = user.username;
+    let is_admin_user = role == UserRole::Admin;
+    let can_view_all = role.can_view_all();
+
+    // Check for error or success messages
+    let error_message = params.get("error").map(|code| product_error_message(code));
+
+    let success_message = params.get("success").map(|s| match s.as_str() {
+        "subscribed" => "Someone already tracks that product, so you're now watching it too.",
+        _ => "Product successfully added for tracking!",
+    });
+
+    // Filter products based on user role - admins and viewers see all, regular users see only the ones they watch
+    let visible_products = if can_view_all {
+        state.products.list()?
+    } else {
+        state.products.list_for_user(&username)?
+    };
+    let subscriptions = subscriptions_by_product(&state, &username)?;
+    let deliveries = state.webhooks.recent_deliveries(&username, 10)?;
+
+    // Latest check result for the products shown below
+    let mut latest = std::collections::HashMap::new();
+    for product in visible_products.iter().rev().take(3) {
+        if let Some(observation) = state.observations.latest(product.id)? {
+            latest.insert(product.id, observation);
+        }
+    }
+
+    Ok(html! {
+        (header())
+        body class="font-display" data-live-updates="/ws/updates" {
+            div class="max-w-7xl mx-auto px-4 sm:px-6 lg:px-8 py-8" {
+                div class="mb-10" {
+                    div class="flex justify-between items-center mb-6" {
+                        div class="flex items-center" {
+                            h1 class="text-3xl font-bold text-gray-900" { "Dashboard" }
+                            @if is_admin_user {
+                                span class="ml-3 inline-flex items-center rounded-full bg-purple-100 px-2.5 py-0.5 text-xs font-medium text-purple-800" {
+                                    "Admin"
+                                }
+                            }
+                            @if role == UserRole::Viewer {
+                                span class="ml-3 inline-flex items-center rounded-full bg-gray-100 px-2.5 py-0.5 text-xs font-medium text-gray-800" {
+                                    "Viewer"
+                                }
+                            }
+                        }
+                        div class="flex items-center space-x-4" {
+                            @if is_admin_user {
+                                a href="/admin/users" class="text-indigo-600 hover:text-indigo-800" { "Manage Users" }
+                            }
+                            a href="/account/notifications" class="text-indigo-600 hover:text-indigo-800" { "Notifications" }
+                            a href="/account/webhooks" class="text-indigo-600 hover:text-indigo-800" { "Webhooks" }
+                            a href="/account/tokens" class="text-indigo-600 hover:text-indigo-800" { "API Tokens" }
+                            a href="/account/password" class="text-indigo-600 hover:text-indigo-800" { "Change Password" }
+                            form action="/logout" method="POST" {
+                                button type="submit" class="text-indigo-600 hover:text-indigo-800" { "Sign Out" }
+                            }
+                        }
+                    }
+                    p class="text-gray-600" {
+                        @if is_admin_user {
+                            "Admin dashboard - you can view and manage all user products"
+                        } @else if role == UserRole::Viewer {
+                            "Read-only dashboard - you can view all user products"
+                        } @else {
+                            "Welcome to your Midas Product Tracker dashboard!"
+                        }
+                    }
+
+                    // Show error message if present
+                    @if let Some(message) = error_message {
+                        (error_alert(message))
+                    }
+
+                    // Show success message if present
+                    @if let Some(message) = success_message {
+                        (success_alert(message))
+                    }
+                }
+
+                // Product Tracker Section, hidden from read-only viewers
+                @if role.can_edit() {
+                    div class="bg-white shadow rounded-lg p-6 mb-8" {
+                        h2 class="text-2xl font-bold mb-4 text-gray-800" { "Add Product to Track" }
+                        p class="mb-6 text-gray-600" { "Submit products you'd like to track for availability and price changes." }
+
+                        div class="mb-6 bg-blue-50 rounded-lg p-4 border border-blue-200" {
+                            div class="flex items-center" {
+                                svg class="h-5 w-5 text-blue-400 mr-2" fill="currentColor" viewBox="0 0 20 20" {
+                                    path d="M10 18a8 8 0 100-16 8 8 0 000 16zm1-11a1 1 0 10-2 0v2H7a1 1 0 100 2h2v2a1 1 0 102 0v-2h2a1 1 0 100-2h-2V7z" clip-rule="evenodd" fill-rule="evenodd" {}
+                                }
+                                span class="text-blue-800 font-medium" { "Currently Supported Retailers:" }
+                            }
+                            div class="mt-2 flex flex-wrap gap-2" {
+                                @for retailer in state.retailers.all() {
+                                    span class=(format!("inline-flex items-center rounded-full {} px-3 py-1 text-sm font-medium", retailer.style().badge)) {
+                                        (retailer.name())
+                                    }
+                                }
+                            }
+                        }
+
+                        (add_product_form(&state, &ProductForm::default(), &ProductErrors::default()))
+                    }
+                }
+
+                // View Products Section
+                div class="bg-white shadow rounded-lg p-6" {
+                    div class="flex justify-between items-center mb-4" {
+                        h2 class="text-2xl font-bold text-gray-800" { "Your Tracked Products" }
+                        div class="space-x-4" {
+                            @if role.can_edit() {
+                                a href="/products/import" class="text-indigo-600 hover:text-indigo-800" { "Import / Export" }
+                            }
+                            a href="/products" class="text-indigo-600 hover:text-indigo-800" { "View All Products" }
+                        }
+                    }
+
+                    @if visible_products.is_empty() {
+                        div class="text-center py-8 text-gray-500" {
+                            p { "You haven't added any products to track yet." }
+                        }
+                    } @else {
+                        // Admin badge if applicable
+                        @if is_admin_user {
+                            div class="mb-4 flex justify-between items-center" {
+                                span class="inline-flex items-center rounded-full bg-purple-100 px-2.5 py-0.5 text-xs font-medium text-purple-800" {
+                                    svg class="h-3 w-3 mr-1" fill="currentColor" viewBox="0 0 20 20" {
+                                        path d="M13.586 3.586a2 2 0 112.828 2.828l-.793.793-2.828-2.828.793-.793zM11.379 5.793L3 14.172V17h2.828l8.38-8.379-2.83-2.828z" {}
+                                    }
+                                    "Admin View"
+                                }
+                                span class="text-xs text-gray-500" { "Showing all user products" }
+                            }
+                        }
+
+                        // Display the 3 most recent products
+                        div class="space-y-4" {
+                            @for product in visible_products.iter().rev().take(3) {
+                                div class="border rounded-lg p-4 hover:bg-gray-50" {
+                                    div class="flex justify-between" {
+                                        h3 class="font-semibold text-lg text-gray-800" {
+                                            a href=(format!("/products/{}", product.id)) class="hover:text-indigo-600" { (product.name) }
+                                        }
+
+                                        @if can_view_all && product.added_by != username {
+                                            span class="text-xs bg-gray-100 text-gray-700 px-2 py-1 rounded" {
+                                                "Added by: " (product.added_by)
+                                            }
+                                        }
+                                    }
+
+                                    div class="text-sm text-gray-600 mt-1 overflow-hidden text-ellipsis" {
+                                        a href=(product.url) target="_blank" class="text-indigo-600 hover:underline" { "View on " (product.retailer) }
+                                    }
+                                    @if let Some(price) = subscriptions.get(&product.id).and_then(|s| s.target_price) {
+                                        p class="mt-2 text-sm text-gray-700" { "Target Price: " (price) }
+                                    }
+                                    (product_status(product.id, latest.get(&product.id), false))
+                                }
+                            }
+                        }
+                    }
+                }
+
+                @if !deliveries.is_empty() {
+                    div class="bg-white shadow rounded-lg p-6 mt-8" {
+                        div class="flex justify-between items-center mb-4" {
+                            h2 class="text-2xl font-bold text-gray-800" { "Recent Webhook Deliveries" }
+                            a href="/account/webhooks" class="text-indigo-600 hover:text-indigo-800" { "Manage Webhooks" }
+                        }
+                        div class="overflow-x-auto" {
+                            table class="min-w-full text-sm" {
+                                thead {
+                                    tr class="text-left text-gray-500 border-b" {
+                                        th class="py-2 pr-4 font-medium" { "Time" }
+                                        th class="py-2 pr-4 font-medium" { "Webhook" }
+                                        th class="py-2 pr-4 font-medium" { "Alert" }
+                                        th class="py-2 font-medium" { "Result" }
+                                    }
+                                }
+                                tbody {
+                                    @for delivery in &deliveries {
+                                        tr class="border-b last:border-0 align-top" {
+                                            td class="py-2 pr-4 whitespace-nowrap text-gray-700" { (format_time(delivery.attempted_at)) }
+                                            td class="py-2 pr-4 text-gray-700 break-all" { (delivery.webhook_url) }
+                                            td class="py-2 pr-4 text-gray-700" {
+                                                span class="block text-xs text-gray-500" { (delivery.alert_kind.label()) }
+                                                a href=(format!("/products/{}", delivery.product_id)) class="hover:text-indigo-600" { (delivery.message) }
+                                            }
+                                            td class="py-2 whitespace-nowrap" {
+                                                @match &delivery.error {
+                                                    None => span class="text-green-700" {
+                                                        "Delivered"
+                                                        @if let Some(status) = delivery.status_code { " (" (status) ")" }
+                                                    },
+                                                    Some(error) => span class="text-red-700" title=(error) { (error) },
+                                                }
+                                                @if delivery.attempts > 1 {
+                                                    span class="ml-1 text-gray-500" { "after " (delivery.attempts) " attempts" }
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+// Messages for the `?error=` codes the product forms redirect with when htmx isn't there to
+// show errors next to their fields
+fn product_error_message(code: &str) -> &'static str {
+    ProductError::from_code(code).map_or(
+        "An error occurred. Please try again.",
+        ProductError::message,
+    )
+}
+
+// Turn the add and edit product forms into validated product details, or every field's error
+async fn validate_product_form(
+    state: &AppState,
+    form: ProductForm,
+) -> Result<ProductInput, ProductErrors> {
+    // Target prices are in the retailer's currency. A malformed one is reported alongside
+    // whatever else is wrong, including an unknown retailer.
+    let currency = state
+        .retailers
+        .get(&form.retailer)
+        .map_or(Currency::Usd, |r| r.currency());
+    let (target_price, price_error) =
+        match products::parse_target_price(form.target_price.as_deref().unwrap_or(""), currency) {
+            Ok(price) => (price, None),
+            Err(e) => (None, Some(e)),
+        };
+    let poll_interval = form.poll_interval();
+
+    let result = ProductInput {
+        url: form.url,
+        name: form.name,
+        retailer: form.retailer,
+        retailer_id: None,
+        currency,
+        target_price,
+        poll_interval,
+    }
+    .validate(&state.retailers, state.link_resolver.as_ref())
+    .await;
+
+    match (result, price_error) {
+        (result, None) => result,
+        (Ok(_), Some(e)) => Err(e.into()),
+        (Err(mut errors), Some(e)) => {
+            errors.add(e);
+            Err(errors)
+        }
+    }
+}
+
+// Send the browser to `url`. htmx gets told through `HX-Redirect`, since it would follow a
+// plain redirect itself and swap the page it lands on into the form.
+fn form_redirect(headers: &HeaderMap, url: &str) -> Response {
+    if headers.contains_key("HX-Request") {
+        ([("HX-Redirect", url)], StatusCode::OK).into_response()
+    } else {
+        axum::response::Redirect::to(url).into_response()
+    }
+}
+
+// A product form that failed validation. htmx swaps `form` in, with each error next to its
+// field and the input kept; without htmx the browser goes back to `page` with the first
+// error's code.
+fn form_errors(headers: &HeaderMap, page: &str, errors: &ProductErrors, form: Markup) -> Response {
+    if headers.contains_key("HX-Request") {
+        return form.into_response();
+    }
+    let code = errors.first().map_or("invalid", ProductError::code);
+    axum::response::Redirect::to(&format!("{page}?error={code}")).into_response()
+}
+
+// Whether a field failed validation, in the border colour of its input
+fn field_border(errors: &ProductErrors, field: &str) -> &'static str {
+    if errors.get(field).is_some() {
+        "border-red-500"
+    } else {
+        "border-gray-300"
+    }
+}
+
+fn field_error(errors: &ProductErrors, field: &str) -> Markup {
+    html! {
+        @if let Some(error) = errors.get(field) {
+            p id=(format!("{field}-error")) class="mt-1 text-sm text-red-600" { (error.message()) }
+        }
+    }
+}
+
+// The inputs shared by the add and edit product forms, filled in with `values`. The target
+// price is the user's own, so it's left out for editors who don't watch the product.
+fn product_fields(
+    state: &AppState,
+    values: &ProductForm,
+    errors: &ProductErrors,
+    with_target: bool,
+) -> Markup {
+    let invalid = |field| errors.get(field).map(|_| "true");
+    let described_by = |field: &str| errors.get(field).map(|_| format!("{field}-error"));
+    html! {
+        div {
+            label class="block text-sm font-medium text-gray-700" for="url" { "Product URL" }
+            // Plain text so addresses without https:// get to the server, which accepts them
+            input id="url" name="url" type="text" inputmode="url" required value=(values.url)
+                placeholder="https://www.amazon.com/dp/B08FC6MR62 or https://www.bestbuy.com/site/..."
+                aria-invalid=[invalid("url")] aria-describedby=[described_by("url")]
+                class=(format!("w-full px-3 py-2 mt-1 border {} rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500", field_border(errors, "url")));
+            (field_error(errors, "url"))
+        }
+
+        div {
+            label class="block text-sm font-medium text-gray-700" for="name" { "Product Name" }
+            input id="name" name="name" type="text" required maxlength="200" value=(values.name)
+                placeholder="e.g. PlayStation 5 Digital Edition"
+                aria-invalid=[invalid("name")] aria-describedby=[described_by("name")]
+                class=(format!("w-full px-3 py-2 mt-1 border {} rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500", field_border(errors, "name")));
+            (field_error(errors, "name"))
+        }
+
+        div {
+            label class="block text-sm font-medium text-gray-700" for="retailer" { "Retailer" }
+            select id="retailer" name="retailer" required
+                aria-invalid=[invalid("retailer")] aria-describedby=[described_by("retailer")]
+                class=(format!("w-full px-3 py-2 mt-1 border {} rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500", field_border(errors, "retailer"))) {
+                @for retailer in state.retailers.all() {
+                    option value=(retailer.name()) selected[retailer.name() == values.retailer] {
+                        (retailer.name()) " (" (retailer.currency().code()) ")"
+                    }
+                }
+            }
+            (field_error(errors, "retailer"))
+        }
+
+        @if with_target {
+            div {
+                label class="block text-sm font-medium text-gray-700" for="target_price" { "Your Target Price (Optional)" }
+                // The retailer can be changed in the form, so there's no currency symbol to
+                // show next to the input
+                input id="target_price" name="target_price" type="text" inputmode="decimal" placeholder="399.99"
+                    value=(values.target_price.as_deref().unwrap_or_default())
+                    aria-invalid=[invalid("target_price")] aria-describedby=[described_by("target_price")]
+                    class=(format!("w-full px-3 py-2 mt-1 border {} rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500", field_border(errors, "target_price")));
+                p class="mt-1 text-xs text-gray-500" { "In the retailer's currency" }
+                (field_error(errors, "target_price"))
+            }
+        }
+
+        div {
+            label class="block text-sm font-medium text-gray-700" for="poll_interval" { "Check Every" }
+            select id="poll_interval" name="poll_interval"
+                aria-invalid=[invalid("poll_interval")] aria-describedby=[described_by("poll_interval")]
+                class=(format!("w-full px-3 py-2 mt-1 border {} rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500", field_border(errors, "poll_interval"))) {
+                (poll_interval_options(values.poll_interval(), state.default_poll_interval))
+            }
+            (field_error(errors, "poll_interval"))
+        }
+    }
+}
+
+// htmx posts the form and swaps the response in for it: the form again, with errors, when
+// something's wrong
+fn add_product_form(state: &AppState, values: &ProductForm, errors: &ProductErrors) -> Markup {
+    html! {
+        form id="add-product-form" class="space-y-4" action="/add-product" method="POST"
+            hx-post="/add-product" hx-swap="outerHTML" {
+            (product_fields(state, values, errors, true))
+
+            div {
+                button type="submit"
+                    class="w-full px-4 py-2 text-white bg-indigo-600 rounded-md hover:bg-indigo-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-indigo-500" {
+                    "Add Product"
+                }
+            }
+        }
+    }
+}
+
+async fn add_product(
+    EditorUser(user): EditorUser,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(form): Form<ProductForm>,
+) -> Result<Response, AppError> {
+    let username = user.username;
+    let values = form.clone();
+
+    // If validation fails, show the form again with the errors
+    let input = match validate_product_form(&state, form).await {
+        Ok(input) => input,
+        Err(errors) => {
+            // Log validation failure
+            warn!(
+                "Product validation failed - errors: {}, url: {}, retailer: {}, added by: {}",
+                errors, values.url, values.retailer, username
+            );
+            let form = add_product_form(&state, &values, &errors);
+            return Ok(form_errors(&headers, "/dashboard", &errors, form));
+        }
+    };
+
+    // Store the new product, or subscribe to it if someone else already tracks it
+    let target_price = input.target_price;
+    let (product, tracked) = products::track(state.products.as_ref(), &username, input)?;
+    let redirect_url = match tracked {
+        Tracked::Added => {
+            info!(
+                "Product added - id: {}, name: {}, retailer: {}, added by: {}, target price: {:?}",
+                product.id,
+                product.name,
+                product.retailer,
+                username,
+                target_price.map(|p| p.to_string())
+            );
+            "/dashboard?success=true"
+        }
+        Tracked::Subscribed => {
+            info!(
+                "Product subscribed - id: {}, name: {}, username: {}, target price: {:?}",
+                product.id,
+                product.name,
+                username,
+                target_price.map(|p| p.to_string())
+            );
+            "/dashboard?success=subscribed"
+        }
+        Tracked::AlreadySubscribed => {
+            warn!(
+                "Product already tracked - id: {}, url: {}, added by: {}",
+                product.id, product.url, username
+            );
+            let errors = ProductErrors::from(ProductError::AlreadyTracked);
+            let form = add_product_form(&state, &values, &errors);
+            return Ok(form_errors(&headers, "/dashboard", &errors, form));
+        }
+    };
+
+    Ok(form_redirect(&headers, redirect_url))
+}
+
+// The import form, with a message when the last upload couldn't be read
+fn import_page(error: Option<&str>) -> Markup {
+    html! {
+        (header())
+        body class="font-display" {
+            div class="max-w-3xl mx-auto px-4 sm:px-6 lg:px-8 py-8" {
+                div class="flex justify-between items-center mb-6" {
+                    h1 class="text-3xl font-bold text-gray-900" { "Import & Export" }
+                    a href="/dashboard" class="text-indigo-600 hover:text-indigo-800" { "Back to Dashboard" }
+                }
+
+                @if let Some(message) = error {
+                    (error_alert(message))
+                }
+
+                div class="bg-white shadow rounded-lg p-6 mt-6" {
+                    h2 class="text-xl font-bold mb-4 text-gray-800" { "Import Products" }
+                    p class="text-sm text-gray-600 mb-4" {
+                        "Upload a CSV file with " code { "name" } ", " code { "url" } ", " code { "retailer" }
+                        " and optionally " code { "target_price" } " columns, or a JSON export. "
+                        "You'll see what would happen to each row before anything is tracked. At most "
+                        (transfer::MAX_IMPORT_ROWS) " rows can be imported at once."
+                    }
+                    form class="space-y-4" action="/products/import/preview" method="POST" enctype="multipart/form-data" {
+                        input name="file" type="file" required accept=".csv,.json"
+                            class="block w-full text-sm text-gray-700 file:mr-4 file:px-4 file:py-2 file:rounded-md file:border-0 file:bg-indigo-50 file:text-indigo-700 hover:file:bg-indigo-100";
+                        button type="submit"
+                            class="w-full px-4 py-2 text-white bg-indigo-600 rounded-md hover:bg-indigo-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-indigo-500" {
+                            "Preview Import"
+                        }
+                    }
+                }
+
+                div class="bg-white shadow rounded-lg p-6 mt-6" {
+                    h2 class="text-xl font-bold mb-4 text-gray-800" { "Export Products" }
+                    p class="text-sm text-gray-600 mb-4" {
+                        "Download the products you watch with your target prices. The JSON export also has every price check."
+                    }
+                    div class="flex space-x-6" {
+                        a href="/products/export.csv" class="text-indigo-600 hover:text-indigo-800" { "Products (CSV)" }
+                        a href="/products/export.json" class="text-indigo-600 hover:text-indigo-800" { "Products with history (JSON)" }
+                        a href="/products/history.csv" class="text-indigo-600 hover:text-indigo-800" { "Price history (CSV)" }
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn import_products_page(_user: EditorUser) -> Markup {
+    import_page(None)
+}
+
+// One row per record with what importing it would do or did, or why it can't be imported
+fn import_results(records: &[ProductRecord], outcomes: &[Result<Markup, RecordError>]) -> Markup {
+    html! {
+        div class="bg-white shadow rounded-lg mt-6 overflow-x-auto" {
+            table class="min-w-full divide-y divide-gray-200" {
+                thead class="bg-gray-50" {
+                    tr {
+                        th class="px-4 py-3 text-left text-xs font-medium text-gray-500 uppercase" { "Row" }
+                        th class="px-4 py-3 text-left text-xs font-medium text-gray-500 uppercase" { "Product" }
+                        th class="px-4 py-3 text-left text-xs font-medium text-gray-500 uppercase" { "Target" }
+                        th class="px-4 py-3 text-left text-xs font-medium text-gray-500 uppercase" { "Result" }
+                    }
+                }
+                tbody class="divide-y divide-gray-200" {
+                    @for (index, (record, outcome)) in records.iter().zip(outcomes).enumerate() {
+                        tr class=[outcome.is_err().then_some("bg-red-50")] {
+                            td class="px-4 py-3 text-sm text-gray-500" { (index + 1) }
+                            td class="px-4 py-3 text-sm" {
+                                p class="font-medium text-gray-900" { (record.name) }
+                                p class="text-gray-500" { (record.retailer) }
+                                p class="text-gray-500 break-all" { (record.url) }
+                            }
+                            td class="px-4 py-3 text-sm text-gray-900" {
+                                (record.target_price.as_deref().unwrap_or("—"))
+                            }
+                            td class="px-4 py-3 text-sm" {
+                                @match outcome {
+                                    Ok(result) => span class="text-green-700" { (result) },
+                                    Err(e) => span class="text-red-700" { (e.message()) },
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Reads the uploaded file and shows what importing it would do, with a button to go ahead
+async fn preview_import(
+    EditorUser(user): EditorUser,
+    State(state): State<AppState>,
+    mut multipart: axum::extract::Multipart,
+) -> Result<Markup, AppError> {
+    let mut upload = None;
+    while let Some(field) = multipart.next_field().await? {
+        if field.name() == Some("file") {
+            let file_name = field.file_name().unwrap_or_default().to_string();
+            upload = Some((file_name, field.text().await?));
+        }
+    }
+    let Some((file_name, text)) = upload.filter(|(_, text)| !text.trim().is_empty()) else {
+        return Ok(import_page(Some("Choose a CSV or JSON file to import.")));
+    };
+
+    let format = transfer::Format::detect(std::path::Path::new(&file_name), &text);
+    let mut records = match transfer::parse_records(&text, format) {
+        Ok(records) => records,
+        Err(e) => {
+            warn!(
+                "Import file rejected - username: {}, file: {}, reason: {:#}",
+                user.username, file_name, e
+            );
+            return Ok(import_page(Some(&format!(
+                "Couldn't read {}: {:#}",
+                file_name, e
+            ))));
+        }
+    };
+    // Everything on the page is tracked for whoever uploads it, whatever the file says
+    for record in &mut records {
+        record.username = None;
+    }
+
+    let outcomes = importer(&state)
+        .preview(&records, Some(&user.username))
+        .await?;
+    let importable = outcomes.iter().filter(|outcome| outcome.is_ok()).count();
+    let outcomes: Vec<_> = outcomes
+        .into_iter()
+        .map(|outcome| {
+            outcome.map(|tracked| {
+                html! {
+                    @match tracked {
+                        Tracked::Added => "Will be added",
+                        Tracked::Subscribed => "Will watch (already tracked by someone else)",
+                        Tracked::AlreadySubscribed => "Already watching",
+                    }
+                }
+            })
+        })
+        .collect();
+
+    Ok(html! {
+        (header())
+        body class="font-display" {
+            div class="max-w-5xl mx-auto px-4 sm:px-6 lg:px-8 py-8" {
+                div class="flex justify-between items-center mb-6" {
+                    h1 class="text-3xl font-bold text-gray-900" { "Preview Import" }
+                    a href="/products/import" class="text-indigo-600 hover:text-indigo-800" { "Choose Another File" }
+                }
+                p class="text-gray-600" {
+                    (importable) " of " (records.len()) " rows in " (file_name) " can be imported. "
+                    "Nothing has been tracked yet."
+                    @if importable < records.len() {
+                        " Rows with problems are skipped; fix them in the file and upload it again to include them."
+                    }
+                }
+
+                (import_results(&records, &outcomes))
+
+                @if importable > 0 {
+                    form class="mt-6" action="/products/import" method="POST" {
+                        input type="hidden" name="records" value=(serde_json::to_string(&records)?);
+                        button type="submit"
+                            class="w-full px-4 py-2 text-white bg-indigo-600 rounded-md hover:bg-indigo-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-indigo-500" {
+                            "Import " (importable) " Products"
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+// Tracks the previewed records. They're checked again, since things may have changed since
+// the preview.
+async fn import_products(
+    EditorUser(user): EditorUser,
+    State(state): State<AppState>,
+    Form(form): Form<ImportForm>,
+) -> Result<Markup, AppError> {
+    let mut records: Vec<ProductRecord> = match serde_json::from_str(&form.records) {
+        Ok(records) => records,
+        Err(_) => {
+            return Ok(import_page(Some(
+                "The import was garbled. Please upload the file again.",
+            )));
+        }
+    };
+    records.truncate(transfer::MAX_IMPORT_ROWS);
+    for record in &mut records {
+        record.username = None;
+    }
+
+    let outcomes = importer(&state)
+        .import(&records, Some(&user.username))
+        .await?;
+    let imported = outcomes.iter().filter(|outcome| outcome.is_ok()).count();
+    info!(
+        "Products imported - username: {}, imported: {}, skipped: {}",
+        user.username,
+        imported,
+        records.len() - imported
+    );
+    let outcomes: Vec<_> = outcomes
+        .into_iter()
+        .map(|outcome| {
+            outcome.map(|(product, tracked)| {
+                html! {
+                    a href=(format!("/products/{}", product.id)) class="text-indigo-600 hover:text-indigo-800" {
+                        @match tracked {
+                            Tracked::Added => "Added",
+                            Tracked::Subscribed => "Watching",
+                            Tracked::AlreadySubscribed => "Already watching",
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+
+    Ok(html! {
+        (header())
+        body class="font-display" {
+            div class="max-w-5xl mx-auto px-4 sm:px-6 lg:px-8 py-8" {
+                div class="flex justify-between items-center mb-6" {
+                    h1 class="text-3xl font-bold text-gray-900" { "Import Finished" }
+                    a href="/dashboard" class="text-indigo-600 hover:text-indigo-800" { "Back to Dashboard" }
+                }
+                (success_alert(&format!("Imported {} of {} products.", imported, records.len())))
+                (import_results(&records, &outcomes))
+            }
+        }
+    })
+}
+
+fn importer(state: &AppState) -> Importer<'_> {
+    Importer {
+        users: &state.users,
+        products: state.products.as_ref(),
+        retailers: &state.retailers,
+        resolver: state.link_resolver.as_ref(),
+    }
+}
+
+// The signed-in user's products as a download
+fn export_download(
+    state: &AppState,
+    user: &User,
+    format: transfer::Format,
+    history: bool,
+) -> Result<Response, AppError> {
+    let observations = history.then_some(state.observations.as_ref());
+    let records = transfer::export(state.products.as_ref(), observations, Some(&user.username))?;
+    let (name, body) = match (format, history) {
+        (transfer::Format::Csv, true) => ("history", transfer::write_history_csv(&records)?),
+        _ => ("products", transfer::write_records(&records, format)?),
+    };
+    let disposition = format!(
+        "attachment; filename=\"midas-{}.{}\"",
+        name,
+        format.as_str()
+    );
+    Ok((
+        [
+            (
+                axum::http::header::CONTENT_TYPE,
+                format.content_type().to_string(),
+            ),
+            (axum::http::header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+async fn export_products_csv(
+    user: User,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    export_download(&state, &user, transfer::Format::Csv, false)
+}
+
+async fn export_products_json(
+    user: User,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    export_download(&state, &user, transfer::Format::Json, true)
+}
+
+async fn export_history_csv(
+    user: User,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    export_download(&state, &user, transfer::Format::Csv, true)
+}
+
+// `username`'s subscriptions, by product id
+fn subscriptions_by_product(
+    state: &AppState,
+    username: &str,
+) -> anyhow::Result<std::collections::HashMap<i64, Subscription>> {
+    Ok(state
+        .products
+        .subscriptions_for_user(username)?
+        .into_iter()
+        .map(|s| (s.product_id, s))
+        .collect())
+}
+
+// Options for the "Check Every" select, with `current` selected. Leaving it empty uses the
+// server's `default` interval.
+fn poll_interval_options(current: Option<Duration>, default: Duration) -> Markup {
+    let current_minutes = current.map(|d| d.as_secs() / 60);
+    html! {
+        option value="" selected[current_minutes.is_none()] { "Default (" (interval_label(default)) ")" }
+        @for (minutes, label) in POLL_INTERVALS {
+            option value=(minutes) selected[current_minutes == Some(minutes)] { (label) }
+        }
+        // Keep an interval set some other way rather than silently changing it
+        @if let Some(minutes) = current_minutes.filter(|m| !POLL_INTERVALS.iter().any(|(p, _)| p == m)) {
+            option value=(minutes) selected { (interval_label(Duration::from_secs(minutes * 60))) }
+        }
+    }
+}
+
+// In the largest unit it's a whole number of, e.g. "15 minutes" or "2 days"
+fn interval_label(interval: Duration) -> String {
+    let secs = interval.as_secs();
+    let (count, unit) = [(24 * 60 * 60, "day"), (60 * 60, "hour"), (60, "minute")]
+        .into_iter()
+        .find(|(unit, _)| secs >= *unit && secs.is_multiple_of(*unit))
+        .map_or((secs, "second"), |(size, unit)| (secs / size, unit));
+    if count == 1 {
+        format!("1 {unit}")
+    } else {
+        format!("{count} {unit}s")
+    }
+}
+
+fn product_not_found() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        not_found_page("That product doesn't exist or isn't yours."),
+    )
+        .into_response()
+}
+
+fn product_forbidden(user: &User, product: &Product, action: &str) -> Response {
+    warn!(
+        "Product {} forbidden - id: {}, username: {}, owner: {}",
+        action, product.id, user.username, product.added_by
+    );
+    (
+        StatusCode::FORBIDDEN,
+        forbidden_page("You can only change products you added."),
+    )
+        .into_response()
+}
+
+async fn edit_product_page(
+    EditorUser(user): EditorUser,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let Some((product, subscription)) = products::find_visible(state.products.as_ref(), &user, id)?
+    else {
+        return Ok(product_not_found());
+    };
+    if !can_manage(&user, &product) {
+        return Ok(product_forbidden(&user, &product, "edit"));
+    }
+
+    let error_message = params.get("error").map(|code| product_error_message(code));
+    let values = ProductForm {
+        url: product.url.clone(),
+        name: product.name.clone(),
+        retailer: product.retailer.clone(),
+        target_price: subscription
+            .as_ref()
+            .and_then(|s| s.target_price)
+            .map(Money::amount),
+        poll_interval: product
+            .poll_interval
+            .map(|d| (d.as_secs() / 60).to_string()),
+    };
+
+    Ok(html! {
+        (header())
+        body class="font-display" {
+            div class="max-w-xl mx-auto px-4 sm:px-6 lg:px-8 py-8" {
+                div class="flex justify-between items-center mb-6" {
+                    h1 class="text-3xl font-bold text-gray-900" { "Edit Product" }
+                    a href=(format!("/products/{}", product.id)) class="text-indigo-600 hover:text-indigo-800" { "Back to Product" }
+                }
+
+                @if let Some(message) = error_message {
+                    (error_alert(message))
+                }
+
+                div class="bg-white shadow rounded-lg p-6 mt-6" {
+                    (edit_product_form(&state, product.id, &values, &ProductErrors::default(), subscription.is_some()))
+                }
+
+                // Subscribers only ever remove themselves; the product goes with the last one
+                div class="bg-white shadow rounded-lg p-6 mt-6 border border-red-200" {
+                    @if subscription.is_some() {
+                        h2 class="text-lg font-semibold text-red-700" { "Stop Watching" }
+                        p class="mt-1 text-sm text-gray-600" { "Stops tracking this product for you. Once nobody watches it, its price history and alerts are removed too." }
+                        button type="button" class="mt-4 px-4 py-2 text-white bg-red-600 rounded-md hover:bg-red-700"
+                            hx-delete=(format!("/products/{}", product.id))
+                            hx-confirm=(format!("Stop watching {}?", product.name)) {
+                            "Stop Watching"
+                        }
+                    } @else {
+                        h2 class="text-lg font-semibold text-red-700" { "Delete Product" }
+                        p class="mt-1 text-sm text-gray-600" { "Stops tracking this product for everyone watching it and removes its price history and alerts." }
+                        button type="button" class="mt-4 px-4 py-2 text-white bg-red-600 rounded-md hover:bg-red-700"
+                            hx-delete=(format!("/products/{}", product.id))
+                            hx-confirm=(format!("Delete {}? Its price history and alerts will be removed for everyone watching it.", product.name)) {
+                            "Delete Product"
+                        }
+                    }
+                }
+            }
+        }
+    }
+    .into_response())
+}
+
+// Same as the add form: htmx swaps the response in for the form
+fn edit_product_form(
+    state: &AppState,
+    id: i64,
+    values: &ProductForm,
+    errors: &ProductErrors,
+    with_target: bool,
+) -> Markup {
+    let action = format!("/products/{}/edit", id);
+    html! {
+        form class="space-y-4" action=(action) method="POST" hx-post=(action) hx-swap="outerHTML" {
+            (product_fields(state, values, errors, with_target))
+
+            div {
+                button type="submit"
+                    class="w-full px-4 py-2 text-white bg-indigo-600 rounded-md hover:bg-indigo-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-indigo-500" {
+                    "Save Changes"
+                }
+            }
+        }
+    }
+}
+
+async fn edit_product_handler(
+    EditorUser(user): EditorUser,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+    Form(form): Form<ProductForm>,
+) -> Result<Response, AppError> {
+    let Some((product, subscription)) = products::find_visible(state.products.as_ref(), &user, id)?
+    else {
+        return Ok(product_not_found());
+    };
+    if !can_manage(&user, &product) {
+        return Ok(product_forbidden(&user, &product, "edit"));
+    }
+
+    let values = form.clone();
+    let edit_page = format!("/products/{}/edit", id);
+    let input = match validate_product_form(&state, form).await {
+        Ok(input) => input,
+        Err(errors) => {
+            warn!(
+                "Product validation failed - errors: {}, url: {}, retailer: {}, edited by: {}",
+                errors, values.url, values.retailer, user.username
+            );
+            let form = edit_product_form(&state, id, &values, &errors, subscription.is_some());
+            return Ok(form_errors(&headers, &edit_page, &errors, form));
+        }
+    };
+
+    let subscribed = subscription.is_some();
+    match products::update(
+        state.products.as_ref(),
+        product,
+        &user.username,
+        subscribed,
+        input,
+    )? {
+        Ok(Some(_)) => {}
+        Ok(None) => return Ok(product_not_found()),
+        Err(e) => {
+            let errors = ProductErrors::from(e);
+            let form = edit_product_form(&state, id, &values, &errors, subscribed);
+            return Ok(form_errors(&headers, &edit_page, &errors, form));
+        }
+    }
+
+    Ok(form_redirect(
+        &headers,
+        &format!("/products/{}?success=updated", id),
+    ))
+}
+
+// Start watching a product someone else tracks, or change your target price for one you
+// already watch
+async fn watch_product(
+    EditorUser(user): EditorUser,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(form): Form<WatchForm>,
+) -> Result<Response, AppError> {
+    let Some((product, subscription)) = products::find_visible(state.products.as_ref(), &user, id)?
+    else {
+        return Ok(product_not_found());
+    };
+
+    let target_price = match products::parse_target_price(
+        form.target_price.as_deref().unwrap_or(""),
+        product.currency,
+    ) {
+        Ok(target_price) => target_price,
+        Err(e) => {
+            let redirect_url = format!("/products/{}?error={}", id, e.code());
+            return Ok(axum::response::Redirect::to(&redirect_url).into_response());
+        }
+    };
+
+    state.products.subscribe(NewSubscription {
+        product_id: product.id,
+        username: user.username.clone(),
+        target_price,
+    })?;
+    info!(
+        "Product subscribed - id: {}, name: {}, username: {}, target price: {:?}",
+        product.id,
+        product.name,
+        user.username,
+        target_price.map(|p| p.to_string())
+    );
+
+    let success = if subscription.is_some() {
+        "target"
+    } else {
+        "watching"
+    };
+    let redirect_url = format!("/products/{}?success={}", id, success);
+    Ok(axum::response::Redirect::to(&redirect_url).into_response())
+}
+
+// Called by htmx. Subscribers stop watching the product, which goes once nobody watches it;
+// admins who don't watch it delete it outright. A product list targets the product's card,
+// which the empty response replaces; from anywhere else the browser is sent on to the
+// product list.
+async fn delete_product(
+    EditorUser(user): EditorUser,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let Some((product, subscription)) = products::find_visible(state.products.as_ref(), &user, id)?
+    else {
+        return Ok(product_not_found());
+    };
+    let removed = products::remove(
+        state.products.as_ref(),
+        &user,
+        &product,
+        subscription.is_some(),
+    )?;
+    if removed == products::Removed::Forbidden {
+        return Ok(product_forbidden(&user, &product, "delete"));
+    }
+
+    if headers.contains_key("HX-Target") {
+        Ok(StatusCode::OK.into_response())
+    } else {
+        Ok(([("HX-Redirect", "/products")], StatusCode::OK).into_response())
+    }
+}
+
+// Latest check result on a product card. Live updates replace it by id, with `oob` set so
+// htmx swaps it in out of band.
+fn product_status(product_id: i64, observation: Option<&Observation>, oob: bool) -> Markup {
+    html! {
+        div id=(format!("product-{}-status", product_id)) class="mt-2 text-sm" hx-swap-oob=[oob.then_some("true")] {
+            @if let Some(observation) = observation {
+                @if let Some(error) = &observation.error {
+                    p class="text-red-600 truncate" title=(error) { "Last check failed" }
+                } @else {
+                    @let stock_color = match observation.stock {
+                        StockState::InStock => "text-green-700",
+                        StockState::OutOfStock => "text-red-700",
+                        StockState::Unknown => "text-gray-600",
+                    };
+                    p class="text-gray-700" {
+                        @if let Some(price) = observation.price {
+                            "Current Price: " (price) " · "
+                        }
+                        span class=(stock_color) { (observation.stock.label()) }
+                    }
+                    @if observation.ships_from.is_some() || observation.sold_by.is_some() {
+                        p class=(if observation.third_party_seller { "text-xs text-amber-700" } else { "text-xs text-gray-500" }) {
+                            @if let Some(ships_from) = &observation.ships_from {
+                                "Ships from " (ships_from)
+                                @if observation.sold_by.is_some() { " · " }
+                            }
+                            @if let Some(sold_by) = &observation.sold_by {
+                                "Sold by " (sold_by)
+                            }
+                            @if observation.third_party_seller {
+                                span class="ml-1 px-1.5 py-0.5 rounded bg-amber-100 font-medium" { "Third-party seller" }
+                            }
+                        }
+                    }
+                }
+                p class="text-xs text-gray-500" { "Checked " (time_ago(observation.observed_at)) }
+            } @else {
+                p class="text-xs text-gray-500" { "Not checked yet" }
+            }
+        }
     }
 }
 
-async fn add_product(
-    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+async fn view_products(
+    user: User,
     State(state): State<AppState>,
-    Form(form): Form<ProductForm>,
-) -> impl IntoResponse {
-    // Get username and role from query params
-    let username = params
-        .get("user")
-        .cloned()
-        .unwrap_or_else(|| "Anonymous".to_string());
-    let role = params
-        .get("role")
-        .cloned()
-        .unwrap_or_else(|| "regular".to_string());
-
-    // Validate that the URL is from a supported retailer
-    let is_valid_retailer = supported_retailers().contains(&form.retailer.as_str());
-
-    // Validate that URLs actually come from the corresponding domains
-    let is_valid_url = match form.retailer.as_str() {
-        "Best Buy" => form.url.to_lowercase().contains("bestbuy.com"),
-        "Amazon" => {
-            let url = form.url.to_lowercase();
-            url.contains("amazon.com") || url.contains("amzn.to") || url.contains("a.co")
-        }
-        _ => false,
+    headers: HeaderMap,
+    axum::extract::Query(query): axum::extract::Query<ProductQuery>,
+) -> Result<Markup, AppError> {
+    let query = query.normalized();
+    let username = user.username.clone();
+    let is_admin_user = user.role == UserRole::Admin;
+    let can_view_all = user.role.can_view_all();
+
+    // Filter products based on user role - admins and viewers see all, regular users see only the ones they watch
+    let visible_products = if can_view_all {
+        state.products.list()?
+    } else {
+        state.products.list_for_user(&username)?
     };
-
-    // If validation fails, redirect back to dashboard with error
-    if !is_valid_retailer || !is_valid_url {
-        // Construct appropriate error message
-        let error_msg = if !is_valid_retailer {
-            "invalid_retailer"
-        } else {
-            "invalid_url"
-        };
-
-        // Log validation failure
-        warn!(
-            "Product validation failed - error: {}, url: {}, retailer: {}, added by: {}",
-            error_msg, form.url, form.retailer, username
-        );
-
-        let redirect_url = format!(
-            "/dashboard?user={}&role={}&error={}",
-            username, role, error_msg
-        );
-        return axum::response::Redirect::to(&redirect_url).into_response();
+    let mut subscriptions = subscriptions_by_product(&state, &username)?;
+
+    let mut owners: Vec<String> = visible_products
+        .iter()
+        .map(|p| p.added_by.clone())
+        .collect();
+    owners.sort_by_key(|o| o.to_lowercase());
+    owners.dedup();
+
+    // Pair each product with its latest check result, which filtering and sorting look at
+    let has_products = !visible_products.is_empty();
+    let rows = visible_products
+        .into_iter()
+        .map(|product| {
+            Ok(ListingRow {
+                latest: state.observations.latest(product.id)?,
+                subscription: subscriptions.remove(&product.id),
+                product,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let results = product_results(&state, &user, &query, &listing::apply(&query, rows));
+
+    // The filter form and pagination links only ask htmx for the results; restoring a page
+    // from history needs all of it
+    if headers.contains_key("HX-Request") && !headers.contains_key("HX-History-Restore-Request") {
+        return Ok(results);
     }
 
-    // Convert target price from string to float if provided
-    let target_price = form
-        .target_price
-        .filter(|s| !s.is_empty())
-        .and_then(|s| s.parse::<f64>().ok());
-
-    // Create new product
-    let product = Product {
-        url: form.url,
-        name: form.name,
-        retailer: form.retailer,
-        target_price,
-        added_by: username.clone(),
-        created_at: std::time::SystemTime::now(),
-    };
-
-    // Log product addition
-    info!(
-        "Product added - name: {}, retailer: {}, added by: {}, target price: {:?}",
-        product.name, product.retailer, username, product.target_price
-    );
-
-    // Add to state
-    state.lock().unwrap().push(product);
-
-    // Redirect back to dashboard
-    let redirect_url = format!("/dashboard?user={}&role={}&success=true", username, role);
-    axum::response::Redirect::to(&redirect_url).into_response()
-}
-
-async fn view_products(
-    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
-    State(state): State<AppState>,
-) -> impl IntoResponse {
-    let username = params
-        .get("user")
-        .cloned()
-        .unwrap_or_else(|| "Anonymous".to_string());
-    let role = params
-        .get("role")
-        .cloned()
-        .unwrap_or_else(|| "regular".to_string());
-    let is_admin_user = role == "admin";
-
-    html! {
+    Ok(html! {
         (header())
-        body class="font-display" {
+        body class="font-display" data-live-updates="/ws/updates" {
             div class="max-w-7xl mx-auto px-4 sm:px-6 lg:px-8 py-8" {
                 div class="flex justify-between items-center mb-6" {
                     h1 class="text-3xl font-bold text-gray-900" {
-                        @if is_admin_user {
+                        @if can_view_all {
                             "All User Products"
                         } @else {
                             "Your Tracked Products"
                         }
                     }
-                    a href=(format!("/dashboard?user={}&role={}", username, role)) class="text-indigo-600 hover:text-indigo-800" { "Back to Dashboard" }
+                    a href="/dashboard" class="text-indigo-600 hover:text-indigo-800" { "Back to Dashboard" }
                 }
 
                 @if is_admin_user {
@@ -590,90 +2670,426 @@ async fn view_products(
                         span class="font-medium text-purple-800" { "Admin View: " }
                         span class="ml-1 text-purple-700" { "You can see all user products" }
                     }
+                } @else if can_view_all {
+                    div class="mb-6 bg-gray-50 p-4 rounded-lg border border-gray-200 flex items-center" {
+                        span class="font-medium text-gray-800" { "Read-only View: " }
+                        span class="ml-1 text-gray-700" { "You can see all user products but not change them" }
+                    }
                 }
 
                 div class="bg-white shadow rounded-lg p-6" {
-                    @let all_products = state.lock().unwrap();
-
-                    // Filter products based on user role - admins see all, regular users see only their own
-                    @let visible_products: Vec<_> = if is_admin_user {
-                        all_products.iter().collect()
-                    } else {
-                        all_products.iter().filter(|p| p.added_by == username).collect()
-                    };
-
-                    @if visible_products.is_empty() {
+                    @if !has_products {
                         div class="text-center py-10 text-gray-500" {
                             p class="text-lg" { "No products found" }
                             p class="mt-2" { "Add your first product on the dashboard" }
                         }
                     } @else {
-                        // Admin tools if admin user
-                        @if is_admin_user {
-                            div class="mb-6 flex justify-between items-center" {
-                                div class="text-sm text-gray-500" {
-                                    span class="font-medium" { "Total products: " } (visible_products.len())
+                        // Changing any field refreshes the results below; without JavaScript the
+                        // form still works as a plain GET
+                        form class="mb-6 grid gap-3 sm:grid-cols-2 lg:grid-cols-6 text-sm" action="/products" method="GET"
+                            hx-get="/products" hx-target="#product-results" hx-push-url="true" hx-trigger="input delay:300ms, submit" {
+                            input type="search" name="q" value=(query.q.as_deref().unwrap_or("")) placeholder="Search by name"
+                                class="lg:col-span-2 px-3 py-2 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500";
+                            select name="retailer" aria-label="Retailer" class="px-3 py-2 border border-gray-300 rounded-md" {
+                                option value="" { "All retailers" }
+                                @for retailer in state.retailers.all() {
+                                    option value=(retailer.name()) selected[query.retailer.as_deref() == Some(retailer.name())] { (retailer.name()) }
                                 }
-
-                                // In a real app, you'd have filtering options here
-                                div class="flex space-x-2 text-sm" {
-                                    span class="text-gray-600" { "Filter by:" }
-                                    a href="#" class="text-indigo-600 hover:text-indigo-800" { "All" }
-                                    a href="#" class="text-gray-600 hover:text-indigo-600" { "Amazon" }
-                                    a href="#" class="text-gray-600 hover:text-indigo-600" { "Best Buy" }
+                            }
+                            @if can_view_all {
+                                select name="owner" aria-label="Added by" class="px-3 py-2 border border-gray-300 rounded-md" {
+                                    option value="" { "All users" }
+                                    @for owner in &owners {
+                                        option value=(owner) selected[query.owner.as_deref().is_some_and(|o| o.eq_ignore_ascii_case(owner))] { (owner) }
+                                    }
+                                }
+                            }
+                            select name="stock" aria-label="Stock" class="px-3 py-2 border border-gray-300 rounded-md" {
+                                option value="" { "Any stock" }
+                                @for stock in [StockState::InStock, StockState::OutOfStock, StockState::Unknown] {
+                                    option value=(stock.as_str()) selected[query.stock_state() == Some(stock)] { (stock.label()) }
+                                }
+                            }
+                            select name="target" aria-label="Price vs target" class="px-3 py-2 border border-gray-300 rounded-md" {
+                                option value="" { "Any price" }
+                                @for target in TargetFilter::ALL {
+                                    option value=(target.as_str()) selected[query.target_filter() == Some(target)] { (target.label()) }
+                                }
+                            }
+                            select name="sort" aria-label="Sort" class="px-3 py-2 border border-gray-300 rounded-md" {
+                                @for sort in SortOrder::ALL {
+                                    option value=(sort.as_str()) selected[query.sort_order() == sort] { (sort.label()) }
                                 }
                             }
                         }
 
-                        div class="grid gap-6 md:grid-cols-2 lg:grid-cols-3" {
-                            @for product in visible_products.iter().rev() {
-                                @let (border_color, bg_hover) = match product.retailer.as_str() {
-                                    "Amazon" => ("border-orange-200", "hover:bg-orange-50"),
-                                    "Best Buy" => ("border-blue-200", "hover:bg-blue-50"),
-                                    _ => ("border-gray-200", "hover:bg-gray-50"),
-                                };
+                        div id="product-results" {
+                            (results)
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+// The product grid with its count and pagination; swapped on its own when filters change
+fn product_results(
+    state: &AppState,
+    user: &User,
+    query: &ProductQuery,
+    listing: &ListingPage,
+) -> Markup {
+    let can_view_all = user.role.can_view_all();
+    let first = (listing.page - 1) * listing::PAGE_SIZE + 1;
+    let last = first + listing.rows.len() - 1;
 
-                                div class=(format!("border rounded-lg p-6 shadow-sm hover:shadow-md transition-shadow {} {}", border_color, bg_hover)) {
-                                    div class="flex justify-between items-start" {
-                                        h3 class="font-semibold text-lg text-gray-800" { (product.name) }
+    html! {
+        @if listing.rows.is_empty() {
+            div class="text-center py-10 text-gray-500" {
+                p class="text-lg" { "No products match these filters" }
+                a href="/products" class="mt-2 inline-block text-indigo-600 hover:text-indigo-800" { "Clear filters" }
+            }
+        } @else {
+            div class="mb-4 flex justify-between items-center text-sm text-gray-500" {
+                span {
+                    "Showing " (first) "–" (last) " of " (listing.total)
+                    (if listing.total == 1 { " product" } else { " products" })
+                }
+                @if query.is_filtered() {
+                    a href="/products" class="text-indigo-600 hover:text-indigo-800" { "Clear filters" }
+                }
+            }
 
-                                        @let (badge_color, badge_text) = match product.retailer.as_str() {
-                                            "Amazon" => ("bg-orange-100 text-orange-800", "Amazon"),
-                                            "Best Buy" => ("bg-blue-100 text-blue-800", "Best Buy"),
-                                            _ => ("bg-gray-100 text-gray-800", product.retailer.as_str()),
-                                        };
+            div class="grid gap-6 md:grid-cols-2 lg:grid-cols-3" {
+                @for row in &listing.rows {
+                    @let product = &row.product;
+                    @let style = state.retailers.style(&product.retailer);
 
-                                        span class=(format!("text-xs rounded-full px-2 py-1 {}", badge_color)) {
-                                            (badge_text)
-                                        }
+                    div id=(format!("product-{}", product.id)) class=(format!("border rounded-lg p-6 shadow-sm hover:shadow-md transition-shadow {}", style.card)) {
+                        div class="flex justify-between items-start" {
+                            h3 class="font-semibold text-lg text-gray-800" {
+                                a href=(format!("/products/{}", product.id)) class="hover:text-indigo-600" { (product.name) }
+                            }
+
+                            span class=(format!("text-xs rounded-full px-2 py-1 {}", style.badge)) {
+                                (product.retailer)
+                            }
+                        }
+
+                        div class="text-sm text-gray-600 mt-2 truncate" {
+                            a href=(product.url) target="_blank" class="text-indigo-600 hover:underline" { "View product" }
+                        }
+
+                        @if let Some(price) = row.target_price() {
+                            p class="mt-3 text-sm text-gray-700" { "Target Price: " (price) }
+                        }
+
+                        (product_status(product.id, row.latest.as_ref(), false))
+
+                        @if can_view_all || row.subscription.is_some() {
+                            div class="mt-4 pt-3 border-t border-gray-100 flex justify-between items-center" {
+                                p class="text-xs text-gray-500" {
+                                    "Added by: "
+                                    span class=(if product.added_by == user.username { "font-medium text-indigo-600" } else { "text-gray-600" }) {
+                                        (product.added_by)
                                     }
+                                    span class="text-gray-400" { " · " (time_ago(product.created_at)) }
+                                }
 
-                                    div class="text-sm text-gray-600 mt-2 truncate" {
-                                        a href=(product.url) target="_blank" class="text-indigo-600 hover:underline" { "View product" }
+                                div class="flex space-x-2" {
+                                    @if can_manage(user, product) {
+                                        a href=(format!("/products/{}/edit", product.id)) class="text-xs text-gray-600 hover:text-indigo-600" {
+                                            "Edit"
+                                        }
+                                    }
+                                    @if row.subscription.is_some() && user.role.can_edit() {
+                                        button type="button" class="text-xs text-gray-600 hover:text-red-600"
+                                            hx-delete=(format!("/products/{}", product.id))
+                                            hx-confirm=(format!("Stop watching {}?", product.name))
+                                            hx-target=(format!("#product-{}", product.id))
+                                            hx-swap="outerHTML" {
+                                            "Stop watching"
+                                        }
+                                    } @else if can_manage(user, product) {
+                                        button type="button" class="text-xs text-gray-600 hover:text-red-600"
+                                            hx-delete=(format!("/products/{}", product.id))
+                                            hx-confirm=(format!("Delete {}? Its price history and alerts will be removed for everyone watching it.", product.name))
+                                            hx-target=(format!("#product-{}", product.id))
+                                            hx-swap="outerHTML" {
+                                            "Delete"
+                                        }
                                     }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            @if listing.pages > 1 {
+                nav class="mt-6 flex justify-center items-center space-x-1 text-sm" aria-label="Pagination" {
+                    @if listing.page > 1 {
+                        (page_link(query, listing.page - 1, "Previous", false))
+                    }
+                    @for page in 1..=listing.pages {
+                        (page_link(query, page, &page.to_string(), page == listing.page))
+                    }
+                    @if listing.page < listing.pages {
+                        (page_link(query, listing.page + 1, "Next", false))
+                    }
+                }
+            }
+        }
+    }
+}
 
-                                    @if let Some(price) = product.target_price {
-                                        p class="mt-3 text-sm text-gray-700" { "Target Price: $" (format!("{:.2}", price)) }
+fn page_link(query: &ProductQuery, page: usize, label: &str, current: bool) -> Markup {
+    let href = format!("/products?{}", query.with_page(page));
+    html! {
+        @if current {
+            span class="px-3 py-1 rounded-md bg-indigo-600 text-white" aria-current="page" { (label) }
+        } @else {
+            a href=(href) hx-get=(href) hx-target="#product-results" hx-push-url="true"
+                class="px-3 py-1 rounded-md text-gray-600 hover:bg-gray-100" { (label) }
+        }
+    }
+}
+
+async fn product_detail(
+    user: User,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    // Products the user isn't allowed to see look the same as ones that don't exist
+    let Some((product, subscription)) = products::find_visible(state.products.as_ref(), &user, id)?
+    else {
+        return Ok(product_not_found());
+    };
+    let success_message = params.get("success").map(|s| match s.as_str() {
+        "watching" => "You're now watching this product.",
+        "target" => "Target price saved.",
+        "auto_buy_on" => "Auto-buy saved.",
+        "auto_buy_off" => "Auto-buy turned off.",
+        _ => "Product updated.",
+    });
+    let error_message = params.get("error").map(|code| {
+        AutoBuyError::parse(code).map_or_else(|| product_error_message(code), AutoBuyError::message)
+    });
+    let target_price = subscription.as_ref().and_then(|s| s.target_price);
+    let auto_buy = state.checkouts.rule(product.id, &user.username)?;
+    let checkout_runs = state.checkouts.recent_runs(product.id, &user.username, 5)?;
+
+    let history = state.observations.history(product.id)?;
+    // Other subscribers' target alerts are theirs alone
+    let mut alerts = state.alerts.history(product.id)?;
+    alerts.retain(|alert| alert.is_for(&user.username));
+    let checks: Vec<_> = history.iter().filter(|o| o.error.is_none()).collect();
+    let current = checks.last().and_then(|o| o.price);
+    // Prices the page showed in some other currency can't be ranked against the rest
+    let prices = || {
+        checks.iter().filter_map(|o| {
+            o.price
+                .filter(|price| price.currency() == product.currency)
+                .map(|price| (price, o.observed_at))
+        })
+    };
+    let lowest = prices().min_by_key(|(price, _)| price.minor());
+    let highest = prices().max_by_key(|(price, _)| price.minor());
+    let in_stock_now = checks
+        .last()
+        .is_some_and(|o| o.stock == StockState::InStock);
+    let last_in_stock = checks
+        .iter()
+        .rev()
+        .find(|o| o.stock == StockState::InStock)
+        .map(|o| o.observed_at);
+    let style = state.retailers.style(&product.retailer);
+
+    Ok(html! {
+        (header())
+        body class="font-display" {
+            div class="max-w-5xl mx-auto px-4 sm:px-6 lg:px-8 py-8" {
+                div class="flex justify-between items-center mb-6" {
+                    div {
+                        h1 class="text-3xl font-bold text-gray-900" { (product.name) }
+                        div class="mt-2 flex items-center space-x-3 text-sm" {
+                            span class=(format!("text-xs rounded-full px-2 py-1 {}", style.badge)) { (product.retailer) }
+                            a href=(product.url) target="_blank" class="text-indigo-600 hover:underline" { "View product" }
+                            span class="text-gray-500" { "Added by " (product.added_by) " · " (time_ago(product.created_at)) }
+                            @if let Some(subscription) = &subscription {
+                                span class="text-gray-500" { "Watching since " (format_time(subscription.created_at)) }
+                            }
+                        }
+                    }
+                    div class="flex items-center space-x-4" {
+                        @if can_manage(&user, &product) {
+                            a href=(format!("/products/{}/edit", product.id)) class="text-indigo-600 hover:text-indigo-800" { "Edit" }
+                        }
+                        a href="/products" class="text-indigo-600 hover:text-indigo-800" { "Back to Products" }
+                    }
+                }
+
+                @if let Some(message) = success_message {
+                    div class="mb-6" { (success_alert(message)) }
+                }
+                @if let Some(message) = error_message {
+                    div class="mb-6" { (error_alert(message)) }
+                }
+
+                @if user.role.can_edit() {
+                    div class="bg-white shadow rounded-lg p-4 mb-6" {
+                        form class="flex flex-wrap items-end gap-3" action=(format!("/products/{}/watch", product.id)) method="POST" {
+                            div {
+                                label class="block text-sm font-medium text-gray-700" for="target_price" {
+                                    @if subscription.is_some() { "Your Target Price" } @else { "Watch this product" }
+                                }
+                                div class="mt-1 relative rounded-md shadow-sm" {
+                                    div class="absolute inset-y-0 left-0 pl-3 flex items-center pointer-events-none" {
+                                        span class="text-gray-500 sm:text-sm" { (product.currency.symbol()) }
                                     }
+                                    // Room for the symbol, which is wider for "CA$"
+                                    @let padding = if product.currency.symbol().chars().count() > 1 { "pl-11" } else { "pl-7" };
+                                    input id="target_price" name="target_price" type="text" inputmode="decimal" placeholder="Target (optional)"
+                                        value=(target_price.map(Money::amount).unwrap_or_default())
+                                        class=(format!("{padding} pr-3 py-2 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500"));
+                                }
+                            }
+                            button type="submit" class="px-4 py-2 text-white bg-indigo-600 rounded-md hover:bg-indigo-700" {
+                                @if subscription.is_some() { "Save Target" } @else { "Watch" }
+                            }
+                            @if subscription.is_some() {
+                                button type="button" class="px-4 py-2 text-gray-700 border border-gray-300 rounded-md hover:text-red-700 hover:border-red-300"
+                                    hx-delete=(format!("/products/{}", product.id))
+                                    hx-confirm=(format!("Stop watching {}?", product.name)) {
+                                    "Stop Watching"
+                                }
+                            }
+                        }
+                    }
+                }
 
-                                    @if is_admin_user || product.added_by == username {
-                                        div class="mt-4 pt-3 border-t border-gray-100 flex justify-between items-center" {
-                                            p class="text-xs text-gray-500" {
-                                                "Added by: "
-                                                span class=(if product.added_by == username { "font-medium text-indigo-600" } else { "text-gray-600" }) {
-                                                    (product.added_by)
-                                                }
+                @if let (true, Some(_)) = (user.role.can_edit(), &subscription) {
+                    @if state.auto_buyer.supports(&product.retailer) {
+                        (auto_buy_card(&state, &product, auto_buy.as_ref(), &checkout_runs))
+                    }
+                }
+
+                // Headline numbers
+                div class="grid gap-4 grid-cols-2 md:grid-cols-4 mb-6" {
+                    div class="bg-white shadow rounded-lg p-4" {
+                        p class="text-sm text-gray-500" { "Current" }
+                        p class="text-2xl font-semibold text-gray-900" {
+                            @if let Some(price) = current { (price) } @else { "—" }
+                        }
+                        @if let Some(target) = target_price {
+                            p class="text-xs text-gray-500" { "Target " (target) }
+                        }
+                    }
+                    div class="bg-white shadow rounded-lg p-4" {
+                        p class="text-sm text-gray-500" { "Lowest" }
+                        @if let Some((price, at)) = lowest {
+                            p class="text-2xl font-semibold text-green-700" { (price) }
+                            p class="text-xs text-gray-500" { (format_time(at)) }
+                        } @else {
+                            p class="text-2xl font-semibold text-gray-900" { "—" }
+                        }
+                    }
+                    div class="bg-white shadow rounded-lg p-4" {
+                        p class="text-sm text-gray-500" { "Highest" }
+                        @if let Some((price, at)) = highest {
+                            p class="text-2xl font-semibold text-red-700" { (price) }
+                            p class="text-xs text-gray-500" { (format_time(at)) }
+                        } @else {
+                            p class="text-2xl font-semibold text-gray-900" { "—" }
+                        }
+                    }
+                    div class="bg-white shadow rounded-lg p-4" {
+                        p class="text-sm text-gray-500" { "Last in stock" }
+                        @if in_stock_now {
+                            p class="text-2xl font-semibold text-green-700" { "Now" }
+                        } @else if let Some(at) = last_in_stock {
+                            p class="text-2xl font-semibold text-gray-900" { (time_ago(at)) }
+                            p class="text-xs text-gray-500" { (format_time(at)) }
+                        } @else {
+                            p class="text-2xl font-semibold text-gray-900" { "Never" }
+                        }
+                    }
+                }
+
+                div class="bg-white shadow rounded-lg p-6 mb-6" {
+                    h2 class="text-lg font-medium text-gray-900 mb-4" { "Price History" }
+                    (chart::price_chart(&history, product.currency, target_price))
+                    div class="mt-2 flex space-x-4 text-xs text-gray-500" {
+                        span { span class="inline-block w-3 h-3 rounded-sm bg-green-600 mr-1" {} "In stock" }
+                        span { span class="inline-block w-3 h-3 rounded-sm bg-red-600 mr-1" {} "Out of stock" }
+                        span { span class="inline-block w-3 h-3 rounded-sm bg-gray-400 mr-1" {} "Unknown" }
+                    }
+                }
+
+                div class="bg-white shadow rounded-lg p-6 mb-6" {
+                    h2 class="text-lg font-medium text-gray-900 mb-4" { "Alerts" }
+                    @if alerts.is_empty() {
+                        p class="text-gray-500" { "No alerts yet" }
+                    } @else {
+                        ul class="divide-y divide-gray-100" {
+                            @for alert in &alerts {
+                                li class="py-2 flex justify-between items-center text-sm" {
+                                    div {
+                                        span class="font-medium text-gray-900" { (alert.kind.label()) }
+                                        span class="ml-2 text-gray-600" { (alert.message) }
+                                    }
+                                    div class="text-right whitespace-nowrap ml-4" {
+                                        @if let Some(price) = alert.price {
+                                            span class="text-gray-900" { (price) }
+                                            @if let Some(previous) = alert.previous_price.filter(|previous| *previous != price) {
+                                                span class="ml-1 text-xs text-gray-400 line-through" { (previous) }
                                             }
+                                        }
+                                        p class="text-xs text-gray-500" { (format_time(alert.triggered_at)) }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
 
-                                            @if is_admin_user {
-                                                // Admin actions (in a real app, these would be functional)
-                                                div class="flex space-x-1" {
-                                                    button type="button" class="text-xs text-gray-600 hover:text-indigo-600" {
-                                                        "Edit"
-                                                    }
-                                                    button type="button" class="text-xs text-gray-600 hover:text-red-600" {
-                                                        "Delete"
-                                                    }
+                div class="bg-white shadow rounded-lg p-6" {
+                    h2 class="text-lg font-medium text-gray-900 mb-4" { "All Checks" }
+                    @if history.is_empty() {
+                        p class="text-gray-500" { "Not checked yet" }
+                    } @else {
+                        div class="max-h-96 overflow-y-auto" {
+                            table class="min-w-full divide-y divide-gray-200 text-sm" {
+                                thead class="bg-gray-50 sticky top-0" {
+                                    tr {
+                                        th class="px-4 py-2 text-left font-medium text-gray-500" { "Checked" }
+                                        th class="px-4 py-2 text-left font-medium text-gray-500" { "Price" }
+                                        th class="px-4 py-2 text-left font-medium text-gray-500" { "Stock" }
+                                        th class="px-4 py-2 text-left font-medium text-gray-500" { "Sold by" }
+                                    }
+                                }
+                                tbody class="divide-y divide-gray-100" {
+                                    @for observation in history.iter().rev() {
+                                        tr {
+                                            td class="px-4 py-2 text-gray-700 whitespace-nowrap" { (format_time(observation.observed_at)) }
+                                            @if let Some(error) = &observation.error {
+                                                td colspan="3" class="px-4 py-2 text-red-600 truncate" title=(error) { "Check failed: " (error) }
+                                            } @else {
+                                                td class="px-4 py-2 text-gray-900" {
+                                                    @if let Some(price) = observation.price { (price) } @else { "—" }
+                                                }
+                                                td class="px-4 py-2" {
+                                                    @let stock_color = match observation.stock {
+                                                        StockState::InStock => "text-green-700",
+                                                        StockState::OutOfStock => "text-red-700",
+                                                        StockState::Unknown => "text-gray-600",
+                                                    };
+                                                    span class=(stock_color) { (observation.stock.label()) }
+                                                }
+                                                td class=(if observation.third_party_seller { "px-4 py-2 text-amber-700" } else { "px-4 py-2 text-gray-600" }) {
+                                                    (observation.sold_by.as_deref().unwrap_or("—"))
                                                 }
                                             }
                                         }
@@ -686,6 +3102,158 @@ async fn view_products(
             }
         }
     }
+    .into_response())
+}
+
+// Auto-buy settings for the signed-in subscriber, with their latest checkout runs
+fn auto_buy_card(
+    state: &AppState,
+    product: &Product,
+    rule: Option<&AutoBuyRule>,
+    runs: &[CheckoutRun],
+) -> Markup {
+    let config = state.auto_buyer.config();
+    html! {
+        div class="bg-white shadow rounded-lg p-6 mb-6" {
+            h2 class="text-lg font-medium text-gray-900 mb-2" { "Auto-buy" }
+            p class="text-sm text-gray-600 mb-4" {
+                "When a check finds this product in stock at or below your target price, Midas goes through checkout at "
+                (product.retailer) " for you, within the limits below. Placing an order turns auto-buy off again."
+            }
+            @if !config.live_orders {
+                p class="text-sm text-amber-700 mb-4" {
+                    "Live orders are turned off on this server, so every run stops before payment."
+                }
+            }
+            form class="grid gap-4 sm:grid-cols-2 items-end" action=(format!("/products/{}/auto-buy", product.id)) method="POST" {
+                div {
+                    label class="block text-sm font-medium text-gray-700" for="max_spend" { "Max spend per order" }
+                    div class="mt-1 relative rounded-md shadow-sm" {
+                        div class="absolute inset-y-0 left-0 pl-3 flex items-center pointer-events-none" {
+                            span class="text-gray-500 sm:text-sm" { (product.currency.symbol()) }
+                        }
+                        @let padding = if product.currency.symbol().chars().count() > 1 { "pl-11" } else { "pl-7" };
+                        input id="max_spend" name="max_spend" type="text" inputmode="decimal" placeholder="Taxes and shipping included"
+                            value=(rule.map(|r| r.max_spend.amount()).unwrap_or_default())
+                            class=(format!("w-full {padding} pr-3 py-2 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500"));
+                    }
+                }
+                div {
+                    label class="block text-sm font-medium text-gray-700" for="max_quantity" { "Quantity (at most " (config.max_quantity) ")" }
+                    input id="max_quantity" name="max_quantity" type="number" min="1" max=(config.max_quantity)
+                        value=(rule.map_or(1, |r| r.max_quantity))
+                        class="w-full mt-1 px-3 py-2 border border-gray-300 rounded-md focus:outline-none focus:ring-indigo-500 focus:border-indigo-500";
+                }
+                label class="flex items-center space-x-2 text-sm text-gray-700" {
+                    input type="checkbox" name="enabled" value="on" checked[rule.is_some_and(|r| r.enabled)];
+                    span { "Buy automatically" }
+                }
+                label class="flex items-center space-x-2 text-sm text-gray-700" {
+                    input type="checkbox" name="dry_run" value="on" checked[rule.is_none_or(|r| r.dry_run)];
+                    span { "Dry run: stop before paying" }
+                }
+                div class="sm:col-span-2" {
+                    button type="submit" class="px-4 py-2 text-white bg-indigo-600 rounded-md hover:bg-indigo-700" { "Save Auto-buy" }
+                }
+            }
+
+            @if !runs.is_empty() {
+                h3 class="mt-6 mb-2 text-sm font-medium text-gray-900" { "Recent Runs" }
+                div class="space-y-3" {
+                    @for run in runs {
+                        @let status_color = match run.status {
+                            CheckoutStatus::Ordered => "text-green-700",
+                            CheckoutStatus::DryRun => "text-indigo-700",
+                            CheckoutStatus::Stopped => "text-amber-700",
+                            CheckoutStatus::Failed => "text-red-700",
+                        };
+                        div class="border rounded-lg p-3 text-sm" {
+                            div class="flex justify-between" {
+                                div {
+                                    span class=(format!("font-medium {status_color}")) { (run.status.label()) }
+                                    @if run.dry_run && run.status != CheckoutStatus::DryRun {
+                                        span class="ml-1 text-gray-500" { "(dry run)" }
+                                    }
+                                    @if let Some(order_number) = &run.order_number {
+                                        span class="ml-2 text-gray-600" { "Order " (order_number) }
+                                    }
+                                    @if let Some(total) = run.total {
+                                        span class="ml-2 text-gray-600" { (run.quantity) " for " (total) }
+                                    }
+                                }
+                                span class="text-xs text-gray-500" { (format_time(run.started_at)) }
+                            }
+                            ol class="mt-2 space-y-1" {
+                                @for step in &run.steps {
+                                    li class=(if step.ok { "text-gray-700" } else { "text-red-700" }) {
+                                        (if step.ok { "✓ " } else { "✗ " })
+                                        span class="font-medium" { (step.name) }
+                                        ": " (step.message)
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Turns auto-buy on with the submitted limits, or off when the box is unticked
+async fn save_auto_buy(
+    EditorUser(user): EditorUser,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(form): Form<AutoBuyForm>,
+) -> Result<Response, AppError> {
+    let Some((product, subscription)) = products::find_visible(state.products.as_ref(), &user, id)?
+    else {
+        return Ok(product_not_found());
+    };
+
+    if form.enabled.is_none() {
+        let rule = state.checkouts.rule(product.id, &user.username)?;
+        if let Some(rule) = rule.filter(|rule| rule.enabled) {
+            state.checkouts.save_rule(&AutoBuyRule {
+                enabled: false,
+                updated_at: std::time::SystemTime::now(),
+                ..rule
+            })?;
+            info!(
+                "Auto-buy turned off - product id: {}, username: {}",
+                product.id, user.username
+            );
+        }
+        let redirect_url = format!("/products/{}?success=auto_buy_off", id);
+        return Ok(axum::response::Redirect::to(&redirect_url).into_response());
+    }
+
+    let rule = state.auto_buyer.rule(
+        &product,
+        subscription.as_ref(),
+        &form.max_spend,
+        form.max_quantity.trim().parse().unwrap_or(0),
+        form.dry_run.is_some(),
+    );
+    let redirect_url = match rule {
+        Ok(rule) => {
+            state.checkouts.save_rule(&rule)?;
+            info!(
+                "Auto-buy turned on - product id: {}, username: {}, max spend: {}, max quantity: {}, dry run: {}",
+                product.id, user.username, rule.max_spend, rule.max_quantity, rule.dry_run
+            );
+            format!("/products/{}?success=auto_buy_on", id)
+        }
+        Err(e) => {
+            warn!(
+                "Auto-buy not saved - product id: {}, username: {}, reason: {:?}",
+                product.id, user.username, e
+            );
+            format!("/products/{}?error={}", id, e.code())
+        }
+    };
+    Ok(axum::response::Redirect::to(&redirect_url).into_response())
 }
 
 /// Handle Ctrl+C (SIGINT) and SIGTERM signals for graceful shutdown
@@ -715,3 +3283,273 @@ async fn shutdown_signal() {
         _ = terminate => {},
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+    use users::Account;
+
+    /// A request header identifying the caller
+    pub type Credentials = (&'static str, String);
+
+    /// The app on in-memory storage, served on a random local port. It has an admin "alice",
+    /// regular users "bob" and "carol" and a viewer "vera", none of whom can sign in with a
+    /// password; requests carry credentials made for them.
+    pub struct TestApp {
+        pub state: AppState,
+        base_url: String,
+        client: reqwest::Client,
+    }
+
+    impl TestApp {
+        pub async fn start() -> TestApp {
+            let repositories = storage::open(":memory:").unwrap();
+            for (username, role) in [
+                ("alice", UserRole::Admin),
+                ("bob", UserRole::Regular),
+                ("carol", UserRole::Regular),
+                ("vera", UserRole::Viewer),
+            ] {
+                repositories
+                    .users
+                    .insert(Account {
+                        user: User {
+                            username: username.to_string(),
+                            role,
+                        },
+                        password_hash: String::new(),
+                        failed_logins: 0,
+                        locked_until: None,
+                        created_at: SystemTime::now(),
+                    })
+                    .unwrap();
+            }
+            let state = create_app_state(
+                &Config::default(),
+                Arc::new(RetailerRegistry::default()),
+                repositories,
+            )
+            .unwrap();
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let base_url = format!("http://{}", listener.local_addr().unwrap());
+            let app = router(state.clone());
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+            let client = reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .unwrap();
+            TestApp {
+                state,
+                base_url,
+                client,
+            }
+        }
+
+        /// Start a request to `path` with `credentials`
+        pub fn request(
+            &self,
+            method: reqwest::Method,
+            path: &str,
+            credentials: &Credentials,
+        ) -> reqwest::RequestBuilder {
+            let (name, value) = credentials;
+            self.client
+                .request(method, format!("{}{}", self.base_url, path))
+                .header(*name, value)
+        }
+
+        /// Send `body` as JSON, returning the status and JSON body of the response
+        pub async fn call(
+            &self,
+            method: reqwest::Method,
+            path: &str,
+            credentials: &Credentials,
+            body: Option<&serde_json::Value>,
+        ) -> (u16, serde_json::Value) {
+            let mut request = self.request(method, path, credentials);
+            if let Some(body) = body {
+                request = request
+                    .header("Content-Type", "application/json")
+                    .body(body.to_string());
+            }
+            let response = request.send().await.unwrap();
+            let status = response.status().as_u16();
+            let body = response.bytes().await.unwrap();
+            (status, serde_json::from_slice(&body).unwrap_or_default())
+        }
+
+        /// A new API token for `username`
+        pub fn token(&self, username: &str, scope: TokenScope) -> Credentials {
+            let (token, secret) = NewApiToken::generate(username, "tests", scope).unwrap();
+            self.state.tokens.add(token).unwrap();
+            ("Authorization", format!("Bearer {secret}"))
+        }
+
+        /// A browser session for `username`, in a signed cookie
+        pub fn session(&self, username: &str) -> Credentials {
+            let id = self.state.sessions.create(username);
+            let jar = SignedCookieJar::new(self.state.key.clone())
+                .add(Cookie::new(session::SESSION_COOKIE, id));
+            let response = (jar, ()).into_response();
+            let cookie = response.headers()[axum::http::header::SET_COOKIE]
+                .to_str()
+                .unwrap();
+            ("Cookie", cookie.split(';').next().unwrap().to_string())
+        }
+
+        /// Add a product at `url` on Amazon, watched by `username`
+        pub fn track(&self, username: &str, url: &str) -> Product {
+            let input = ProductInput {
+                url: url.to_string(),
+                name: "PS5".to_string(),
+                retailer: "Amazon".to_string(),
+                retailer_id: None,
+                currency: Currency::Usd,
+                target_price: None,
+                poll_interval: None,
+            };
+            products::track(self.state.products.as_ref(), username, input)
+                .unwrap()
+                .0
+        }
+    }
+
+    const PS5: &str = "https://www.amazon.com/dp/B0CL61F39H";
+
+    #[tokio::test]
+    async fn only_owners_and_admins_edit_products() {
+        let app = TestApp::start().await;
+        let product = app.track("bob", PS5);
+        let path = format!("/products/{}/edit", product.id);
+        let form = [("url", PS5), ("name", "PS5 Slim"), ("retailer", "Amazon")];
+        let edit = |credentials| {
+            let request = app.request(reqwest::Method::POST, &path, &credentials);
+            async move {
+                let body = serde_urlencoded::to_string(form).unwrap();
+                let response = request
+                    .header("Content-Type", "application/x-www-form-urlencoded")
+                    .body(body)
+                    .send()
+                    .await
+                    .unwrap();
+                response.status().as_u16()
+            }
+        };
+
+        // Even watching it doesn't let carol change it
+        app.track("carol", PS5);
+        assert_eq!(edit(app.session("carol")).await, 403);
+        assert_eq!(
+            app.state.products.get(product.id).unwrap().unwrap().name,
+            "PS5"
+        );
+
+        assert_eq!(edit(app.session("bob")).await, 303);
+        assert_eq!(edit(app.session("alice")).await, 303);
+        assert_eq!(
+            app.state.products.get(product.id).unwrap().unwrap().name,
+            "PS5 Slim"
+        );
+    }
+
+    #[tokio::test]
+    async fn deleting_a_shared_product_only_unsubscribes() {
+        let app = TestApp::start().await;
+        let product = app.track("bob", PS5);
+        app.track("carol", PS5);
+        let path = format!("/products/{}", product.id);
+        let delete = |username| {
+            let request = app.request(reqwest::Method::DELETE, &path, &app.session(username));
+            async move { request.send().await.unwrap().status().as_u16() }
+        };
+        let subscribers = || {
+            app.state
+                .products
+                .subscribers(product.id)
+                .unwrap()
+                .into_iter()
+                .map(|s| s.username)
+                .collect::<Vec<_>>()
+        };
+
+        // bob added it, but carol still watches it
+        assert_eq!(delete("bob").await, 200);
+        assert_eq!(subscribers(), ["carol"]);
+        assert!(app.state.products.get(product.id).unwrap().is_some());
+
+        // Now bob can't see it, let alone delete it
+        assert_eq!(delete("bob").await, 404);
+
+        assert_eq!(delete("carol").await, 200);
+        assert!(app.state.products.get(product.id).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn only_managers_delete_products_they_dont_watch() {
+        let app = TestApp::start().await;
+        let product = app.track("bob", PS5);
+        let path = format!("/products/{}", product.id);
+        let delete = |username| {
+            let request = app.request(reqwest::Method::DELETE, &path, &app.session(username));
+            async move { request.send().await.unwrap().status().as_u16() }
+        };
+
+        assert_eq!(delete("vera").await, 403);
+        assert_eq!(delete("carol").await, 404);
+        assert!(app.state.products.get(product.id).unwrap().is_some());
+
+        assert_eq!(delete("alice").await, 200);
+        assert!(app.state.products.get(product.id).unwrap().is_none());
+    }
+
+    #[test]
+    fn labels_the_configured_default_interval() {
+        let options = poll_interval_options(None, Duration::from_secs(2 * 60 * 60)).into_string();
+        assert!(options.contains("Default (2 hours)"), "{options}");
+
+        let options =
+            poll_interval_options(Some(Duration::from_secs(90 * 60)), Duration::from_secs(900))
+                .into_string();
+        assert!(options.contains("Default (15 minutes)"), "{options}");
+        assert!(
+            options.contains("<option value=\"90\" selected>90 minutes</option>"),
+            "{options}"
+        );
+
+        for (secs, label) in [
+            (60, "1 minute"),
+            (90, "90 seconds"),
+            (3 * 60 * 60, "3 hours"),
+            (24 * 60 * 60, "1 day"),
+            (7 * 24 * 60 * 60, "7 days"),
+        ] {
+            assert_eq!(interval_label(Duration::from_secs(secs)), label);
+        }
+    }
+
+    #[tokio::test]
+    async fn live_updates_only_upgrade_same_origin_pages() {
+        let app = TestApp::start().await;
+        let session = app.session("bob");
+        for (origin, expected) in [
+            (Some(app.base_url.clone()), 101),
+            (Some("https://evil.example".to_string()), 403),
+            (Some("http://127.0.0.1:1".to_string()), 403),
+            (None, 403),
+        ] {
+            let mut request = app
+                .request(reqwest::Method::GET, "/ws/updates", &session)
+                .header("Connection", "Upgrade")
+                .header("Upgrade", "websocket")
+                .header("Sec-WebSocket-Version", "13")
+                .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==");
+            if let Some(origin) = &origin {
+                request = request.header("Origin", origin);
+            }
+            let status = request.send().await.unwrap().status().as_u16();
+            assert_eq!(status, expected, "{origin:?}");
+        }
+    }
+}
@@ -0,0 +1,365 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+/// The currencies retailers price products in. Every one of them has 100 minor units (cents,
+/// pence) to the major unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Currency {
+    Usd,
+    Cad,
+    Gbp,
+}
+
+impl Currency {
+    pub const ALL: [Currency; 3] = [Currency::Usd, Currency::Cad, Currency::Gbp];
+
+    /// ISO 4217 code, also what gets stored
+    pub fn code(self) -> &'static str {
+        match self {
+            Currency::Usd => "USD",
+            Currency::Cad => "CAD",
+            Currency::Gbp => "GBP",
+        }
+    }
+
+    /// Look a currency up by its code, in any case
+    pub fn parse(code: &str) -> Option<Currency> {
+        let code = code.trim();
+        Currency::ALL
+            .into_iter()
+            .find(|c| c.code().eq_ignore_ascii_case(code))
+    }
+
+    /// The symbol amounts are written with. Canadian dollars get "CA$" so they can't be
+    /// mistaken for US ones when both show up on the same page.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Currency::Usd => "$",
+            Currency::Cad => "CA$",
+            Currency::Gbp => "£",
+        }
+    }
+}
+
+/// What a currency marker next to an amount says about its currency
+#[derive(Debug, Clone, Copy)]
+enum Marker {
+    Is(Currency),
+    /// A bare "$", which is whichever dollar the context calls for
+    Dollar,
+}
+
+impl Marker {
+    fn currency(self, expected: Currency) -> Currency {
+        match self {
+            Marker::Is(currency) => currency,
+            Marker::Dollar if expected == Currency::Cad => Currency::Cad,
+            Marker::Dollar => Currency::Usd,
+        }
+    }
+}
+
+// Longest first, so "US$" isn't read as a bare "$"
+const MARKERS: &[(&str, Marker)] = &[
+    ("CDN$", Marker::Is(Currency::Cad)),
+    ("US$", Marker::Is(Currency::Usd)),
+    ("CA$", Marker::Is(Currency::Cad)),
+    ("USD", Marker::Is(Currency::Usd)),
+    ("CAD", Marker::Is(Currency::Cad)),
+    ("GBP", Marker::Is(Currency::Gbp)),
+    ("C$", Marker::Is(Currency::Cad)),
+    ("$", Marker::Dollar),
+    ("£", Marker::Is(Currency::Gbp)),
+];
+
+/// An amount of money in a specific currency, held as a whole number of minor units so sums
+/// and comparisons are exact.
+///
+/// Amounts in different currencies are never equal and don't compare at all: `partial_cmp`
+/// returns `None`, so `<`, `<=`, `>` and `>=` are all false between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    minor: i64,
+    currency: Currency,
+}
+
+impl Money {
+    pub fn new(minor: i64, currency: Currency) -> Money {
+        Money { minor, currency }
+    }
+
+    /// The amount in minor units, e.g. 129999 for $1,299.99
+    pub fn minor(self) -> i64 {
+        self.minor
+    }
+
+    pub fn currency(self) -> Currency {
+        self.currency
+    }
+
+    /// An amount given in major units, as in JSON, rounded to the nearest minor unit.
+    /// `None` if it isn't a number or is too large to hold.
+    pub fn from_major(amount: f64, currency: Currency) -> Option<Money> {
+        let minor = (amount * 100.0).round();
+        (minor.is_finite() && minor.abs() < i64::MAX as f64)
+            .then(|| Money::new(minor as i64, currency))
+    }
+
+    /// The amount in major units, for arithmetic that doesn't need to be exact (percentages,
+    /// chart coordinates) and for JSON
+    pub fn to_major(self) -> f64 {
+        self.minor as f64 / 100.0
+    }
+
+    /// Parse an amount as typed by a user, e.g. "1299.99", "$1,299.99", "£899" or "1,299.99
+    /// CAD". An amount without a currency is taken to be in `currency`; one marked as being in
+    /// any other currency is refused, and a bare "$" only passes for dollar currencies.
+    pub fn parse(input: &str, currency: Currency) -> Result<Money, MoneyError> {
+        let input = input.trim();
+        let (negative, input) = match input.strip_prefix('-') {
+            Some(rest) => (true, rest.trim_start()),
+            None => (false, input),
+        };
+        let (marker, amount) = match strip_prefix_marker(input) {
+            Some((marker, rest)) => (Some(marker), rest),
+            None => match strip_suffix_marker(input) {
+                Some((marker, rest)) => (Some(marker), rest),
+                None => (None, input),
+            },
+        };
+
+        let minor = parse_amount(amount.trim()).ok_or(MoneyError::Malformed)?;
+        if marker.is_some_and(|marker| marker.currency(currency) != currency) {
+            return Err(MoneyError::WrongCurrency);
+        }
+        Ok(Money::new(if negative { -minor } else { minor }, currency))
+    }
+
+    /// The first amount in a piece of page text like "Was $2,199.99" or "£449.00 with
+    /// Prime". The currency is read off the symbol or code around the amount, falling back to
+    /// `currency` when there isn't one.
+    pub fn find(text: &str, currency: Currency) -> Option<Money> {
+        let start = text.find(|c: char| c.is_ascii_digit())?;
+        let len = text[start..]
+            .find(|c: char| !(c.is_ascii_digit() || c == ',' || c == '.'))
+            .unwrap_or(text.len() - start);
+        let amount = text[start..start + len].trim_end_matches(['.', ',']);
+
+        let marker = strip_suffix_marker(text[..start].trim_end())
+            .or_else(|| strip_prefix_marker(text[start + len..].trim_start()))
+            .map(|(marker, _)| marker);
+        let minor = parse_amount(amount)?;
+        Some(Money::new(
+            minor,
+            marker.map_or(currency, |marker| marker.currency(currency)),
+        ))
+    }
+
+    /// The plain amount without symbol or grouping, e.g. "1299.99", as used in form inputs
+    pub fn amount(self) -> String {
+        let sign = if self.minor < 0 { "-" } else { "" };
+        let minor = self.minor.unsigned_abs();
+        format!("{sign}{}.{:02}", minor / 100, minor % 100)
+    }
+}
+
+/// Written the way an English-speaking shopper expects, e.g. "$1,299.99", "CA$1,299.99" or
+/// "£1,299.99"
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.minor < 0 { "-" } else { "" };
+        let minor = self.minor.unsigned_abs();
+        let whole = (minor / 100).to_string();
+
+        let mut grouped = String::with_capacity(whole.len() + whole.len() / 3);
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i).is_multiple_of(3) {
+                grouped.push(',');
+            }
+            grouped.push(digit);
+        }
+        write!(
+            f,
+            "{sign}{}{grouped}.{:02}",
+            self.currency.symbol(),
+            minor % 100
+        )
+    }
+}
+
+impl PartialOrd for Money {
+    fn partial_cmp(&self, other: &Money) -> Option<Ordering> {
+        (self.currency == other.currency).then(|| self.minor.cmp(&other.minor))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoneyError {
+    /// Not an amount with at most two decimal places
+    Malformed,
+    /// Marked as being in a different currency than the one wanted
+    WrongCurrency,
+}
+
+fn strip_prefix_marker(text: &str) -> Option<(Marker, &str)> {
+    MARKERS.iter().find_map(|(symbol, marker)| {
+        text.get(..symbol.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(symbol))
+            .map(|_| (*marker, &text[symbol.len()..]))
+    })
+}
+
+fn strip_suffix_marker(text: &str) -> Option<(Marker, &str)> {
+    MARKERS.iter().find_map(|(symbol, marker)| {
+        let split = text.len().checked_sub(symbol.len())?;
+        text.get(split..)
+            .filter(|suffix| suffix.eq_ignore_ascii_case(symbol))
+            .map(|_| (*marker, &text[..split]))
+    })
+}
+
+// A plain amount in major units, e.g. "1,299.99", "899" or ".99", as minor units
+fn parse_amount(amount: &str) -> Option<i64> {
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if (whole.is_empty() && fraction.is_empty())
+        || whole.starts_with(',')
+        || whole.ends_with(',')
+        || fraction.len() > 2
+    {
+        return None;
+    }
+    let digits_only = whole.chars().all(|c| c.is_ascii_digit() || c == ',')
+        && fraction.chars().all(|c| c.is_ascii_digit());
+    if !digits_only {
+        return None;
+    }
+
+    let whole: i64 = match whole.replace(',', "") {
+        digits if digits.is_empty() => 0,
+        digits => digits.parse().ok()?,
+    };
+    let fraction: i64 = format!("{fraction:0<2}").parse().ok()?;
+    whole.checked_mul(100)?.checked_add(fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_typed_amounts() {
+        let usd = |minor| Ok(Money::new(minor, Currency::Usd));
+        assert_eq!(Money::parse("$1,299.99", Currency::Usd), usd(129999));
+        assert_eq!(Money::parse(" 399.9 ", Currency::Usd), usd(39990));
+        assert_eq!(Money::parse("US$ 5", Currency::Usd), usd(500));
+        assert_eq!(Money::parse("12.50 usd", Currency::Usd), usd(1250));
+        assert_eq!(Money::parse(".99", Currency::Usd), usd(99));
+        assert_eq!(Money::parse("-$5", Currency::Usd), usd(-500));
+
+        assert_eq!(
+            Money::parse("£1,049", Currency::Gbp),
+            Ok(Money::new(104900, Currency::Gbp))
+        );
+        assert_eq!(
+            Money::parse("$49.99", Currency::Cad),
+            Ok(Money::new(4999, Currency::Cad))
+        );
+
+        for malformed in [
+            "",
+            "$",
+            "abc",
+            "1.999",
+            "1..2",
+            ",99",
+            "12,",
+            "1e5",
+            "NaN",
+            "9999999999999999999",
+        ] {
+            assert_eq!(
+                Money::parse(malformed, Currency::Usd),
+                Err(MoneyError::Malformed),
+                "{malformed}"
+            );
+        }
+    }
+
+    #[test]
+    fn refuses_other_currencies() {
+        assert_eq!(
+            Money::parse("£10", Currency::Usd),
+            Err(MoneyError::WrongCurrency)
+        );
+        assert_eq!(
+            Money::parse("$10", Currency::Gbp),
+            Err(MoneyError::WrongCurrency)
+        );
+        assert_eq!(
+            Money::parse("CA$10", Currency::Usd),
+            Err(MoneyError::WrongCurrency)
+        );
+    }
+
+    #[test]
+    fn finds_amounts_in_page_text() {
+        assert_eq!(
+            Money::find("Was $2,199.99", Currency::Usd),
+            Some(Money::new(219999, Currency::Usd))
+        );
+        assert_eq!(
+            Money::find("£449.00", Currency::Gbp),
+            Some(Money::new(44900, Currency::Gbp))
+        );
+        // The page says what it is, whatever the retailer usually prices in
+        assert_eq!(
+            Money::find("CDN$ 1,299.00", Currency::Usd),
+            Some(Money::new(129900, Currency::Cad))
+        );
+        assert_eq!(
+            Money::find("1,299.99 GBP", Currency::Usd),
+            Some(Money::new(129999, Currency::Gbp))
+        );
+        assert_eq!(
+            Money::find("$89.99.", Currency::Cad),
+            Some(Money::new(8999, Currency::Cad))
+        );
+        assert_eq!(Money::find("Currently unavailable", Currency::Usd), None);
+    }
+
+    #[test]
+    fn displays_with_symbol_and_grouping() {
+        assert_eq!(Money::new(129999, Currency::Usd).to_string(), "$1,299.99");
+        assert_eq!(Money::new(5, Currency::Usd).to_string(), "$0.05");
+        assert_eq!(
+            Money::new(100000000, Currency::Cad).to_string(),
+            "CA$1,000,000.00"
+        );
+        assert_eq!(Money::new(-44900, Currency::Gbp).to_string(), "-£449.00");
+        assert_eq!(Money::new(129999, Currency::Usd).amount(), "1299.99");
+    }
+
+    #[test]
+    fn compares_within_a_currency_only() {
+        let usd = Money::new(10000, Currency::Usd);
+        let cad = Money::new(5000, Currency::Cad);
+        assert!(usd > Money::new(9999, Currency::Usd));
+        assert_ne!(usd, Money::new(10000, Currency::Cad));
+        assert_eq!(usd.partial_cmp(&cad), None);
+        assert!(!usd.le(&cad) && !usd.gt(&cad));
+    }
+
+    #[test]
+    fn converts_major_units() {
+        assert_eq!(
+            Money::from_major(1299.99, Currency::Usd),
+            Some(Money::new(129999, Currency::Usd))
+        );
+        assert_eq!(
+            Money::from_major(0.1 + 0.2, Currency::Usd).unwrap().minor(),
+            30
+        );
+        assert_eq!(Money::from_major(f64::NAN, Currency::Usd), None);
+        assert_eq!(Money::new(129999, Currency::Usd).to_major(), 1299.99);
+    }
+}
@@ -0,0 +1,301 @@
+use super::{NotificationPreferences, Notifier};
+use crate::storage::{Alert, AlertKind, Product, Subscription};
+use anyhow::Context;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use maud::{Markup, html};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How the connection to the SMTP server is secured
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain connection upgraded with STARTTLS (usually port 587)
+    #[default]
+    StartTls,
+    /// TLS from the start (usually port 465)
+    Tls,
+    /// No encryption at all. Only for local test servers like Mailpit.
+    None,
+}
+
+impl SmtpTls {
+    /// Parse the name used in configuration: starttls, tls or none
+    pub fn parse(name: &str) -> Option<SmtpTls> {
+        match name {
+            "starttls" => Some(SmtpTls::StartTls),
+            "tls" => Some(SmtpTls::Tls),
+            "none" => Some(SmtpTls::None),
+            _ => None,
+        }
+    }
+
+    pub fn default_port(self) -> u16 {
+        match self {
+            SmtpTls::StartTls => 587,
+            SmtpTls::Tls => 465,
+            SmtpTls::None => 25,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sender address, e.g. "Midas <midas@example.com>"
+    pub from: String,
+    /// Base URL of this server, used to link to products from emails
+    pub public_url: String,
+}
+
+/// Sends alerts by email over SMTP
+pub struct EmailNotifier {
+    transport: SmtpTransport,
+    from: Mailbox,
+    public_url: String,
+}
+
+impl EmailNotifier {
+    pub fn new(config: SmtpConfig) -> anyhow::Result<Self> {
+        let builder = match config.tls {
+            SmtpTls::StartTls => SmtpTransport::starttls_relay(&config.host)?,
+            SmtpTls::Tls => SmtpTransport::relay(&config.host)?,
+            SmtpTls::None => SmtpTransport::builder_dangerous(&config.host),
+        };
+        let mut builder = builder
+            .port(config.port)
+            .timeout(Some(Duration::from_secs(30)));
+        if let (Some(username), Some(password)) = (config.username, config.password) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(EmailNotifier {
+            transport: builder.build(),
+            from: config
+                .from
+                .parse()
+                .with_context(|| format!("Invalid sender address {:?}", config.from))?,
+            public_url: config.public_url.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn channel(&self) -> &'static str {
+        "email"
+    }
+
+    fn notify(
+        &self,
+        preferences: &NotificationPreferences,
+        product: &Product,
+        _subscription: &Subscription,
+        alert: &Alert,
+    ) -> anyhow::Result<bool> {
+        let Some(email) = preferences
+            .email
+            .as_deref()
+            .filter(|_| preferences.email_enabled)
+        else {
+            return Ok(false);
+        };
+
+        let link = format!("{}/products/{}", self.public_url, product.id);
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(email.parse().context("Invalid recipient address")?)
+            .subject(format!("{}: {}", alert.kind.label(), product.name))
+            .multipart(MultiPart::alternative_plain_html(
+                text_body(product, alert, &link),
+                html_body(product, alert, &link).into_string(),
+            ))?;
+        self.transport.send(&message)?;
+        Ok(true)
+    }
+}
+
+fn text_body(product: &Product, alert: &Alert, link: &str) -> String {
+    format!(
+        "{}\n\nRetailer: {}\nProduct page: {}\nPrice history: {}\n",
+        alert.message, product.retailer, product.url, link
+    )
+}
+
+// Emails can't rely on stylesheets, so everything is styled inline
+fn html_body(product: &Product, alert: &Alert, link: &str) -> Markup {
+    let accent = match alert.kind {
+        AlertKind::TargetReached | AlertKind::PriceDrop => "#15803d",
+        AlertKind::BackInStock => "#4f46e5",
+    };
+
+    html! {
+        div style="font-family: Arial, sans-serif; max-width: 560px; margin: 0 auto; color: #111827;" {
+            p style=(format!("font-size: 12px; text-transform: uppercase; letter-spacing: 0.05em; color: {accent}; margin: 0;")) {
+                (alert.kind.label())
+            }
+            h1 style="font-size: 20px; margin: 8px 0 16px;" { (product.name) }
+            p style="font-size: 16px; margin: 0 0 16px;" { (alert.message) }
+            @if let Some(price) = alert.price {
+                p style="font-size: 28px; font-weight: bold; margin: 0 0 16px;" {
+                    (price)
+                    @if let Some(previous) = alert.previous_price.filter(|previous| *previous > price) {
+                        span style="font-size: 16px; font-weight: normal; color: #9ca3af; text-decoration: line-through; margin-left: 8px;" {
+                            (previous)
+                        }
+                    }
+                }
+            }
+            p style="margin: 0 0 24px;" {
+                a href=(product.url) style="display: inline-block; padding: 10px 16px; background: #4f46e5; color: #ffffff; text-decoration: none; border-radius: 6px;" {
+                    "Open at " (product.retailer)
+                }
+            }
+            p style="font-size: 13px; color: #6b7280;" {
+                a href=(link) style="color: #4f46e5;" { "View price history" }
+                " · You're getting this because you track this product on Midas."
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::{Currency, Money};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::SystemTime;
+
+    // Just enough of an SMTP server to accept one message and hand back what was sent
+    fn smtp_sink() -> (u16, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut transcript = String::new();
+            let mut in_data = false;
+            writer.write_all(b"220 sink ESMTP\r\n").unwrap();
+
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 {
+                transcript.push_str(&line);
+                let command = line.trim_end().to_ascii_uppercase();
+                let reply: &[u8] = if in_data {
+                    if command == "." {
+                        in_data = false;
+                        b"250 queued\r\n"
+                    } else {
+                        b""
+                    }
+                } else if command.starts_with("EHLO") {
+                    b"250 sink\r\n"
+                } else if command == "DATA" {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                } else if command == "QUIT" {
+                    writer.write_all(b"221 bye\r\n").unwrap();
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                writer.write_all(reply).unwrap();
+                line.clear();
+            }
+            sender.send(transcript).unwrap();
+        });
+
+        (port, receiver)
+    }
+
+    fn product() -> Product {
+        Product {
+            id: 7,
+            url: "https://www.bestbuy.com/site/tv/6578534.p".to_string(),
+            name: "Sony 65\" TV".to_string(),
+            retailer: "Best Buy".to_string(),
+            retailer_id: Some("6578534".to_string()),
+            currency: Currency::Usd,
+            added_by: "alice".to_string(),
+            created_at: SystemTime::now(),
+            poll_interval: None,
+        }
+    }
+
+    fn subscription() -> Subscription {
+        Subscription {
+            product_id: 7,
+            username: "alice".to_string(),
+            target_price: Some(Money::new(110000, Currency::Usd)),
+            created_at: SystemTime::now(),
+        }
+    }
+
+    fn alert() -> Alert {
+        Alert {
+            id: 1,
+            product_id: 7,
+            username: Some("alice".to_string()),
+            kind: AlertKind::TargetReached,
+            triggered_at: SystemTime::now(),
+            price: Some(Money::new(109999, Currency::Usd)),
+            previous_price: Some(Money::new(119999, Currency::Usd)),
+            message: "Sony 65\" TV is $1,099.99, at or below your target of $1,100.00".to_string(),
+        }
+    }
+
+    fn notifier(port: u16) -> EmailNotifier {
+        EmailNotifier::new(SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            tls: SmtpTls::None,
+            username: None,
+            password: None,
+            from: "Midas <midas@example.com>".to_string(),
+            public_url: "http://midas.test/".to_string(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn sends_alert_email() {
+        let (port, transcript) = smtp_sink();
+        let preferences = NotificationPreferences {
+            email: Some("alice@example.com".to_string()),
+            email_enabled: true,
+            ..NotificationPreferences::new("alice")
+        };
+
+        let sent = notifier(port)
+            .notify(&preferences, &product(), &subscription(), &alert())
+            .unwrap();
+        let transcript = transcript.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        assert!(sent);
+        assert!(transcript.contains("RCPT TO:<alice@example.com>"));
+        assert!(transcript.contains("Subject: Target price reached: Sony 65\" TV"));
+        assert!(transcript.contains("http://midas.test/products/7"));
+    }
+
+    #[test]
+    fn skips_users_without_email() {
+        let preferences = NotificationPreferences::new("alice");
+        // Nothing is listening on port 9; sending would fail
+        let sent = notifier(9)
+            .notify(&preferences, &product(), &subscription(), &alert())
+            .unwrap();
+
+        assert!(!sent);
+    }
+}
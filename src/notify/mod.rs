@@ -0,0 +1,265 @@
+mod email;
+mod webhook;
+
+pub use email::{EmailNotifier, SmtpConfig, SmtpTls};
+pub use webhook::{
+    NewWebhook, NewWebhookDelivery, Webhook, WebhookConfig, WebhookDelivery, WebhookFormat,
+    WebhookNotifier,
+};
+
+use crate::storage::{Alert, AlertKind, PreferencesRepository, Product, Subscription};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// How a user wants to hear about alerts on their products
+#[derive(Debug, Clone)]
+pub struct NotificationPreferences {
+    pub username: String,
+    pub email: Option<String>,
+    pub email_enabled: bool,
+    /// The kinds of alert the user wants to be told about
+    pub alert_kinds: Vec<AlertKind>,
+}
+
+impl NotificationPreferences {
+    /// What a user gets before saving any preferences: every kind of alert, but no channel
+    /// set up to deliver them
+    pub fn new(username: &str) -> Self {
+        NotificationPreferences {
+            username: username.to_string(),
+            email: None,
+            email_enabled: false,
+            alert_kinds: AlertKind::ALL.to_vec(),
+        }
+    }
+
+    pub fn wants(&self, kind: AlertKind) -> bool {
+        self.alert_kinds.contains(&kind)
+    }
+
+    pub fn validate(&self) -> Result<(), PreferencesError> {
+        match self.email.as_deref() {
+            Some(email) if email.parse::<lettre::Address>().is_err() => {
+                Err(PreferencesError::InvalidEmail)
+            }
+            None if self.email_enabled => Err(PreferencesError::MissingEmail),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PreferencesError {
+    InvalidEmail,
+    MissingEmail,
+}
+
+impl PreferencesError {
+    /// Short identifier used in redirect query strings
+    pub fn code(self) -> &'static str {
+        match self {
+            PreferencesError::InvalidEmail => "invalid_email",
+            PreferencesError::MissingEmail => "missing_email",
+        }
+    }
+}
+
+/// A way of telling a user about an alert (email, webhooks, ...)
+pub trait Notifier: Send + Sync {
+    /// Short name used in logs, e.g. "email"
+    fn channel(&self) -> &'static str;
+
+    /// Deliver `alert` to the user `preferences` belong to, who is watching `product` through
+    /// `subscription`. Returns `false` without sending anything if the user hasn't set this
+    /// channel up.
+    fn notify(
+        &self,
+        preferences: &NotificationPreferences,
+        product: &Product,
+        subscription: &Subscription,
+        alert: &Alert,
+    ) -> anyhow::Result<bool>;
+}
+
+/// Sends raised alerts to the people tracking the product, over every configured channel.
+///
+/// Delivery blocks, so call `dispatch` from a blocking task.
+pub struct Notifications {
+    preferences: Arc<dyn PreferencesRepository>,
+    notifiers: Vec<Box<dyn Notifier>>,
+}
+
+impl Notifications {
+    pub fn new(
+        preferences: Arc<dyn PreferencesRepository>,
+        notifiers: Vec<Box<dyn Notifier>>,
+    ) -> Self {
+        Notifications {
+            preferences,
+            notifiers,
+        }
+    }
+
+    /// Whether alerts can be delivered over `channel` at all on this server
+    pub fn has_channel(&self, channel: &str) -> bool {
+        self.notifiers.iter().any(|n| n.channel() == channel)
+    }
+
+    /// Tell everyone subscribed to the product about the `alerts` meant for them, as far as
+    /// their preferences allow. A failing channel or subscriber is logged and doesn't stop
+    /// the others.
+    pub fn dispatch(&self, product: &Product, subscriptions: &[Subscription], alerts: &[Alert]) {
+        for subscription in subscriptions {
+            if let Err(e) = self.dispatch_to(product, subscription, alerts) {
+                warn!(
+                    "Failed to send notifications - username: {}, product id: {}, error: {:#}",
+                    subscription.username, product.id, e
+                );
+            }
+        }
+    }
+
+    fn dispatch_to(
+        &self,
+        product: &Product,
+        subscription: &Subscription,
+        alerts: &[Alert],
+    ) -> anyhow::Result<()> {
+        let preferences = self
+            .preferences
+            .get(&subscription.username)?
+            .unwrap_or_else(|| NotificationPreferences::new(&subscription.username));
+
+        let wanted = alerts
+            .iter()
+            .filter(|alert| alert.is_for(&subscription.username) && preferences.wants(alert.kind));
+        for alert in wanted {
+            for notifier in &self.notifiers {
+                match notifier.notify(&preferences, product, subscription, alert) {
+                    Ok(true) => info!(
+                        "Notification sent - channel: {}, username: {}, product id: {}, kind: {}",
+                        notifier.channel(),
+                        preferences.username,
+                        product.id,
+                        alert.kind.as_str()
+                    ),
+                    Ok(false) => {}
+                    Err(e) => warn!(
+                        "Notification failed - channel: {}, username: {}, product id: {}, error: {:#}",
+                        notifier.channel(),
+                        preferences.username,
+                        product.id,
+                        e
+                    ),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Currency;
+    use crate::storage::InMemoryPreferencesRepository;
+    use std::sync::Mutex;
+    use std::time::SystemTime;
+
+    /// Preferences that can't be read for one user
+    struct BrokenFor(&'static str, InMemoryPreferencesRepository);
+
+    impl PreferencesRepository for BrokenFor {
+        fn get(&self, username: &str) -> anyhow::Result<Option<NotificationPreferences>> {
+            if username == self.0 {
+                anyhow::bail!("database is locked");
+            }
+            self.1.get(username)
+        }
+
+        fn save(&self, preferences: &NotificationPreferences) -> anyhow::Result<()> {
+            self.1.save(preferences)
+        }
+    }
+
+    /// Records who it notified, failing for one user
+    struct Recorder {
+        failing: Option<&'static str>,
+        sent: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Notifier for Recorder {
+        fn channel(&self) -> &'static str {
+            "recorder"
+        }
+
+        fn notify(
+            &self,
+            preferences: &NotificationPreferences,
+            _product: &Product,
+            _subscription: &Subscription,
+            _alert: &Alert,
+        ) -> anyhow::Result<bool> {
+            if self.failing == Some(preferences.username.as_str()) {
+                anyhow::bail!("connection refused");
+            }
+            self.sent.lock().unwrap().push(preferences.username.clone());
+            Ok(true)
+        }
+    }
+
+    fn subscription(username: &str) -> Subscription {
+        Subscription {
+            product_id: 3,
+            username: username.to_string(),
+            target_price: None,
+            created_at: SystemTime::now(),
+        }
+    }
+
+    #[test]
+    fn one_failing_subscriber_doesnt_stop_the_rest() {
+        let product = Product {
+            id: 3,
+            url: "https://www.amazon.com/dp/B0BHS1ZPMS".to_string(),
+            name: "RTX 4090".to_string(),
+            retailer: "Amazon".to_string(),
+            retailer_id: Some("B0BHS1ZPMS".to_string()),
+            currency: Currency::Usd,
+            added_by: "alice".to_string(),
+            created_at: SystemTime::now(),
+            poll_interval: None,
+        };
+        let alert = Alert {
+            id: 1,
+            product_id: 3,
+            username: None,
+            kind: AlertKind::BackInStock,
+            triggered_at: SystemTime::now(),
+            price: None,
+            previous_price: None,
+            message: "RTX 4090 is back in stock".to_string(),
+        };
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let notifiers: Vec<Box<dyn Notifier>> = vec![
+            Box::new(Recorder {
+                failing: Some("carol"),
+                sent: sent.clone(),
+            }),
+            Box::new(Recorder {
+                failing: None,
+                sent: sent.clone(),
+            }),
+        ];
+        let preferences = BrokenFor("bob", InMemoryPreferencesRepository::default());
+        let notifications = Notifications::new(Arc::new(preferences), notifiers);
+
+        let subscriptions = ["alice", "bob", "carol", "dave"].map(subscription);
+        notifications.dispatch(&product, &subscriptions, &[alert]);
+
+        assert_eq!(
+            *sent.lock().unwrap(),
+            ["alice", "alice", "carol", "dave", "dave"]
+        );
+    }
+}
@@ -0,0 +1,762 @@
+use super::{NotificationPreferences, Notifier};
+use crate::money::Money;
+use crate::storage::{Alert, AlertKind, Product, Subscription, WebhookRepository};
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+use reqwest::StatusCode;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// The shape of the JSON body posted to a webhook
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WebhookFormat {
+    /// Our own payload, for custom integrations
+    Json,
+    /// A Discord message with an embed
+    Discord,
+    /// A Slack message using Block Kit
+    Slack,
+}
+
+impl WebhookFormat {
+    pub const ALL: [WebhookFormat; 3] = [
+        WebhookFormat::Json,
+        WebhookFormat::Discord,
+        WebhookFormat::Slack,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WebhookFormat::Json => "json",
+            WebhookFormat::Discord => "discord",
+            WebhookFormat::Slack => "slack",
+        }
+    }
+
+    pub fn parse(format: &str) -> Option<WebhookFormat> {
+        WebhookFormat::ALL
+            .into_iter()
+            .find(|f| f.as_str() == format)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WebhookFormat::Json => "Generic JSON",
+            WebhookFormat::Discord => "Discord",
+            WebhookFormat::Slack => "Slack",
+        }
+    }
+}
+
+/// An outgoing webhook a user has registered
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    pub id: i64,
+    pub username: String,
+    pub url: String,
+    pub format: WebhookFormat,
+    /// Key for the HMAC signature sent with every delivery
+    pub secret: String,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewWebhook {
+    pub username: String,
+    pub url: String,
+    pub format: WebhookFormat,
+    pub secret: String,
+}
+
+impl NewWebhook {
+    /// A webhook for `url` with a freshly generated signing secret. URLs for this machine or
+    /// a private network are refused, so a webhook can't be used to reach internal services.
+    pub fn new(username: &str, url: &str, format: WebhookFormat) -> Result<Self, WebhookError> {
+        let url = url.trim();
+        let parsed = reqwest::Url::parse(url)
+            .ok()
+            .filter(|parsed| matches!(parsed.scheme(), "http" | "https") && parsed.has_host())
+            .ok_or(WebhookError::InvalidUrl)?;
+        if private_host(&parsed) {
+            return Err(WebhookError::PrivateAddress);
+        }
+
+        let secret = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        Ok(NewWebhook {
+            username: username.to_string(),
+            url: url.to_string(),
+            format,
+            secret,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WebhookError {
+    InvalidUrl,
+    /// The URL points at this machine or a private network
+    PrivateAddress,
+}
+
+impl WebhookError {
+    /// Short identifier used in redirect query strings
+    pub fn code(self) -> &'static str {
+        match self {
+            WebhookError::InvalidUrl => "invalid_url",
+            WebhookError::PrivateAddress => "private_address",
+        }
+    }
+}
+
+/// The outcome of delivering one alert to one webhook, after any retries
+#[derive(Debug, Clone)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub webhook_url: String,
+    pub product_id: i64,
+    pub alert_kind: AlertKind,
+    pub message: String,
+    pub attempted_at: SystemTime,
+    pub attempts: u32,
+    /// HTTP status of the last attempt, if the server answered at all
+    pub status_code: Option<u16>,
+    /// Set when the delivery ultimately failed
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewWebhookDelivery {
+    pub webhook_id: i64,
+    pub product_id: i64,
+    pub alert_kind: AlertKind,
+    pub message: String,
+    pub attempted_at: SystemTime,
+    pub attempts: u32,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// Attempts per delivery, including the first one
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubled for every retry after that
+    pub initial_backoff: Duration,
+    pub request_timeout: Duration,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            max_attempts: 4,
+            initial_backoff: Duration::from_secs(2),
+            request_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Posts alerts to the webhooks users have registered.
+///
+/// Every request carries `X-Midas-Timestamp` (unix seconds) and `X-Midas-Signature`
+/// (`sha256=` followed by the hex HMAC-SHA256 of `"{timestamp}.{body}"`, keyed with the
+/// webhook's secret), so receivers can check it really came from us.
+pub struct WebhookNotifier {
+    webhooks: Arc<dyn WebhookRepository>,
+    // Created on first delivery: a blocking client can't be built on the async runtime, but
+    // deliveries always run on blocking threads
+    client: OnceLock<reqwest::blocking::Client>,
+    config: WebhookConfig,
+    // Only the tests turn this on, to post to a stand-in server on 127.0.0.1
+    allow_private: bool,
+}
+
+impl WebhookNotifier {
+    pub fn new(webhooks: Arc<dyn WebhookRepository>, config: WebhookConfig) -> Self {
+        WebhookNotifier {
+            webhooks,
+            client: OnceLock::new(),
+            config,
+            allow_private: false,
+        }
+    }
+
+    fn client(&self) -> anyhow::Result<&reqwest::blocking::Client> {
+        if let Some(client) = self.client.get() {
+            return Ok(client);
+        }
+        // A redirect could lead anywhere, so they count as a failed delivery
+        let mut builder = reqwest::blocking::Client::builder()
+            .user_agent(concat!("Midas/", env!("CARGO_PKG_VERSION")))
+            .timeout(self.config.request_timeout)
+            .redirect(reqwest::redirect::Policy::none());
+        if !self.allow_private {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+        let client = builder.build()?;
+        Ok(self.client.get_or_init(|| client))
+    }
+
+    // Post `body`, retrying with exponential backoff on network errors, 429s and 5xx
+    fn deliver(
+        &self,
+        webhook: &Webhook,
+        event: AlertKind,
+        body: &[u8],
+    ) -> (u32, Option<u16>, Option<String>) {
+        // Webhooks saved before private addresses were refused, or given one directly as an
+        // IP; host names are checked as they're resolved
+        let private = reqwest::Url::parse(&webhook.url).is_ok_and(|url| private_host(&url));
+        if private && !self.allow_private {
+            return (
+                0,
+                None,
+                Some("Refusing to post to a private address".to_string()),
+            );
+        }
+
+        let mut backoff = self.config.initial_backoff;
+        let mut last = (None, None);
+
+        for attempt in 1..=self.config.max_attempts.max(1) {
+            let retryable = match self.send(webhook, event, body) {
+                Ok(status) if status.is_success() => return (attempt, Some(status.as_u16()), None),
+                Ok(status) => {
+                    last = (Some(status.as_u16()), Some(format!("HTTP {}", status)));
+                    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+                }
+                Err(e) => {
+                    last = (None, Some(format!("{:#}", e)));
+                    true
+                }
+            };
+
+            if !retryable || attempt == self.config.max_attempts {
+                return (attempt, last.0, last.1);
+            }
+            thread::sleep(backoff);
+            backoff *= 2;
+        }
+        (self.config.max_attempts, last.0, last.1)
+    }
+
+    fn send(&self, webhook: &Webhook, event: AlertKind, body: &[u8]) -> anyhow::Result<StatusCode> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+            .to_string();
+        let response = self
+            .client()?
+            .post(&webhook.url)
+            .header("Content-Type", "application/json")
+            .header("X-Midas-Event", event.as_str())
+            .header("X-Midas-Timestamp", &timestamp)
+            .header(
+                "X-Midas-Signature",
+                signature(&webhook.secret, &timestamp, body),
+            )
+            .body(body.to_vec())
+            .send()?;
+        Ok(response.status())
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn channel(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn notify(
+        &self,
+        preferences: &NotificationPreferences,
+        product: &Product,
+        subscription: &Subscription,
+        alert: &Alert,
+    ) -> anyhow::Result<bool> {
+        let webhooks = self.webhooks.list_for_user(&preferences.username)?;
+        let mut delivered = false;
+
+        for webhook in &webhooks {
+            let body = serde_json::to_vec(&payload(webhook.format, product, subscription, alert))?;
+            let attempted_at = SystemTime::now();
+            let (attempts, status_code, error) = self.deliver(webhook, alert.kind, &body);
+
+            if let Some(error) = &error {
+                warn!(
+                    "Webhook delivery failed - webhook id: {}, attempts: {}, error: {}",
+                    webhook.id, attempts, error
+                );
+            }
+            delivered |= error.is_none();
+            self.webhooks.record_delivery(NewWebhookDelivery {
+                webhook_id: webhook.id,
+                product_id: product.id,
+                alert_kind: alert.kind,
+                message: alert.message.clone(),
+                attempted_at,
+                attempts,
+                status_code,
+                error,
+            })?;
+        }
+        Ok(delivered)
+    }
+}
+
+// Resolves webhook hosts, refusing any that lead to this machine or a private network.
+// Connections only go to the addresses checked here, so the answer can't change in between.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let lookup = host.clone();
+            let addrs: Vec<SocketAddr> =
+                tokio::task::spawn_blocking(move || (lookup.as_str(), 0).to_socket_addrs())
+                    .await??
+                    .collect();
+            if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+                return Err(format!("{} resolves to private address {}", host, addr.ip()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+// Whether the URL's host is known to be private without looking it up: localhost, or an IP
+// address that isn't public
+fn private_host(url: &reqwest::Url) -> bool {
+    match url.host() {
+        Some(url::Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+        Some(url::Host::Ipv4(ip)) => !is_public(ip.into()),
+        Some(url::Host::Ipv6(ip)) => !is_public(ip.into()),
+        None => false,
+    }
+}
+
+// Anything but loopback, private, link-local (which covers cloud metadata services at
+// 169.254.169.254), carrier-grade NAT and unspecified addresses
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || first == 0
+                || (first == 100 && second & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(ip.into()),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+fn signature(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn price(price: Option<Money>) -> String {
+    price.map_or("—".to_string(), |price| price.to_string())
+}
+
+fn payload(
+    format: WebhookFormat,
+    product: &Product,
+    subscription: &Subscription,
+    alert: &Alert,
+) -> Value {
+    let triggered_at = chrono::DateTime::<chrono::Utc>::from(alert.triggered_at).to_rfc3339();
+
+    match format {
+        WebhookFormat::Json => json!({
+            "event": alert.kind.as_str(),
+            "message": alert.message,
+            // Amounts are in major units, in `currency`
+            "price": alert.price.map(Money::to_major),
+            "previous_price": alert.previous_price.map(Money::to_major),
+            "currency": alert.price.map_or(product.currency, Money::currency).code(),
+            "triggered_at": triggered_at,
+            "product": {
+                "id": product.id,
+                "name": product.name,
+                "url": product.url,
+                "retailer": product.retailer,
+                "currency": product.currency.code(),
+                "target_price": subscription.target_price.map(Money::to_major),
+            },
+        }),
+        WebhookFormat::Discord => json!({
+            "username": "Midas",
+            "embeds": [{
+                "title": product.name,
+                "url": product.url,
+                "description": alert.message,
+                "color": match alert.kind {
+                    AlertKind::TargetReached | AlertKind::PriceDrop => 0x16a34a,
+                    AlertKind::BackInStock => 0x4f46e5,
+                },
+                "fields": [
+                    { "name": "Alert", "value": alert.kind.label(), "inline": true },
+                    { "name": "Price", "value": price(alert.price), "inline": true },
+                    { "name": "Retailer", "value": product.retailer, "inline": true },
+                ],
+                "timestamp": triggered_at,
+            }],
+        }),
+        WebhookFormat::Slack => json!({
+            // Shown in notifications and by clients that can't render blocks
+            "text": alert.message,
+            "blocks": [
+                {
+                    "type": "header",
+                    "text": { "type": "plain_text", "text": alert.kind.label() },
+                },
+                {
+                    "type": "section",
+                    "text": {
+                        "type": "mrkdwn",
+                        "text": format!("*<{}|{}>*\n{}", product.url, product.name, alert.message),
+                    },
+                },
+                {
+                    "type": "context",
+                    "elements": [{
+                        "type": "mrkdwn",
+                        "text": format!("{} · {}", product.retailer, price(alert.price)),
+                    }],
+                },
+            ],
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Currency;
+    use crate::storage::InMemoryWebhookRepository;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    struct Request {
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl Request {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+        }
+    }
+
+    // A stand-in HTTP server answering successive requests with `statuses`, passing on
+    // every request it receives
+    fn http_stand_in(statuses: &[u16]) -> (String, mpsc::Receiver<Request>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        let statuses = statuses.to_vec();
+
+        thread::spawn(move || {
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut headers = Vec::new();
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                    let Some((name, value)) = line.trim_end().split_once(": ") else {
+                        break;
+                    };
+                    headers.push((name.to_string(), value.to_string()));
+                }
+                let length = headers
+                    .iter()
+                    .find(|(n, _)| n.eq_ignore_ascii_case("content-length"))
+                    .map_or(0, |(_, v)| v.parse().unwrap());
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                let mut stream = stream;
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )
+                .unwrap();
+                sender.send(Request { headers, body }).unwrap();
+            }
+        });
+
+        (url, receiver)
+    }
+
+    fn product() -> Product {
+        Product {
+            id: 3,
+            url: "https://www.amazon.com/dp/B0BHS1ZPMS".to_string(),
+            name: "RTX 4090".to_string(),
+            retailer: "Amazon".to_string(),
+            retailer_id: Some("B0BHS1ZPMS".to_string()),
+            currency: Currency::Usd,
+            added_by: "alice".to_string(),
+            created_at: SystemTime::now(),
+            poll_interval: None,
+        }
+    }
+
+    fn subscription() -> Subscription {
+        Subscription {
+            product_id: 3,
+            username: "alice".to_string(),
+            target_price: Some(Money::new(170000, Currency::Usd)),
+            created_at: SystemTime::now(),
+        }
+    }
+
+    fn alert() -> Alert {
+        Alert {
+            id: 1,
+            product_id: 3,
+            username: None,
+            kind: AlertKind::BackInStock,
+            triggered_at: SystemTime::now(),
+            price: Some(Money::new(159999, Currency::Usd)),
+            previous_price: None,
+            message: "RTX 4090 is back in stock at $1,599.99".to_string(),
+        }
+    }
+
+    fn notifier(
+        url: &str,
+        format: WebhookFormat,
+    ) -> (WebhookNotifier, Arc<InMemoryWebhookRepository>, Webhook) {
+        let repository = Arc::new(InMemoryWebhookRepository::default());
+        // Built directly, as `NewWebhook::new` refuses the stand-in's local address
+        let webhook = repository
+            .add(NewWebhook {
+                username: "alice".to_string(),
+                url: url.to_string(),
+                format,
+                secret: "stand-in secret".to_string(),
+            })
+            .unwrap();
+        let config = WebhookConfig {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(10),
+            request_timeout: Duration::from_secs(5),
+        };
+        let mut notifier = WebhookNotifier::new(repository.clone(), config);
+        notifier.allow_private = true;
+        (notifier, repository, webhook)
+    }
+
+    #[test]
+    fn signs_generic_json_payload() {
+        let (url, requests) = http_stand_in(&[204]);
+        let (notifier, repository, webhook) = notifier(&url, WebhookFormat::Json);
+
+        let delivered = notifier
+            .notify(
+                &NotificationPreferences::new("alice"),
+                &product(),
+                &subscription(),
+                &alert(),
+            )
+            .unwrap();
+        let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        assert!(delivered);
+        let timestamp = request.header("X-Midas-Timestamp").unwrap();
+        assert_eq!(
+            request.header("X-Midas-Signature").unwrap(),
+            signature(&webhook.secret, timestamp, &request.body)
+        );
+        assert_eq!(request.header("X-Midas-Event"), Some("back_in_stock"));
+        let body: Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["event"], "back_in_stock");
+        assert_eq!(body["product"]["id"], 3);
+        assert_eq!(body["product"]["target_price"], 1700.0);
+        assert_eq!(body["price"], 1599.99);
+        assert_eq!(body["currency"], "USD");
+
+        let deliveries = repository.recent_deliveries("alice", 10).unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].status_code, Some(204));
+        assert_eq!(deliveries[0].error, None);
+    }
+
+    #[test]
+    fn retries_server_errors() {
+        let (url, requests) = http_stand_in(&[503, 500, 200]);
+        let (notifier, repository, _) = notifier(&url, WebhookFormat::Discord);
+
+        let delivered = notifier
+            .notify(
+                &NotificationPreferences::new("alice"),
+                &product(),
+                &subscription(),
+                &alert(),
+            )
+            .unwrap();
+
+        assert!(delivered);
+        assert_eq!(requests.iter().take(3).count(), 3);
+        let deliveries = repository.recent_deliveries("alice", 10).unwrap();
+        assert_eq!(deliveries[0].attempts, 3);
+        assert_eq!(deliveries[0].status_code, Some(200));
+    }
+
+    #[test]
+    fn gives_up_on_client_errors() {
+        let (url, requests) = http_stand_in(&[404]);
+        let (notifier, repository, _) = notifier(&url, WebhookFormat::Slack);
+
+        let delivered = notifier
+            .notify(
+                &NotificationPreferences::new("alice"),
+                &product(),
+                &subscription(),
+                &alert(),
+            )
+            .unwrap();
+        let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        assert!(!delivered);
+        let body: Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["blocks"][0]["text"]["text"], "Back in stock");
+        let deliveries = repository.recent_deliveries("alice", 10).unwrap();
+        assert_eq!(deliveries[0].attempts, 1);
+        assert_eq!(deliveries[0].error.as_deref(), Some("HTTP 404 Not Found"));
+    }
+
+    #[test]
+    fn builds_discord_embed() {
+        let body = payload(
+            WebhookFormat::Discord,
+            &product(),
+            &subscription(),
+            &alert(),
+        );
+
+        assert_eq!(body["embeds"][0]["title"], "RTX 4090");
+        assert_eq!(
+            body["embeds"][0]["url"],
+            "https://www.amazon.com/dp/B0BHS1ZPMS"
+        );
+        assert_eq!(body["embeds"][0]["fields"][1]["value"], "$1,599.99");
+    }
+
+    #[test]
+    fn rejects_non_http_urls() {
+        for url in ["ftp://example.com/hook", "not a url", "https://"] {
+            assert_eq!(
+                NewWebhook::new("alice", url, WebhookFormat::Json).err(),
+                Some(WebhookError::InvalidUrl),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_private_hosts() {
+        for url in [
+            "http://localhost:8080/hook",
+            "http://api.localhost/hook",
+            "http://127.0.0.1/hook",
+            "http://2130706433/hook",
+            "http://10.0.0.5/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://0.0.0.0/hook",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+        ] {
+            assert_eq!(
+                NewWebhook::new("alice", url, WebhookFormat::Json).err(),
+                Some(WebhookError::PrivateAddress),
+                "{url}"
+            );
+        }
+        for url in [
+            "https://discord.com/api/webhooks/1/abc",
+            "http://93.184.216.34/hook",
+        ] {
+            assert!(
+                NewWebhook::new("alice", url, WebhookFormat::Json).is_ok(),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn refuses_private_addresses_when_sending() {
+        let (url, requests) = http_stand_in(&[204]);
+        let (mut notifier, repository, _) = notifier(&url, WebhookFormat::Json);
+        notifier.allow_private = false;
+
+        let delivered = notifier
+            .notify(
+                &NotificationPreferences::new("alice"),
+                &product(),
+                &subscription(),
+                &alert(),
+            )
+            .unwrap();
+
+        assert!(!delivered);
+        assert!(requests.try_recv().is_err());
+        let deliveries = repository.recent_deliveries("alice", 10).unwrap();
+        assert_eq!(deliveries[0].attempts, 0);
+        assert_eq!(
+            deliveries[0].error.as_deref(),
+            Some("Refusing to post to a private address")
+        );
+    }
+
+    #[tokio::test]
+    async fn resolver_refuses_private_addresses() {
+        match PublicResolver.resolve("localhost".parse().unwrap()).await {
+            Ok(_) => panic!("localhost resolved"),
+            Err(e) => assert!(
+                e.to_string()
+                    .starts_with("localhost resolves to private address")
+            ),
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::money::{Currency, Money};
+use crate::storage::StockState;
+use scraper::{Html, Selector};
+use serde_json::Value;
+
+/// What we could read off a product page
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageInfo {
+    /// The retailer's own id for the product (Best Buy SKU, Amazon ASIN, ...)
+    pub retailer_id: Option<String>,
+    pub title: Option<String>,
+    /// What the product costs right now
+    pub price: Option<Money>,
+    /// The regular price, when the page shows the current price as a discount from it
+    pub was_price: Option<Money>,
+    pub stock: StockState,
+    /// What the page says beyond in or out of stock, e.g. "Coming soon"
+    pub availability: Option<String>,
+    pub ships_from: Option<String>,
+    pub sold_by: Option<String>,
+    /// Set when the listing is sold by a marketplace seller rather than the retailer itself
+    pub third_party_seller: bool,
+}
+
+/// Read price and availability from the schema.org `Product` markup most retailers embed
+/// in `<script type="application/ld+json">` blocks. Returns `None` if there isn't any.
+///
+/// Offers that don't say what currency they're in are taken to be in `currency`.
+pub fn parse_json_ld(html: &str, currency: Currency) -> Option<PageInfo> {
+    json_ld_blocks(html)
+        .filter_map(|block| serde_json::from_str::<Value>(block).ok())
+        .find_map(|value| find_product(&value).map(|product| product_info(product, currency)))
+}
+
+// The raw contents of every JSON-LD script tag in the page
+fn json_ld_blocks(html: &str) -> impl Iterator<Item = &str> {
+    let lower = html.to_ascii_lowercase();
+    let mut blocks = Vec::new();
+    let mut pos = 0;
+
+    while let Some(start) = lower[pos..].find("<script") {
+        let tag_start = pos + start;
+        let Some(tag_len) = lower[tag_start..].find('>') else {
+            break;
+        };
+        let content_start = tag_start + tag_len + 1;
+        let Some(content_len) = lower[content_start..].find("</script") else {
+            break;
+        };
+
+        if lower[tag_start..content_start].contains("application/ld+json") {
+            blocks.push(html[content_start..content_start + content_len].trim());
+        }
+        pos = content_start + content_len;
+    }
+    blocks.into_iter()
+}
+
+// Depth-first search for the first object typed as a Product, looking through arrays and
+// `@graph` wrappers
+fn find_product(value: &Value) -> Option<&Value> {
+    match value {
+        Value::Array(items) => items.iter().find_map(find_product),
+        Value::Object(map) => {
+            if has_type(value, "Product") {
+                return Some(value);
+            }
+            map.get("@graph").and_then(find_product)
+        }
+        _ => None,
+    }
+}
+
+fn has_type(value: &Value, wanted: &str) -> bool {
+    match value.get("@type") {
+        Some(Value::String(t)) => t == wanted,
+        Some(Value::Array(types)) => types.iter().any(|t| t.as_str() == Some(wanted)),
+        _ => false,
+    }
+}
+
+fn product_info(product: &Value, currency: Currency) -> PageInfo {
+    // `offers` may be a single Offer, a list of them, or an AggregateOffer
+    let offers: Vec<&Value> = match product.get("offers") {
+        Some(Value::Array(offers)) => offers.iter().collect(),
+        Some(offer @ Value::Object(_)) => vec![offer],
+        _ => Vec::new(),
+    };
+
+    // The cheapest offer, among those in the first currency seen
+    let price = offers
+        .iter()
+        .filter_map(|offer| {
+            let spec = offer.get("priceSpecification");
+            let currency = match offer
+                .get("priceCurrency")
+                .or_else(|| spec.and_then(|spec| spec.get("priceCurrency")))
+                .and_then(Value::as_str)
+            {
+                // Prices in a currency we don't know are no use
+                Some(code) => Currency::parse(code)?,
+                None => currency,
+            };
+            money(offer.get("price"), currency)
+                .or_else(|| money(offer.get("lowPrice"), currency))
+                .or_else(|| spec.and_then(|spec| money(spec.get("price"), currency)))
+        })
+        .reduce(|cheapest, price| if price < cheapest { price } else { cheapest });
+
+    let states: Vec<StockState> = offers
+        .iter()
+        .filter_map(|offer| offer.get("availability").and_then(Value::as_str))
+        .map(availability)
+        .collect();
+    let stock = if states.contains(&StockState::InStock) {
+        StockState::InStock
+    } else if states.contains(&StockState::OutOfStock) {
+        StockState::OutOfStock
+    } else {
+        StockState::Unknown
+    };
+
+    let sold_by = offers
+        .iter()
+        .find_map(|offer| text(offer.get("seller").and_then(|seller| seller.get("name"))));
+
+    PageInfo {
+        retailer_id: text(product.get("sku")).or_else(|| text(product.get("productID"))),
+        title: text(product.get("name")),
+        price,
+        was_price: None,
+        stock,
+        availability: None,
+        ships_from: None,
+        sold_by,
+        third_party_seller: false,
+    }
+}
+
+fn text(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Parse a displayed price like "$1,299.99" or "Was £2,199.99". Prices without a currency
+/// symbol are taken to be in `currency`.
+pub fn parse_price(text: &str, currency: Currency) -> Option<Money> {
+    Money::find(text, currency)
+}
+
+/// The whitespace-normalised text of every element matching `selector`, skipping empty ones
+pub fn select_texts<'a>(document: &'a Html, selector: &str) -> impl Iterator<Item = String> + 'a {
+    let selector = Selector::parse(selector).expect("selectors are hard-coded and valid");
+    document
+        .select(&selector)
+        .map(|element| {
+            element
+                .text()
+                .flat_map(str::split_whitespace)
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// The text of the first non-empty element matching `selector`
+pub fn select_text(document: &Html, selector: &str) -> Option<String> {
+    select_texts(document, selector).next()
+}
+
+/// An attribute of the first element matching `selector` that has it
+pub fn select_attr(document: &Html, selector: &str, attr: &str) -> Option<String> {
+    let selector = Selector::parse(selector).expect("selectors are hard-coded and valid");
+    document
+        .select(&selector)
+        .filter_map(|element| element.value().attr(attr))
+        .map(str::trim)
+        .find(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// Whether anything in the page matches `selector`
+pub fn exists(document: &Html, selector: &str) -> bool {
+    let selector = Selector::parse(selector).expect("selectors are hard-coded and valid");
+    document.select(&selector).next().is_some()
+}
+
+// Prices show up both as JSON numbers and as strings like "1,299.99"
+fn money(value: Option<&Value>, currency: Currency) -> Option<Money> {
+    match value? {
+        Value::Number(n) => n.as_f64().and_then(|n| Money::from_major(n, currency)),
+        Value::String(s) => Money::parse(s, currency).ok(),
+        _ => None,
+    }
+}
+
+// Map a schema.org ItemAvailability (full URL or bare name) to our stock state
+fn availability(value: &str) -> StockState {
+    let name = value.rsplit('/').next().unwrap_or(value);
+    match name {
+        "InStock" | "InStoreOnly" | "OnlineOnly" | "LimitedAvailability" | "PreOrder" => {
+            StockState::InStock
+        }
+        "OutOfStock" | "SoldOut" | "Discontinued" | "PreSale" | "BackOrder" => {
+            StockState::OutOfStock
+        }
+        _ => StockState::Unknown,
+    }
+}
@@ -0,0 +1,911 @@
+use crate::money::{Currency, Money, MoneyError};
+use crate::retailers::{Retailer, RetailerRegistry};
+use crate::storage::{NewProduct, NewSubscription, Product, ProductRepository, Subscription};
+use crate::urls::{self, ShortLinkResolver};
+use crate::users::{User, UserRole};
+use std::fmt;
+use std::time::Duration;
+use tracing::{info, warn};
+use url::Url;
+
+/// Never check a product page more often than this
+pub const MIN_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Nor less often than this, which also keeps due times well within what the clock can add up
+pub const MAX_POLL_INTERVAL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+const MAX_NAME_LEN: usize = 200;
+
+/// Anything above this is surely a typo. In minor units, so 1,000,000 in any currency.
+const MAX_TARGET_PRICE: i64 = 100_000_000;
+
+/// Product details as submitted through the web forms, the API, the command line or an import
+#[derive(Debug, Clone)]
+pub struct ProductInput {
+    pub url: String,
+    pub name: String,
+    pub retailer: String,
+    /// The retailer's id for the product, read off the URL by `validate`
+    pub retailer_id: Option<String>,
+    /// What the retailer prices in, set from the retailer by `validate`
+    pub currency: Currency,
+    pub target_price: Option<Money>,
+    pub poll_interval: Option<Duration>,
+}
+
+/// Why submitted product details were refused. `code()` is what ends up in the `?error=`
+/// query param and in API error bodies.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProductError {
+    MissingName,
+    NameTooLong,
+    InvalidRetailer,
+    MissingUrl,
+    /// Not something that parses as a web address at all
+    MalformedUrl,
+    InvalidUrl,
+    /// A short link that couldn't be followed to a product page
+    UnresolvedLink,
+    InvalidTargetPrice,
+    TargetPriceOutOfRange,
+    /// A target price in a currency the retailer doesn't price in
+    TargetPriceCurrency,
+    PollIntervalTooLong,
+    /// The user is already subscribed to the same product
+    AlreadyTracked,
+    /// Editing a product would make it the same as another tracked product
+    DuplicateProduct,
+    /// Editing a product other people watch would point it at a different page
+    SharedProduct,
+}
+
+impl ProductError {
+    pub const ALL: [ProductError; 14] = [
+        ProductError::MissingName,
+        ProductError::NameTooLong,
+        ProductError::InvalidRetailer,
+        ProductError::MissingUrl,
+        ProductError::MalformedUrl,
+        ProductError::InvalidUrl,
+        ProductError::UnresolvedLink,
+        ProductError::InvalidTargetPrice,
+        ProductError::TargetPriceOutOfRange,
+        ProductError::TargetPriceCurrency,
+        ProductError::PollIntervalTooLong,
+        ProductError::AlreadyTracked,
+        ProductError::DuplicateProduct,
+        ProductError::SharedProduct,
+    ];
+
+    pub fn from_code(code: &str) -> Option<ProductError> {
+        ProductError::ALL.into_iter().find(|e| e.code() == code)
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            ProductError::MissingName => "missing_name",
+            ProductError::NameTooLong => "name_too_long",
+            ProductError::InvalidRetailer => "invalid_retailer",
+            ProductError::MissingUrl => "missing_url",
+            ProductError::MalformedUrl => "malformed_url",
+            ProductError::InvalidUrl => "invalid_url",
+            ProductError::UnresolvedLink => "unresolved_link",
+            ProductError::InvalidTargetPrice => "invalid_target_price",
+            ProductError::TargetPriceOutOfRange => "target_price_out_of_range",
+            ProductError::TargetPriceCurrency => "target_price_currency",
+            ProductError::PollIntervalTooLong => "poll_interval_too_long",
+            ProductError::AlreadyTracked => "already_tracked",
+            ProductError::DuplicateProduct => "duplicate_product",
+            ProductError::SharedProduct => "shared_product",
+        }
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            ProductError::MissingName => "Product name can't be empty",
+            ProductError::NameTooLong => "Product name can be at most 200 characters",
+            ProductError::InvalidRetailer => "Retailer isn't one of the supported retailers",
+            ProductError::MissingUrl => "Product URL can't be empty",
+            ProductError::MalformedUrl => "Enter the full web address of the product page",
+            ProductError::InvalidUrl => "URL doesn't belong to the selected retailer",
+            ProductError::UnresolvedLink => "Short link couldn't be followed to a product page",
+            ProductError::InvalidTargetPrice => "Target price must be an amount like 399.99",
+            ProductError::TargetPriceOutOfRange => {
+                "Target price must be more than 0 and at most 1,000,000"
+            }
+            ProductError::TargetPriceCurrency => {
+                "Target price must be in the currency the retailer charges in"
+            }
+            ProductError::PollIntervalTooLong => "Products must be checked at least every 7 days",
+            ProductError::AlreadyTracked => "You're already tracking this product",
+            ProductError::DuplicateProduct => "Another tracked product already has that URL",
+            ProductError::SharedProduct => {
+                "Others watch this product, so its URL, retailer and currency can't change. Track the new product instead."
+            }
+        }
+    }
+
+    /// The form field the error is about, named as in the forms and the API
+    pub fn field(self) -> &'static str {
+        match self {
+            ProductError::MissingName | ProductError::NameTooLong => "name",
+            ProductError::InvalidRetailer => "retailer",
+            ProductError::MissingUrl
+            | ProductError::MalformedUrl
+            | ProductError::InvalidUrl
+            | ProductError::UnresolvedLink
+            | ProductError::AlreadyTracked
+            | ProductError::DuplicateProduct
+            | ProductError::SharedProduct => "url",
+            ProductError::InvalidTargetPrice
+            | ProductError::TargetPriceOutOfRange
+            | ProductError::TargetPriceCurrency => "target_price",
+            ProductError::PollIntervalTooLong => "poll_interval",
+        }
+    }
+}
+
+/// Everything wrong with submitted product details, at most one error per field, in the
+/// order the fields were checked
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProductErrors(Vec<ProductError>);
+
+impl ProductErrors {
+    /// Record `error`, unless its field already has one
+    pub fn add(&mut self, error: ProductError) {
+        if self.get(error.field()).is_none() {
+            self.0.push(error);
+        }
+    }
+
+    pub fn get(&self, field: &str) -> Option<ProductError> {
+        self.0.iter().copied().find(|e| e.field() == field)
+    }
+
+    pub fn first(&self) -> Option<ProductError> {
+        self.0.first().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ProductError> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+// The codes, for logs
+impl fmt::Display for ProductErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let codes: Vec<_> = self.iter().map(ProductError::code).collect();
+        f.write_str(&codes.join(", "))
+    }
+}
+
+impl From<ProductError> for ProductErrors {
+    fn from(error: ProductError) -> Self {
+        ProductErrors(vec![error])
+    }
+}
+
+impl ProductInput {
+    /// Check the details against the rules shared by every way of adding or editing a
+    /// product, returning them ready to store or everything that's wrong with them. Short
+    /// links are expanded through `resolver`.
+    pub async fn validate(
+        self,
+        retailers: &RetailerRegistry,
+        resolver: &dyn ShortLinkResolver,
+    ) -> Result<ProductInput, ProductErrors> {
+        let mut errors = ProductErrors::default();
+
+        let name = self.name.trim();
+        if name.is_empty() {
+            errors.add(ProductError::MissingName);
+        } else if name.chars().count() > MAX_NAME_LEN {
+            errors.add(ProductError::NameTooLong);
+        }
+
+        // Validate that the URL is from a supported retailer
+        let retailer = retailers.get(&self.retailer);
+        if retailer.is_none() {
+            errors.add(ProductError::InvalidRetailer);
+        }
+
+        let url = match product_url(&self.url, retailer, resolver).await {
+            Ok(url) => url,
+            Err(e) => {
+                errors.add(e);
+                None
+            }
+        };
+
+        if let Some(Err(e)) = self.target_price.map(check_target_price) {
+            errors.add(e);
+        }
+        let wrong_currency = retailer
+            .zip(self.target_price)
+            .is_some_and(|(retailer, target)| target.currency() != retailer.currency());
+        if wrong_currency {
+            errors.add(ProductError::TargetPriceCurrency);
+        }
+        if self.poll_interval.is_some_and(|d| d > MAX_POLL_INTERVAL) {
+            errors.add(ProductError::PollIntervalTooLong);
+        }
+
+        match (retailer, url) {
+            (Some(retailer), Some(url)) if errors.is_empty() => Ok(ProductInput {
+                // Store the canonical form of the URL so the same product always looks the same
+                url: retailer.canonicalize_url(&url).into(),
+                name: name.to_string(),
+                retailer: self.retailer,
+                retailer_id: retailer.product_id(&url),
+                currency: retailer.currency(),
+                target_price: self.target_price,
+                poll_interval: self.poll_interval.map(|d| d.max(MIN_POLL_INTERVAL)),
+            }),
+            _ => Err(errors),
+        }
+    }
+}
+
+// The product page `input` points at, with short links expanded. Without a retailer to check
+// it against there's nothing to return, though an empty or unparseable URL is still an error.
+async fn product_url(
+    input: &str,
+    retailer: Option<&dyn Retailer>,
+    resolver: &dyn ShortLinkResolver,
+) -> Result<Option<Url>, ProductError> {
+    if input.trim().is_empty() {
+        return Err(ProductError::MissingUrl);
+    }
+    let mut url = urls::parse_user_url(input).ok_or(ProductError::MalformedUrl)?;
+    let Some(retailer) = retailer else {
+        return Ok(None);
+    };
+
+    if retailer.is_short_link(&url) {
+        url = resolver.resolve(&url).await.map_err(|e| {
+            warn!(
+                "Failed to resolve short link - url: {}, error: {:#}",
+                url, e
+            );
+            ProductError::UnresolvedLink
+        })?;
+    }
+
+    // Validate that URLs actually come from the corresponding domains. Expanded short
+    // links are checked too, so they can't lead anywhere else.
+    if !retailer.matches_url(&url) {
+        return Err(ProductError::InvalidUrl);
+    }
+    Ok(Some(url))
+}
+
+/// A check interval given in whole minutes. One too large to even represent comes out as
+/// `Duration::MAX`, which `validate` refuses like any other interval over the maximum.
+pub fn poll_interval_minutes(minutes: u64) -> Duration {
+    minutes
+        .checked_mul(60)
+        .map_or(Duration::MAX, Duration::from_secs)
+}
+
+/// Parse a target price in `currency` as typed into a form, e.g. "$1,299.99". Empty means
+/// no target.
+pub fn parse_target_price(input: &str, currency: Currency) -> Result<Option<Money>, ProductError> {
+    if input.trim().is_empty() {
+        return Ok(None);
+    }
+    let price = Money::parse(input, currency).map_err(|e| match e {
+        MoneyError::Malformed => ProductError::InvalidTargetPrice,
+        MoneyError::WrongCurrency => ProductError::TargetPriceCurrency,
+    })?;
+    check_target_price(price).map(Some)
+}
+
+/// Target prices have to be a positive amount, within reason
+pub fn check_target_price(price: Money) -> Result<Money, ProductError> {
+    if price.minor() <= 0 || price.minor() > MAX_TARGET_PRICE {
+        return Err(ProductError::TargetPriceOutOfRange);
+    }
+    Ok(price)
+}
+
+/// How `track` went
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tracked {
+    /// Nobody tracked the product yet, so it was added
+    Added,
+    /// Someone else already tracked it; the user is now subscribed too
+    Subscribed,
+    /// The user was already subscribed, and nothing changed
+    AlreadySubscribed,
+}
+
+/// Subscribe `username` to the product `input` describes, adding the product first if nobody
+/// tracks it yet. Products are matched on the retailer's id for them, then on canonical URL,
+/// so each is only ever fetched once however many people watch it.
+pub fn track(
+    products: &dyn ProductRepository,
+    username: &str,
+    input: ProductInput,
+) -> anyhow::Result<(Product, Tracked)> {
+    let existing =
+        products.find_existing(&input.retailer, input.retailer_id.as_deref(), &input.url)?;
+    let (product, tracked) = match existing {
+        Some(product) if products.subscription(product.id, username)?.is_some() => {
+            return Ok((product, Tracked::AlreadySubscribed));
+        }
+        Some(product) => (product, Tracked::Subscribed),
+        None => {
+            let added = products.add(NewProduct {
+                url: input.url.clone(),
+                name: input.name,
+                retailer: input.retailer.clone(),
+                retailer_id: input.retailer_id.clone(),
+                currency: input.currency,
+                added_by: username.to_string(),
+                poll_interval: input.poll_interval,
+            });
+            match added {
+                Ok(product) => (product, Tracked::Added),
+                // Someone else added it in the meantime and the unique retailer id stopped
+                // this one, so watch theirs
+                Err(e) => {
+                    let Some(product) = products.find_existing(
+                        &input.retailer,
+                        input.retailer_id.as_deref(),
+                        &input.url,
+                    )?
+                    else {
+                        return Err(e);
+                    };
+                    if products.subscription(product.id, username)?.is_some() {
+                        return Ok((product, Tracked::AlreadySubscribed));
+                    }
+                    (product, Tracked::Subscribed)
+                }
+            }
+        }
+    };
+
+    products.subscribe(NewSubscription {
+        product_id: product.id,
+        username: username.to_string(),
+        target_price: input.target_price,
+    })?;
+    Ok((product, tracked))
+}
+
+/// What `track` would do, without doing it
+pub fn plan_track(
+    products: &dyn ProductRepository,
+    username: &str,
+    input: &ProductInput,
+) -> anyhow::Result<Tracked> {
+    let existing =
+        products.find_existing(&input.retailer, input.retailer_id.as_deref(), &input.url)?;
+    Ok(match existing {
+        Some(product) if products.subscription(product.id, username)?.is_some() => {
+            Tracked::AlreadySubscribed
+        }
+        Some(_) => Tracked::Subscribed,
+        None => Tracked::Added,
+    })
+}
+
+// Whether editing `product` into `input` would point it at a different page while anyone
+// but `username` watches it. Their target prices, alerts and auto-buy limits are for the
+// product as it is, so only its name and check interval may change then.
+fn moves_shared_product(
+    products: &dyn ProductRepository,
+    product: &Product,
+    username: &str,
+    input: &ProductInput,
+) -> anyhow::Result<bool> {
+    let moved = input.url != product.url
+        || input.retailer != product.retailer
+        || input.currency != product.currency;
+    if !moved {
+        return Ok(false);
+    }
+    let subscribers = products.subscribers(product.id)?;
+    Ok(subscribers
+        .iter()
+        .any(|s| !s.username.eq_ignore_ascii_case(username)))
+}
+
+/// Replace `product`'s details with validated `input`, as edited by `username`, saving their
+/// target price too if they're `subscribed`. Returns the product as saved, or `None` if it
+/// was deleted in the meantime.
+pub fn update(
+    products: &dyn ProductRepository,
+    product: Product,
+    username: &str,
+    subscribed: bool,
+    input: ProductInput,
+) -> anyhow::Result<Result<Option<Product>, ProductError>> {
+    // Changing the URL to that of another tracked product would duplicate it
+    if let Some(existing) = products
+        .find_existing(&input.retailer, input.retailer_id.as_deref(), &input.url)?
+        .filter(|existing| existing.id != product.id)
+    {
+        warn!(
+            "Product already tracked - id: {}, url: {}, edited by: {}",
+            existing.id, input.url, username
+        );
+        return Ok(Err(ProductError::DuplicateProduct));
+    }
+    if moves_shared_product(products, &product, username, &input)? {
+        warn!(
+            "Shared product not moved - id: {}, url: {}, edited by: {}",
+            product.id, input.url, username
+        );
+        return Ok(Err(ProductError::SharedProduct));
+    }
+
+    let product = Product {
+        url: input.url,
+        name: input.name,
+        retailer: input.retailer,
+        retailer_id: input.retailer_id,
+        currency: input.currency,
+        poll_interval: input.poll_interval,
+        ..product
+    };
+    if !products.update(&product)? {
+        return Ok(Ok(None));
+    }
+    if subscribed {
+        products.subscribe(NewSubscription {
+            product_id: product.id,
+            username: username.to_string(),
+            target_price: input.target_price,
+        })?;
+    }
+    info!(
+        "Product updated - id: {}, name: {}, retailer: {}, edited by: {}, target price: {:?}",
+        product.id,
+        product.name,
+        product.retailer,
+        username,
+        input.target_price.map(|p| p.to_string())
+    );
+    Ok(Ok(Some(product)))
+}
+
+/// How `remove` went
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Removed {
+    /// The user stopped watching the product, which others still watch
+    Unsubscribed,
+    /// The product is gone for everyone
+    Deleted,
+    /// The user doesn't watch the product and may not delete it
+    Forbidden,
+}
+
+/// Take `product` off `user`'s hands. Subscribers stop watching it, and it goes once nobody
+/// does; those who may manage it but don't watch it delete it outright.
+pub fn remove(
+    products: &dyn ProductRepository,
+    user: &User,
+    product: &Product,
+    subscribed: bool,
+) -> anyhow::Result<Removed> {
+    if subscribed {
+        info!(
+            "Product unsubscribed - id: {}, name: {}, username: {}",
+            product.id, product.name, user.username
+        );
+        if !untrack(products, product, &user.username)? {
+            return Ok(Removed::Unsubscribed);
+        }
+    } else if can_manage(user, product) {
+        products.delete(product.id)?;
+    } else {
+        return Ok(Removed::Forbidden);
+    }
+    info!(
+        "Product deleted - id: {}, name: {}, deleted by: {}",
+        product.id, product.name, user.username
+    );
+    Ok(Removed::Deleted)
+}
+
+/// Unsubscribe `username` from a product, deleting the product once nobody watches it.
+/// Returns whether the product was deleted.
+pub fn untrack(
+    products: &dyn ProductRepository,
+    product: &Product,
+    username: &str,
+) -> anyhow::Result<bool> {
+    products.unsubscribe(product.id, username)?;
+    if products.subscribers(product.id)?.is_empty() {
+        return products.delete(product.id);
+    }
+    Ok(false)
+}
+
+/// The product with `id` and `user`'s subscription to it, if it exists and they may see it
+pub fn find_visible(
+    products: &dyn ProductRepository,
+    user: &User,
+    id: i64,
+) -> anyhow::Result<Option<(Product, Option<Subscription>)>> {
+    let Some(product) = products.get(id)? else {
+        return Ok(None);
+    };
+    let subscription = products.subscription(product.id, &user.username)?;
+    Ok(can_view(user, subscription.as_ref()).then_some((product, subscription)))
+}
+
+/// Admins and viewers see every product, everybody else only the ones they're subscribed to
+pub fn can_view(user: &User, subscription: Option<&Subscription>) -> bool {
+    user.role.can_view_all() || subscription.is_some()
+}
+
+/// Whoever added a product may change its details, admins any product's; read-only viewers
+/// never can. Subscribers set their own target price either way.
+pub fn can_manage(user: &User, product: &Product) -> bool {
+    user.role == UserRole::Admin || (user.role.can_edit() && product.added_by == user.username)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryProductRepository;
+    use crate::urls::ResolveFuture;
+
+    /// Resolves every short link to the same place, or fails when there's nowhere to go
+    struct StubResolver(Option<&'static str>);
+
+    impl ShortLinkResolver for StubResolver {
+        fn resolve<'a>(&'a self, _url: &'a Url) -> ResolveFuture<'a> {
+            Box::pin(async move {
+                let target = self
+                    .0
+                    .ok_or_else(|| anyhow::anyhow!("connection refused"))?;
+                Ok(Url::parse(target)?)
+            })
+        }
+    }
+
+    fn input(url: &str, retailer: &str) -> ProductInput {
+        ProductInput {
+            url: url.to_string(),
+            name: "PS5".to_string(),
+            retailer: retailer.to_string(),
+            retailer_id: None,
+            currency: Currency::Usd,
+            target_price: None,
+            poll_interval: None,
+        }
+    }
+
+    fn usd(amount: f64) -> Money {
+        Money::from_major(amount, Currency::Usd).unwrap()
+    }
+
+    async fn validate_url(
+        url: &str,
+        retailer: &str,
+        resolver: StubResolver,
+    ) -> Result<String, ProductError> {
+        let input = input(url, retailer)
+            .validate(&RetailerRegistry::default(), &resolver)
+            .await
+            .map_err(|errors| errors.first().unwrap())?;
+        Ok(input.url)
+    }
+
+    #[test]
+    fn errors_come_back_from_their_codes() {
+        for error in ProductError::ALL {
+            assert_eq!(ProductError::from_code(error.code()), Some(error));
+        }
+        assert_eq!(ProductError::from_code("mismatch"), None);
+    }
+
+    #[tokio::test]
+    async fn reports_every_field_at_once() {
+        let errors = ProductInput {
+            name: "x".repeat(201),
+            target_price: Some(usd(0.0)),
+            ..input("not a url", "Walmart")
+        }
+        .validate(&RetailerRegistry::default(), &StubResolver(None))
+        .await
+        .unwrap_err();
+
+        assert_eq!(errors.get("name"), Some(ProductError::NameTooLong));
+        assert_eq!(errors.get("retailer"), Some(ProductError::InvalidRetailer));
+        assert_eq!(errors.get("url"), Some(ProductError::MalformedUrl));
+        assert_eq!(
+            errors.get("target_price"),
+            Some(ProductError::TargetPriceOutOfRange)
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_retailer_id_from_expanded_link() {
+        let resolver = StubResolver(Some("https://www.amazon.com/PS5/dp/B0CL61F39H?th=1"));
+        let input = input("https://amzn.to/3xYz", "Amazon")
+            .validate(&RetailerRegistry::default(), &resolver)
+            .await
+            .unwrap();
+        assert_eq!(input.retailer_id.as_deref(), Some("B0CL61F39H"));
+    }
+
+    #[tokio::test]
+    async fn expands_short_links() {
+        let resolver = StubResolver(Some(
+            "https://www.amazon.com/PlayStation-5/dp/B0CL61F39H?ref_=share&tag=x-20",
+        ));
+        assert_eq!(
+            validate_url("https://amzn.to/3xYz", "Amazon", resolver).await,
+            Ok("https://www.amazon.com/dp/B0CL61F39H".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn short_links_must_land_on_the_retailer() {
+        let resolver = StubResolver(Some("https://evil.example/dp/B0CL61F39H"));
+        assert_eq!(
+            validate_url("https://a.co/d/abc", "Amazon", resolver).await,
+            Err(ProductError::InvalidUrl)
+        );
+        assert_eq!(
+            validate_url("https://a.co/d/abc", "Amazon", StubResolver(None)).await,
+            Err(ProductError::UnresolvedLink)
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_urls_only_mentioning_the_retailer() {
+        for url in [
+            "https://evil.com/?a.co",
+            "https://evil.com/bestbuy.com/site/x/1.p",
+            "https://bestbuy.com.evil.com/site/x/1.p",
+        ] {
+            let retailer = if url.contains("a.co") {
+                "Amazon"
+            } else {
+                "Best Buy"
+            };
+            assert_eq!(
+                validate_url(url, retailer, StubResolver(None)).await,
+                Err(ProductError::InvalidUrl),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_target_prices() {
+        let parse = |input| parse_target_price(input, Currency::Usd);
+        assert_eq!(parse(" $399.99 "), Ok(Some(usd(399.99))));
+        assert_eq!(parse("1,299"), Ok(Some(usd(1299.0))));
+        assert_eq!(parse(""), Ok(None));
+        for malformed in ["cheap", "12.345", "1e3", "NaN"] {
+            assert_eq!(
+                parse(malformed),
+                Err(ProductError::InvalidTargetPrice),
+                "{malformed}"
+            );
+        }
+        for out_of_range in ["-5", "0", "0.00", "1000000.01"] {
+            assert_eq!(
+                parse(out_of_range),
+                Err(ProductError::TargetPriceOutOfRange),
+                "{out_of_range}"
+            );
+        }
+        assert_eq!(parse("£300"), Err(ProductError::TargetPriceCurrency));
+        assert_eq!(
+            parse_target_price("£300", Currency::Gbp),
+            Ok(Some(Money::new(30000, Currency::Gbp)))
+        );
+    }
+
+    #[tokio::test]
+    async fn takes_the_retailers_currency() {
+        let url = "https://www.amazon.co.uk/dp/B0CL61F39H";
+        let validated = input(url, "Amazon.co.uk")
+            .validate(&RetailerRegistry::default(), &StubResolver(None))
+            .await
+            .unwrap();
+        assert_eq!(validated.currency, Currency::Gbp);
+
+        let errors = ProductInput {
+            target_price: Some(usd(300.0)),
+            ..input(url, "Amazon.co.uk")
+        }
+        .validate(&RetailerRegistry::default(), &StubResolver(None))
+        .await
+        .unwrap_err();
+        assert_eq!(
+            errors.get("target_price"),
+            Some(ProductError::TargetPriceCurrency)
+        );
+    }
+
+    #[test]
+    fn same_product_is_shared_between_users() {
+        let products = InMemoryProductRepository::default();
+        let amazon = |url: &str, target_price| ProductInput {
+            url: url.to_string(),
+            retailer_id: Some("B0CL61F39H".to_string()),
+            target_price,
+            ..input(url, "Amazon")
+        };
+
+        let (first, tracked) = track(
+            &products,
+            "alice",
+            amazon("https://www.amazon.com/dp/B0CL61F39H", Some(usd(450.0))),
+        )
+        .unwrap();
+        assert_eq!(tracked, Tracked::Added);
+
+        // Legacy URLs for the same ASIN still find it
+        let (second, tracked) = track(
+            &products,
+            "bob",
+            amazon(
+                "https://www.amazon.com/gp/product/B0CL61F39H",
+                Some(usd(400.0)),
+            ),
+        )
+        .unwrap();
+        assert_eq!(tracked, Tracked::Subscribed);
+        assert_eq!(second.id, first.id);
+
+        let (_, tracked) = track(
+            &products,
+            "bob",
+            amazon("https://www.amazon.com/dp/B0CL61F39H", None),
+        )
+        .unwrap();
+        assert_eq!(tracked, Tracked::AlreadySubscribed);
+
+        let targets: Vec<_> = products
+            .subscribers(first.id)
+            .unwrap()
+            .iter()
+            .map(|s| (s.username.clone(), s.target_price))
+            .collect();
+        assert_eq!(
+            targets,
+            [
+                ("alice".to_string(), Some(usd(450.0))),
+                ("bob".to_string(), Some(usd(400.0)))
+            ]
+        );
+
+        assert!(!untrack(&products, &first, "alice").unwrap());
+        assert!(untrack(&products, &first, "bob").unwrap());
+        assert!(products.get(first.id).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn canonicalizes_full_urls_without_resolving() {
+        assert_eq!(
+            validate_url(
+                "bestbuy.com/site/sony-tv/6578534.p?skuId=6578534&utm_source=mail",
+                "Best Buy",
+                StubResolver(None)
+            )
+            .await,
+            Ok("https://www.bestbuy.com/site/sony-tv/6578534.p".to_string())
+        );
+    }
+
+    #[test]
+    fn shared_products_keep_their_page() {
+        let products = InMemoryProductRepository::default();
+        let url = "https://www.amazon.com/dp/B0CL61F39H";
+        let (product, _) = track(&products, "alice", input(url, "Amazon")).unwrap();
+        let renamed = ProductInput {
+            name: "PS5 Slim".to_string(),
+            ..input(url, "Amazon")
+        };
+        let moved = input("https://www.amazon.com/dp/B0BCNKKZ91", "Amazon");
+        let moves = |input: &ProductInput| {
+            moves_shared_product(&products, &product, "alice", input).unwrap()
+        };
+
+        // Nobody else watches it yet
+        assert!(!moves(&moved));
+
+        track(&products, "bob", input(url, "Amazon")).unwrap();
+        assert!(moves(&moved));
+        assert!(!moves(&renamed));
+        assert!(moves(&ProductInput {
+            currency: Currency::Cad,
+            ..input(url, "Amazon")
+        }));
+    }
+
+    #[tokio::test]
+    async fn bounds_the_check_interval() {
+        let validate = |minutes| {
+            let input = ProductInput {
+                poll_interval: Some(poll_interval_minutes(minutes)),
+                ..input("https://www.amazon.com/dp/B0CL61F39H", "Amazon")
+            };
+            async move {
+                input
+                    .validate(&RetailerRegistry::default(), &StubResolver(None))
+                    .await
+                    .map(|input| input.poll_interval.unwrap())
+            }
+        };
+
+        assert_eq!(validate(0).await, Ok(MIN_POLL_INTERVAL));
+        assert_eq!(validate(7 * 24 * 60).await, Ok(MAX_POLL_INTERVAL));
+        for too_long in [7 * 24 * 60 + 1, u64::MAX / 60 + 1, u64::MAX] {
+            assert_eq!(
+                validate(too_long).await.unwrap_err().get("poll_interval"),
+                Some(ProductError::PollIntervalTooLong),
+                "{too_long}"
+            );
+        }
+    }
+
+    #[test]
+    fn owners_and_admins_manage_products() {
+        let products = InMemoryProductRepository::default();
+        let (product, _) = track(
+            &products,
+            "bob",
+            input("https://www.amazon.com/dp/B0CL61F39H", "Amazon"),
+        )
+        .unwrap();
+        let user = |username: &str, role| User {
+            username: username.to_string(),
+            role,
+        };
+
+        assert!(can_manage(&user("bob", UserRole::Regular), &product));
+        assert!(can_manage(&user("alice", UserRole::Admin), &product));
+        assert!(!can_manage(&user("carol", UserRole::Regular), &product));
+        // Demoted to viewer, bob can't change his own products any more
+        assert!(!can_manage(&user("bob", UserRole::Viewer), &product));
+    }
+
+    #[test]
+    fn subscribers_admins_and_viewers_see_products() {
+        let products = InMemoryProductRepository::default();
+        let (product, _) = track(
+            &products,
+            "bob",
+            input("https://www.amazon.com/dp/B0CL61F39H", "Amazon"),
+        )
+        .unwrap();
+        let subscription = products.subscription(product.id, "bob").unwrap();
+        let user = |role| User {
+            username: "carol".to_string(),
+            role,
+        };
+
+        assert!(can_view(&user(UserRole::Regular), subscription.as_ref()));
+        assert!(!can_view(&user(UserRole::Regular), None));
+        assert!(can_view(&user(UserRole::Admin), None));
+        assert!(can_view(&user(UserRole::Viewer), None));
+
+        let visible = |username: &str, role| {
+            let user = User {
+                username: username.to_string(),
+                role,
+            };
+            find_visible(&products, &user, product.id)
+                .unwrap()
+                .map(|(product, subscription)| (product.id, subscription.is_some()))
+        };
+        assert_eq!(visible("bob", UserRole::Regular), Some((product.id, true)));
+        assert_eq!(visible("carol", UserRole::Regular), None);
+        assert_eq!(visible("vera", UserRole::Viewer), Some((product.id, false)));
+    }
+}
@@ -0,0 +1,383 @@
+use super::{Retailer, RetailerStyle};
+use crate::money::{Currency, Money};
+use crate::parse::{self, PageInfo};
+use crate::storage::StockState;
+use crate::urls;
+use scraper::Html;
+use url::Url;
+
+/// One of Amazon's country stores. They share page layouts but each prices in its own
+/// currency, and the same ASIN is a separate listing in each.
+pub struct Amazon {
+    name: &'static str,
+    domains: &'static [&'static str],
+    currency: Currency,
+}
+
+impl Amazon {
+    pub const US: Amazon = Amazon {
+        name: "Amazon",
+        domains: &["amazon.com"],
+        currency: Currency::Usd,
+    };
+    pub const CANADA: Amazon = Amazon {
+        name: "Amazon.ca",
+        domains: &["amazon.ca"],
+        currency: Currency::Cad,
+    };
+    pub const UK: Amazon = Amazon {
+        name: "Amazon.co.uk",
+        domains: &["amazon.co.uk"],
+        currency: Currency::Gbp,
+    };
+}
+
+impl Retailer for Amazon {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn domains(&self) -> &'static [&'static str] {
+        self.domains
+    }
+
+    fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    // Shared by every store; an expanded link has to land on this one's domain
+    fn short_link_domains(&self) -> &'static [&'static str] {
+        &["amzn.to", "amzn.eu", "a.co"]
+    }
+
+    // Product pages are reachable as /dp/<ASIN>; drop the SEO slug and ref/query noise
+    fn canonicalize_url(&self, url: &Url) -> Url {
+        let mut url = urls::clean(url);
+        if let Some(asin) = asin_from_path(url.path()) {
+            let path = format!("/dp/{asin}");
+            url.set_path(&path);
+            url.set_query(None);
+            let _ = url.set_host(Some(&format!("www.{}", self.domains[0])));
+        }
+        url
+    }
+
+    fn product_id(&self, url: &Url) -> Option<String> {
+        asin_from_path(url.path()).map(str::to_ascii_uppercase)
+    }
+
+    fn parse_product_page(&self, html: &str) -> Option<PageInfo> {
+        parse_page(html, self.currency)
+    }
+
+    fn style(&self) -> RetailerStyle {
+        RetailerStyle {
+            badge: "bg-orange-100 text-orange-800",
+            card: "border-orange-200 hover:bg-orange-50",
+        }
+    }
+}
+
+// The ASIN following /dp/ or /gp/product/ in an Amazon URL path
+fn asin_from_path(path: &str) -> Option<&str> {
+    let after = ["/dp/", "/gp/product/"]
+        .iter()
+        .find_map(|marker| path.find(marker).map(|i| &path[i + marker.len()..]))?;
+    let asin = after
+        .split('/')
+        .next()
+        .filter(|asin| asin.len() == 10 && asin.chars().all(|c| c.is_ascii_alphanumeric()))?;
+    Some(asin)
+}
+
+// Where the buy box price has lived across Amazon's page layouts, newest first
+const PRICE_SELECTORS: &[&str] = &[
+    ".priceToPay .a-offscreen",
+    // Some pages leave the screen-reader copy empty and only render the split-up price
+    ".priceToPay span[aria-hidden=\"true\"]",
+    "#corePrice_feature_div .a-price .a-offscreen",
+    "#apex_desktop .a-price .a-offscreen",
+    "#priceblock_dealprice",
+    "#priceblock_ourprice",
+    "#price_inside_buybox",
+];
+
+// Checked before `IN_STOCK_PHRASES`, since "we don't know when or if this item will be back
+// in stock" is an out of stock message
+const OUT_OF_STOCK_PHRASES: &[&str] = &[
+    "currently unavailable",
+    "out of stock",
+    "available from these sellers",
+];
+
+const IN_STOCK_PHRASES: &[&str] = &["in stock", "usually ships", "ships within"];
+
+fn parse_page(html: &str, currency: Currency) -> Option<PageInfo> {
+    let document = Html::parse_document(html);
+
+    let retailer_id = parse::select_attr(&document, "input#ASIN", "value")
+        .or_else(|| {
+            parse::select_attr(&document, "link[rel=\"canonical\"]", "href")
+                .and_then(|href| asin_from_path(&href).map(str::to_string))
+        })
+        .or_else(|| parse::select_attr(&document, "[data-asin]", "data-asin"));
+    let title = parse::select_text(&document, "#productTitle");
+    let price = PRICE_SELECTORS
+        .iter()
+        .find_map(|selector| select_price(&document, selector, currency));
+    // "List Price" / "Typical price", shown struck through next to a discount
+    let was_price = select_price(&document, ".basisPrice .a-offscreen", currency)
+        .filter(|was| price.is_none_or(|price| *was > price));
+    let stock = stock(&document);
+
+    if title.is_none() && price.is_none() && stock == StockState::Unknown {
+        // Not a product page we recognise (a robot check, most likely)
+        return parse::parse_json_ld(html, currency);
+    }
+
+    let (ships_from, sold_by) = seller(&document);
+    let third_party_seller = sold_by.as_deref().is_some_and(|seller| !is_amazon(seller));
+
+    Some(PageInfo {
+        retailer_id,
+        title,
+        price,
+        was_price,
+        stock,
+        availability: None,
+        ships_from,
+        sold_by,
+        third_party_seller,
+    })
+}
+
+fn select_price(document: &Html, selector: &str, currency: Currency) -> Option<Money> {
+    parse::select_texts(document, selector)
+        .find_map(|text| parse::parse_price(&text.replace(' ', ""), currency))
+}
+
+fn stock(document: &Html) -> StockState {
+    let availability = parse::select_text(document, "#availability")
+        .unwrap_or_default()
+        .to_lowercase();
+
+    if OUT_OF_STOCK_PHRASES
+        .iter()
+        .any(|p| availability.contains(p))
+    {
+        StockState::OutOfStock
+    } else if IN_STOCK_PHRASES.iter().any(|p| availability.contains(p))
+        || parse::exists(document, "#add-to-cart-button")
+    {
+        StockState::InStock
+    } else if parse::exists(document, "#outOfStock") {
+        StockState::OutOfStock
+    } else {
+        StockState::Unknown
+    }
+}
+
+// "Ships from" and "Sold by" for the buy box offer
+fn seller(document: &Html) -> (Option<String>, Option<String>) {
+    let ships_from = parse::select_text(
+        document,
+        "#fulfillerInfoFeature_feature_div .offer-display-feature-text",
+    )
+    .or_else(|| {
+        parse::select_text(
+            document,
+            "[tabular-attribute-name=\"Ships from\"] .tabular-buybox-text-message",
+        )
+    });
+    let sold_by = parse::select_text(
+        document,
+        "#merchantInfoFeature_feature_div .offer-display-feature-text",
+    )
+    .or_else(|| {
+        parse::select_text(
+            document,
+            "[tabular-attribute-name=\"Sold by\"] .tabular-buybox-text-message",
+        )
+    });
+
+    if ships_from.is_some() || sold_by.is_some() {
+        return (ships_from, sold_by);
+    }
+    parse::select_text(document, "#merchant-info").map_or((None, None), |text| merchant_info(&text))
+}
+
+// Older pages sum it up in one sentence: "Ships from and sold by Amazon.com." or
+// "Sold by Some Seller and Fulfilled by Amazon."
+fn merchant_info(text: &str) -> (Option<String>, Option<String>) {
+    let text = text.trim().trim_end_matches('.');
+    if let Some(seller) = text.strip_prefix("Ships from and sold by ") {
+        return (Some(seller.to_string()), Some(seller.to_string()));
+    }
+    match text.strip_prefix("Sold by ") {
+        Some(rest) => match rest.split_once(" and Fulfilled by ") {
+            Some((seller, fulfiller)) => (Some(fulfiller.to_string()), Some(seller.to_string())),
+            None => (None, Some(rest.to_string())),
+        },
+        None => (None, None),
+    }
+}
+
+// Amazon sells under a few names: "Amazon.com", "Amazon.com Services LLC", "Amazon Export
+// Sales LLC", "Amazon.co.uk", ... Marketplace sellers can't register names like these.
+fn is_amazon(seller: &str) -> bool {
+    let seller = seller.trim().trim_end_matches('.').to_lowercase();
+    let (name, rest) = seller.split_once(' ').unwrap_or((&seller, ""));
+    let amazon_name = name == "amazon"
+        || name
+            .strip_prefix("amazon.")
+            .is_some_and(|tld| tld.chars().all(|c| c.is_ascii_alphabetic() || c == '.'));
+    amazon_name
+        && matches!(
+            rest.trim(),
+            "" | "llc" | "services llc" | "export sales llc" | "digital services llc" | "warehouse"
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_product_sold_by_amazon() {
+        let html = include_str!("../../tests/fixtures/amazon/sold_by_amazon.html");
+        let info = parse_page(html, Currency::Usd).unwrap();
+
+        assert_eq!(info.retailer_id.as_deref(), Some("B0CL61F39H"));
+        assert_eq!(info.title.as_deref(), Some("PlayStation®5 Console (Slim)"));
+        assert_eq!(info.price, Some(Money::new(44900, Currency::Usd)));
+        assert_eq!(info.was_price, Some(Money::new(49999, Currency::Usd)));
+        assert_eq!(info.stock, StockState::InStock);
+        assert_eq!(info.ships_from.as_deref(), Some("Amazon.com"));
+        assert_eq!(info.sold_by.as_deref(), Some("Amazon.com"));
+        assert!(!info.third_party_seller);
+    }
+
+    #[test]
+    fn flags_third_party_seller() {
+        let html = include_str!("../../tests/fixtures/amazon/third_party.html");
+        let info = parse_page(html, Currency::Usd).unwrap();
+
+        assert_eq!(info.retailer_id.as_deref(), Some("B0BHS1ZPMS"));
+        assert_eq!(info.price, Some(Money::new(284995, Currency::Usd)));
+        assert_eq!(info.was_price, None);
+        assert_eq!(info.stock, StockState::InStock);
+        assert_eq!(info.ships_from.as_deref(), Some("Amazon"));
+        assert_eq!(info.sold_by.as_deref(), Some("TechDealz Direct"));
+        assert!(info.third_party_seller);
+    }
+
+    #[test]
+    fn parses_unavailable_product() {
+        let html = include_str!("../../tests/fixtures/amazon/unavailable.html");
+        let info = parse_page(html, Currency::Usd).unwrap();
+
+        assert_eq!(info.retailer_id.as_deref(), Some("B0BMGBJFMH"));
+        assert_eq!(info.price, None);
+        assert_eq!(info.stock, StockState::OutOfStock);
+        assert_eq!(info.sold_by, None);
+        assert!(!info.third_party_seller);
+    }
+
+    #[test]
+    fn parses_legacy_layout() {
+        let html = include_str!("../../tests/fixtures/amazon/legacy_merchant_info.html");
+        let info = parse_page(html, Currency::Usd).unwrap();
+
+        assert_eq!(info.retailer_id.as_deref(), Some("B09HM94VDS"));
+        assert_eq!(info.price, Some(Money::new(8999, Currency::Usd)));
+        assert_eq!(info.stock, StockState::InStock);
+        assert_eq!(info.sold_by.as_deref(), Some("Amazon.com"));
+        assert!(!info.third_party_seller);
+    }
+
+    #[test]
+    fn reads_merchant_info_sentence() {
+        assert_eq!(
+            merchant_info("Sold by GPU Outlet and Fulfilled by Amazon."),
+            (Some("Amazon".to_string()), Some("GPU Outlet".to_string()))
+        );
+        assert_eq!(
+            merchant_info("Ships from and sold by Amazon.com."),
+            (
+                Some("Amazon.com".to_string()),
+                Some("Amazon.com".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn recognises_amazon_seller_names() {
+        for name in [
+            "Amazon.com",
+            "Amazon",
+            "Amazon.com Services LLC",
+            "Amazon.co.uk",
+        ] {
+            assert!(is_amazon(name), "{name}");
+        }
+        for name in ["TechDealz Direct", "Amazon Deals Outlet", "amazonGPUs"] {
+            assert!(!is_amazon(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn canonicalizes_to_asin_url() {
+        for url in [
+            "https://www.Amazon.com/PlayStation-5-Console-Slim/dp/B0CL61F39H/ref=sr_1_1?keywords=ps5",
+            "https://smile.amazon.com/gp/product/B0CL61F39H?psc=1&tag=deals-20#reviews",
+        ] {
+            assert_eq!(
+                Amazon::US
+                    .canonicalize_url(&Url::parse(url).unwrap())
+                    .as_str(),
+                "https://www.amazon.com/dp/B0CL61F39H"
+            );
+        }
+    }
+
+    #[test]
+    fn product_id_is_the_asin() {
+        let url = Url::parse("https://www.amazon.com/Console/dp/b0cl61f39h?th=1").unwrap();
+        assert_eq!(Amazon::US.product_id(&url).as_deref(), Some("B0CL61F39H"));
+
+        let url = Url::parse("https://www.amazon.com/s?k=ps5").unwrap();
+        assert_eq!(Amazon::US.product_id(&url), None);
+    }
+
+    #[test]
+    fn country_stores_price_in_their_own_currency() {
+        let url = Url::parse("https://www.amazon.co.uk/Console/dp/B0CL61F39H?tag=x-21").unwrap();
+        assert!(Amazon::UK.matches_url(&url));
+        assert!(!Amazon::US.matches_url(&url));
+        assert_eq!(
+            Amazon::UK.canonicalize_url(&url).as_str(),
+            "https://www.amazon.co.uk/dp/B0CL61F39H"
+        );
+
+        let html = r#"<span id="productTitle">PlayStation 5</span>
+            <div class="priceToPay"><span class="a-offscreen">£389.99</span></div>
+            <div class="basisPrice"><span class="a-offscreen">£479.99</span></div>"#;
+        let info = parse_page(html, Amazon::UK.currency()).unwrap();
+        assert_eq!(info.price, Some(Money::new(38999, Currency::Gbp)));
+        assert_eq!(info.was_price, Some(Money::new(47999, Currency::Gbp)));
+
+        // amazon.ca writes plain "$", which is Canadian there
+        let html = r#"<div class="priceToPay"><span class="a-offscreen">$599.99</span></div>"#;
+        let info = parse_page(html, Amazon::CANADA.currency()).unwrap();
+        assert_eq!(info.price, Some(Money::new(59999, Currency::Cad)));
+    }
+
+    #[test]
+    fn keeps_other_pages_minus_tracking() {
+        let url = Url::parse("https://www.amazon.com/s?k=ps5&utm_source=newsletter").unwrap();
+        assert_eq!(
+            Amazon::US.canonicalize_url(&url).as_str(),
+            "https://www.amazon.com/s?k=ps5"
+        );
+    }
+}
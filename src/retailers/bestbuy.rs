@@ -0,0 +1,260 @@
+use super::{Retailer, RetailerStyle};
+use crate::money::Currency;
+use crate::parse::{self, PageInfo};
+use crate::storage::StockState;
+use crate::urls;
+use scraper::Html;
+use url::Url;
+
+pub struct BestBuy;
+
+impl Retailer for BestBuy {
+    fn name(&self) -> &'static str {
+        "Best Buy"
+    }
+
+    fn domains(&self) -> &'static [&'static str] {
+        &["bestbuy.com"]
+    }
+
+    // The SKU is part of the path (/site/<slug>/<sku>.p), so the query string is only tracking
+    fn canonicalize_url(&self, url: &Url) -> Url {
+        let mut url = urls::clean(url);
+        url.set_query(None);
+        let _ = url.set_host(Some("www.bestbuy.com"));
+        url
+    }
+
+    fn product_id(&self, url: &Url) -> Option<String> {
+        let is_sku = |sku: &str| !sku.is_empty() && sku.chars().all(|c| c.is_ascii_digit());
+        url.path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .and_then(|last| last.strip_suffix(".p"))
+            .filter(|sku| is_sku(sku))
+            .map(str::to_string)
+            .or_else(|| {
+                url.query_pairs()
+                    .find(|(name, value)| name == "skuId" && is_sku(value))
+                    .map(|(_, value)| value.into_owned())
+            })
+    }
+
+    fn parse_product_page(&self, html: &str) -> Option<PageInfo> {
+        parse_page(html)
+    }
+
+    fn style(&self) -> RetailerStyle {
+        RetailerStyle {
+            badge: "bg-blue-100 text-blue-800",
+            card: "border-blue-200 hover:bg-blue-50",
+        }
+    }
+}
+
+/// What the fulfillment button on a product page offers
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ButtonState {
+    AddToCart,
+    PreOrder,
+    SoldOut,
+    ComingSoon,
+    // Not sold online, only in some stores
+    CheckStores,
+}
+
+impl ButtonState {
+    // Prefer the machine-readable `data-button-state`, falling back to the button label
+    fn parse(state: &str) -> Option<ButtonState> {
+        let state = state.to_ascii_uppercase().replace([' ', '-'], "_");
+        match state.as_str() {
+            "ADD_TO_CART" => Some(ButtonState::AddToCart),
+            "PRE_ORDER" | "PREORDER" => Some(ButtonState::PreOrder),
+            "SOLD_OUT" => Some(ButtonState::SoldOut),
+            "COMING_SOON" => Some(ButtonState::ComingSoon),
+            "CHECK_STORES" | "UNAVAILABLE_NEARBY" => Some(ButtonState::CheckStores),
+            _ => None,
+        }
+    }
+
+    fn stock(self) -> StockState {
+        match self {
+            ButtonState::AddToCart | ButtonState::PreOrder => StockState::InStock,
+            ButtonState::SoldOut | ButtonState::ComingSoon | ButtonState::CheckStores => {
+                StockState::OutOfStock
+            }
+        }
+    }
+
+    // Out of stock for now, but not sold out the way a plain "Sold Out" is
+    fn availability(self) -> Option<&'static str> {
+        match self {
+            ButtonState::ComingSoon => Some("Coming soon"),
+            ButtonState::CheckStores => Some("Only in stores"),
+            ButtonState::AddToCart | ButtonState::PreOrder | ButtonState::SoldOut => None,
+        }
+    }
+}
+
+fn button_state(document: &Html) -> Option<ButtonState> {
+    parse::select_attr(document, ".add-to-cart-button", "data-button-state")
+        .and_then(|state| ButtonState::parse(&state))
+        .or_else(|| {
+            parse::select_text(document, ".add-to-cart-button")
+                .and_then(|label| ButtonState::parse(&label))
+        })
+}
+
+// Best Buy renders the product details server-side. Whatever the markup is missing (it
+// changes from time to time) is filled in from the JSON-LD block.
+fn parse_page(html: &str) -> Option<PageInfo> {
+    let document = Html::parse_document(html);
+
+    let retailer_id = parse::select_text(&document, ".sku.product-data .product-data-value")
+        .or_else(|| parse::select_attr(&document, ".add-to-cart-button", "data-sku-id"));
+    let title = parse::select_text(&document, ".sku-title h1");
+    let price = parse::select_text(
+        &document,
+        ".priceView-customer-price span[aria-hidden=\"true\"]",
+    )
+    .and_then(|text| parse::parse_price(&text, Currency::Usd));
+    // Only shown while the product is on sale, as "Was $1,399.99"
+    let was_price = parse::select_texts(&document, ".pricing-price__regular-price")
+        .find_map(|text| parse::parse_price(&text, Currency::Usd));
+    let button = button_state(&document);
+
+    let markup = PageInfo {
+        retailer_id,
+        title,
+        price,
+        was_price,
+        stock: button.map_or(StockState::Unknown, ButtonState::stock),
+        availability: button
+            .and_then(ButtonState::availability)
+            .map(str::to_string),
+        ships_from: None,
+        sold_by: None,
+        third_party_seller: false,
+    };
+    let info = match parse::parse_json_ld(html, Currency::Usd) {
+        Some(json_ld) => PageInfo {
+            retailer_id: markup.retailer_id.or(json_ld.retailer_id),
+            title: markup.title.or(json_ld.title),
+            price: markup.price.or(json_ld.price),
+            was_price: markup.was_price,
+            stock: match markup.stock {
+                StockState::Unknown => json_ld.stock,
+                stock => stock,
+            },
+            availability: markup.availability,
+            ships_from: None,
+            sold_by: json_ld.sold_by,
+            third_party_seller: false,
+        },
+        None if button.is_none() && markup.price.is_none() && markup.title.is_none() => {
+            return None;
+        }
+        None => markup,
+    };
+
+    // A "was" price that isn't above the current one isn't a discount
+    Some(PageInfo {
+        was_price: info
+            .was_price
+            .filter(|was| info.price.is_none_or(|price| *was > price)),
+        ..info
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+
+    #[test]
+    fn parses_in_stock_product_on_sale() {
+        let html = include_str!("../../tests/fixtures/bestbuy/in_stock_sale.html");
+        let info = parse_page(html).unwrap();
+
+        assert_eq!(info.retailer_id.as_deref(), Some("6578534"));
+        assert_eq!(
+            info.title.as_deref(),
+            Some("Sony - 65\" Class BRAVIA 7 LED 4K UHD Smart Google TV - Black")
+        );
+        assert_eq!(info.price, Some(Money::new(119999, Currency::Usd)));
+        assert_eq!(info.was_price, Some(Money::new(139999, Currency::Usd)));
+        assert_eq!(info.stock, StockState::InStock);
+    }
+
+    #[test]
+    fn parses_sold_out_product() {
+        let html = include_str!("../../tests/fixtures/bestbuy/sold_out.html");
+        let document = Html::parse_document(html);
+        let info = parse_page(html).unwrap();
+
+        assert_eq!(button_state(&document), Some(ButtonState::SoldOut));
+        assert_eq!(info.retailer_id.as_deref(), Some("6521430"));
+        assert_eq!(info.price, Some(Money::new(159999, Currency::Usd)));
+        assert_eq!(info.was_price, None);
+        assert_eq!(info.stock, StockState::OutOfStock);
+        assert_eq!(info.availability, None);
+    }
+
+    #[test]
+    fn reads_coming_soon_from_button_label() {
+        let html = include_str!("../../tests/fixtures/bestbuy/coming_soon.html");
+        let document = Html::parse_document(html);
+        let info = parse_page(html).unwrap();
+
+        assert_eq!(button_state(&document), Some(ButtonState::ComingSoon));
+        assert_eq!(info.retailer_id.as_deref(), Some("6614313"));
+        assert_eq!(info.title.as_deref(), Some("Nintendo Switch 2 System"));
+        assert_eq!(info.price, Some(Money::new(44999, Currency::Usd)));
+        assert_eq!(info.stock, StockState::OutOfStock);
+        assert_eq!(info.availability.as_deref(), Some("Coming soon"));
+    }
+
+    #[test]
+    fn falls_back_to_json_ld() {
+        let html = include_str!("../../tests/fixtures/bestbuy/json_ld_only.html");
+        let info = parse_page(html).unwrap();
+
+        assert_eq!(info.retailer_id.as_deref(), Some("6447382"));
+        assert_eq!(
+            info.title.as_deref(),
+            Some("Apple - AirPods Pro 2 (USB-C) - White")
+        );
+        assert_eq!(info.price, Some(Money::new(18999, Currency::Usd)));
+        assert_eq!(info.stock, StockState::InStock);
+    }
+
+    #[test]
+    fn gives_up_on_pages_without_product_data() {
+        assert_eq!(parse_page("<html><body><h2>Oops</h2></body></html>"), None);
+    }
+
+    #[test]
+    fn canonicalizes_to_sku_path() {
+        let url = Url::parse(
+            "http://bestbuy.com/site/sony-65-bravia-7/6578534.p?skuId=6578534&intl=nosplash#anchor",
+        )
+        .unwrap();
+        assert_eq!(
+            BestBuy.canonicalize_url(&url).as_str(),
+            "https://www.bestbuy.com/site/sony-65-bravia-7/6578534.p"
+        );
+    }
+
+    #[test]
+    fn product_id_is_the_sku() {
+        let sku = |url: &str| BestBuy.product_id(&Url::parse(url).unwrap());
+        assert_eq!(
+            sku("https://www.bestbuy.com/site/sony-tv/6578534.p?intl=nosplash").as_deref(),
+            Some("6578534")
+        );
+        assert_eq!(
+            sku("https://www.bestbuy.com/product/sony-tv?skuId=6578534").as_deref(),
+            Some("6578534")
+        );
+        assert_eq!(sku("https://www.bestbuy.com/site/promo/tv-deals"), None);
+    }
+}
@@ -0,0 +1,128 @@
+mod amazon;
+mod bestbuy;
+
+pub use amazon::Amazon;
+pub use bestbuy::BestBuy;
+
+use crate::checkout::CheckoutSteps;
+use crate::money::Currency;
+use crate::parse::{self, PageInfo};
+use crate::urls;
+use url::Url;
+
+/// Tailwind classes used to render a retailer's products
+#[derive(Debug, Clone, Copy)]
+pub struct RetailerStyle {
+    /// Pill badge with the retailer name, e.g. "bg-orange-100 text-orange-800"
+    pub badge: &'static str,
+    /// Border and hover colours for product cards
+    pub card: &'static str,
+}
+
+/// Style for products whose retailer is no longer registered
+pub const DEFAULT_STYLE: RetailerStyle = RetailerStyle {
+    badge: "bg-gray-100 text-gray-800",
+    card: "border-gray-200 hover:bg-gray-50",
+};
+
+/// Everything the app needs to know about a store. Supporting a new one means implementing
+/// this and adding it to `RetailerRegistry::default`.
+pub trait Retailer: Send + Sync {
+    /// Display name, also what gets stored with each product
+    fn name(&self) -> &'static str;
+
+    /// Domains product pages are served from. Subdomains match too.
+    fn domains(&self) -> &'static [&'static str];
+
+    /// What the retailer prices products in
+    fn currency(&self) -> Currency {
+        Currency::Usd
+    }
+
+    /// Domains of the retailer's link shortener, whose links redirect to product pages
+    fn short_link_domains(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Whether `url` is on one of this retailer's sites
+    fn matches_url(&self, url: &Url) -> bool {
+        urls::host_matches(url, self.domains())
+    }
+
+    /// Whether `url` needs expanding before it can be checked with `matches_url`
+    fn is_short_link(&self, url: &Url) -> bool {
+        urls::host_matches(url, self.short_link_domains())
+    }
+
+    /// Normalise a product URL so the same product always ends up with the same URL.
+    /// Defaults to dropping tracking parameters.
+    fn canonicalize_url(&self, url: &Url) -> Url {
+        urls::clean(url)
+    }
+
+    /// The retailer's own id for the product at `url`, which identifies it however the URL
+    /// is spelled. Defaults to none, leaving products told apart by canonical URL alone.
+    fn product_id(&self, _url: &Url) -> Option<String> {
+        None
+    }
+
+    /// Read price and stock state off a product page. Defaults to schema.org JSON-LD markup.
+    fn parse_product_page(&self, html: &str) -> Option<PageInfo> {
+        parse::parse_json_ld(html, self.currency())
+    }
+
+    fn style(&self) -> RetailerStyle;
+
+    /// How to buy a product from the retailer, for auto-buy. Defaults to none, which leaves
+    /// auto-buy unavailable for its products.
+    fn checkout(&self) -> Option<&dyn CheckoutSteps> {
+        None
+    }
+}
+
+/// The retailers products can be tracked at
+pub struct RetailerRegistry {
+    retailers: Vec<Box<dyn Retailer>>,
+}
+
+impl Default for RetailerRegistry {
+    fn default() -> Self {
+        RetailerRegistry::new(vec![
+            Box::new(BestBuy),
+            Box::new(Amazon::US),
+            Box::new(Amazon::CANADA),
+            Box::new(Amazon::UK),
+        ])
+    }
+}
+
+impl RetailerRegistry {
+    pub fn new(retailers: Vec<Box<dyn Retailer>>) -> Self {
+        RetailerRegistry { retailers }
+    }
+
+    pub fn all(&self) -> impl Iterator<Item = &dyn Retailer> {
+        self.retailers.iter().map(|r| r.as_ref())
+    }
+
+    /// Look a retailer up by its display name
+    pub fn get(&self, name: &str) -> Option<&dyn Retailer> {
+        self.all().find(|r| r.name() == name)
+    }
+
+    /// The retailer whose site `url` is on. Short links have to be expanded first, since
+    /// several stores can share a shortener.
+    pub fn for_url(&self, url: &Url) -> Option<&dyn Retailer> {
+        self.all().find(|r| r.matches_url(url))
+    }
+
+    /// Whether `url` is a short link of any retailer's
+    pub fn is_short_link(&self, url: &Url) -> bool {
+        self.all().any(|r| r.is_short_link(url))
+    }
+
+    /// Styling for a stored retailer name, falling back to grey for unknown ones
+    pub fn style(&self, name: &str) -> RetailerStyle {
+        self.get(name).map_or(DEFAULT_STYLE, |r| r.style())
+    }
+}
@@ -0,0 +1,457 @@
+use crate::alerts::AlertEngine;
+use crate::checkout::AutoBuyer;
+use crate::notify::Notifications;
+use crate::parse::{self, PageInfo};
+use crate::products::MAX_POLL_INTERVAL;
+use crate::retailers::RetailerRegistry;
+use crate::storage::{
+    Alert, NewObservation, Observation, ObservationRepository, Product, ProductRepository,
+    StockState, Subscription,
+};
+use crate::updates::{ProductUpdate, UpdateHub};
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+pub const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0 Safari/537.36";
+
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// How often to check a product that doesn't set its own interval
+    pub default_interval: Duration,
+    /// Each interval is randomly stretched or shrunk by up to this fraction, so checks
+    /// don't line up into bursts
+    pub jitter: f64,
+    /// Maximum number of page fetches in flight at once
+    pub max_concurrent: usize,
+    /// Minimum gap between two requests to the same retailer
+    pub retailer_min_gap: Duration,
+    /// Per-retailer overrides for `retailer_min_gap`, keyed by retailer name
+    pub retailer_gaps: HashMap<String, Duration>,
+    /// How often the scheduler looks for due products
+    pub tick: Duration,
+    /// Timeout for a single page fetch
+    pub request_timeout: Duration,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        SchedulerConfig {
+            default_interval: Duration::from_secs(15 * 60),
+            jitter: 0.1,
+            max_concurrent: 4,
+            retailer_min_gap: Duration::from_secs(10),
+            retailer_gaps: HashMap::new(),
+            tick: Duration::from_secs(10),
+            request_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Hands out request slots per retailer, at least the configured gap apart
+struct RetailerRateLimiter {
+    default_gap: Duration,
+    gaps: HashMap<String, Duration>,
+    next_slot: Mutex<HashMap<String, Instant>>,
+}
+
+impl RetailerRateLimiter {
+    /// Wait until we're allowed to send the next request to `retailer`
+    async fn wait(&self, retailer: &str) {
+        let gap = self.gaps.get(retailer).copied().unwrap_or(self.default_gap);
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let now = Instant::now();
+            let slot = next_slot.get(retailer).copied().unwrap_or(now).max(now);
+            next_slot.insert(retailer.to_string(), slot + gap);
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Periodically fetches every tracked product page and records what it finds.
+///
+/// When a product is next due is derived from its latest stored observation, so the
+/// schedule carries over across restarts.
+pub struct Scheduler {
+    products: Arc<dyn ProductRepository>,
+    observations: Arc<dyn ObservationRepository>,
+    retailers: Arc<RetailerRegistry>,
+    alerts: Arc<AlertEngine>,
+    notifications: Arc<Notifications>,
+    auto_buyer: Arc<AutoBuyer>,
+    updates: Arc<UpdateHub>,
+    client: reqwest::Client,
+    config: SchedulerConfig,
+    fetch_permits: Semaphore,
+    rate_limiter: RetailerRateLimiter,
+    next_due: Mutex<HashMap<i64, SystemTime>>,
+    in_flight: Mutex<HashSet<i64>>,
+}
+
+impl Scheduler {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        products: Arc<dyn ProductRepository>,
+        observations: Arc<dyn ObservationRepository>,
+        retailers: Arc<RetailerRegistry>,
+        alerts: Arc<AlertEngine>,
+        notifications: Arc<Notifications>,
+        auto_buyer: Arc<AutoBuyer>,
+        updates: Arc<UpdateHub>,
+        config: SchedulerConfig,
+    ) -> anyhow::Result<Arc<Self>> {
+        let client = page_client(config.request_timeout)?;
+
+        Ok(Arc::new(Scheduler {
+            products,
+            observations,
+            retailers,
+            alerts,
+            notifications,
+            auto_buyer,
+            updates,
+            client,
+            fetch_permits: Semaphore::new(config.max_concurrent.max(1)),
+            rate_limiter: RetailerRateLimiter {
+                default_gap: config.retailer_min_gap,
+                gaps: config.retailer_gaps.clone(),
+                next_slot: Mutex::new(HashMap::new()),
+            },
+            config,
+            next_due: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashSet::new()),
+        }))
+    }
+
+    /// Run forever, starting checks for products as they become due
+    pub async fn run(self: Arc<Self>) {
+        info!(
+            "Scheduler started - default interval: {:?}, max concurrent: {}",
+            self.config.default_interval, self.config.max_concurrent
+        );
+        let mut ticker = tokio::time::interval(self.config.tick);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.start_due_checks() {
+                warn!("Scheduler tick failed: {:#}", e);
+            }
+        }
+    }
+
+    fn start_due_checks(self: &Arc<Self>) -> anyhow::Result<()> {
+        let now = SystemTime::now();
+        let products = self.products.list()?;
+
+        // Forget products that have been removed
+        let ids: HashSet<i64> = products.iter().map(|p| p.id).collect();
+        self.next_due
+            .lock()
+            .unwrap()
+            .retain(|id, _| ids.contains(id));
+
+        for product in products {
+            let interval = self.interval_for(&product);
+
+            let known_due = self.next_due.lock().unwrap().get(&product.id).copied();
+            let due = match known_due {
+                Some(due) => due,
+                None => {
+                    // First time we see this product (or first tick after a restart)
+                    let due = self
+                        .observations
+                        .latest(product.id)?
+                        .map_or(now, |last| last.observed_at + interval);
+                    self.next_due.lock().unwrap().insert(product.id, due);
+                    due
+                }
+            };
+
+            if due > now || !self.in_flight.lock().unwrap().insert(product.id) {
+                continue;
+            }
+            self.next_due
+                .lock()
+                .unwrap()
+                .insert(product.id, now + self.jittered(interval));
+
+            let scheduler = self.clone();
+            tokio::spawn(async move {
+                let id = product.id;
+                scheduler.check(product).await;
+                scheduler.in_flight.lock().unwrap().remove(&id);
+            });
+        }
+        Ok(())
+    }
+
+    fn interval_for(&self, product: &Product) -> Duration {
+        // Capped in case one was stored before there was a limit
+        product
+            .poll_interval
+            .unwrap_or(self.config.default_interval)
+            .min(MAX_POLL_INTERVAL)
+    }
+
+    fn jittered(&self, interval: Duration) -> Duration {
+        let jitter = self.config.jitter.clamp(0.0, 0.9);
+        if jitter == 0.0 {
+            return interval;
+        }
+        interval.mul_f64(1.0 + rand::thread_rng().gen_range(-jitter..=jitter))
+    }
+
+    // Fetch and parse one product page, recording the outcome either way, raising any alerts
+    // it triggers and buying the product for anyone who asked for that
+    async fn check(&self, product: Product) {
+        self.rate_limiter.wait(&product.retailer).await;
+        let page = {
+            let _permit = self
+                .fetch_permits
+                .acquire()
+                .await
+                .expect("fetch semaphore is never closed");
+            fetch_page(&self.client, &product.url).await
+        };
+
+        let result = match page {
+            Ok(html) => self
+                .parse(&product, &html)
+                .ok_or_else(|| "No product data found on page".to_string()),
+            Err(e) => Err(format!("{:#}", e)),
+        };
+
+        let observation = match result {
+            Ok(info) => {
+                info!(
+                    "Checked product - id: {}, name: {}, retailer id: {:?}, title: {:?}, price: {:?}, was: {:?}, stock: {:?}, sold by: {:?}",
+                    product.id,
+                    product.name,
+                    info.retailer_id,
+                    info.title,
+                    info.price.map(|p| p.to_string()),
+                    info.was_price.map(|p| p.to_string()),
+                    info.stock,
+                    info.sold_by
+                );
+                if let Some(price) = info.price.filter(|p| p.currency() != product.currency) {
+                    // Target and price drop alerts only compare prices in the same currency
+                    warn!(
+                        "Page shows a price in another currency - id: {}, name: {}, price: {}, expected: {}",
+                        product.id,
+                        product.name,
+                        price,
+                        product.currency.code()
+                    );
+                }
+                if info.third_party_seller {
+                    warn!(
+                        "Listing is sold by a third party - id: {}, name: {}, sold by: {}",
+                        product.id,
+                        product.name,
+                        info.sold_by.as_deref().unwrap_or("unknown")
+                    );
+                }
+                NewObservation {
+                    product_id: product.id,
+                    observed_at: SystemTime::now(),
+                    price: info.price,
+                    stock: info.stock,
+                    error: None,
+                    ships_from: info.ships_from,
+                    sold_by: info.sold_by,
+                    third_party_seller: info.third_party_seller,
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "Product check failed - id: {}, name: {}, error: {}",
+                    product.id, product.name, e
+                );
+                NewObservation {
+                    product_id: product.id,
+                    observed_at: SystemTime::now(),
+                    price: None,
+                    stock: StockState::Unknown,
+                    error: Some(e),
+                    ships_from: None,
+                    sold_by: None,
+                    third_party_seller: false,
+                }
+            }
+        };
+        let (subscriptions, observation, alerts) = match self.record(&product, observation) {
+            Ok(recorded) => recorded,
+            Err(e) => {
+                warn!(
+                    "Failed to record observation - product id: {}, error: {:#}",
+                    product.id, e
+                );
+                return;
+            }
+        };
+
+        if alerts.is_empty() {
+            return;
+        }
+        // Sending email and the like blocks, so keep it off the async workers
+        let notifications = self.notifications.clone();
+        let (notify_product, notify_subscriptions, notify_alerts) =
+            (product.clone(), subscriptions.clone(), alerts.clone());
+        tokio::task::spawn_blocking(move || {
+            notifications.dispatch(&notify_product, &notify_subscriptions, &notify_alerts)
+        });
+
+        // The product stays in flight meanwhile, so it isn't checked out twice at once
+        self.auto_buyer
+            .handle(&product, &subscriptions, &observation, &alerts)
+            .await;
+    }
+
+    // Store the observation, push it to live pages if anything visible changed and raise
+    // any alerts it triggers for the product's subscribers
+    fn record(
+        &self,
+        product: &Product,
+        observation: NewObservation,
+    ) -> anyhow::Result<(Vec<Subscription>, Observation, Vec<Alert>)> {
+        let subscriptions = self.products.subscribers(product.id)?;
+        let last = self.observations.latest(product.id)?;
+        let previous = self.observations.latest_successful(product.id)?;
+        let observation = self.observations.record(observation)?;
+
+        let changed = last.is_none_or(|last| {
+            last.price != observation.price
+                || last.stock != observation.stock
+                || last.error.is_some() != observation.error.is_some()
+        });
+        if changed {
+            self.updates.publish(ProductUpdate {
+                product: product.clone(),
+                subscribers: subscriptions.iter().map(|s| s.username.clone()).collect(),
+                observation: observation.clone(),
+            });
+        }
+
+        let alerts =
+            self.alerts
+                .evaluate(product, &subscriptions, previous.as_ref(), &observation)?;
+        Ok((subscriptions, observation, alerts))
+    }
+
+    // Use the retailer's own parser, or plain JSON-LD if it's no longer registered
+    fn parse(&self, product: &Product, html: &str) -> Option<PageInfo> {
+        match self.retailers.get(&product.retailer) {
+            Some(retailer) => retailer.parse_product_page(html),
+            None => parse::parse_json_ld(html, product.currency),
+        }
+    }
+}
+
+/// HTTP client for fetching product pages, which looks like a regular browser
+pub fn page_client(timeout: Duration) -> anyhow::Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(timeout)
+        .build()?)
+}
+
+/// Fetch a product page's HTML, treating error statuses as failures
+pub async fn fetch_page(client: &reqwest::Client, url: &str) -> anyhow::Result<String> {
+    let response = client.get(url).send().await?.error_for_status()?;
+    Ok(response.text().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::AlertConfig;
+    use crate::checkout::fake_store::{FakeStore, NAME};
+    use crate::checkout::{CheckoutConfig, CheckoutStatus};
+    use crate::money::{Currency, Money};
+    use crate::storage::{
+        AlertKind, AlertRepository, CheckoutRepository, InMemoryAlertRepository,
+        InMemoryCheckoutRepository, InMemoryObservationRepository, InMemoryPreferencesRepository,
+        InMemoryProductRepository, NewProduct, NewSubscription,
+    };
+
+    #[tokio::test]
+    async fn check_alerts_and_auto_buys() {
+        let store = FakeStore::start("649.99").await;
+        let retailers = Arc::new(RetailerRegistry::new(vec![Box::new(store.retailer())]));
+        let products = Arc::new(InMemoryProductRepository::default());
+        let alerts = Arc::new(InMemoryAlertRepository::default());
+        let checkouts = Arc::new(InMemoryCheckoutRepository::default());
+        let auto_buyer = AutoBuyer::new(
+            checkouts.clone(),
+            retailers.clone(),
+            CheckoutConfig::default(),
+        )
+        .unwrap();
+        let scheduler = Scheduler::new(
+            products.clone(),
+            Arc::new(InMemoryObservationRepository::default()),
+            retailers,
+            Arc::new(AlertEngine::new(alerts.clone(), AlertConfig::default())),
+            Arc::new(Notifications::new(
+                Arc::new(InMemoryPreferencesRepository::default()),
+                Vec::new(),
+            )),
+            Arc::new(auto_buyer),
+            Arc::new(UpdateHub::new(16)),
+            SchedulerConfig::default(),
+        )
+        .unwrap();
+
+        let product = products
+            .add(NewProduct {
+                url: store.product_url(),
+                name: "Graphics Card".to_string(),
+                retailer: NAME.to_string(),
+                retailer_id: None,
+                currency: Currency::Cad,
+                added_by: "alice".to_string(),
+                poll_interval: None,
+            })
+            .unwrap();
+        let subscription = products
+            .subscribe(NewSubscription {
+                product_id: product.id,
+                username: "alice".to_string(),
+                target_price: Some(Money::parse("700.00", Currency::Cad).unwrap()),
+            })
+            .unwrap();
+        let rule = scheduler
+            .auto_buyer
+            .rule(&product, Some(&subscription), "800.00", 1, false)
+            .unwrap();
+        checkouts.save_rule(&rule).unwrap();
+
+        scheduler.check(product.clone()).await;
+
+        let raised = alerts.history(product.id).unwrap();
+        assert!(raised.iter().any(|a| a.kind == AlertKind::TargetReached));
+        let runs = checkouts.recent_runs(product.id, "alice", 5).unwrap();
+        assert_eq!(runs.len(), 1);
+        // Live orders are off by default, so the rule's own setting doesn't matter
+        assert_eq!(runs[0].status, CheckoutStatus::DryRun);
+        assert_eq!(
+            runs[0].total,
+            Some(Money::parse("649.99", Currency::Cad).unwrap())
+        );
+        assert_eq!(
+            store.requests(),
+            [
+                "GET /products/gpu-1",
+                "POST /api/cart",
+                "POST /api/checkout"
+            ]
+        );
+        assert!(store.orders().is_empty());
+    }
+}
@@ -0,0 +1,271 @@
+use crate::AppState;
+use crate::error::AppError;
+use crate::users::{User, UserRole};
+use axum::extract::FromRequestParts;
+use axum::http::StatusCode;
+use axum::http::request::Parts;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum_extra::extract::SignedCookieJar;
+use axum_extra::extract::cookie::{Cookie, Key, SameSite};
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tracing::warn;
+
+/// Name of the cookie holding the (signed) session id
+pub const SESSION_COOKIE: &str = "midas_session";
+
+/// How long a session stays valid after sign in
+const SESSION_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Debug, Clone)]
+struct Session {
+    username: String,
+    expires_at: SystemTime,
+}
+
+/// Server-side session storage, keyed by a random session id
+#[derive(Debug, Clone, Default)]
+pub struct SessionStore {
+    sessions: Arc<Mutex<HashMap<String, Session>>>,
+}
+
+impl SessionStore {
+    /// Start a new session for `username` and return its id
+    pub fn create(&self, username: &str) -> String {
+        let id: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(48)
+            .map(char::from)
+            .collect();
+
+        let session = Session {
+            username: username.to_string(),
+            expires_at: SystemTime::now() + SESSION_TTL,
+        };
+        self.sessions.lock().unwrap().insert(id.clone(), session);
+        id
+    }
+
+    /// Look up the username for a session id, dropping the session if it has expired
+    pub fn get(&self, id: &str) -> Option<String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get(id) {
+            Some(session) if session.expires_at > SystemTime::now() => {
+                Some(session.username.clone())
+            }
+            Some(_) => {
+                sessions.remove(id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Destroy a session, e.g. on sign out
+    pub fn destroy(&self, id: &str) {
+        self.sessions.lock().unwrap().remove(id);
+    }
+
+    /// Destroy every session belonging to `username` except `keep`
+    pub fn destroy_others(&self, username: &str, keep: &str) {
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|id, session| id == keep || !session.username.eq_ignore_ascii_case(username));
+    }
+}
+
+/// Derive the cookie signing key from the configured session secret, or generate a random
+/// one.
+///
+/// A generated key means every restart signs everybody out, so set a secret (at least 64
+/// bytes) for anything long-running.
+pub fn load_key(secret: Option<&str>) -> anyhow::Result<Key> {
+    match secret {
+        Some(secret) => Key::try_from(secret.as_bytes())
+            .map_err(|_| anyhow::anyhow!("session.secret must be at least 64 bytes long")),
+        None => {
+            warn!("No session secret configured, generating a random session key");
+            Ok(Key::generate())
+        }
+    }
+}
+
+/// Build the session cookie for a freshly created session id
+pub fn session_cookie(id: String) -> Cookie<'static> {
+    Cookie::build((SESSION_COOKIE, id))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(
+            SESSION_TTL
+                .try_into()
+                .expect("session TTL fits in a cookie max-age"),
+        )
+        .build()
+}
+
+/// The user signed in on this request, if its session cookie is valid and the account still
+/// exists
+pub async fn session_user(parts: &mut Parts, state: &AppState) -> anyhow::Result<Option<User>> {
+    let jar = SignedCookieJar::<Key>::from_request_parts(parts, state)
+        .await
+        .unwrap_or_else(|never| match never {});
+
+    match jar.get(SESSION_COOKIE) {
+        Some(cookie) => user_for_session(state, cookie.value()),
+        None => Ok(None),
+    }
+}
+
+/// The user signed in with session `id`, if it hasn't expired or been destroyed and the
+/// account still exists
+pub fn user_for_session(state: &AppState, id: &str) -> anyhow::Result<Option<User>> {
+    match state.sessions.get(id) {
+        Some(username) => state.users.get(&username),
+        None => Ok(None),
+    }
+}
+
+/// Extracting a `User` requires a valid session for an existing account; anonymous requests
+/// are sent to the login page
+impl FromRequestParts<AppState> for User {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        match session_user(parts, state).await {
+            Ok(Some(user)) => Ok(user),
+            Ok(None) => Err(Redirect::to("/").into_response()),
+            Err(e) => Err(AppError::from(e).into_response()),
+        }
+    }
+}
+
+/// Guard for admin-only routes
+#[derive(Debug, Clone)]
+pub struct AdminUser(pub User);
+
+/// Guard for routes that change data, which read-only viewers may not use
+#[derive(Debug, Clone)]
+pub struct EditorUser(pub User);
+
+// Resolve the signed-in user and check their role, answering 403 if it isn't allowed
+async fn require_role(
+    parts: &mut Parts,
+    state: &AppState,
+    allowed: fn(UserRole) -> bool,
+) -> Result<User, Response> {
+    let user = User::from_request_parts(parts, state).await?;
+
+    if allowed(user.role) {
+        Ok(user)
+    } else {
+        warn!(
+            "Forbidden - username: {}, role: {:?}, path: {}",
+            user.username, user.role, parts.uri
+        );
+        Err((
+            StatusCode::FORBIDDEN,
+            crate::forbidden_page("You don't have permission to do that."),
+        )
+            .into_response())
+    }
+}
+
+impl FromRequestParts<AppState> for AdminUser {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        require_role(parts, state, |role| role == UserRole::Admin)
+            .await
+            .map(AdminUser)
+    }
+}
+
+impl FromRequestParts<AppState> for EditorUser {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        require_role(parts, state, UserRole::can_edit)
+            .await
+            .map(EditorUser)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TestApp;
+
+    #[test]
+    fn drops_expired_sessions() {
+        let store = SessionStore::default();
+        let live = store.create("alice");
+        store.sessions.lock().unwrap().insert(
+            "expired".to_string(),
+            Session {
+                username: "alice".to_string(),
+                expires_at: SystemTime::now() - Duration::from_secs(1),
+            },
+        );
+
+        assert_eq!(store.get(&live).as_deref(), Some("alice"));
+        assert_eq!(store.get("expired"), None);
+        assert!(!store.sessions.lock().unwrap().contains_key("expired"));
+        assert_eq!(store.get("unknown"), None);
+    }
+
+    #[test]
+    fn destroys_sessions() {
+        let store = SessionStore::default();
+        let first = store.create("alice");
+        let second = store.create("alice");
+
+        store.destroy(&first);
+        assert_eq!(store.get(&first), None);
+        assert_eq!(store.get(&second).as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn destroys_every_other_session_of_the_user() {
+        let store = SessionStore::default();
+        let current = store.create("alice");
+        let other = store.create("Alice");
+        let bobs = store.create("bob");
+
+        store.destroy_others("ALICE", &current);
+        assert_eq!(store.get(&current).as_deref(), Some("alice"));
+        assert_eq!(store.get(&other), None);
+        assert_eq!(store.get(&bobs).as_deref(), Some("bob"));
+    }
+
+    #[tokio::test]
+    async fn sessions_end_with_their_account() {
+        let app = TestApp::start().await;
+        let bob = app.state.sessions.create("bob");
+        // As if the account had been removed since signing in
+        let dave = app.state.sessions.create("dave");
+
+        let user = user_for_session(&app.state, &bob).unwrap().unwrap();
+        assert_eq!(
+            (user.username.as_str(), user.role),
+            ("bob", UserRole::Regular)
+        );
+        assert!(user_for_session(&app.state, &dave).unwrap().is_none());
+
+        app.state.sessions.destroy(&bob);
+        assert!(user_for_session(&app.state, &bob).unwrap().is_none());
+    }
+}
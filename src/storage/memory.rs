@@ -0,0 +1,663 @@
+use super::{
+    Alert, AlertKind, AlertRepository, CheckoutRepository, NewAlert, NewObservation, NewProduct,
+    NewSubscription, Observation, ObservationRepository, PreferencesRepository, Product,
+    ProductRepository, Subscription, TokenRepository, UserRepository, WebhookRepository,
+};
+use crate::checkout::{AutoBuyRule, CheckoutRun, NewCheckoutRun};
+use crate::notify::{
+    NewWebhook, NewWebhookDelivery, NotificationPreferences, Webhook, WebhookDelivery,
+};
+use crate::tokens::{ApiToken, NewApiToken};
+use crate::users::{Account, SetRoleError, User, UserRole};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::SystemTime;
+
+/// Products and subscriptions kept in `Vec`s, gone on restart
+#[derive(Debug, Default)]
+pub struct InMemoryProductRepository {
+    products: Mutex<Vec<Product>>,
+    subscriptions: Mutex<Vec<Subscription>>,
+    last_id: AtomicI64,
+}
+
+impl ProductRepository for InMemoryProductRepository {
+    fn get(&self, id: i64) -> anyhow::Result<Option<Product>> {
+        Ok(self
+            .products
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|p| p.id == id)
+            .cloned())
+    }
+
+    fn list(&self) -> anyhow::Result<Vec<Product>> {
+        Ok(self.products.lock().unwrap().clone())
+    }
+
+    fn list_for_user(&self, username: &str) -> anyhow::Result<Vec<Product>> {
+        let ids: Vec<i64> = self
+            .subscriptions_for_user(username)?
+            .iter()
+            .map(|s| s.product_id)
+            .collect();
+        Ok(self
+            .products
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| ids.contains(&p.id))
+            .cloned()
+            .collect())
+    }
+
+    fn find_existing(
+        &self,
+        retailer: &str,
+        retailer_id: Option<&str>,
+        url: &str,
+    ) -> anyhow::Result<Option<Product>> {
+        let products = self.products.lock().unwrap();
+        let by_id = retailer_id.and_then(|retailer_id| {
+            products
+                .iter()
+                .find(|p| p.retailer == retailer && p.retailer_id.as_deref() == Some(retailer_id))
+        });
+        Ok(by_id
+            .or_else(|| products.iter().find(|p| p.url == url))
+            .cloned())
+    }
+
+    fn add(&self, product: NewProduct) -> anyhow::Result<Product> {
+        let mut products = self.products.lock().unwrap();
+        let product = Product {
+            id: self.last_id.fetch_add(1, Ordering::Relaxed) + 1,
+            url: product.url,
+            name: product.name,
+            retailer: product.retailer,
+            retailer_id: product.retailer_id,
+            currency: product.currency,
+            added_by: product.added_by,
+            created_at: SystemTime::now(),
+            poll_interval: product.poll_interval,
+        };
+        products.push(product.clone());
+        Ok(product)
+    }
+
+    fn update(&self, product: &Product) -> anyhow::Result<bool> {
+        let mut products = self.products.lock().unwrap();
+        let Some(existing) = products.iter_mut().find(|p| p.id == product.id) else {
+            return Ok(false);
+        };
+        existing.url = product.url.clone();
+        existing.name = product.name.clone();
+        existing.retailer = product.retailer.clone();
+        existing.retailer_id = product.retailer_id.clone();
+        existing.currency = product.currency;
+        existing.poll_interval = product.poll_interval;
+        Ok(true)
+    }
+
+    fn delete(&self, id: i64) -> anyhow::Result<bool> {
+        let mut products = self.products.lock().unwrap();
+        let before = products.len();
+        products.retain(|p| p.id != id);
+        if products.len() == before {
+            return Ok(false);
+        }
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .retain(|s| s.product_id != id);
+        Ok(true)
+    }
+
+    fn subscription(
+        &self,
+        product_id: i64,
+        username: &str,
+    ) -> anyhow::Result<Option<Subscription>> {
+        Ok(self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|s| s.product_id == product_id && s.username.eq_ignore_ascii_case(username))
+            .cloned())
+    }
+
+    fn subscribers(&self, product_id: i64) -> anyhow::Result<Vec<Subscription>> {
+        Ok(self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|s| s.product_id == product_id)
+            .cloned()
+            .collect())
+    }
+
+    fn subscriptions_for_user(&self, username: &str) -> anyhow::Result<Vec<Subscription>> {
+        Ok(self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|s| s.username.eq_ignore_ascii_case(username))
+            .cloned()
+            .collect())
+    }
+
+    fn subscribe(&self, subscription: NewSubscription) -> anyhow::Result<Subscription> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        if let Some(existing) = subscriptions.iter_mut().find(|s| {
+            s.product_id == subscription.product_id
+                && s.username.eq_ignore_ascii_case(&subscription.username)
+        }) {
+            existing.target_price = subscription.target_price;
+            return Ok(existing.clone());
+        }
+
+        let subscription = Subscription {
+            product_id: subscription.product_id,
+            username: subscription.username,
+            target_price: subscription.target_price,
+            created_at: SystemTime::now(),
+        };
+        subscriptions.push(subscription.clone());
+        Ok(subscription)
+    }
+
+    fn unsubscribe(&self, product_id: i64, username: &str) -> anyhow::Result<bool> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let before = subscriptions.len();
+        subscriptions
+            .retain(|s| !(s.product_id == product_id && s.username.eq_ignore_ascii_case(username)));
+        Ok(subscriptions.len() < before)
+    }
+}
+
+/// Accounts kept in a `HashMap`, gone on restart
+#[derive(Debug, Default)]
+pub struct InMemoryUserRepository {
+    accounts: Mutex<HashMap<String, Account>>,
+}
+
+impl UserRepository for InMemoryUserRepository {
+    fn get(&self, username: &str) -> anyhow::Result<Option<Account>> {
+        Ok(self
+            .accounts
+            .lock()
+            .unwrap()
+            .get(&username.to_lowercase())
+            .cloned())
+    }
+
+    fn list(&self) -> anyhow::Result<Vec<Account>> {
+        let mut accounts: Vec<_> = self.accounts.lock().unwrap().values().cloned().collect();
+        accounts.sort_by_key(|account| account.created_at);
+        Ok(accounts)
+    }
+
+    fn insert(&self, mut account: Account) -> anyhow::Result<Option<UserRole>> {
+        let mut accounts = self.accounts.lock().unwrap();
+        let key = account.user.username.to_lowercase();
+        if accounts.contains_key(&key) {
+            return Ok(None);
+        }
+        if accounts.is_empty() {
+            account.user.role = UserRole::Admin;
+        }
+        let role = account.user.role;
+        accounts.insert(key, account);
+        Ok(Some(role))
+    }
+
+    fn record_login_result(
+        &self,
+        username: &str,
+        succeeded: bool,
+        max_failures: u32,
+        lock_until: SystemTime,
+    ) -> anyhow::Result<bool> {
+        let mut accounts = self.accounts.lock().unwrap();
+        let Some(account) = accounts.get_mut(&username.to_lowercase()) else {
+            return Ok(false);
+        };
+        if succeeded {
+            account.failed_logins = 0;
+            account.locked_until = None;
+            return Ok(false);
+        }
+        account.failed_logins += 1;
+        if account.failed_logins < max_failures {
+            return Ok(false);
+        }
+        account.failed_logins = 0;
+        account.locked_until = Some(lock_until);
+        Ok(true)
+    }
+
+    fn set_password_hash(&self, username: &str, password_hash: &str) -> anyhow::Result<()> {
+        if let Some(account) = self
+            .accounts
+            .lock()
+            .unwrap()
+            .get_mut(&username.to_lowercase())
+        {
+            account.password_hash = password_hash.to_string();
+        }
+        Ok(())
+    }
+
+    fn set_role(
+        &self,
+        username: &str,
+        role: UserRole,
+    ) -> anyhow::Result<Result<User, SetRoleError>> {
+        let mut accounts = self.accounts.lock().unwrap();
+        let admins = accounts
+            .values()
+            .filter(|account| account.user.role == UserRole::Admin)
+            .count();
+        let Some(account) = accounts.get_mut(&username.to_lowercase()) else {
+            return Ok(Err(SetRoleError::UnknownUser));
+        };
+        if account.user.role == UserRole::Admin && role != UserRole::Admin && admins <= 1 {
+            return Ok(Err(SetRoleError::LastAdmin));
+        }
+        account.user.role = role;
+        Ok(Ok(account.user.clone()))
+    }
+}
+
+/// Observations kept in a `Vec`, gone on restart
+#[derive(Debug, Default)]
+pub struct InMemoryObservationRepository {
+    observations: Mutex<Vec<Observation>>,
+}
+
+impl ObservationRepository for InMemoryObservationRepository {
+    fn record(&self, observation: NewObservation) -> anyhow::Result<Observation> {
+        let mut observations = self.observations.lock().unwrap();
+        let observation = Observation {
+            id: observations.last().map_or(1, |o| o.id + 1),
+            product_id: observation.product_id,
+            observed_at: observation.observed_at,
+            price: observation.price,
+            stock: observation.stock,
+            error: observation.error,
+            ships_from: observation.ships_from,
+            sold_by: observation.sold_by,
+            third_party_seller: observation.third_party_seller,
+        };
+        observations.push(observation.clone());
+        Ok(observation)
+    }
+
+    fn latest(&self, product_id: i64) -> anyhow::Result<Option<Observation>> {
+        Ok(self
+            .observations
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|o| o.product_id == product_id)
+            .cloned())
+    }
+
+    fn latest_successful(&self, product_id: i64) -> anyhow::Result<Option<Observation>> {
+        Ok(self
+            .observations
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|o| o.product_id == product_id && o.error.is_none())
+            .cloned())
+    }
+
+    fn history(&self, product_id: i64) -> anyhow::Result<Vec<Observation>> {
+        let mut history: Vec<_> = self
+            .observations
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|o| o.product_id == product_id)
+            .cloned()
+            .collect();
+        history.sort_by_key(|o| o.observed_at);
+        Ok(history)
+    }
+}
+
+/// Alerts kept in a `Vec`, gone on restart
+#[derive(Debug, Default)]
+pub struct InMemoryAlertRepository {
+    alerts: Mutex<Vec<Alert>>,
+}
+
+impl AlertRepository for InMemoryAlertRepository {
+    fn record(&self, alert: NewAlert) -> anyhow::Result<Alert> {
+        let mut alerts = self.alerts.lock().unwrap();
+        let alert = Alert {
+            id: alerts.last().map_or(1, |a| a.id + 1),
+            product_id: alert.product_id,
+            username: alert.username,
+            kind: alert.kind,
+            triggered_at: alert.triggered_at,
+            price: alert.price,
+            previous_price: alert.previous_price,
+            message: alert.message,
+        };
+        alerts.push(alert.clone());
+        Ok(alert)
+    }
+
+    fn latest(
+        &self,
+        product_id: i64,
+        kind: AlertKind,
+        username: Option<&str>,
+    ) -> anyhow::Result<Option<Alert>> {
+        Ok(self
+            .alerts
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|a| {
+                a.product_id == product_id
+                    && a.kind == kind
+                    && match (a.username.as_deref(), username) {
+                        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+                        (a, b) => a == b,
+                    }
+            })
+            .cloned())
+    }
+
+    fn history(&self, product_id: i64) -> anyhow::Result<Vec<Alert>> {
+        Ok(self
+            .alerts
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|a| a.product_id == product_id)
+            .cloned()
+            .collect())
+    }
+}
+
+/// Notification preferences kept in a `HashMap`, gone on restart
+#[derive(Debug, Default)]
+pub struct InMemoryPreferencesRepository {
+    preferences: Mutex<HashMap<String, NotificationPreferences>>,
+}
+
+impl PreferencesRepository for InMemoryPreferencesRepository {
+    fn get(&self, username: &str) -> anyhow::Result<Option<NotificationPreferences>> {
+        Ok(self
+            .preferences
+            .lock()
+            .unwrap()
+            .get(&username.to_lowercase())
+            .cloned())
+    }
+
+    fn save(&self, preferences: &NotificationPreferences) -> anyhow::Result<()> {
+        self.preferences
+            .lock()
+            .unwrap()
+            .insert(preferences.username.to_lowercase(), preferences.clone());
+        Ok(())
+    }
+}
+
+/// Webhooks and their delivery log kept in `Vec`s, gone on restart
+#[derive(Debug, Default)]
+pub struct InMemoryWebhookRepository {
+    webhooks: Mutex<Vec<Webhook>>,
+    deliveries: Mutex<Vec<WebhookDelivery>>,
+}
+
+impl WebhookRepository for InMemoryWebhookRepository {
+    fn list_for_user(&self, username: &str) -> anyhow::Result<Vec<Webhook>> {
+        Ok(self
+            .webhooks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|w| w.username.eq_ignore_ascii_case(username))
+            .cloned()
+            .collect())
+    }
+
+    fn add(&self, webhook: NewWebhook) -> anyhow::Result<Webhook> {
+        let mut webhooks = self.webhooks.lock().unwrap();
+        let webhook = Webhook {
+            id: webhooks.last().map_or(1, |w| w.id + 1),
+            username: webhook.username,
+            url: webhook.url,
+            format: webhook.format,
+            secret: webhook.secret,
+            created_at: SystemTime::now(),
+        };
+        webhooks.push(webhook.clone());
+        Ok(webhook)
+    }
+
+    fn delete(&self, id: i64, username: &str) -> anyhow::Result<bool> {
+        let mut webhooks = self.webhooks.lock().unwrap();
+        let before = webhooks.len();
+        webhooks.retain(|w| !(w.id == id && w.username.eq_ignore_ascii_case(username)));
+        if webhooks.len() == before {
+            return Ok(false);
+        }
+        self.deliveries
+            .lock()
+            .unwrap()
+            .retain(|d| d.webhook_id != id);
+        Ok(true)
+    }
+
+    fn record_delivery(&self, delivery: NewWebhookDelivery) -> anyhow::Result<()> {
+        let webhook_url = self
+            .webhooks
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|w| w.id == delivery.webhook_id)
+            .map(|w| w.url.clone())
+            .unwrap_or_default();
+        let mut deliveries = self.deliveries.lock().unwrap();
+        let delivery = WebhookDelivery {
+            id: deliveries.last().map_or(1, |d| d.id + 1),
+            webhook_id: delivery.webhook_id,
+            webhook_url,
+            product_id: delivery.product_id,
+            alert_kind: delivery.alert_kind,
+            message: delivery.message,
+            attempted_at: delivery.attempted_at,
+            attempts: delivery.attempts,
+            status_code: delivery.status_code,
+            error: delivery.error,
+        };
+        deliveries.push(delivery);
+        Ok(())
+    }
+
+    fn recent_deliveries(
+        &self,
+        username: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<WebhookDelivery>> {
+        let ids: Vec<i64> = self.list_for_user(username)?.iter().map(|w| w.id).collect();
+        Ok(self
+            .deliveries
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|d| ids.contains(&d.webhook_id))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+}
+
+/// API tokens kept in a `Vec`, gone on restart
+#[derive(Debug, Default)]
+pub struct InMemoryTokenRepository {
+    tokens: Mutex<Vec<ApiToken>>,
+    last_id: AtomicI64,
+}
+
+impl TokenRepository for InMemoryTokenRepository {
+    fn list_for_user(&self, username: &str) -> anyhow::Result<Vec<ApiToken>> {
+        Ok(self
+            .tokens
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|t| t.username.eq_ignore_ascii_case(username))
+            .cloned()
+            .collect())
+    }
+
+    fn add(&self, token: NewApiToken) -> anyhow::Result<ApiToken> {
+        let token = ApiToken {
+            id: self.last_id.fetch_add(1, Ordering::SeqCst) + 1,
+            username: token.username,
+            name: token.name,
+            scope: token.scope,
+            token_hash: token.token_hash,
+            display_prefix: token.display_prefix,
+            created_at: SystemTime::now(),
+            last_used_at: None,
+        };
+        self.tokens.lock().unwrap().push(token.clone());
+        Ok(token)
+    }
+
+    fn find_by_hash(&self, token_hash: &str) -> anyhow::Result<Option<ApiToken>> {
+        Ok(self
+            .tokens
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|t| t.token_hash == token_hash)
+            .cloned())
+    }
+
+    fn touch(&self, id: i64, used_at: SystemTime) -> anyhow::Result<()> {
+        if let Some(token) = self.tokens.lock().unwrap().iter_mut().find(|t| t.id == id) {
+            token.last_used_at = Some(used_at);
+        }
+        Ok(())
+    }
+
+    fn delete(&self, id: i64, username: &str) -> anyhow::Result<bool> {
+        let mut tokens = self.tokens.lock().unwrap();
+        let before = tokens.len();
+        tokens.retain(|t| !(t.id == id && t.username.eq_ignore_ascii_case(username)));
+        Ok(tokens.len() != before)
+    }
+}
+
+/// Auto-buy rules and checkout runs kept in `Vec`s, gone on restart
+#[derive(Debug, Default)]
+pub struct InMemoryCheckoutRepository {
+    rules: Mutex<Vec<AutoBuyRule>>,
+    runs: Mutex<Vec<CheckoutRun>>,
+}
+
+fn set_rule_enabled(rules: &mut [AutoBuyRule], product_id: i64, username: &str, enabled: bool) {
+    let rule = rules
+        .iter_mut()
+        .find(|r| r.product_id == product_id && r.username.eq_ignore_ascii_case(username));
+    if let Some(rule) = rule {
+        rule.enabled = enabled;
+        rule.updated_at = SystemTime::now();
+    }
+}
+
+impl CheckoutRepository for InMemoryCheckoutRepository {
+    fn rule(&self, product_id: i64, username: &str) -> anyhow::Result<Option<AutoBuyRule>> {
+        Ok(self
+            .rules
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|r| r.product_id == product_id && r.username.eq_ignore_ascii_case(username))
+            .cloned())
+    }
+
+    fn save_rule(&self, rule: &AutoBuyRule) -> anyhow::Result<()> {
+        let mut rules = self.rules.lock().unwrap();
+        rules.retain(|r| {
+            !(r.product_id == rule.product_id && r.username.eq_ignore_ascii_case(&rule.username))
+        });
+        rules.push(rule.clone());
+        Ok(())
+    }
+
+    fn set_rule_enabled(
+        &self,
+        product_id: i64,
+        username: &str,
+        enabled: bool,
+    ) -> anyhow::Result<()> {
+        set_rule_enabled(
+            &mut self.rules.lock().unwrap(),
+            product_id,
+            username,
+            enabled,
+        );
+        Ok(())
+    }
+
+    fn record_run(&self, run: NewCheckoutRun) -> anyhow::Result<CheckoutRun> {
+        let mut rules = self.rules.lock().unwrap();
+        let mut runs = self.runs.lock().unwrap();
+        if let Some(enabled) = run.rule_enabled {
+            set_rule_enabled(&mut rules, run.product_id, &run.username, enabled);
+        }
+        let run = CheckoutRun {
+            id: runs.last().map_or(1, |r| r.id + 1),
+            product_id: run.product_id,
+            username: run.username,
+            started_at: run.started_at,
+            dry_run: run.dry_run,
+            status: run.status,
+            quantity: run.quantity,
+            total: run.total,
+            order_number: run.order_number,
+            steps: run.steps,
+        };
+        runs.push(run.clone());
+        Ok(run)
+    }
+
+    fn recent_runs(
+        &self,
+        product_id: i64,
+        username: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<CheckoutRun>> {
+        Ok(self
+            .runs
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|r| r.product_id == product_id && r.username.eq_ignore_ascii_case(username))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+}
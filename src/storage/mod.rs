@@ -0,0 +1,435 @@
+mod memory;
+mod sqlite;
+
+pub use memory::{
+    InMemoryAlertRepository, InMemoryCheckoutRepository, InMemoryObservationRepository,
+    InMemoryPreferencesRepository, InMemoryProductRepository, InMemoryTokenRepository,
+    InMemoryUserRepository, InMemoryWebhookRepository,
+};
+pub use sqlite::Database;
+
+use crate::checkout::{AutoBuyRule, CheckoutRun, NewCheckoutRun};
+use crate::money::{Currency, Money};
+use crate::notify::{
+    NewWebhook, NewWebhookDelivery, NotificationPreferences, Webhook, WebhookDelivery,
+};
+use crate::tokens::{ApiToken, NewApiToken};
+use crate::users::{Account, SetRoleError, User, UserRole};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::info;
+
+/// A product page being tracked. Every user watching the same product shares one, so it's
+/// only fetched once; what each of them wants from it is in their `Subscription`.
+#[derive(Debug, Clone)]
+pub struct Product {
+    pub id: i64,
+    pub url: String,
+    pub name: String,
+    pub retailer: String,
+    /// The retailer's own id for the product (ASIN, Best Buy SKU) when the URL has one. No
+    /// two products share a retailer and id.
+    pub retailer_id: Option<String>,
+    /// What the retailer prices the product in
+    pub currency: Currency,
+    // Whoever added the product first; they and admins manage its details
+    pub added_by: String,
+    pub created_at: SystemTime,
+    // How often to check the product page; `None` uses the scheduler default
+    pub poll_interval: Option<Duration>,
+}
+
+/// A product as submitted, before the repository has assigned it an id
+#[derive(Debug, Clone)]
+pub struct NewProduct {
+    pub url: String,
+    pub name: String,
+    pub retailer: String,
+    pub retailer_id: Option<String>,
+    pub currency: Currency,
+    pub added_by: String,
+    pub poll_interval: Option<Duration>,
+}
+
+/// A user watching a product, with their own target price. Each user subscribes to a product
+/// at most once.
+#[derive(Debug, Clone)]
+pub struct Subscription {
+    pub product_id: i64,
+    pub username: String,
+    /// In the product's currency when it was set
+    pub target_price: Option<Money>,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewSubscription {
+    pub product_id: i64,
+    pub username: String,
+    pub target_price: Option<Money>,
+}
+
+/// Whether a product could be bought when it was last checked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StockState {
+    InStock,
+    OutOfStock,
+    Unknown,
+}
+
+impl StockState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StockState::InStock => "in_stock",
+            StockState::OutOfStock => "out_of_stock",
+            StockState::Unknown => "unknown",
+        }
+    }
+
+    pub fn parse(state: &str) -> StockState {
+        match state {
+            "in_stock" => StockState::InStock,
+            "out_of_stock" => StockState::OutOfStock,
+            _ => StockState::Unknown,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StockState::InStock => "In stock",
+            StockState::OutOfStock => "Out of stock",
+            StockState::Unknown => "Unknown",
+        }
+    }
+}
+
+/// One check of a product page by the scheduler
+#[derive(Debug, Clone)]
+pub struct Observation {
+    pub id: i64,
+    pub product_id: i64,
+    pub observed_at: SystemTime,
+    /// In whatever currency the page showed, normally the product's
+    pub price: Option<Money>,
+    pub stock: StockState,
+    // Set when the page couldn't be fetched or parsed
+    pub error: Option<String>,
+    pub ships_from: Option<String>,
+    pub sold_by: Option<String>,
+    // The listing was sold by a marketplace seller, not the retailer itself
+    pub third_party_seller: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewObservation {
+    pub product_id: i64,
+    pub observed_at: SystemTime,
+    pub price: Option<Money>,
+    pub stock: StockState,
+    pub error: Option<String>,
+    pub ships_from: Option<String>,
+    pub sold_by: Option<String>,
+    pub third_party_seller: bool,
+}
+
+/// What made an alert fire
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlertKind {
+    /// The price reached the product's target price
+    TargetReached,
+    /// The price fell by at least the configured percentage since the previous check
+    PriceDrop,
+    /// The product went from out of stock to in stock
+    BackInStock,
+}
+
+impl AlertKind {
+    pub const ALL: [AlertKind; 3] = [
+        AlertKind::TargetReached,
+        AlertKind::PriceDrop,
+        AlertKind::BackInStock,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertKind::TargetReached => "target_reached",
+            AlertKind::PriceDrop => "price_drop",
+            AlertKind::BackInStock => "back_in_stock",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<AlertKind> {
+        AlertKind::ALL.into_iter().find(|k| k.as_str() == kind)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AlertKind::TargetReached => "Target price reached",
+            AlertKind::PriceDrop => "Price drop",
+            AlertKind::BackInStock => "Back in stock",
+        }
+    }
+}
+
+/// An alert raised for a product
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub id: i64,
+    pub product_id: i64,
+    /// The subscriber whose target price was reached; `None` for alerts about the product
+    /// itself, which go to every subscriber
+    pub username: Option<String>,
+    pub kind: AlertKind,
+    pub triggered_at: SystemTime,
+    pub price: Option<Money>,
+    // Price at the check before, for price drops. Always in the same currency as `price`.
+    pub previous_price: Option<Money>,
+    pub message: String,
+}
+
+impl Alert {
+    /// Whether `username` should see this alert
+    pub fn is_for(&self, username: &str) -> bool {
+        self.username
+            .as_deref()
+            .is_none_or(|u| u.eq_ignore_ascii_case(username))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewAlert {
+    pub product_id: i64,
+    pub username: Option<String>,
+    pub kind: AlertKind,
+    pub triggered_at: SystemTime,
+    pub price: Option<Money>,
+    pub previous_price: Option<Money>,
+    pub message: String,
+}
+
+/// Storage for tracked products and users' subscriptions to them. Listings are returned
+/// oldest first.
+pub trait ProductRepository: Send + Sync {
+    fn get(&self, id: i64) -> anyhow::Result<Option<Product>>;
+
+    fn list(&self) -> anyhow::Result<Vec<Product>>;
+
+    /// The products `username` is subscribed to
+    fn list_for_user(&self, username: &str) -> anyhow::Result<Vec<Product>>;
+
+    /// The product with this retailer id, or failing that with exactly this canonical URL
+    fn find_existing(
+        &self,
+        retailer: &str,
+        retailer_id: Option<&str>,
+        url: &str,
+    ) -> anyhow::Result<Option<Product>>;
+
+    fn add(&self, product: NewProduct) -> anyhow::Result<Product>;
+
+    /// Save changes to a product's details, returning `false` if it doesn't exist. The id,
+    /// owner and creation time never change.
+    fn update(&self, product: &Product) -> anyhow::Result<bool>;
+
+    /// Delete a product and every subscription to it, returning `false` if it doesn't
+    /// exist. Ids of deleted products are never handed out again, so history left behind
+    /// can't attach to a new product.
+    fn delete(&self, id: i64) -> anyhow::Result<bool>;
+
+    fn subscription(&self, product_id: i64, username: &str)
+    -> anyhow::Result<Option<Subscription>>;
+
+    /// Everyone subscribed to a product, oldest subscription first
+    fn subscribers(&self, product_id: i64) -> anyhow::Result<Vec<Subscription>>;
+
+    fn subscriptions_for_user(&self, username: &str) -> anyhow::Result<Vec<Subscription>>;
+
+    /// Subscribe a user to a product, or change their target price if they already are
+    fn subscribe(&self, subscription: NewSubscription) -> anyhow::Result<Subscription>;
+
+    /// Returns `false` if the user wasn't subscribed
+    fn unsubscribe(&self, product_id: i64, username: &str) -> anyhow::Result<bool>;
+}
+
+/// Storage for user accounts, keyed by lowercased username
+pub trait UserRepository: Send + Sync {
+    fn get(&self, username: &str) -> anyhow::Result<Option<Account>>;
+
+    /// All accounts, oldest first
+    fn list(&self) -> anyhow::Result<Vec<Account>>;
+
+    /// Insert a new account, as an admin instead of its own role if there are no accounts
+    /// yet. Returns the role it got, or `None` if the username is already taken.
+    fn insert(&self, account: Account) -> anyhow::Result<Option<UserRole>>;
+
+    /// Count a sign-in attempt against the account: a success clears its failures, and the
+    /// `max_failures`th failure in a row locks it until `lock_until`. Returns whether it's
+    /// now locked.
+    fn record_login_result(
+        &self,
+        username: &str,
+        succeeded: bool,
+        max_failures: u32,
+        lock_until: SystemTime,
+    ) -> anyhow::Result<bool>;
+
+    fn set_password_hash(&self, username: &str, password_hash: &str) -> anyhow::Result<()>;
+
+    /// Give a user a new role, in one step with checking they aren't the last admin
+    fn set_role(
+        &self,
+        username: &str,
+        role: UserRole,
+    ) -> anyhow::Result<Result<User, SetRoleError>>;
+}
+
+/// Storage for the results of product page checks
+pub trait ObservationRepository: Send + Sync {
+    fn record(&self, observation: NewObservation) -> anyhow::Result<Observation>;
+
+    /// The most recent observation of a product, if it has ever been checked
+    fn latest(&self, product_id: i64) -> anyhow::Result<Option<Observation>>;
+
+    /// The most recent observation of a product that didn't fail
+    fn latest_successful(&self, product_id: i64) -> anyhow::Result<Option<Observation>>;
+
+    /// Every observation of a product, oldest first
+    fn history(&self, product_id: i64) -> anyhow::Result<Vec<Observation>>;
+}
+
+/// Storage for raised alerts
+pub trait AlertRepository: Send + Sync {
+    fn record(&self, alert: NewAlert) -> anyhow::Result<Alert>;
+
+    /// The most recent alert of a given kind for a product, limited to `username`'s alerts
+    /// when given and to product-wide ones otherwise
+    fn latest(
+        &self,
+        product_id: i64,
+        kind: AlertKind,
+        username: Option<&str>,
+    ) -> anyhow::Result<Option<Alert>>;
+
+    /// Every alert raised for a product, newest first
+    fn history(&self, product_id: i64) -> anyhow::Result<Vec<Alert>>;
+}
+
+/// Storage for users' notification preferences
+pub trait PreferencesRepository: Send + Sync {
+    /// `None` if the user has never saved any
+    fn get(&self, username: &str) -> anyhow::Result<Option<NotificationPreferences>>;
+
+    /// Insert or overwrite a user's preferences
+    fn save(&self, preferences: &NotificationPreferences) -> anyhow::Result<()>;
+}
+
+/// Storage for users' outgoing webhooks and the log of deliveries to them
+pub trait WebhookRepository: Send + Sync {
+    /// A user's webhooks, oldest first
+    fn list_for_user(&self, username: &str) -> anyhow::Result<Vec<Webhook>>;
+
+    fn add(&self, webhook: NewWebhook) -> anyhow::Result<Webhook>;
+
+    /// Delete one of `username`'s webhooks along with its delivery log, returning `false` if
+    /// they have no webhook with that id
+    fn delete(&self, id: i64, username: &str) -> anyhow::Result<bool>;
+
+    fn record_delivery(&self, delivery: NewWebhookDelivery) -> anyhow::Result<()>;
+
+    /// The latest deliveries to any of `username`'s webhooks, newest first
+    fn recent_deliveries(
+        &self,
+        username: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<WebhookDelivery>>;
+}
+
+/// Storage for personal API tokens
+pub trait TokenRepository: Send + Sync {
+    /// A user's tokens, oldest first
+    fn list_for_user(&self, username: &str) -> anyhow::Result<Vec<ApiToken>>;
+
+    fn add(&self, token: NewApiToken) -> anyhow::Result<ApiToken>;
+
+    /// The token whose secret hashes to `token_hash`, if it exists and hasn't been revoked
+    fn find_by_hash(&self, token_hash: &str) -> anyhow::Result<Option<ApiToken>>;
+
+    /// Record that a token was just used to authenticate a request
+    fn touch(&self, id: i64, used_at: SystemTime) -> anyhow::Result<()>;
+
+    /// Revoke one of `username`'s tokens, returning `false` if they have no token with that id
+    fn delete(&self, id: i64, username: &str) -> anyhow::Result<bool>;
+}
+
+/// Storage for auto-buy rules and the log of checkout runs
+pub trait CheckoutRepository: Send + Sync {
+    /// `username`'s auto-buy rule for a product, if they've set one up
+    fn rule(&self, product_id: i64, username: &str) -> anyhow::Result<Option<AutoBuyRule>>;
+
+    /// Insert or overwrite a user's rule for a product
+    fn save_rule(&self, rule: &AutoBuyRule) -> anyhow::Result<()>;
+
+    /// Turn a user's rule on or off, leaving the rest of it as it is
+    fn set_rule_enabled(
+        &self,
+        product_id: i64,
+        username: &str,
+        enabled: bool,
+    ) -> anyhow::Result<()>;
+
+    /// Store a run and its steps, turning the rule on or off as the run says, all at once
+    fn record_run(&self, run: NewCheckoutRun) -> anyhow::Result<CheckoutRun>;
+
+    /// `username`'s latest checkout runs for a product, steps included, newest first
+    fn recent_runs(
+        &self,
+        product_id: i64,
+        username: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<CheckoutRun>>;
+}
+
+/// The repositories the app runs on
+#[derive(Clone)]
+pub struct Repositories {
+    pub products: Arc<dyn ProductRepository>,
+    pub users: Arc<dyn UserRepository>,
+    pub observations: Arc<dyn ObservationRepository>,
+    pub alerts: Arc<dyn AlertRepository>,
+    pub preferences: Arc<dyn PreferencesRepository>,
+    pub webhooks: Arc<dyn WebhookRepository>,
+    pub tokens: Arc<dyn TokenRepository>,
+    pub checkouts: Arc<dyn CheckoutRepository>,
+}
+
+/// Open the SQLite database at `path`, creating it if needed.
+///
+/// `:memory:` keeps everything in memory and loses it on restart.
+pub fn open(path: &str) -> anyhow::Result<Repositories> {
+    if path == ":memory:" {
+        info!("Using in-memory storage, data will not survive a restart");
+        return Ok(Repositories {
+            products: Arc::new(InMemoryProductRepository::default()),
+            users: Arc::new(InMemoryUserRepository::default()),
+            observations: Arc::new(InMemoryObservationRepository::default()),
+            alerts: Arc::new(InMemoryAlertRepository::default()),
+            preferences: Arc::new(InMemoryPreferencesRepository::default()),
+            webhooks: Arc::new(InMemoryWebhookRepository::default()),
+            tokens: Arc::new(InMemoryTokenRepository::default()),
+            checkouts: Arc::new(InMemoryCheckoutRepository::default()),
+        });
+    }
+
+    let database = Database::open(path)?;
+    info!("Using SQLite database at {}", path);
+    Ok(Repositories {
+        products: Arc::new(database.products()),
+        users: Arc::new(database.users()),
+        observations: Arc::new(database.observations()),
+        alerts: Arc::new(database.alerts()),
+        preferences: Arc::new(database.preferences()),
+        webhooks: Arc::new(database.webhooks()),
+        tokens: Arc::new(database.tokens()),
+        checkouts: Arc::new(database.checkouts()),
+    })
+}
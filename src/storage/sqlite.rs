@@ -0,0 +1,1350 @@
+use super::{
+    Alert, AlertKind, AlertRepository, CheckoutRepository, NewAlert, NewObservation, NewProduct,
+    NewSubscription, Observation, ObservationRepository, PreferencesRepository, Product,
+    ProductRepository, StockState, Subscription, TokenRepository, UserRepository,
+    WebhookRepository,
+};
+use crate::checkout::{AutoBuyRule, CheckoutRun, CheckoutStatus, CheckoutStep, NewCheckoutRun};
+use crate::money::{Currency, Money};
+use crate::notify::{
+    NewWebhook, NewWebhookDelivery, NotificationPreferences, Webhook, WebhookDelivery,
+    WebhookFormat,
+};
+use crate::tokens::{ApiToken, NewApiToken, TokenScope};
+use crate::users::{Account, SetRoleError, User, UserRole};
+use anyhow::Context;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+/// Schema migrations, applied in order. `PRAGMA user_version` records how many have run,
+/// so only ever append to this list.
+const MIGRATIONS: &[&str] = &[
+    // 1: products and user accounts
+    "CREATE TABLE products (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        url TEXT NOT NULL,
+        name TEXT NOT NULL,
+        retailer TEXT NOT NULL,
+        target_price REAL,
+        added_by TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX products_added_by ON products (added_by);
+
+    CREATE TABLE users (
+        username TEXT PRIMARY KEY COLLATE NOCASE,
+        role TEXT NOT NULL,
+        password_hash TEXT NOT NULL,
+        failed_logins INTEGER NOT NULL DEFAULT 0,
+        locked_until INTEGER,
+        created_at INTEGER NOT NULL
+    );",
+    // 2: per-product polling intervals and page check results
+    "ALTER TABLE products ADD COLUMN poll_interval_secs INTEGER;
+
+    CREATE TABLE observations (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        product_id INTEGER NOT NULL REFERENCES products (id) ON DELETE CASCADE,
+        observed_at INTEGER NOT NULL,
+        price REAL,
+        stock TEXT NOT NULL,
+        error TEXT
+    );
+    CREATE INDEX observations_product ON observations (product_id, observed_at);",
+    // 3: who was selling the listing at each check
+    "ALTER TABLE observations ADD COLUMN ships_from TEXT;
+    ALTER TABLE observations ADD COLUMN sold_by TEXT;
+    ALTER TABLE observations ADD COLUMN third_party_seller INTEGER NOT NULL DEFAULT 0;",
+    // 4: alert history
+    "CREATE TABLE alerts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        product_id INTEGER NOT NULL REFERENCES products (id) ON DELETE CASCADE,
+        kind TEXT NOT NULL,
+        triggered_at INTEGER NOT NULL,
+        price REAL,
+        previous_price REAL,
+        message TEXT NOT NULL
+    );
+    CREATE INDEX alerts_product ON alerts (product_id, kind, triggered_at);",
+    // 5: per-user notification preferences
+    "CREATE TABLE notification_preferences (
+        username TEXT PRIMARY KEY COLLATE NOCASE,
+        email TEXT,
+        email_enabled INTEGER NOT NULL DEFAULT 0,
+        alert_kinds TEXT NOT NULL
+    );",
+    // 6: outgoing webhooks and their delivery log
+    "CREATE TABLE webhooks (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        username TEXT NOT NULL COLLATE NOCASE,
+        url TEXT NOT NULL,
+        format TEXT NOT NULL,
+        secret TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX webhooks_username ON webhooks (username);
+
+    CREATE TABLE webhook_deliveries (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        webhook_id INTEGER NOT NULL REFERENCES webhooks (id) ON DELETE CASCADE,
+        product_id INTEGER NOT NULL,
+        alert_kind TEXT NOT NULL,
+        message TEXT NOT NULL,
+        attempted_at INTEGER NOT NULL,
+        attempts INTEGER NOT NULL,
+        status_code INTEGER,
+        error TEXT
+    );
+    CREATE INDEX webhook_deliveries_webhook ON webhook_deliveries (webhook_id, attempted_at);",
+    // 7: personal API tokens, stored as hashes
+    "CREATE TABLE api_tokens (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        username TEXT NOT NULL COLLATE NOCASE,
+        name TEXT NOT NULL,
+        scope TEXT NOT NULL,
+        token_hash TEXT NOT NULL UNIQUE,
+        display_prefix TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        last_used_at INTEGER
+    );
+    CREATE INDEX api_tokens_username ON api_tokens (username);",
+    // 8: products shared between users, who each subscribe with their own target price.
+    // Existing products become their adder's subscription and target alerts belong to them.
+    // Products already tracked twice stay separate, with no retailer id to clash on.
+    "ALTER TABLE products ADD COLUMN retailer_id TEXT;
+    CREATE UNIQUE INDEX products_retailer_id ON products (retailer, retailer_id);
+    CREATE INDEX products_url ON products (url);
+
+    CREATE TABLE subscriptions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        product_id INTEGER NOT NULL REFERENCES products (id) ON DELETE CASCADE,
+        username TEXT NOT NULL COLLATE NOCASE,
+        target_price REAL,
+        created_at INTEGER NOT NULL,
+        UNIQUE (product_id, username)
+    );
+    CREATE INDEX subscriptions_username ON subscriptions (username);
+    INSERT INTO subscriptions (product_id, username, target_price, created_at)
+        SELECT id, added_by, target_price, created_at FROM products;
+    ALTER TABLE products DROP COLUMN target_price;
+
+    ALTER TABLE alerts ADD COLUMN username TEXT COLLATE NOCASE;
+    UPDATE alerts SET username = (SELECT added_by FROM products WHERE products.id = alerts.product_id)
+        WHERE kind = 'target_reached';",
+    // 9: prices as whole minor units (cents, pence) alongside the currency they're in.
+    // Everything tracked until now was priced in US dollars.
+    "ALTER TABLE products ADD COLUMN currency TEXT NOT NULL DEFAULT 'USD';
+
+    ALTER TABLE subscriptions ADD COLUMN target_price_minor INTEGER;
+    ALTER TABLE subscriptions ADD COLUMN currency TEXT;
+    UPDATE subscriptions SET target_price_minor = CAST(ROUND(target_price * 100) AS INTEGER),
+        currency = 'USD' WHERE target_price IS NOT NULL;
+    ALTER TABLE subscriptions DROP COLUMN target_price;
+
+    ALTER TABLE observations ADD COLUMN price_minor INTEGER;
+    ALTER TABLE observations ADD COLUMN currency TEXT;
+    UPDATE observations SET price_minor = CAST(ROUND(price * 100) AS INTEGER), currency = 'USD'
+        WHERE price IS NOT NULL;
+    ALTER TABLE observations DROP COLUMN price;
+
+    ALTER TABLE alerts ADD COLUMN price_minor INTEGER;
+    ALTER TABLE alerts ADD COLUMN previous_price_minor INTEGER;
+    ALTER TABLE alerts ADD COLUMN currency TEXT;
+    UPDATE alerts SET price_minor = CAST(ROUND(price * 100) AS INTEGER),
+        previous_price_minor = CAST(ROUND(previous_price * 100) AS INTEGER), currency = 'USD'
+        WHERE price IS NOT NULL OR previous_price IS NOT NULL;
+    ALTER TABLE alerts DROP COLUMN price;
+    ALTER TABLE alerts DROP COLUMN previous_price;",
+    // 10: auto-buy rules, which go with the subscription they're for, and the step-by-step
+    // log of every checkout run
+    "CREATE TABLE auto_buy_rules (
+        product_id INTEGER NOT NULL,
+        username TEXT NOT NULL COLLATE NOCASE,
+        enabled INTEGER NOT NULL,
+        dry_run INTEGER NOT NULL,
+        max_quantity INTEGER NOT NULL,
+        max_spend_minor INTEGER NOT NULL,
+        currency TEXT NOT NULL,
+        updated_at INTEGER NOT NULL,
+        PRIMARY KEY (product_id, username),
+        FOREIGN KEY (product_id, username)
+            REFERENCES subscriptions (product_id, username) ON DELETE CASCADE
+    );
+
+    CREATE TABLE checkout_runs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        product_id INTEGER NOT NULL REFERENCES products (id) ON DELETE CASCADE,
+        username TEXT NOT NULL COLLATE NOCASE,
+        started_at INTEGER NOT NULL,
+        dry_run INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        quantity INTEGER NOT NULL,
+        total_minor INTEGER,
+        currency TEXT,
+        order_number TEXT
+    );
+    CREATE INDEX checkout_runs_product ON checkout_runs (product_id, username, started_at);
+
+    CREATE TABLE checkout_steps (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        run_id INTEGER NOT NULL REFERENCES checkout_runs (id) ON DELETE CASCADE,
+        at INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        ok INTEGER NOT NULL,
+        message TEXT NOT NULL
+    );
+    CREATE INDEX checkout_steps_run ON checkout_steps (run_id);",
+];
+
+pub(super) fn to_unix(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+pub(super) fn from_unix(secs: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64)
+}
+
+fn currency_from_row(row: &Row, index: usize) -> rusqlite::Result<Currency> {
+    let code: String = row.get(index)?;
+    Currency::parse(&code).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            index,
+            rusqlite::types::Type::Text,
+            format!("unknown currency {code:?}").into(),
+        )
+    })
+}
+
+// An amount stored as minor units in one column and its currency in another, both NULL when
+// there's no amount
+fn money_from_row(row: &Row, minor: usize, currency: usize) -> rusqlite::Result<Option<Money>> {
+    match row.get::<_, Option<i64>>(minor)? {
+        Some(amount) => Ok(Some(Money::new(amount, currency_from_row(row, currency)?))),
+        None => Ok(None),
+    }
+}
+
+/// A SQLite database file shared by all repositories
+#[derive(Clone)]
+pub struct Database {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Database {
+    /// Open (or create) the database at `path` and bring its schema up to date
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut conn = Connection::open(path)
+            .with_context(|| format!("Failed to open database {}", path.display()))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+        migrate(&mut conn)?;
+        Ok(Database {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// How many migrations have been applied
+    pub fn schema_version(&self) -> anyhow::Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+    }
+
+    pub fn products(&self) -> SqliteProductRepository {
+        SqliteProductRepository {
+            conn: self.conn.clone(),
+        }
+    }
+
+    pub fn users(&self) -> SqliteUserRepository {
+        SqliteUserRepository {
+            conn: self.conn.clone(),
+        }
+    }
+
+    pub fn observations(&self) -> SqliteObservationRepository {
+        SqliteObservationRepository {
+            conn: self.conn.clone(),
+        }
+    }
+
+    pub fn alerts(&self) -> SqliteAlertRepository {
+        SqliteAlertRepository {
+            conn: self.conn.clone(),
+        }
+    }
+
+    pub fn preferences(&self) -> SqlitePreferencesRepository {
+        SqlitePreferencesRepository {
+            conn: self.conn.clone(),
+        }
+    }
+
+    pub fn webhooks(&self) -> SqliteWebhookRepository {
+        SqliteWebhookRepository {
+            conn: self.conn.clone(),
+        }
+    }
+
+    pub fn tokens(&self) -> SqliteTokenRepository {
+        SqliteTokenRepository {
+            conn: self.conn.clone(),
+        }
+    }
+
+    pub fn checkouts(&self) -> SqliteCheckoutRepository {
+        SqliteCheckoutRepository {
+            conn: self.conn.clone(),
+        }
+    }
+}
+
+fn migrate(conn: &mut Connection) -> anyhow::Result<()> {
+    let applied: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let version = index + 1;
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)
+            .with_context(|| format!("Failed to apply database migration {}", version))?;
+        tx.pragma_update(None, "user_version", version)?;
+        tx.commit()?;
+        info!("Applied database migration {}", version);
+    }
+    Ok(())
+}
+
+pub struct SqliteProductRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+const PRODUCT_COLUMNS: &str =
+    "id, url, name, retailer, retailer_id, added_by, created_at, poll_interval_secs, currency";
+
+fn product_from_row(row: &Row) -> rusqlite::Result<Product> {
+    Ok(Product {
+        id: row.get(0)?,
+        url: row.get(1)?,
+        name: row.get(2)?,
+        retailer: row.get(3)?,
+        retailer_id: row.get(4)?,
+        added_by: row.get(5)?,
+        created_at: from_unix(row.get(6)?),
+        poll_interval: row
+            .get::<_, Option<i64>>(7)?
+            .map(|secs| Duration::from_secs(secs.max(0) as u64)),
+        currency: currency_from_row(row, 8)?,
+    })
+}
+
+impl ProductRepository for SqliteProductRepository {
+    fn get(&self, id: i64) -> anyhow::Result<Option<Product>> {
+        let conn = self.conn.lock().unwrap();
+        let product = conn
+            .query_row(
+                &format!("SELECT {PRODUCT_COLUMNS} FROM products WHERE id = ?1"),
+                [id],
+                product_from_row,
+            )
+            .optional()?;
+        Ok(product)
+    }
+
+    fn list(&self) -> anyhow::Result<Vec<Product>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {PRODUCT_COLUMNS} FROM products ORDER BY id"
+        ))?;
+        let products = stmt
+            .query_map([], product_from_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(products)
+    }
+
+    fn list_for_user(&self, username: &str) -> anyhow::Result<Vec<Product>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {PRODUCT_COLUMNS} FROM products
+             WHERE id IN (SELECT product_id FROM subscriptions WHERE username = ?1)
+             ORDER BY id"
+        ))?;
+        let products = stmt
+            .query_map([username], product_from_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(products)
+    }
+
+    // Products added before retailer ids were recorded can still be found by their URL
+    fn find_existing(
+        &self,
+        retailer: &str,
+        retailer_id: Option<&str>,
+        url: &str,
+    ) -> anyhow::Result<Option<Product>> {
+        let conn = self.conn.lock().unwrap();
+        let product = conn
+            .query_row(
+                &format!(
+                    "SELECT {PRODUCT_COLUMNS} FROM products
+                     WHERE (retailer = ?1 AND retailer_id = ?2) OR url = ?3
+                     ORDER BY retailer_id IS NULL, id LIMIT 1"
+                ),
+                params![retailer, retailer_id, url],
+                product_from_row,
+            )
+            .optional()?;
+        Ok(product)
+    }
+
+    fn add(&self, product: NewProduct) -> anyhow::Result<Product> {
+        let created_at = SystemTime::now();
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO products
+                (url, name, retailer, retailer_id, added_by, created_at, poll_interval_secs, currency)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                product.url,
+                product.name,
+                product.retailer,
+                product.retailer_id,
+                product.added_by,
+                to_unix(created_at),
+                product.poll_interval.map(|d| d.as_secs() as i64),
+                product.currency.code()
+            ],
+        )?;
+        Ok(Product {
+            id: conn.last_insert_rowid(),
+            url: product.url,
+            name: product.name,
+            retailer: product.retailer,
+            retailer_id: product.retailer_id,
+            currency: product.currency,
+            added_by: product.added_by,
+            created_at,
+            poll_interval: product.poll_interval,
+        })
+    }
+
+    fn update(&self, product: &Product) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE products
+             SET url = ?2, name = ?3, retailer = ?4, retailer_id = ?5, poll_interval_secs = ?6,
+                 currency = ?7
+             WHERE id = ?1",
+            params![
+                product.id,
+                product.url,
+                product.name,
+                product.retailer,
+                product.retailer_id,
+                product.poll_interval.map(|d| d.as_secs() as i64),
+                product.currency.code()
+            ],
+        )?;
+        Ok(updated == 1)
+    }
+
+    // Observations, alerts and subscriptions go with the product through ON DELETE CASCADE,
+    // and AUTOINCREMENT keeps the id from being reused
+    fn delete(&self, id: i64) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute("DELETE FROM products WHERE id = ?1", [id])?;
+        Ok(deleted == 1)
+    }
+
+    fn subscription(
+        &self,
+        product_id: i64,
+        username: &str,
+    ) -> anyhow::Result<Option<Subscription>> {
+        let conn = self.conn.lock().unwrap();
+        let subscription = conn
+            .query_row(
+                &format!(
+                    "SELECT {SUBSCRIPTION_COLUMNS} FROM subscriptions
+                     WHERE product_id = ?1 AND username = ?2"
+                ),
+                params![product_id, username],
+                subscription_from_row,
+            )
+            .optional()?;
+        Ok(subscription)
+    }
+
+    fn subscribers(&self, product_id: i64) -> anyhow::Result<Vec<Subscription>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SUBSCRIPTION_COLUMNS} FROM subscriptions WHERE product_id = ?1 ORDER BY id"
+        ))?;
+        let subscriptions = stmt
+            .query_map([product_id], subscription_from_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(subscriptions)
+    }
+
+    fn subscriptions_for_user(&self, username: &str) -> anyhow::Result<Vec<Subscription>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SUBSCRIPTION_COLUMNS} FROM subscriptions WHERE username = ?1 ORDER BY id"
+        ))?;
+        let subscriptions = stmt
+            .query_map([username], subscription_from_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(subscriptions)
+    }
+
+    fn subscribe(&self, subscription: NewSubscription) -> anyhow::Result<Subscription> {
+        let conn = self.conn.lock().unwrap();
+        let subscription = conn.query_row(
+            &format!(
+                "INSERT INTO subscriptions
+                    (product_id, username, target_price_minor, currency, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (product_id, username) DO UPDATE
+                    SET target_price_minor = excluded.target_price_minor,
+                        currency = excluded.currency
+                 RETURNING {SUBSCRIPTION_COLUMNS}"
+            ),
+            params![
+                subscription.product_id,
+                subscription.username,
+                subscription.target_price.map(Money::minor),
+                subscription.target_price.map(|p| p.currency().code()),
+                to_unix(SystemTime::now())
+            ],
+            subscription_from_row,
+        )?;
+        Ok(subscription)
+    }
+
+    fn unsubscribe(&self, product_id: i64, username: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM subscriptions WHERE product_id = ?1 AND username = ?2",
+            params![product_id, username],
+        )?;
+        Ok(deleted == 1)
+    }
+}
+
+const SUBSCRIPTION_COLUMNS: &str = "product_id, username, target_price_minor, currency, created_at";
+
+fn subscription_from_row(row: &Row) -> rusqlite::Result<Subscription> {
+    Ok(Subscription {
+        product_id: row.get(0)?,
+        username: row.get(1)?,
+        target_price: money_from_row(row, 2, 3)?,
+        created_at: from_unix(row.get(4)?),
+    })
+}
+
+pub struct SqliteUserRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+const USER_COLUMNS: &str = "username, role, password_hash, failed_logins, locked_until, created_at";
+
+fn account_from_row(row: &Row) -> rusqlite::Result<Account> {
+    let role: String = row.get(1)?;
+    Ok(Account {
+        user: User {
+            username: row.get(0)?,
+            role: UserRole::parse(&role).unwrap_or(UserRole::Regular),
+        },
+        password_hash: row.get(2)?,
+        failed_logins: row.get(3)?,
+        locked_until: row.get::<_, Option<i64>>(4)?.map(from_unix),
+        created_at: from_unix(row.get(5)?),
+    })
+}
+
+impl UserRepository for SqliteUserRepository {
+    fn get(&self, username: &str) -> anyhow::Result<Option<Account>> {
+        let conn = self.conn.lock().unwrap();
+        let account = conn
+            .query_row(
+                &format!("SELECT {USER_COLUMNS} FROM users WHERE username = ?1"),
+                [username],
+                account_from_row,
+            )
+            .optional()?;
+        Ok(account)
+    }
+
+    fn list(&self) -> anyhow::Result<Vec<Account>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {USER_COLUMNS} FROM users ORDER BY created_at, username"
+        ))?;
+        let accounts = stmt
+            .query_map([], account_from_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(accounts)
+    }
+
+    fn insert(&self, account: Account) -> anyhow::Result<Option<UserRole>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let inserted = tx.execute(
+            "INSERT INTO users (username, role, password_hash, failed_logins, locked_until, created_at)
+             VALUES (?1, CASE WHEN EXISTS (SELECT 1 FROM users) THEN ?2 ELSE 'admin' END,
+                     ?3, ?4, ?5, ?6)
+             ON CONFLICT (username) DO NOTHING",
+            params![
+                account.user.username,
+                account.user.role.as_str(),
+                account.password_hash,
+                account.failed_logins,
+                account.locked_until.map(to_unix),
+                to_unix(account.created_at)
+            ],
+        )?;
+        if inserted == 0 {
+            return Ok(None);
+        }
+        let role: String = tx.query_row(
+            "SELECT role FROM users WHERE username = ?1",
+            [&account.user.username],
+            |row| row.get(0),
+        )?;
+        tx.commit()?;
+        Ok(Some(UserRole::parse(&role).unwrap_or(UserRole::Regular)))
+    }
+
+    fn record_login_result(
+        &self,
+        username: &str,
+        succeeded: bool,
+        max_failures: u32,
+        lock_until: SystemTime,
+    ) -> anyhow::Result<bool> {
+        let mut conn = self.conn.lock().unwrap();
+        if succeeded {
+            conn.execute(
+                "UPDATE users SET failed_logins = 0, locked_until = NULL WHERE username = ?1",
+                [username],
+            )?;
+            return Ok(false);
+        }
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE users SET failed_logins = failed_logins + 1 WHERE username = ?1",
+            [username],
+        )?;
+        let locked = tx.execute(
+            "UPDATE users SET failed_logins = 0, locked_until = ?2
+             WHERE username = ?1 AND failed_logins >= ?3",
+            params![username, to_unix(lock_until), max_failures],
+        )?;
+        tx.commit()?;
+        Ok(locked == 1)
+    }
+
+    fn set_password_hash(&self, username: &str, password_hash: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE users SET password_hash = ?2 WHERE username = ?1",
+            params![username, password_hash],
+        )?;
+        Ok(())
+    }
+
+    fn set_role(
+        &self,
+        username: &str,
+        role: UserRole,
+    ) -> anyhow::Result<Result<User, SetRoleError>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let account = tx
+            .query_row(
+                &format!("SELECT {USER_COLUMNS} FROM users WHERE username = ?1"),
+                [username],
+                account_from_row,
+            )
+            .optional()?;
+        let Some(account) = account else {
+            return Ok(Err(SetRoleError::UnknownUser));
+        };
+        // Only demoting an admin needs another admin left over
+        let updated = tx.execute(
+            "UPDATE users SET role = ?2
+             WHERE username = ?1
+               AND (?2 = 'admin' OR role != 'admin'
+                    OR (SELECT COUNT(*) FROM users WHERE role = 'admin') > 1)",
+            params![username, role.as_str()],
+        )?;
+        if updated == 0 {
+            return Ok(Err(SetRoleError::LastAdmin));
+        }
+        tx.commit()?;
+        Ok(Ok(User {
+            username: account.user.username,
+            role,
+        }))
+    }
+}
+
+pub struct SqliteObservationRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+const OBSERVATION_COLUMNS: &str = "id, product_id, observed_at, price_minor, stock, error, ships_from, \
+     sold_by, third_party_seller, currency";
+
+fn observation_from_row(row: &Row) -> rusqlite::Result<Observation> {
+    let stock: String = row.get(4)?;
+    Ok(Observation {
+        id: row.get(0)?,
+        product_id: row.get(1)?,
+        observed_at: from_unix(row.get(2)?),
+        price: money_from_row(row, 3, 9)?,
+        stock: StockState::parse(&stock),
+        error: row.get(5)?,
+        ships_from: row.get(6)?,
+        sold_by: row.get(7)?,
+        third_party_seller: row.get(8)?,
+    })
+}
+
+impl ObservationRepository for SqliteObservationRepository {
+    fn record(&self, observation: NewObservation) -> anyhow::Result<Observation> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO observations
+                (product_id, observed_at, price_minor, currency, stock, error, ships_from, sold_by,
+                 third_party_seller)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                observation.product_id,
+                to_unix(observation.observed_at),
+                observation.price.map(Money::minor),
+                observation.price.map(|p| p.currency().code()),
+                observation.stock.as_str(),
+                observation.error,
+                observation.ships_from,
+                observation.sold_by,
+                observation.third_party_seller
+            ],
+        )?;
+        Ok(Observation {
+            id: conn.last_insert_rowid(),
+            product_id: observation.product_id,
+            observed_at: observation.observed_at,
+            price: observation.price,
+            stock: observation.stock,
+            error: observation.error,
+            ships_from: observation.ships_from,
+            sold_by: observation.sold_by,
+            third_party_seller: observation.third_party_seller,
+        })
+    }
+
+    fn latest(&self, product_id: i64) -> anyhow::Result<Option<Observation>> {
+        let conn = self.conn.lock().unwrap();
+        let observation = conn
+            .query_row(
+                &format!(
+                    "SELECT {OBSERVATION_COLUMNS} FROM observations
+                     WHERE product_id = ?1 ORDER BY observed_at DESC, id DESC LIMIT 1"
+                ),
+                [product_id],
+                observation_from_row,
+            )
+            .optional()?;
+        Ok(observation)
+    }
+
+    fn latest_successful(&self, product_id: i64) -> anyhow::Result<Option<Observation>> {
+        let conn = self.conn.lock().unwrap();
+        let observation = conn
+            .query_row(
+                &format!(
+                    "SELECT {OBSERVATION_COLUMNS} FROM observations
+                     WHERE product_id = ?1 AND error IS NULL
+                     ORDER BY observed_at DESC, id DESC LIMIT 1"
+                ),
+                [product_id],
+                observation_from_row,
+            )
+            .optional()?;
+        Ok(observation)
+    }
+
+    fn history(&self, product_id: i64) -> anyhow::Result<Vec<Observation>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {OBSERVATION_COLUMNS} FROM observations
+             WHERE product_id = ?1 ORDER BY observed_at, id"
+        ))?;
+        let history = stmt
+            .query_map([product_id], observation_from_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(history)
+    }
+}
+
+pub struct SqliteAlertRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+const ALERT_COLUMNS: &str = "id, product_id, kind, triggered_at, price_minor, previous_price_minor, \
+     message, username, currency";
+
+fn alert_from_row(row: &Row) -> rusqlite::Result<Alert> {
+    let kind: String = row.get(2)?;
+    let kind = AlertKind::parse(&kind).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            2,
+            rusqlite::types::Type::Text,
+            format!("unknown alert kind {kind:?}").into(),
+        )
+    })?;
+    Ok(Alert {
+        id: row.get(0)?,
+        product_id: row.get(1)?,
+        kind,
+        triggered_at: from_unix(row.get(3)?),
+        price: money_from_row(row, 4, 8)?,
+        previous_price: money_from_row(row, 5, 8)?,
+        message: row.get(6)?,
+        username: row.get(7)?,
+    })
+}
+
+impl AlertRepository for SqliteAlertRepository {
+    fn record(&self, alert: NewAlert) -> anyhow::Result<Alert> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO alerts
+                (product_id, kind, triggered_at, price_minor, previous_price_minor, currency,
+                 message, username)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                alert.product_id,
+                alert.kind.as_str(),
+                to_unix(alert.triggered_at),
+                alert.price.map(Money::minor),
+                alert.previous_price.map(Money::minor),
+                alert
+                    .price
+                    .or(alert.previous_price)
+                    .map(|p| p.currency().code()),
+                alert.message,
+                alert.username
+            ],
+        )?;
+        Ok(Alert {
+            id: conn.last_insert_rowid(),
+            product_id: alert.product_id,
+            username: alert.username,
+            kind: alert.kind,
+            triggered_at: alert.triggered_at,
+            price: alert.price,
+            previous_price: alert.previous_price,
+            message: alert.message,
+        })
+    }
+
+    fn latest(
+        &self,
+        product_id: i64,
+        kind: AlertKind,
+        username: Option<&str>,
+    ) -> anyhow::Result<Option<Alert>> {
+        let conn = self.conn.lock().unwrap();
+        let alert = conn
+            .query_row(
+                &format!(
+                    "SELECT {ALERT_COLUMNS} FROM alerts
+                     WHERE product_id = ?1 AND kind = ?2 AND username IS ?3
+                     ORDER BY triggered_at DESC, id DESC LIMIT 1"
+                ),
+                params![product_id, kind.as_str(), username],
+                alert_from_row,
+            )
+            .optional()?;
+        Ok(alert)
+    }
+
+    fn history(&self, product_id: i64) -> anyhow::Result<Vec<Alert>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {ALERT_COLUMNS} FROM alerts
+             WHERE product_id = ?1 ORDER BY triggered_at DESC, id DESC"
+        ))?;
+        let alerts = stmt
+            .query_map([product_id], alert_from_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(alerts)
+    }
+}
+
+pub struct SqlitePreferencesRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl PreferencesRepository for SqlitePreferencesRepository {
+    fn get(&self, username: &str) -> anyhow::Result<Option<NotificationPreferences>> {
+        let conn = self.conn.lock().unwrap();
+        let preferences = conn
+            .query_row(
+                "SELECT username, email, email_enabled, alert_kinds
+                 FROM notification_preferences WHERE username = ?1",
+                [username],
+                |row| {
+                    // Stored as a comma-separated list of `AlertKind::as_str` names
+                    let kinds: String = row.get(3)?;
+                    Ok(NotificationPreferences {
+                        username: row.get(0)?,
+                        email: row.get(1)?,
+                        email_enabled: row.get(2)?,
+                        alert_kinds: kinds.split(',').filter_map(AlertKind::parse).collect(),
+                    })
+                },
+            )
+            .optional()?;
+        Ok(preferences)
+    }
+
+    fn save(&self, preferences: &NotificationPreferences) -> anyhow::Result<()> {
+        let kinds: Vec<&str> = preferences.alert_kinds.iter().map(|k| k.as_str()).collect();
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO notification_preferences (username, email, email_enabled, alert_kinds)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (username) DO UPDATE SET
+                email = excluded.email,
+                email_enabled = excluded.email_enabled,
+                alert_kinds = excluded.alert_kinds",
+            params![
+                preferences.username,
+                preferences.email,
+                preferences.email_enabled,
+                kinds.join(",")
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+pub struct SqliteWebhookRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+const WEBHOOK_COLUMNS: &str = "id, username, url, format, secret, created_at";
+
+fn webhook_from_row(row: &Row) -> rusqlite::Result<Webhook> {
+    let format: String = row.get(3)?;
+    Ok(Webhook {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        url: row.get(2)?,
+        format: WebhookFormat::parse(&format).unwrap_or(WebhookFormat::Json),
+        secret: row.get(4)?,
+        created_at: from_unix(row.get(5)?),
+    })
+}
+
+fn delivery_from_row(row: &Row) -> rusqlite::Result<WebhookDelivery> {
+    let kind: String = row.get(4)?;
+    let alert_kind = AlertKind::parse(&kind).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            4,
+            rusqlite::types::Type::Text,
+            format!("unknown alert kind {kind:?}").into(),
+        )
+    })?;
+    Ok(WebhookDelivery {
+        id: row.get(0)?,
+        webhook_id: row.get(1)?,
+        webhook_url: row.get(2)?,
+        product_id: row.get(3)?,
+        alert_kind,
+        message: row.get(5)?,
+        attempted_at: from_unix(row.get(6)?),
+        attempts: row.get(7)?,
+        status_code: row.get(8)?,
+        error: row.get(9)?,
+    })
+}
+
+impl WebhookRepository for SqliteWebhookRepository {
+    fn list_for_user(&self, username: &str) -> anyhow::Result<Vec<Webhook>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {WEBHOOK_COLUMNS} FROM webhooks WHERE username = ?1 ORDER BY id"
+        ))?;
+        let webhooks = stmt
+            .query_map([username], webhook_from_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(webhooks)
+    }
+
+    fn add(&self, webhook: NewWebhook) -> anyhow::Result<Webhook> {
+        let created_at = SystemTime::now();
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO webhooks (username, url, format, secret, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                webhook.username,
+                webhook.url,
+                webhook.format.as_str(),
+                webhook.secret,
+                to_unix(created_at)
+            ],
+        )?;
+        Ok(Webhook {
+            id: conn.last_insert_rowid(),
+            username: webhook.username,
+            url: webhook.url,
+            format: webhook.format,
+            secret: webhook.secret,
+            created_at,
+        })
+    }
+
+    fn delete(&self, id: i64, username: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM webhooks WHERE id = ?1 AND username = ?2",
+            params![id, username],
+        )?;
+        Ok(deleted == 1)
+    }
+
+    fn record_delivery(&self, delivery: NewWebhookDelivery) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO webhook_deliveries
+                (webhook_id, product_id, alert_kind, message, attempted_at, attempts, status_code, error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                delivery.webhook_id,
+                delivery.product_id,
+                delivery.alert_kind.as_str(),
+                delivery.message,
+                to_unix(delivery.attempted_at),
+                delivery.attempts,
+                delivery.status_code,
+                delivery.error
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn recent_deliveries(
+        &self,
+        username: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<WebhookDelivery>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT d.id, d.webhook_id, w.url, d.product_id, d.alert_kind, d.message,
+                    d.attempted_at, d.attempts, d.status_code, d.error
+             FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+             WHERE w.username = ?1
+             ORDER BY d.attempted_at DESC, d.id DESC LIMIT ?2",
+        )?;
+        let deliveries = stmt
+            .query_map(params![username, limit as i64], delivery_from_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(deliveries)
+    }
+}
+
+pub struct SqliteTokenRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+const TOKEN_COLUMNS: &str =
+    "id, username, name, scope, token_hash, display_prefix, created_at, last_used_at";
+
+fn token_from_row(row: &Row) -> rusqlite::Result<ApiToken> {
+    let scope: String = row.get(3)?;
+    Ok(ApiToken {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        name: row.get(2)?,
+        // An unrecognised scope grants the least
+        scope: TokenScope::parse(&scope).unwrap_or(TokenScope::ReadOnly),
+        token_hash: row.get(4)?,
+        display_prefix: row.get(5)?,
+        created_at: from_unix(row.get(6)?),
+        last_used_at: row.get::<_, Option<i64>>(7)?.map(from_unix),
+    })
+}
+
+impl TokenRepository for SqliteTokenRepository {
+    fn list_for_user(&self, username: &str) -> anyhow::Result<Vec<ApiToken>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TOKEN_COLUMNS} FROM api_tokens WHERE username = ?1 ORDER BY id"
+        ))?;
+        let tokens = stmt
+            .query_map([username], token_from_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(tokens)
+    }
+
+    fn add(&self, token: NewApiToken) -> anyhow::Result<ApiToken> {
+        let created_at = SystemTime::now();
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO api_tokens (username, name, scope, token_hash, display_prefix, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                token.username,
+                token.name,
+                token.scope.as_str(),
+                token.token_hash,
+                token.display_prefix,
+                to_unix(created_at)
+            ],
+        )?;
+        Ok(ApiToken {
+            id: conn.last_insert_rowid(),
+            username: token.username,
+            name: token.name,
+            scope: token.scope,
+            token_hash: token.token_hash,
+            display_prefix: token.display_prefix,
+            created_at,
+            last_used_at: None,
+        })
+    }
+
+    fn find_by_hash(&self, token_hash: &str) -> anyhow::Result<Option<ApiToken>> {
+        let conn = self.conn.lock().unwrap();
+        let token = conn
+            .query_row(
+                &format!("SELECT {TOKEN_COLUMNS} FROM api_tokens WHERE token_hash = ?1"),
+                [token_hash],
+                token_from_row,
+            )
+            .optional()?;
+        Ok(token)
+    }
+
+    fn touch(&self, id: i64, used_at: SystemTime) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE api_tokens SET last_used_at = ?1 WHERE id = ?2",
+            params![to_unix(used_at), id],
+        )?;
+        Ok(())
+    }
+
+    fn delete(&self, id: i64, username: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM api_tokens WHERE id = ?1 AND username = ?2",
+            params![id, username],
+        )?;
+        Ok(deleted == 1)
+    }
+}
+
+pub struct SqliteCheckoutRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+const RULE_COLUMNS: &str =
+    "product_id, username, enabled, dry_run, max_quantity, max_spend_minor, currency, updated_at";
+
+fn rule_from_row(row: &Row) -> rusqlite::Result<AutoBuyRule> {
+    Ok(AutoBuyRule {
+        product_id: row.get(0)?,
+        username: row.get(1)?,
+        enabled: row.get(2)?,
+        dry_run: row.get(3)?,
+        max_quantity: row.get(4)?,
+        max_spend: Money::new(row.get(5)?, currency_from_row(row, 6)?),
+        updated_at: from_unix(row.get(7)?),
+    })
+}
+
+const RUN_COLUMNS: &str = "id, product_id, username, started_at, dry_run, status, quantity, total_minor, currency, order_number";
+
+fn run_from_row(row: &Row) -> rusqlite::Result<CheckoutRun> {
+    let status: String = row.get(5)?;
+    let status = CheckoutStatus::parse(&status).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            5,
+            rusqlite::types::Type::Text,
+            format!("unknown checkout status {status:?}").into(),
+        )
+    })?;
+    Ok(CheckoutRun {
+        id: row.get(0)?,
+        product_id: row.get(1)?,
+        username: row.get(2)?,
+        started_at: from_unix(row.get(3)?),
+        dry_run: row.get(4)?,
+        status,
+        quantity: row.get(6)?,
+        total: money_from_row(row, 7, 8)?,
+        order_number: row.get(9)?,
+        steps: Vec::new(),
+    })
+}
+
+fn step_from_row(row: &Row) -> rusqlite::Result<CheckoutStep> {
+    Ok(CheckoutStep {
+        at: from_unix(row.get(0)?),
+        name: row.get(1)?,
+        ok: row.get(2)?,
+        message: row.get(3)?,
+    })
+}
+
+fn set_rule_enabled(
+    conn: &Connection,
+    product_id: i64,
+    username: &str,
+    enabled: bool,
+) -> anyhow::Result<()> {
+    conn.execute(
+        "UPDATE auto_buy_rules SET enabled = ?1, updated_at = ?2
+         WHERE product_id = ?3 AND username = ?4",
+        params![enabled, to_unix(SystemTime::now()), product_id, username],
+    )?;
+    Ok(())
+}
+
+impl CheckoutRepository for SqliteCheckoutRepository {
+    fn rule(&self, product_id: i64, username: &str) -> anyhow::Result<Option<AutoBuyRule>> {
+        let conn = self.conn.lock().unwrap();
+        let rule = conn
+            .query_row(
+                &format!(
+                    "SELECT {RULE_COLUMNS} FROM auto_buy_rules
+                     WHERE product_id = ?1 AND username = ?2"
+                ),
+                params![product_id, username],
+                rule_from_row,
+            )
+            .optional()?;
+        Ok(rule)
+    }
+
+    fn save_rule(&self, rule: &AutoBuyRule) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO auto_buy_rules
+                (product_id, username, enabled, dry_run, max_quantity, max_spend_minor, currency,
+                 updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT (product_id, username) DO UPDATE
+                SET enabled = excluded.enabled,
+                    dry_run = excluded.dry_run,
+                    max_quantity = excluded.max_quantity,
+                    max_spend_minor = excluded.max_spend_minor,
+                    currency = excluded.currency,
+                    updated_at = excluded.updated_at",
+            params![
+                rule.product_id,
+                rule.username,
+                rule.enabled,
+                rule.dry_run,
+                rule.max_quantity,
+                rule.max_spend.minor(),
+                rule.max_spend.currency().code(),
+                to_unix(rule.updated_at)
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn set_rule_enabled(
+        &self,
+        product_id: i64,
+        username: &str,
+        enabled: bool,
+    ) -> anyhow::Result<()> {
+        set_rule_enabled(&self.conn.lock().unwrap(), product_id, username, enabled)
+    }
+
+    fn record_run(&self, run: NewCheckoutRun) -> anyhow::Result<CheckoutRun> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO checkout_runs
+                (product_id, username, started_at, dry_run, status, quantity, total_minor, currency,
+                 order_number)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                run.product_id,
+                run.username,
+                to_unix(run.started_at),
+                run.dry_run,
+                run.status.as_str(),
+                run.quantity,
+                run.total.map(Money::minor),
+                run.total.map(|t| t.currency().code()),
+                run.order_number
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        for step in &run.steps {
+            tx.execute(
+                "INSERT INTO checkout_steps (run_id, at, name, ok, message)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![id, to_unix(step.at), step.name, step.ok, step.message],
+            )?;
+        }
+        if let Some(enabled) = run.rule_enabled {
+            set_rule_enabled(&tx, run.product_id, &run.username, enabled)?;
+        }
+        tx.commit()?;
+
+        Ok(CheckoutRun {
+            id,
+            product_id: run.product_id,
+            username: run.username,
+            started_at: run.started_at,
+            dry_run: run.dry_run,
+            status: run.status,
+            quantity: run.quantity,
+            total: run.total,
+            order_number: run.order_number,
+            steps: run.steps,
+        })
+    }
+
+    fn recent_runs(
+        &self,
+        product_id: i64,
+        username: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<CheckoutRun>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {RUN_COLUMNS} FROM checkout_runs
+             WHERE product_id = ?1 AND username = ?2
+             ORDER BY started_at DESC, id DESC LIMIT ?3"
+        ))?;
+        let mut runs: Vec<CheckoutRun> = stmt
+            .query_map(params![product_id, username, limit as i64], run_from_row)?
+            .collect::<Result<_, _>>()?;
+
+        let mut steps = conn.prepare(
+            "SELECT at, name, ok, message FROM checkout_steps WHERE run_id = ?1 ORDER BY id",
+        )?;
+        for run in &mut runs {
+            run.steps = steps
+                .query_map([run.id], step_from_row)?
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(runs)
+    }
+}
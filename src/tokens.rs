@@ -0,0 +1,170 @@
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+use sha2::{Digest, Sha256};
+use std::time::SystemTime;
+
+/// Every token starts with this, so leaked ones are easy to spot in logs and scanners
+const TOKEN_PREFIX: &str = "midas_";
+
+/// Characters of a token kept in the clear so its owner can tell tokens apart
+const DISPLAY_LEN: usize = TOKEN_PREFIX.len() + 6;
+
+const MAX_NAME_LEN: usize = 64;
+
+/// What an API token is allowed to do, on top of what its owner's role allows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenScope {
+    ReadOnly,
+    ReadWrite,
+}
+
+impl TokenScope {
+    pub const ALL: [TokenScope; 2] = [TokenScope::ReadOnly, TokenScope::ReadWrite];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenScope::ReadOnly => "read",
+            TokenScope::ReadWrite => "read_write",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<TokenScope> {
+        TokenScope::ALL.into_iter().find(|s| s.as_str() == scope)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TokenScope::ReadOnly => "Read only",
+            TokenScope::ReadWrite => "Read and write",
+        }
+    }
+
+    pub fn can_write(self) -> bool {
+        self == TokenScope::ReadWrite
+    }
+}
+
+/// A personal API token. Only a hash of the secret is kept; the secret itself is shown once,
+/// when the token is created.
+#[derive(Debug, Clone)]
+pub struct ApiToken {
+    pub id: i64,
+    pub username: String,
+    pub name: String,
+    pub scope: TokenScope,
+    /// SHA-256 of the full token, hex encoded
+    pub token_hash: String,
+    /// The first few characters of the token, e.g. "midas_a1B2c3"
+    pub display_prefix: String,
+    pub created_at: SystemTime,
+    pub last_used_at: Option<SystemTime>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewApiToken {
+    pub username: String,
+    pub name: String,
+    pub scope: TokenScope,
+    pub token_hash: String,
+    pub display_prefix: String,
+}
+
+impl NewApiToken {
+    /// A token with a freshly generated secret, returned alongside it since it can't be
+    /// recovered from what gets stored
+    pub fn generate(
+        username: &str,
+        name: &str,
+        scope: TokenScope,
+    ) -> Result<(NewApiToken, String), TokenError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(TokenError::MissingName);
+        }
+        if name.chars().count() > MAX_NAME_LEN {
+            return Err(TokenError::NameTooLong);
+        }
+
+        let random: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(40)
+            .map(char::from)
+            .collect();
+        let secret = format!("{TOKEN_PREFIX}{random}");
+        let token = NewApiToken {
+            username: username.to_string(),
+            name: name.to_string(),
+            scope,
+            token_hash: hash_token(&secret),
+            display_prefix: secret[..DISPLAY_LEN].to_string(),
+        };
+        Ok((token, secret))
+    }
+}
+
+/// The hash tokens are stored and looked up by. Tokens are long and random, so a fast hash
+/// is enough; there's nothing to brute force the way there is with passwords.
+pub fn hash_token(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.trim().as_bytes()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenError {
+    MissingName,
+    NameTooLong,
+}
+
+impl TokenError {
+    /// Short identifier used in redirect query strings
+    pub fn code(self) -> &'static str {
+        match self {
+            TokenError::MissingName => "missing_name",
+            TokenError::NameTooLong => "name_too_long",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_token_matches_its_hash() {
+        let (token, secret) =
+            NewApiToken::generate("alice", " deploy script ", TokenScope::ReadOnly).unwrap();
+
+        assert!(secret.starts_with(TOKEN_PREFIX));
+        assert_eq!(secret.len(), TOKEN_PREFIX.len() + 40);
+        assert_eq!(token.name, "deploy script");
+        assert_eq!(token.token_hash, hash_token(&secret));
+        assert!(secret.starts_with(&token.display_prefix));
+        assert!(!token.token_hash.contains(&secret));
+    }
+
+    #[test]
+    fn tokens_are_unique() {
+        let (_, first) = NewApiToken::generate("alice", "a", TokenScope::ReadWrite).unwrap();
+        let (_, second) = NewApiToken::generate("alice", "b", TokenScope::ReadWrite).unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn name_is_required() {
+        assert_eq!(
+            NewApiToken::generate("alice", "  ", TokenScope::ReadOnly).unwrap_err(),
+            TokenError::MissingName
+        );
+        assert_eq!(
+            NewApiToken::generate("alice", &"x".repeat(65), TokenScope::ReadOnly).unwrap_err(),
+            TokenError::NameTooLong
+        );
+    }
+
+    #[test]
+    fn scopes_round_trip() {
+        for scope in TokenScope::ALL {
+            assert_eq!(TokenScope::parse(scope.as_str()), Some(scope));
+        }
+        assert_eq!(TokenScope::parse("admin"), None);
+    }
+}
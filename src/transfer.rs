@@ -0,0 +1,767 @@
+use crate::money::Currency;
+use crate::products::{self, ProductError, ProductErrors, ProductInput, Tracked};
+use crate::retailers::RetailerRegistry;
+use crate::storage::{
+    Observation, ObservationRepository, Product, ProductRepository, Subscription,
+};
+use crate::urls::{ResolveFuture, ShortLinkResolver};
+use crate::users::UserStore;
+use anyhow::{Context, bail};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+use url::Url;
+
+/// More rows than this in one file is refused
+pub const MAX_IMPORT_ROWS: usize = 500;
+
+/// Most short links one preview or import follows. Each is a request to the link service,
+/// and rows past the limit fail as unresolved.
+pub const MAX_IMPORT_LINKS: usize = 20;
+
+/// How long one preview or import may spend following short links altogether
+const IMPORT_LINKS_DEADLINE: Duration = Duration::from_secs(60);
+
+/// Columns of a product CSV, in the order they're exported
+const CSV_COLUMNS: [&str; 7] = [
+    "username",
+    "retailer",
+    "name",
+    "url",
+    "currency",
+    "target_price",
+    "poll_interval_minutes",
+];
+
+/// Columns of a price history CSV
+const HISTORY_COLUMNS: [&str; 8] = [
+    "retailer",
+    "name",
+    "url",
+    "checked_at",
+    "price",
+    "currency",
+    "stock",
+    "error",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    pub const ALL: [Format; 2] = [Format::Csv, Format::Json];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Json => "json",
+        }
+    }
+
+    pub fn parse(format: &str) -> Option<Format> {
+        Format::ALL
+            .into_iter()
+            .find(|f| f.as_str().eq_ignore_ascii_case(format))
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Json => "application/json",
+        }
+    }
+
+    /// The format of an uploaded file, going by its extension and failing that its contents
+    pub fn detect(path: &Path, text: &str) -> Format {
+        path.extension()
+            .and_then(|extension| Format::parse(&extension.to_string_lossy()))
+            .unwrap_or_else(|| {
+                if text.trim_start().starts_with(['[', '{']) {
+                    Format::Json
+                } else {
+                    Format::Csv
+                }
+            })
+    }
+}
+
+/// One user's subscription to a tracked product, as exported and imported
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ProductRecord {
+    /// Who watches the product. Optional on import, where a default user can be given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    pub retailer: String,
+    pub name: String,
+    pub url: String,
+    /// Checked against the retailer's currency when given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    /// In the retailer's currency, as a number or as typed into the product form ("$1,299.99")
+    #[serde(default, deserialize_with = "amount")]
+    pub target_price: Option<String>,
+    #[serde(default)]
+    pub poll_interval_minutes: Option<u64>,
+    /// Every check of the product, oldest first. Only exported as JSON, and ignored on import.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<HistoryEntry>,
+}
+
+/// One check of a product page, as exported
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    pub checked_at: String,
+    pub price: Option<String>,
+    pub currency: Option<String>,
+    pub stock: String,
+    pub error: Option<String>,
+}
+
+impl From<Observation> for HistoryEntry {
+    fn from(observation: Observation) -> Self {
+        HistoryEntry {
+            checked_at: timestamp(observation.observed_at),
+            price: observation.price.map(|p| p.amount()),
+            currency: observation.price.map(|p| p.currency().code().to_string()),
+            stock: observation.stock.as_str().to_string(),
+            error: observation.error,
+        }
+    }
+}
+
+// Amounts can be written as JSON numbers as well as text
+fn amount<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Amount {
+        Number(f64),
+        Text(String),
+    }
+    Ok(match Option::<Amount>::deserialize(deserializer)? {
+        Some(Amount::Number(number)) => Some(number.to_string()),
+        Some(Amount::Text(text)) => Some(text).filter(|t| !t.trim().is_empty()),
+        None => None,
+    })
+}
+
+fn timestamp(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+}
+
+impl ProductRecord {
+    fn new(product: &Product, subscription: &Subscription) -> Self {
+        ProductRecord {
+            username: Some(subscription.username.clone()),
+            retailer: product.retailer.clone(),
+            name: product.name.clone(),
+            url: product.url.clone(),
+            currency: Some(product.currency.code().to_string()),
+            target_price: subscription.target_price.map(|p| p.amount()),
+            poll_interval_minutes: product.poll_interval.map(|d| d.as_secs() / 60),
+            history: Vec::new(),
+        }
+    }
+
+    // The record as product form input, before the checks every submitted form goes through
+    fn input(&self, retailers: &RetailerRegistry) -> Result<ProductInput, ProductErrors> {
+        // Without a known retailer there's no currency to read amounts in, but `validate`
+        // still reports the other problems with the record
+        let currency = retailers
+            .get(&self.retailer)
+            .map_or(Currency::Usd, |r| r.currency());
+        let currency_matches = self
+            .currency
+            .as_deref()
+            .is_none_or(|code| Currency::parse(code) == Some(currency));
+        if !currency_matches {
+            return Err(ProductError::TargetPriceCurrency.into());
+        }
+        let target_price =
+            products::parse_target_price(self.target_price.as_deref().unwrap_or(""), currency)?;
+
+        Ok(ProductInput {
+            url: self.url.clone(),
+            name: self.name.clone(),
+            retailer: self.retailer.clone(),
+            retailer_id: None,
+            currency,
+            target_price,
+            poll_interval: self
+                .poll_interval_minutes
+                .map(products::poll_interval_minutes),
+        })
+    }
+}
+
+/// Read the records in an import file. Column names in CSV files are matched loosely, so
+/// "Target Price" works as well as "target_price", and columns nobody asked for are ignored.
+pub fn parse_records(text: &str, format: Format) -> anyhow::Result<Vec<ProductRecord>> {
+    // Spreadsheets like to start their CSV files with a byte order mark
+    let text = text.trim_start_matches('\u{feff}');
+    let records: Vec<ProductRecord> = match format {
+        Format::Json => serde_json::from_str(text)?,
+        Format::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(text.as_bytes());
+            let headers: csv::StringRecord = reader
+                .headers()?
+                .iter()
+                .map(|header| header.to_lowercase().replace([' ', '-'], "_"))
+                .collect();
+            reader.set_headers(headers);
+            reader
+                .deserialize()
+                .collect::<Result<_, _>>()
+                .context("Couldn't read the CSV file")?
+        }
+    };
+
+    if records.is_empty() {
+        bail!("There are no products in the file");
+    }
+    if records.len() > MAX_IMPORT_ROWS {
+        bail!(
+            "There are {} products in the file, but at most {} can be imported at once",
+            records.len(),
+            MAX_IMPORT_ROWS
+        );
+    }
+    Ok(records)
+}
+
+/// Write records in `format`. CSV only has the products, without their history.
+pub fn write_records(records: &[ProductRecord], format: Format) -> anyhow::Result<String> {
+    match format {
+        Format::Json => Ok(serde_json::to_string_pretty(records)? + "\n"),
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            writer.write_record(CSV_COLUMNS)?;
+            for record in records {
+                writer.write_record([
+                    record.username.as_deref().unwrap_or(""),
+                    &record.retailer,
+                    &record.name,
+                    &record.url,
+                    record.currency.as_deref().unwrap_or(""),
+                    record.target_price.as_deref().unwrap_or(""),
+                    &record
+                        .poll_interval_minutes
+                        .map(|minutes| minutes.to_string())
+                        .unwrap_or_default(),
+                ])?;
+            }
+            Ok(String::from_utf8(writer.into_inner()?)?)
+        }
+    }
+}
+
+/// The price history of the records' products as CSV, one row per check
+pub fn write_history_csv(records: &[ProductRecord]) -> anyhow::Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(HISTORY_COLUMNS)?;
+    for record in records {
+        for entry in &record.history {
+            writer.write_record([
+                &record.retailer,
+                &record.name,
+                &record.url,
+                &entry.checked_at,
+                entry.price.as_deref().unwrap_or(""),
+                entry.currency.as_deref().unwrap_or(""),
+                &entry.stock,
+                entry.error.as_deref().unwrap_or(""),
+            ])?;
+        }
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Every subscription to a tracked product, or only `username`'s, ordered by product. With
+/// `observations`, each record carries its product's price history.
+pub fn export(
+    products: &dyn ProductRepository,
+    observations: Option<&dyn ObservationRepository>,
+    username: Option<&str>,
+) -> anyhow::Result<Vec<ProductRecord>> {
+    let mut records = Vec::new();
+    let mut add = |product: &Product, subscription: &Subscription| -> anyhow::Result<()> {
+        let mut record = ProductRecord::new(product, subscription);
+        if let Some(observations) = observations {
+            record.history = observations
+                .history(product.id)?
+                .into_iter()
+                .map(HistoryEntry::from)
+                .collect();
+        }
+        records.push(record);
+        Ok(())
+    };
+
+    match username {
+        Some(username) => {
+            for subscription in products.subscriptions_for_user(username)? {
+                if let Some(product) = products.get(subscription.product_id)? {
+                    add(&product, &subscription)?;
+                }
+            }
+        }
+        None => {
+            for product in products.list()? {
+                for subscription in products.subscribers(product.id)? {
+                    add(&product, &subscription)?;
+                }
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// Why a record couldn't be imported
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordError {
+    /// Neither the record nor the import names a user
+    MissingUser,
+    UnknownUser(String),
+    /// Viewers can't track products
+    ReadOnlyUser(String),
+    Product(ProductErrors),
+}
+
+impl RecordError {
+    pub fn message(&self) -> String {
+        match self {
+            RecordError::MissingUser => "No user to track the product for".to_string(),
+            RecordError::UnknownUser(username) => format!("No user named {:?}", username),
+            RecordError::ReadOnlyUser(username) => {
+                format!("{} has read-only access and can't track products", username)
+            }
+            RecordError::Product(errors) => {
+                let messages: Vec<&str> = errors.iter().map(ProductError::message).collect();
+                messages.join("; ")
+            }
+        }
+    }
+}
+
+/// Tracks imported records for their users. Every record goes through the same checks as
+/// the product form, and records that fail them are skipped rather than stopping the import.
+pub struct Importer<'a> {
+    pub users: &'a UserStore,
+    pub products: &'a dyn ProductRepository,
+    pub retailers: &'a RetailerRegistry,
+    pub resolver: &'a dyn ShortLinkResolver,
+}
+
+impl Importer<'_> {
+    /// What importing `records` would do, row by row, without changing anything. Records
+    /// that don't name a user are for `default_user`.
+    pub async fn preview(
+        &self,
+        records: &[ProductRecord],
+        default_user: Option<&str>,
+    ) -> anyhow::Result<Vec<Result<Tracked, RecordError>>> {
+        // Earlier rows of the same file count as already tracked, keyed like `find_existing`
+        let mut in_file: HashMap<(String, String), HashSet<String>> = HashMap::new();
+        let resolver = self.link_budget();
+        let mut outcomes = Vec::new();
+        for record in records {
+            let (username, input) = match self.check(record, default_user, &resolver).await? {
+                Ok(checked) => checked,
+                Err(e) => {
+                    outcomes.push(Err(e));
+                    continue;
+                }
+            };
+            let key = (
+                input.retailer.clone(),
+                input
+                    .retailer_id
+                    .clone()
+                    .unwrap_or_else(|| input.url.clone()),
+            );
+            let outcome = match in_file.get(&key) {
+                Some(usernames) if usernames.contains(&username) => Tracked::AlreadySubscribed,
+                Some(_) => Tracked::Subscribed,
+                None => products::plan_track(self.products, &username, &input)?,
+            };
+            in_file.entry(key).or_default().insert(username);
+            outcomes.push(Ok(outcome));
+        }
+        Ok(outcomes)
+    }
+
+    /// Track every record that passes the checks, returning what happened to each
+    pub async fn import(
+        &self,
+        records: &[ProductRecord],
+        default_user: Option<&str>,
+    ) -> anyhow::Result<Vec<Result<(Product, Tracked), RecordError>>> {
+        let resolver = self.link_budget();
+        let mut outcomes = Vec::new();
+        for record in records {
+            let outcome = match self.check(record, default_user, &resolver).await? {
+                Ok((username, input)) => Ok(products::track(self.products, &username, input)?),
+                Err(e) => Err(e),
+            };
+            outcomes.push(outcome);
+        }
+        Ok(outcomes)
+    }
+
+    fn link_budget(&self) -> LinkBudget<'_> {
+        LinkBudget {
+            resolver: self.resolver,
+            remaining: AtomicUsize::new(MAX_IMPORT_LINKS),
+            deadline: tokio::time::Instant::now() + IMPORT_LINKS_DEADLINE,
+        }
+    }
+
+    // The user a record is for and the product input it describes, once both are known good
+    async fn check(
+        &self,
+        record: &ProductRecord,
+        default_user: Option<&str>,
+        resolver: &LinkBudget<'_>,
+    ) -> anyhow::Result<Result<(String, ProductInput), RecordError>> {
+        let Some(username) = record.username.as_deref().or(default_user) else {
+            return Ok(Err(RecordError::MissingUser));
+        };
+        let Some(user) = self.users.get(username)? else {
+            return Ok(Err(RecordError::UnknownUser(username.to_string())));
+        };
+        if !user.role.can_edit() {
+            return Ok(Err(RecordError::ReadOnlyUser(user.username)));
+        }
+
+        let input = match record.input(self.retailers) {
+            Ok(input) => input.validate(self.retailers, resolver).await,
+            Err(errors) => Err(errors),
+        };
+        Ok(input
+            .map(|input| (user.username, input))
+            .map_err(RecordError::Product))
+    }
+}
+
+// Follows short links for one import, as many as `MAX_IMPORT_LINKS` and until the deadline,
+// so a file full of them can't keep a request going for long
+struct LinkBudget<'a> {
+    resolver: &'a dyn ShortLinkResolver,
+    remaining: AtomicUsize,
+    deadline: tokio::time::Instant,
+}
+
+impl ShortLinkResolver for LinkBudget<'_> {
+    fn resolve<'a>(&'a self, url: &'a Url) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            let spent = self
+                .remaining
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                .is_err();
+            if spent {
+                bail!(
+                    "More than {} short links in one import, not following the rest",
+                    MAX_IMPORT_LINKS
+                );
+            }
+            tokio::time::timeout_at(self.deadline, self.resolver.resolve(url))
+                .await
+                .context("Import took too long following short links")?
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use crate::storage::{
+        InMemoryObservationRepository, InMemoryProductRepository, InMemoryUserRepository,
+        NewObservation, StockState,
+    };
+    use crate::urls::ResolveFuture;
+    use std::sync::Arc;
+    use url::Url;
+
+    struct NoShortLinks;
+
+    impl ShortLinkResolver for NoShortLinks {
+        fn resolve<'a>(&'a self, _url: &'a Url) -> ResolveFuture<'a> {
+            Box::pin(async { anyhow::bail!("no short links in tests") })
+        }
+    }
+
+    /// Expands every short link to the same product, counting the requests it would make
+    #[derive(Default)]
+    struct CountingResolver(AtomicUsize);
+
+    impl ShortLinkResolver for CountingResolver {
+        fn resolve<'a>(&'a self, _url: &'a Url) -> ResolveFuture<'a> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Box::pin(async { Ok(Url::parse(URL)?) })
+        }
+    }
+
+    struct Fixture {
+        users: UserStore,
+        products: InMemoryProductRepository,
+        retailers: RetailerRegistry,
+    }
+
+    impl Fixture {
+        fn new(usernames: &[&str]) -> Self {
+            let users = UserStore::new(Arc::new(InMemoryUserRepository::default()));
+            for username in usernames {
+                users.register(username, "password1").unwrap().unwrap();
+            }
+            Fixture {
+                users,
+                products: InMemoryProductRepository::default(),
+                retailers: RetailerRegistry::default(),
+            }
+        }
+
+        fn importer(&self) -> Importer<'_> {
+            Importer {
+                users: &self.users,
+                products: &self.products,
+                retailers: &self.retailers,
+                resolver: &NoShortLinks,
+            }
+        }
+    }
+
+    fn record(url: &str, target_price: Option<&str>) -> ProductRecord {
+        ProductRecord {
+            username: None,
+            retailer: "Amazon.ca".to_string(),
+            name: "RTX 5080".to_string(),
+            url: url.to_string(),
+            currency: None,
+            target_price: target_price.map(str::to_string),
+            poll_interval_minutes: None,
+            history: Vec::new(),
+        }
+    }
+
+    const URL: &str = "https://www.amazon.ca/dp/B0DTJFSSZG";
+
+    #[test]
+    fn reads_spreadsheet_csv() {
+        let csv = "\u{feff}Name,URL,Retailer,Target Price,Notes\n\
+                   RTX 5080,https://www.amazon.ca/dp/B0DTJFSSZG,Amazon.ca,\"CA$1,299.99\",want\n\
+                   RX 9070 XT, https://www.bestbuy.com/site/x/6615929.p ,Best Buy,,\n";
+        let records = parse_records(csv, Format::Csv).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].target_price.as_deref(), Some("CA$1,299.99"));
+        assert_eq!(records[1].url, "https://www.bestbuy.com/site/x/6615929.p");
+        assert_eq!(records[1].target_price, None);
+
+        assert!(parse_records("name,url,retailer\n", Format::Csv).is_err());
+        assert!(parse_records("url,retailer\nhttps://a.co/x,Amazon\n", Format::Csv).is_err());
+    }
+
+    #[test]
+    fn reads_numbers_and_text_amounts_from_json() {
+        let json = r#"[
+            {"retailer": "Amazon.ca", "name": "A", "url": "u", "target_price": 1299.99},
+            {"retailer": "Amazon.ca", "name": "B", "url": "u", "target_price": "$999"},
+            {"retailer": "Amazon.ca", "name": "C", "url": "u", "target_price": null}
+        ]"#;
+        let prices: Vec<_> = parse_records(json, Format::Json)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.target_price)
+            .collect();
+        assert_eq!(
+            prices,
+            [Some("1299.99".to_string()), Some("$999".to_string()), None]
+        );
+    }
+
+    #[test]
+    fn detects_formats() {
+        assert_eq!(Format::detect(Path::new("gpus.CSV"), "[]"), Format::Csv);
+        assert_eq!(Format::detect(Path::new("gpus.json"), "name"), Format::Json);
+        assert_eq!(Format::detect(Path::new("gpus"), " [{}]"), Format::Json);
+        assert_eq!(Format::detect(Path::new(""), "name,url"), Format::Csv);
+    }
+
+    #[tokio::test]
+    async fn round_trips_through_export() {
+        let fixture = Fixture::new(&["alice", "bob"]);
+        let importer = fixture.importer();
+
+        let outcomes = importer
+            .import(
+                &[record(&format!("{URL}?tag=aff-20"), Some("1299.99"))],
+                Some("alice"),
+            )
+            .await
+            .unwrap();
+        let (product, tracked) = outcomes[0].clone().unwrap();
+        assert_eq!(tracked, Tracked::Added);
+        assert_eq!(product.currency, Currency::Cad);
+        importer
+            .import(&[record(URL, None)], Some("bob"))
+            .await
+            .unwrap();
+
+        let observations = InMemoryObservationRepository::default();
+        observations
+            .record(NewObservation {
+                product_id: product.id,
+                observed_at: SystemTime::now(),
+                price: Some(Money::new(134999, Currency::Cad)),
+                stock: StockState::InStock,
+                error: None,
+                ships_from: None,
+                sold_by: None,
+                third_party_seller: false,
+            })
+            .unwrap();
+
+        let exported = export(&fixture.products, Some(&observations), None).unwrap();
+        assert_eq!(exported.len(), 2);
+        assert_eq!(exported[0].username.as_deref(), Some("alice"));
+        assert_eq!(exported[0].url, URL);
+        assert_eq!(exported[0].currency.as_deref(), Some("CAD"));
+        assert_eq!(exported[0].target_price.as_deref(), Some("1299.99"));
+        assert_eq!(exported[0].history[0].price.as_deref(), Some("1349.99"));
+        assert_eq!(
+            export(&fixture.products, None, Some("bob")).unwrap().len(),
+            1
+        );
+
+        let history = write_history_csv(&exported).unwrap();
+        assert_eq!(history.lines().count(), 3);
+        assert!(
+            history
+                .lines()
+                .nth(1)
+                .unwrap()
+                .contains(",1349.99,CAD,in_stock,")
+        );
+
+        // Both formats read back what they wrote, and importing them again changes nothing
+        for format in Format::ALL {
+            let written = write_records(&exported, format).unwrap();
+            let mut read = parse_records(&written, format).unwrap();
+            assert_eq!(read[0].target_price.as_deref(), Some("1299.99"));
+            read[0].username = Some("bob".to_string());
+            let outcomes = importer.preview(&read, None).await.unwrap();
+            assert_eq!(
+                outcomes,
+                [
+                    Ok(Tracked::AlreadySubscribed),
+                    Ok(Tracked::AlreadySubscribed)
+                ]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn follows_a_limited_number_of_short_links() {
+        let fixture = Fixture::new(&["alice"]);
+        let resolver = CountingResolver::default();
+        let importer = Importer {
+            resolver: &resolver,
+            ..fixture.importer()
+        };
+        let records: Vec<_> = (0..MAX_IMPORT_LINKS + 5)
+            .map(|i| record(&format!("https://a.co/d/{i}"), None))
+            .collect();
+
+        let outcomes = importer.preview(&records, Some("alice")).await.unwrap();
+        assert_eq!(resolver.0.load(Ordering::Relaxed), MAX_IMPORT_LINKS);
+        assert!(outcomes[..MAX_IMPORT_LINKS].iter().all(Result::is_ok));
+        for outcome in &outcomes[MAX_IMPORT_LINKS..] {
+            assert_eq!(
+                outcome,
+                &Err(RecordError::Product(ProductError::UnresolvedLink.into()))
+            );
+        }
+
+        // The import has a budget of its own
+        importer.import(&records, Some("alice")).await.unwrap();
+        assert_eq!(resolver.0.load(Ordering::Relaxed), 2 * MAX_IMPORT_LINKS);
+    }
+
+    #[tokio::test]
+    async fn previews_without_changing_anything() {
+        let fixture = Fixture::new(&["alice", "bob"]);
+        let importer = fixture.importer();
+        let bobs = ProductRecord {
+            username: Some("bob".to_string()),
+            ..record(URL, None)
+        };
+        let records = [
+            record(URL, Some("1299.99")),
+            record(&format!("{URL}?ref=x"), None),
+            bobs,
+            record("https://www.amazon.com/dp/B0DTJFSSZG", None),
+        ];
+
+        let outcomes = importer.preview(&records, Some("alice")).await.unwrap();
+        assert_eq!(outcomes[0], Ok(Tracked::Added));
+        assert_eq!(outcomes[1], Ok(Tracked::AlreadySubscribed));
+        assert_eq!(outcomes[2], Ok(Tracked::Subscribed));
+        assert_eq!(
+            outcomes[3],
+            Err(RecordError::Product(ProductError::InvalidUrl.into()))
+        );
+        assert!(fixture.products.list().unwrap().is_empty());
+
+        // The real import does what the preview said
+        let imported: Vec<_> = importer
+            .import(&records, Some("alice"))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|outcome| outcome.map(|(_, tracked)| tracked))
+            .collect();
+        assert_eq!(imported, outcomes);
+    }
+
+    #[tokio::test]
+    async fn reports_bad_records() {
+        let fixture = Fixture::new(&["alice"]);
+        let importer = fixture.importer();
+        let error = |record: ProductRecord, user: Option<&'static str>| {
+            let importer = &importer;
+            async move {
+                importer.preview(&[record], user).await.unwrap()[0]
+                    .clone()
+                    .unwrap_err()
+            }
+        };
+
+        assert_eq!(
+            error(record(URL, None), None).await,
+            RecordError::MissingUser
+        );
+        assert_eq!(
+            error(record(URL, None), Some("carol")).await,
+            RecordError::UnknownUser("carol".to_string())
+        );
+        let usd = ProductRecord {
+            currency: Some("USD".to_string()),
+            ..record(URL, Some("999"))
+        };
+        assert_eq!(
+            error(usd, Some("alice")).await,
+            RecordError::Product(ProductError::TargetPriceCurrency.into())
+        );
+        assert_eq!(
+            error(record(URL, Some("cheap")), Some("alice")).await,
+            RecordError::Product(ProductError::InvalidTargetPrice.into())
+        );
+    }
+}
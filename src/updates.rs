@@ -0,0 +1,41 @@
+use crate::storage::{Observation, Product};
+use tokio::sync::broadcast;
+
+/// A product's latest check result, published when its price or stock changes
+#[derive(Debug, Clone)]
+pub struct ProductUpdate {
+    pub product: Product,
+    /// Usernames of everyone watching the product
+    pub subscribers: Vec<String>,
+    pub observation: Observation,
+}
+
+/// Fans product updates out to every open live-update connection.
+///
+/// Connections that fall more than `capacity` updates behind skip the ones they missed; the
+/// next update for a product carries its full state, so nothing is lost for good.
+pub struct UpdateHub {
+    sender: broadcast::Sender<ProductUpdate>,
+}
+
+impl UpdateHub {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        UpdateHub { sender }
+    }
+
+    pub fn publish(&self, update: ProductUpdate) {
+        // Sending only fails when nobody is listening, which is fine
+        let _ = self.sender.send(update);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ProductUpdate> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for UpdateHub {
+    fn default() -> Self {
+        UpdateHub::new(64)
+    }
+}
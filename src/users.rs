@@ -0,0 +1,417 @@
+use crate::storage::UserRepository;
+use argon2::Argon2;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// Failed sign-ins allowed before an account is temporarily locked
+const MAX_FAILED_LOGINS: u32 = 5;
+
+/// How long an account stays locked after too many failed sign-ins
+const LOCKOUT_DURATION: Duration = Duration::from_secs(15 * 60);
+
+const MIN_PASSWORD_LEN: usize = 8;
+
+// Role enum to track user permissions
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UserRole {
+    Regular,
+    Admin,
+    // Can see every tracked product but can't change anything
+    Viewer,
+}
+
+impl UserRole {
+    pub const ALL: [UserRole; 3] = [UserRole::Regular, UserRole::Admin, UserRole::Viewer];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UserRole::Regular => "regular",
+            UserRole::Admin => "admin",
+            UserRole::Viewer => "viewer",
+        }
+    }
+
+    pub fn parse(role: &str) -> Option<UserRole> {
+        UserRole::ALL.into_iter().find(|r| r.as_str() == role)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            UserRole::Regular => "Regular",
+            UserRole::Admin => "Admin",
+            UserRole::Viewer => "Viewer",
+        }
+    }
+
+    /// Whether this role sees every user's products rather than just its own
+    pub fn can_view_all(self) -> bool {
+        matches!(self, UserRole::Admin | UserRole::Viewer)
+    }
+
+    /// Whether this role may add or change products
+    pub fn can_edit(self) -> bool {
+        matches!(self, UserRole::Regular | UserRole::Admin)
+    }
+}
+
+// User structure to store user information
+#[derive(Debug, Clone)]
+pub struct User {
+    pub username: String,
+    pub role: UserRole,
+}
+
+// A stored account: the public user plus its credentials and sign-in bookkeeping
+#[derive(Debug, Clone)]
+pub struct Account {
+    pub user: User,
+    pub password_hash: String,
+    pub failed_logins: u32,
+    pub locked_until: Option<SystemTime>,
+    pub created_at: SystemTime,
+}
+
+/// An account as shown on the admin users page
+#[derive(Debug, Clone)]
+pub struct AccountSummary {
+    pub user: User,
+    pub locked: bool,
+}
+
+/// Why a registration was refused. `code()` is what ends up in the `?error=` query param.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegisterError {
+    InvalidUsername,
+    UsernameTaken,
+    WeakPassword,
+}
+
+impl RegisterError {
+    pub fn code(self) -> &'static str {
+        match self {
+            RegisterError::InvalidUsername => "invalid_username",
+            RegisterError::UsernameTaken => "username_taken",
+            RegisterError::WeakPassword => "weak_password",
+        }
+    }
+}
+
+/// Why a sign-in was refused
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoginError {
+    InvalidCredentials,
+    Locked,
+}
+
+impl LoginError {
+    pub fn code(self) -> &'static str {
+        match self {
+            LoginError::InvalidCredentials => "invalid_credentials",
+            LoginError::Locked => "locked",
+        }
+    }
+}
+
+/// Why a password change was refused
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangePasswordError {
+    WrongPassword,
+    WeakPassword,
+    Mismatch,
+}
+
+impl ChangePasswordError {
+    pub fn code(self) -> &'static str {
+        match self {
+            ChangePasswordError::WrongPassword => "wrong_password",
+            ChangePasswordError::WeakPassword => "weak_password",
+            ChangePasswordError::Mismatch => "mismatch",
+        }
+    }
+}
+
+/// Why a role change was refused
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetRoleError {
+    UnknownUser,
+    LastAdmin,
+}
+
+impl SetRoleError {
+    pub fn code(self) -> &'static str {
+        match self {
+            SetRoleError::UnknownUser => "unknown_user",
+            SetRoleError::LastAdmin => "last_admin",
+        }
+    }
+}
+
+fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("Argon2 hashing with default parameters cannot fail")
+        .to_string()
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+fn valid_username(username: &str) -> bool {
+    (3..=32).contains(&username.len())
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// Account management on top of a `UserRepository`: hashing, lockouts and role rules.
+///
+/// Methods return `Err` for storage failures and `Ok(Err(..))` when the request itself
+/// was refused.
+#[derive(Clone)]
+pub struct UserStore {
+    accounts: Arc<dyn UserRepository>,
+}
+
+impl UserStore {
+    pub fn new(accounts: Arc<dyn UserRepository>) -> Self {
+        UserStore { accounts }
+    }
+
+    /// Create a new account with a freshly hashed password.
+    ///
+    /// The very first account becomes an admin so a fresh install can be managed;
+    /// everyone after that starts as a regular user.
+    pub fn register(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> anyhow::Result<Result<User, RegisterError>> {
+        if !valid_username(username) {
+            return Ok(Err(RegisterError::InvalidUsername));
+        }
+        if password.len() < MIN_PASSWORD_LEN {
+            return Ok(Err(RegisterError::WeakPassword));
+        }
+        if self.accounts.get(username)?.is_some() {
+            return Ok(Err(RegisterError::UsernameTaken));
+        }
+
+        let account = Account {
+            user: User {
+                username: username.to_string(),
+                role: UserRole::Regular,
+            },
+            password_hash: hash_password(password),
+            failed_logins: 0,
+            locked_until: None,
+            created_at: SystemTime::now(),
+        };
+
+        // Someone may have registered the same name while we were hashing, or been first
+        let Some(role) = self.accounts.insert(account)? else {
+            return Ok(Err(RegisterError::UsernameTaken));
+        };
+        Ok(Ok(User {
+            username: username.to_string(),
+            role,
+        }))
+    }
+
+    /// Check a username/password pair, counting failures towards a temporary lockout
+    pub fn authenticate(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> anyhow::Result<Result<User, LoginError>> {
+        let now = SystemTime::now();
+
+        let Some(account) = self.accounts.get(username)? else {
+            return Ok(Err(LoginError::InvalidCredentials));
+        };
+        if account.locked_until.is_some_and(|until| until > now) {
+            return Ok(Err(LoginError::Locked));
+        }
+
+        // Only the sign-in bookkeeping is written, so a role or password changed while the
+        // password was being checked stays changed
+        let verified = verify_password(password, &account.password_hash);
+        let locked = self.accounts.record_login_result(
+            &account.user.username,
+            verified,
+            MAX_FAILED_LOGINS,
+            now + LOCKOUT_DURATION,
+        )?;
+        if !verified {
+            return Ok(Err(if locked {
+                LoginError::Locked
+            } else {
+                LoginError::InvalidCredentials
+            }));
+        }
+        Ok(self
+            .get(&account.user.username)?
+            .ok_or(LoginError::InvalidCredentials))
+    }
+
+    /// Replace a user's password after checking their current one
+    pub fn change_password(
+        &self,
+        username: &str,
+        current_password: &str,
+        new_password: &str,
+        confirm_password: &str,
+    ) -> anyhow::Result<Result<(), ChangePasswordError>> {
+        if new_password != confirm_password {
+            return Ok(Err(ChangePasswordError::Mismatch));
+        }
+        if new_password.len() < MIN_PASSWORD_LEN {
+            return Ok(Err(ChangePasswordError::WeakPassword));
+        }
+
+        let Some(account) = self.accounts.get(username)? else {
+            return Ok(Err(ChangePasswordError::WrongPassword));
+        };
+        if !verify_password(current_password, &account.password_hash) {
+            return Ok(Err(ChangePasswordError::WrongPassword));
+        }
+
+        self.accounts
+            .set_password_hash(&account.user.username, &hash_password(new_password))?;
+        Ok(Ok(()))
+    }
+
+    /// Look up the current state of an account
+    pub fn get(&self, username: &str) -> anyhow::Result<Option<User>> {
+        Ok(self.accounts.get(username)?.map(|account| account.user))
+    }
+
+    /// All accounts, oldest first
+    pub fn list(&self) -> anyhow::Result<Vec<AccountSummary>> {
+        let now = SystemTime::now();
+        Ok(self
+            .accounts
+            .list()?
+            .into_iter()
+            .map(|account| AccountSummary {
+                user: account.user,
+                locked: account.locked_until.is_some_and(|until| until > now),
+            })
+            .collect())
+    }
+
+    /// Assign a new role, refusing to demote the last remaining admin
+    pub fn set_role(
+        &self,
+        username: &str,
+        role: UserRole,
+    ) -> anyhow::Result<Result<User, SetRoleError>> {
+        self.accounts.set_role(username, role)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{Database, InMemoryUserRepository};
+
+    // The same rules have to hold whichever storage is behind them
+    fn stores() -> [UserStore; 2] {
+        let database = Database::open(":memory:").unwrap();
+        [
+            UserStore::new(Arc::new(InMemoryUserRepository::default())),
+            UserStore::new(Arc::new(database.users())),
+        ]
+    }
+
+    #[test]
+    fn only_the_first_account_is_an_admin() {
+        for users in stores() {
+            let alice = users.register("alice", "password1").unwrap().unwrap();
+            let bob = users.register("bob", "password1").unwrap().unwrap();
+            assert_eq!(alice.role, UserRole::Admin);
+            assert_eq!(bob.role, UserRole::Regular);
+            assert_eq!(
+                users.register("Alice", "password1").unwrap().unwrap_err(),
+                RegisterError::UsernameTaken
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_the_last_admin() {
+        for users in stores() {
+            users.register("alice", "password1").unwrap().unwrap();
+            users.register("bob", "password1").unwrap().unwrap();
+
+            assert_eq!(
+                users
+                    .set_role("alice", UserRole::Viewer)
+                    .unwrap()
+                    .unwrap_err(),
+                SetRoleError::LastAdmin
+            );
+            users.set_role("bob", UserRole::Admin).unwrap().unwrap();
+            let alice = users.set_role("alice", UserRole::Viewer).unwrap().unwrap();
+            assert_eq!(alice.role, UserRole::Viewer);
+            assert_eq!(
+                users
+                    .set_role("bob", UserRole::Regular)
+                    .unwrap()
+                    .unwrap_err(),
+                SetRoleError::LastAdmin
+            );
+            assert_eq!(
+                users
+                    .set_role("carol", UserRole::Admin)
+                    .unwrap()
+                    .unwrap_err(),
+                SetRoleError::UnknownUser
+            );
+        }
+    }
+
+    #[test]
+    fn locks_after_repeated_failures() {
+        for users in stores() {
+            users.register("alice", "password1").unwrap().unwrap();
+            users.register("bob", "password1").unwrap().unwrap();
+            users.set_role("bob", UserRole::Viewer).unwrap().unwrap();
+
+            // A sign-in doesn't put back a role it read before the change
+            let bob = users.authenticate("bob", "password1").unwrap().unwrap();
+            assert_eq!(bob.role, UserRole::Viewer);
+
+            for _ in 1..MAX_FAILED_LOGINS {
+                assert_eq!(
+                    users.authenticate("alice", "wrong").unwrap().unwrap_err(),
+                    LoginError::InvalidCredentials
+                );
+            }
+            assert_eq!(
+                users.authenticate("alice", "wrong").unwrap().unwrap_err(),
+                LoginError::Locked
+            );
+            assert_eq!(
+                users
+                    .authenticate("alice", "password1")
+                    .unwrap()
+                    .unwrap_err(),
+                LoginError::Locked
+            );
+            assert!(users.list().unwrap()[0].locked);
+            assert_eq!(users.get("alice").unwrap().unwrap().role, UserRole::Admin);
+        }
+    }
+}